@@ -0,0 +1,7 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Generates the gRPC service and messages; needs protoc
+    tonic_build::compile_protos("proto/grader.proto")?;
+    // sqlx::migrate! embeds the fingerprint store's migrations
+    println!("cargo:rerun-if-changed=migrations");
+    Ok(())
+}
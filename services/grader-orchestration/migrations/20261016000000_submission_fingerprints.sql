@@ -0,0 +1,33 @@
+-- Plagiarism fingerprints of graded submissions, and their LSH band keys
+-- indexed for candidate lookups. IF NOT EXISTS keeps this a no-op on
+-- databases whose tables the worker created before it had migrations.
+CREATE TABLE IF NOT EXISTS submission_fingerprints (
+    submission_id TEXT PRIMARY KEY,
+    challenge_id TEXT NOT NULL,
+    language TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    code TEXT NOT NULL,
+    fingerprint_version INTEGER NOT NULL,
+    fingerprint JSONB NOT NULL,
+    accepted BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Tables created before submissions were marked accepted
+ALTER TABLE submission_fingerprints ADD COLUMN IF NOT EXISTS accepted BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX IF NOT EXISTS submission_fingerprints_challenge_idx ON submission_fingerprints (challenge_id, language);
+CREATE INDEX IF NOT EXISTS submission_fingerprints_version_idx ON submission_fingerprints (fingerprint_version);
+CREATE INDEX IF NOT EXISTS submission_fingerprints_user_idx ON submission_fingerprints (user_id);
+CREATE INDEX IF NOT EXISTS submission_fingerprints_created_idx ON submission_fingerprints (created_at);
+
+CREATE TABLE IF NOT EXISTS submission_fingerprint_bands (
+    submission_id TEXT NOT NULL REFERENCES submission_fingerprints ON DELETE CASCADE,
+    challenge_id TEXT NOT NULL,
+    language TEXT NOT NULL,
+    band BIGINT NOT NULL,
+    PRIMARY KEY (submission_id, band)
+);
+
+CREATE INDEX IF NOT EXISTS submission_fingerprint_bands_lookup_idx ON submission_fingerprint_bands (challenge_id, language, band);
+CREATE INDEX IF NOT EXISTS submission_fingerprint_bands_language_idx ON submission_fingerprint_bands (language, band);
@@ -0,0 +1,241 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashMap};
+
+/// Shares of the AI-likelihood score. Signals that can't be computed (no
+/// identifiers, no perplexity model) leave their share to the others.
+const COMMENT_WEIGHT: f64 = 0.2;
+const NAMING_WEIGHT: f64 = 0.25;
+const FORMATTING_WEIGHT: f64 = 0.25;
+const PERPLEXITY_WEIGHT: f64 = 0.3;
+
+/// Comment density from which comments count fully towards the score.
+const SATURATING_COMMENT_DENSITY: f64 = 0.3;
+
+/// Characters of context the perplexity model conditions on.
+const CONTEXT: usize = 3;
+
+/// Words the identifier scan skips, common to most supported languages.
+pub(crate) const KEYWORDS: &[&str] = &[
+    "as", "break", "case", "class", "const", "continue", "def", "default", "else", "enum", "false", "fn", "for",
+    "func", "function", "if", "impl", "import", "in", "int", "let", "match", "mut", "new", "null", "pub", "public",
+    "private", "return", "self", "static", "struct", "this", "true", "use", "var", "void", "while",
+];
+
+/// Style measurements of a submission.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StylometricSignals {
+    /// Share of non-blank lines that are comments
+    pub comment_density: f64,
+    /// Entropy of the identifiers' naming styles (snake_case, camelCase...),
+    /// from 0 (one style) to 1 (all styles equally)
+    pub naming_entropy: Option<f64>,
+    /// Share of non-blank lines indented in the file's indent unit and
+    /// without trailing whitespace
+    pub formatting_uniformity: f64,
+    /// Per-character perplexity under the local model, when one is loaded
+    pub perplexity: Option<f64>,
+}
+
+/// How much a submission reads like generated code, for instructors to
+/// review. A heuristic: it is never used to escalate or withhold results.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AiLikelihood {
+    /// 0 (human-like) to 1 (generated-like)
+    pub score: f64,
+    pub signals: StylometricSignals,
+}
+
+/// Character n-gram model of human-written code. Generated code is more
+/// predictable than what people write, so it scores a lower perplexity.
+pub struct NgramModel {
+    counts: HashMap<String, HashMap<char, u32>>,
+    vocabulary: usize,
+    /// Perplexity of the training corpus, what code this predictable scores
+    baseline: f64,
+}
+
+impl NgramModel {
+    pub fn train(corpus: &str) -> Self {
+        let chars: Vec<char> = corpus.chars().collect();
+        let mut counts: HashMap<String, HashMap<char, u32>> = HashMap::new();
+        for i in 0..chars.len() {
+            let context: String = chars[i.saturating_sub(CONTEXT)..i].iter().collect();
+            *counts.entry(context).or_default().entry(chars[i]).or_insert(0) += 1;
+        }
+        let vocabulary = chars.iter().collect::<BTreeSet<_>>().len() + 1;
+        let mut model = Self { counts, vocabulary, baseline: 1.0 };
+        model.baseline = model.perplexity(corpus).unwrap_or(1.0);
+        model
+    }
+
+    pub fn from_file(path: &str) -> Result<Self, String> {
+        let corpus = std::fs::read_to_string(path).map_err(|e| format!("Failed to read model corpus {}: {}", path, e))?;
+        Ok(Self::train(&corpus))
+    }
+
+    /// Add-one smoothed perplexity per character, `None` for empty text.
+    pub fn perplexity(&self, text: &str) -> Option<f64> {
+        let chars: Vec<char> = text.chars().collect();
+        if chars.is_empty() {
+            return None;
+        }
+        let mut log_probability = 0.0;
+        for i in 0..chars.len() {
+            let context: String = chars[i.saturating_sub(CONTEXT)..i].iter().collect();
+            let (seen, total) = match self.counts.get(&context) {
+                Some(next) => (next.get(&chars[i]).copied().unwrap_or(0), next.values().sum::<u32>()),
+                None => (0, 0),
+            };
+            log_probability += ((seen as f64 + 1.0) / (total as f64 + self.vocabulary as f64)).ln();
+        }
+        Some((-log_probability / chars.len() as f64).exp())
+    }
+}
+
+/// The optional AI-generated code analyzer.
+pub struct AiHeuristics {
+    model: Option<NgramModel>,
+}
+
+impl AiHeuristics {
+    pub fn new(model: Option<NgramModel>) -> Self {
+        Self { model }
+    }
+
+    /// Enabled by `AI_HEURISTICS_ENABLED`. `AI_HEURISTICS_CORPUS` names a
+    /// file of human-written code to train the perplexity model on; without
+    /// it perplexity is left out.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let enabled = std::env::var("AI_HEURISTICS_ENABLED").map(|v| v == "true" || v == "1").unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+        let model = match std::env::var("AI_HEURISTICS_CORPUS") {
+            Ok(path) if !path.is_empty() => Some(NgramModel::from_file(&path)?),
+            _ => None,
+        };
+        Ok(Some(Self::new(model)))
+    }
+
+    pub fn analyze(&self, code: &str, language: &str) -> AiLikelihood {
+        let signals = StylometricSignals {
+            comment_density: comment_density(code, &language.to_lowercase()),
+            naming_entropy: naming_entropy(code),
+            formatting_uniformity: formatting_uniformity(code),
+            perplexity: self.model.as_ref().and_then(|m| m.perplexity(code)),
+        };
+
+        // Each signal as how generated-like it is, from 0 to 1
+        let parts = [
+            (COMMENT_WEIGHT, Some((signals.comment_density / SATURATING_COMMENT_DENSITY).min(1.0))),
+            (NAMING_WEIGHT, signals.naming_entropy.map(|entropy| 1.0 - entropy)),
+            (FORMATTING_WEIGHT, Some(signals.formatting_uniformity)),
+            (PERPLEXITY_WEIGHT, self.model.as_ref().zip(signals.perplexity).map(|(m, p)| (m.baseline / p).min(1.0))),
+        ];
+        let (weighted, weights) = parts
+            .iter()
+            .filter_map(|(weight, value)| value.map(|v| (weight * v, *weight)))
+            .fold((0.0, 0.0), |(sum, total), (v, w)| (sum + v, total + w));
+
+        AiLikelihood { score: weighted / weights, signals }
+    }
+}
+
+fn comment_density(code: &str, language: &str) -> f64 {
+    let line_comment = if language == "python" { "#" } else { "//" };
+    let (mut lines, mut comments, mut in_block) = (0, 0, false);
+    for line in code.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        lines += 1;
+        if in_block || line.starts_with(line_comment) || (line_comment == "//" && line.starts_with("/*")) {
+            comments += 1;
+        }
+        if line_comment == "//" {
+            in_block = match (line.rfind("/*"), line.rfind("*/")) {
+                (Some(open), Some(close)) => open > close,
+                (Some(_), None) => true,
+                (None, Some(_)) => false,
+                (None, None) => in_block,
+            };
+        }
+    }
+    if lines == 0 { 0.0 } else { comments as f64 / lines as f64 }
+}
+
+fn naming_entropy(code: &str) -> Option<f64> {
+    const STYLES: usize = 6;
+    let identifier = Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").unwrap();
+    let names: BTreeSet<&str> = identifier.find_iter(code).map(|m| m.as_str()).filter(|n| !KEYWORDS.contains(n)).collect();
+    if names.is_empty() {
+        return None;
+    }
+
+    let mut styles: HashMap<&str, usize> = HashMap::new();
+    for name in &names {
+        let has_lower = name.chars().any(|c| c.is_ascii_lowercase());
+        let has_upper = name.chars().any(|c| c.is_ascii_uppercase());
+        let style = match name {
+            n if n.len() == 1 => "single",
+            _ if !has_lower => "upper",
+            n if n.contains('_') => "snake",
+            n if n.starts_with(|c: char| c.is_ascii_uppercase()) => "pascal",
+            _ if has_upper => "camel",
+            _ => "lower",
+        };
+        *styles.entry(style).or_insert(0) += 1;
+    }
+    let entropy: f64 = styles
+        .values()
+        .map(|count| {
+            let p = *count as f64 / names.len() as f64;
+            -p * p.log2()
+        })
+        .sum();
+    Some(entropy / (STYLES as f64).log2())
+}
+
+fn formatting_uniformity(code: &str) -> f64 {
+    let lines: Vec<&str> = code.lines().filter(|l| !l.trim().is_empty()).collect();
+    if lines.is_empty() {
+        return 0.0;
+    }
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    let tabs = lines.iter().filter(|l| l.starts_with('\t')).count();
+    let indent_char = if tabs * 2 > lines.len() { '\t' } else { ' ' };
+    // The smallest indent, which the others should be multiples of
+    let unit = lines.iter().map(|l| indent(l)).filter(|i| *i > 0).min().unwrap_or(1);
+
+    let uniform = lines
+        .iter()
+        .filter(|line| {
+            let leading = &line[..indent(line)];
+            line.trim_end().len() == line.len() && leading.chars().all(|c| c == indent_char) && leading.len() % unit == 0
+        })
+        .count();
+    uniform as f64 / lines.len() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_style_scores_higher() {
+        let generated = "// Sum the values in the list\nfn sum_values(values: &[i32]) -> i32 {\n    // Start from zero\n    let mut running_total = 0;\n    // Add each value\n    for current_value in values {\n        running_total += current_value;\n    }\n    running_total\n}\n";
+        let handwritten = "fn s(v:&[i32])->i32{\n  let mut T=0;  \n   for x in v {T+=x}\n\tT }\nfn helperFn() {}\n";
+
+        let heuristics = AiHeuristics::new(None);
+        let generated_result = heuristics.analyze(generated, "rust");
+        let handwritten_result = heuristics.analyze(handwritten, "rust");
+        assert_eq!(generated_result.signals.formatting_uniformity, 1.0);
+        assert_eq!(generated_result.signals.comment_density, 0.3);
+        assert!(generated_result.score > handwritten_result.score);
+        assert!(generated_result.signals.perplexity.is_none());
+
+        let model = NgramModel::train(handwritten);
+        assert!(model.perplexity(handwritten).unwrap() < model.perplexity(generated).unwrap());
+        let with_model = AiHeuristics::new(Some(model)).analyze(handwritten, "rust");
+        assert!(with_model.score > handwritten_result.score);
+        assert_eq!(comment_density("# note\nx = 1\n", "python"), 0.5);
+    }
+}
@@ -1,5 +1,7 @@
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use petgraph::graph::Graph;
 use petgraph::algo::dijkstra;
 use strsim::jaro_winkler;
@@ -7,6 +9,18 @@ use swc_ecma_parser::{lexer::Lexer, Parser, StringInput, Syntax};
 use swc_common::{SourceMap, FileName};
 use syn::{parse_str, Item, Expr, Stmt, Pat, Type};
 use serde_json::Value;
+use solang_parser::pt::{ContractPart, SourceUnitPart};
+use crate::ai_heuristics::{AiHeuristics, AiLikelihood};
+use crate::anti_cheat_config::AntiCheatConfig;
+use crate::token_fingerprint;
+use crate::collusion::{self, CollusionReport};
+use crate::evasion::{self, EvasionFinding};
+use crate::minhash;
+use crate::plagiarism_batch::{ChallengeReport, RankedSubmission};
+use crate::similarity_report::{self, MatchReport, SimilarityReport, SubmissionMetadata};
+use crate::tree_edit::{self, TokenTree, TreeNode};
+use crate::winnowing;
+use crate::fingerprint_store::{FingerprintStore, MatchScope, MemoryStore, PostgresStore, StoredSubmission};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlagiarismResult {
@@ -14,6 +28,12 @@ pub struct PlagiarismResult {
     pub matched_submissions: Vec<MatchedSubmission>,
     pub risk_level: RiskLevel,
     pub analysis_time_ms: u64,
+    /// Patterns typical of disguising copied code, which raise the risk level
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub evasion: Vec<EvasionFinding>,
+    /// Present when the AI-generated code analyzer is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ai_likelihood: Option<AiLikelihood>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,9 +41,11 @@ pub struct MatchedSubmission {
     pub submission_id: String,
     pub similarity_score: f64,
     pub matched_sections: Vec<String>,
+    #[serde(default)]
+    pub scope: MatchScope,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum RiskLevel {
     Low,
     Medium,
@@ -31,22 +53,105 @@ pub enum RiskLevel {
     Critical,
 }
 
-#[derive(Debug, Clone)]
+impl RiskLevel {
+    /// The next level up, if any.
+    pub fn raised(&self) -> RiskLevel {
+        match self {
+            RiskLevel::Low => RiskLevel::Medium,
+            RiskLevel::Medium => RiskLevel::High,
+            RiskLevel::High | RiskLevel::Critical => RiskLevel::Critical,
+        }
+    }
+}
+
+/// The per-metric similarities of two submissions and their weighted blend.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarityBreakdown {
+    pub winnowing: f64,
+    pub token_sequence: f64,
+    pub structure: f64,
+    /// Absent when either syntax tree couldn't be compared
+    pub tree_edit: Option<f64>,
+    pub combined: f64,
+}
+
+/// Version of the fingerprinting algorithm. Bump whenever fingerprint
+/// generation changes so stored fingerprints can be backfilled.
+pub const FINGERPRINT_VERSION: u32 = 8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CodeFingerprint {
-    pub ast_hash: String,
+    pub version: u32,
+    /// Winnowed k-gram hashes of the token sequence
+    #[serde(default)]
+    pub winnow_hashes: Vec<u64>,
+    /// MinHash signature of `winnow_hashes`, for LSH candidate lookup
+    #[serde(default)]
+    pub minhash: Vec<u64>,
+    /// The normalized tokens as the syntax tree they were extracted from
+    #[serde(default)]
+    pub tree: TreeNode,
     pub token_sequence: Vec<String>,
     pub structural_features: HashMap<String, u32>,
 }
 
+/// How long similarity reports reuse a challenge's collusion clusters.
+/// Submissions stored through this engine drop them sooner; those stored by
+/// other workers sharing the database show up once this has passed.
+const COLLUSION_CACHE_TTL: Duration = Duration::from_secs(300);
+
 pub struct AntiCheatEngine {
-    submission_database: HashMap<String, CodeFingerprint>,
+    store: Box<dyn FingerprintStore>,
+    /// The deployment's config, used unless a challenge overrides it
+    config: AntiCheatConfig,
+    ai_heuristics: Option<AiHeuristics>,
+    /// Collusion clusters by challenge, language and minimum similarity, and
+    /// when they were found
+    collusion_cache: Mutex<HashMap<(String, String, u64), (Instant, CollusionReport)>>,
 }
 
 impl AntiCheatEngine {
+    /// An engine whose submissions are kept in memory.
     pub fn new() -> Self {
-        Self {
-            submission_database: HashMap::new(),
-        }
+        Self::with_store(Box::new(MemoryStore::default()))
+    }
+
+    pub fn with_store(store: Box<dyn FingerprintStore>) -> Self {
+        Self { store, config: AntiCheatConfig::default(), ai_heuristics: None, collusion_cache: Mutex::default() }
+    }
+
+    pub fn with_config(mut self, config: AntiCheatConfig) -> Result<Self, String> {
+        config.validate()?;
+        self.config = config;
+        Ok(self)
+    }
+
+    /// Report an AI-likelihood score with every plagiarism check.
+    pub fn with_ai_heuristics(mut self, ai_heuristics: Option<AiHeuristics>) -> Self {
+        self.ai_heuristics = ai_heuristics;
+        self
+    }
+
+    /// Postgres when `ANTI_CHEAT_DATABASE_URL` is set, so fingerprints
+    /// survive restarts and are shared between workers; memory otherwise.
+    /// Thresholds and weights come from `ANTI_CHEAT_CONFIG`, and the
+    /// AI-generated code analyzer from `AI_HEURISTICS_*`.
+    pub fn from_env() -> Result<Self, String> {
+        let engine = match PostgresStore::from_env() {
+            Ok(Some(store)) => Self::with_store(Box::new(store)),
+            Ok(None) => Self::new(),
+            // A bad URL costs the shared fingerprints, not the worker
+            Err(e) => {
+                eprintln!("Warning: {}; keeping plagiarism fingerprints in memory", e);
+                Self::new()
+            },
+        };
+        Ok(engine.with_config(AntiCheatConfig::from_env()?)?.with_ai_heuristics(AiHeuristics::from_env()?))
+    }
+
+    pub fn config(&self) -> &AntiCheatConfig {
+        &self.config
     }
 
     pub async fn check_plagiarism(
@@ -55,59 +160,299 @@ impl AntiCheatEngine {
         language: &str,
         user_id: &str,
         challenge_id: &str,
+        config: &AntiCheatConfig,
     ) -> Result<PlagiarismResult, String> {
         let start_time = std::time::Instant::now();
 
         // Generate fingerprint for current submission
         let fingerprint = self.generate_fingerprint(code, language)?;
 
-        // Compare against all submissions for this challenge
-        let mut matches = Vec::new();
-        let challenge_key = format!("{}:{}", challenge_id, language.to_lowercase());
-
-        // In a real implementation, this would query a database
-        // For now, we'll simulate with in-memory storage
-        for (submission_key, stored_fingerprint) in &self.submission_database {
-            if submission_key.starts_with(&challenge_key) && !submission_key.contains(user_id) {
-                let similarity = self.calculate_similarity(&fingerprint, stored_fingerprint);
-                if similarity > 0.3 { // Threshold for reporting
+        let matches = self.find_matches(&fingerprint, challenge_id, &language.to_lowercase(), user_id, config).await?;
+        let max_similarity = matches.iter().map(|m| m.similarity_score).fold(0.0, f64::max);
+        let mut risk_level = config.risk_level(max_similarity);
+
+        // Disguising code that matches another submission suggests the match
+        // is no coincidence, so it counts for more than its similarity alone
+        let evasion = evasion::analyze(code, language);
+        if !evasion.is_empty() && !matches.is_empty() {
+            risk_level = risk_level.raised();
+        }
+
+        let result = PlagiarismResult {
+            similarity_score: max_similarity,
+            matched_submissions: matches,
+            risk_level,
+            analysis_time_ms: start_time.elapsed().as_millis() as u64,
+            evasion,
+            ai_likelihood: self.ai_heuristics.as_ref().map(|h| h.analyze(code, language)),
+        };
+
+        Ok(result)
+    }
+
+    /// Score exactly only the submissions in the configured scopes that LSH
+    /// finds likely to be similar.
+    async fn find_matches(
+        &self,
+        fingerprint: &CodeFingerprint,
+        challenge_id: &str,
+        language: &str,
+        user_id: &str,
+        config: &AntiCheatConfig,
+    ) -> Result<Vec<MatchedSubmission>, String> {
+        let band_keys = minhash::band_keys(&fingerprint.minhash);
+        let mut candidates = Vec::new();
+        for scope in &config.scopes {
+            for stored in self.store.candidates(*scope, challenge_id, language, user_id, &band_keys).await? {
+                candidates.push((*scope, stored));
+            }
+        }
+
+        let (fingerprint, config) = (fingerprint.clone(), config.clone());
+        scoring(move || {
+            let mut matches = Vec::new();
+            for (scope, stored) in candidates {
+                let similarity = Self::calculate_similarity(&fingerprint, &stored.fingerprint, &config);
+                if similarity > config.report_threshold {
                     matches.push(MatchedSubmission {
-                        submission_id: submission_key.clone(),
+                        submission_id: stored.submission_id,
                         similarity_score: similarity,
                         matched_sections: vec!["full_code".to_string()], // Simplified
+                        scope,
                     });
                 }
             }
+            matches.sort_by(|a, b| b.similarity_score.total_cmp(&a.similarity_score));
+            matches
+        })
+        .await
+    }
+
+    /// Check every stored submission to the challenge in the language against
+    /// the others, ranking them by their closest match and clustering them,
+    /// for analysing a whole contest after the fact. `progress` is called
+    /// with the submissions checked so far and the total.
+    pub async fn analyze_challenge(
+        &self,
+        challenge_id: &str,
+        language: &str,
+        cluster_similarity: f64,
+        config: &AntiCheatConfig,
+        progress: impl Fn(usize, usize),
+    ) -> Result<ChallengeReport, String> {
+        let start_time = std::time::Instant::now();
+        let language = language.to_lowercase();
+        let submissions = self.store.submissions(challenge_id, &language).await?;
+
+        let mut ranked = Vec::new();
+        for (index, submission) in submissions.iter().enumerate() {
+            let matches = self.find_matches(&submission.fingerprint, challenge_id, &language, &submission.user_id, config).await?;
+            let similarity_score = matches.first().map(|m| m.similarity_score).unwrap_or(0.0);
+            ranked.push(RankedSubmission {
+                submission_id: submission.submission_id.clone(),
+                user_id: submission.user_id.clone(),
+                similarity_score,
+                risk_level: config.risk_level(similarity_score),
+                matched_submissions: matches,
+            });
+            progress(index + 1, submissions.len());
         }
+        ranked.sort_by(|a, b| b.similarity_score.total_cmp(&a.similarity_score).then(a.submission_id.cmp(&b.submission_id)));
 
-        let max_similarity = matches.iter().map(|m| m.similarity_score).fold(0.0, f64::max);
-        let risk_level = self.assess_risk_level(max_similarity);
+        let config = config.clone();
+        let clusters = scoring(move || {
+            collusion::find_clusters(
+                &submissions,
+                |a, b| Self::calculate_similarity(a, b, &config),
+                |s| config.risk_level(s),
+                cluster_similarity,
+            )
+        })
+        .await?;
 
-        let result = PlagiarismResult {
-            similarity_score: max_similarity,
-            matched_submissions: matches,
-            risk_level,
+        Ok(ChallengeReport {
+            challenge_id: challenge_id.to_string(),
+            language,
+            ranked,
+            clusters,
+            analysis_time_ms: start_time.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// Clusters of mutually similar submissions by different users among
+    /// every stored submission to the challenge in the language.
+    pub async fn find_collusion(
+        &self,
+        challenge_id: &str,
+        language: &str,
+        min_similarity: f64,
+    ) -> Result<CollusionReport, String> {
+        let start_time = std::time::Instant::now();
+        let language = language.to_lowercase();
+        let submissions = self.store.submissions(challenge_id, &language).await?;
+
+        let (config, total) = (self.config.clone(), submissions.len());
+        let clusters = scoring(move || {
+            collusion::find_clusters(
+                &submissions,
+                |a, b| Self::calculate_similarity(a, b, &config),
+                |s| config.risk_level(s),
+                min_similarity,
+            )
+        })
+        .await?;
+
+        Ok(CollusionReport {
+            challenge_id: challenge_id.to_string(),
+            language,
+            submissions: total,
+            clusters,
             analysis_time_ms: start_time.elapsed().as_millis() as u64,
+        })
+    }
+
+    /// `find_collusion`, reused for `COLLUSION_CACHE_TTL` so that every
+    /// report on a challenge doesn't score all of its submissions again.
+    async fn cached_collusion(&self, challenge_id: &str, language: &str, min_similarity: f64) -> Result<CollusionReport, String> {
+        let key = (challenge_id.to_string(), language.to_lowercase(), min_similarity.to_bits());
+        if let Some((found_at, report)) = self.collusion_cache.lock().unwrap().get(&key) {
+            if found_at.elapsed() < COLLUSION_CACHE_TTL {
+                return Ok(report.clone());
+            }
+        }
+        let report = self.find_collusion(challenge_id, language, min_similarity).await?;
+        let mut cache = self.collusion_cache.lock().unwrap();
+        cache.retain(|_, (found_at, _)| found_at.elapsed() < COLLUSION_CACHE_TTL);
+        cache.insert(key, (Instant::now(), report.clone()));
+        Ok(report)
+    }
+
+    /// Drop cached collusion clusters of the challenge, or of every challenge.
+    fn invalidate_collusion(&self, challenge_id: Option<&str>) {
+        let mut cache = self.collusion_cache.lock().unwrap();
+        match challenge_id {
+            Some(challenge_id) => cache.retain(|(cached, _, _), _| cached != challenge_id),
+            None => cache.clear(),
+        }
+    }
+
+    /// A reviewer's report on a stored submission: every match at high risk
+    /// or above with per-metric scores and matched line regions, and the
+    /// collusion cluster the submission is in. `None` for unknown ids.
+    pub async fn similarity_report(
+        &self,
+        submission_id: &str,
+        config: &AntiCheatConfig,
+    ) -> Result<Option<SimilarityReport>, String> {
+        let stored = match self.store.get(submission_id).await? {
+            Some(stored) => stored,
+            None => return Ok(None),
         };
 
-        Ok(result)
+        let mut matches = Vec::new();
+        let candidates = self
+            .find_matches(&stored.fingerprint, &stored.challenge_id, &stored.language, &stored.user_id, config)
+            .await?;
+        for matched in candidates {
+            let risk_level = config.risk_level(matched.similarity_score);
+            if risk_level < RiskLevel::High {
+                continue;
+            }
+            let other = match self.store.get(&matched.submission_id).await? {
+                Some(other) => other,
+                None => continue,
+            };
+            let (fingerprint, other_fingerprint, config) = (stored.fingerprint.clone(), other.fingerprint.clone(), config.clone());
+            let scores = scoring(move || Self::similarity_breakdown(&fingerprint, &other_fingerprint, &config)).await?;
+            matches.push(MatchReport {
+                submission: SubmissionMetadata::of(&other),
+                scope: matched.scope,
+                risk_level,
+                scores,
+                regions: similarity_report::matched_regions(&stored.code, &other.code),
+                code: other.code,
+            });
+        }
+
+        let collusion = self.cached_collusion(&stored.challenge_id, &stored.language, config.risk_cutoffs.high).await?;
+        let cluster = collusion.clusters.into_iter().find(|c| c.submission_ids.contains(&stored.submission_id));
+
+        Ok(Some(SimilarityReport {
+            submission: SubmissionMetadata::of(&stored),
+            code: stored.code,
+            matches,
+            cluster,
+            generated_at: chrono::Utc::now(),
+        }))
     }
 
-    pub fn store_submission(
-        &mut self,
+    pub async fn store_submission(
+        &self,
         submission_id: &str,
         code: &str,
         language: &str,
+        user_id: &str,
+        challenge_id: &str,
+        accepted: bool,
     ) -> Result<(), String> {
         let fingerprint = self.generate_fingerprint(code, language)?;
-        self.submission_database.insert(submission_id.to_string(), fingerprint);
+        self.store
+            .store(&StoredSubmission {
+                submission_id: submission_id.to_string(),
+                challenge_id: challenge_id.to_string(),
+                language: language.to_lowercase(),
+                user_id: user_id.to_string(),
+                code: code.to_string(),
+                fingerprint,
+                accepted,
+            })
+            .await?;
+        self.invalidate_collusion(Some(challenge_id));
+        Ok(())
+    }
+
+    /// Ids of stored submissions fingerprinted with an older algorithm version.
+    pub async fn stale_submissions(&self) -> Result<Vec<String>, String> {
+        self.store.stale(FINGERPRINT_VERSION).await
+    }
+
+    /// Regenerate the fingerprint of a stored submission with the current algorithm.
+    pub async fn refingerprint(&self, submission_id: &str) -> Result<(), String> {
+        let mut stored = match self.store.get(submission_id).await? {
+            Some(stored) => stored,
+            None => return Err(format!("Unknown submission: {}", submission_id)),
+        };
+
+        stored.fingerprint = self.generate_fingerprint(&stored.code, &stored.language)?;
+        self.store.store(&stored).await?;
+        self.invalidate_collusion(Some(&stored.challenge_id));
         Ok(())
     }
 
+    /// Forget every submission by the user, for data-deletion requests.
+    /// Returns how many were deleted.
+    pub async fn delete_user_submissions(&self, user_id: &str) -> Result<usize, String> {
+        let deleted = self.store.delete_user(user_id).await?;
+        self.invalidate_collusion(None);
+        Ok(deleted)
+    }
+
+    /// Forget submissions stored before `cutoff`. Returns how many were deleted.
+    pub async fn prune_submissions(&self, cutoff: chrono::DateTime<chrono::Utc>) -> Result<usize, String> {
+        let deleted = self.store.prune(cutoff).await?;
+        self.invalidate_collusion(None);
+        Ok(deleted)
+    }
+
     fn generate_fingerprint(&self, code: &str, language: &str) -> Result<CodeFingerprint, String> {
         match language.to_lowercase().as_str() {
             "typescript" | "javascript" => self.generate_typescript_fingerprint(code),
             "rust" => self.generate_rust_fingerprint(code),
+            "solidity" => self.generate_solidity_fingerprint(code),
+            other if token_fingerprint::supports(other) => {
+                let (tokens, structural_features) = token_fingerprint::extract_tokens(code, other)?;
+                Ok(Self::fingerprint(tokens, structural_features))
+            }
             _ => Err(format!("Unsupported language for plagiarism detection: {}", language)),
         }
     }
@@ -126,7 +471,7 @@ impl AntiCheatEngine {
         let mut parser = Parser::new_from(lexer);
         let module = parser.parse_module().map_err(|e| format!("Parse error: {:?}", e))?;
 
-        let mut token_sequence = Vec::new();
+        let mut token_sequence = TokenTree::default();
         let mut structural_features = HashMap::new();
 
         // Extract tokens and structural features
@@ -142,58 +487,105 @@ impl AntiCheatEngine {
             }
         }
 
-        let ast_hash = format!("{:x}", md5::compute(code));
-
-        Ok(CodeFingerprint {
-            ast_hash,
-            token_sequence,
-            structural_features,
-        })
+        Ok(Self::fingerprint(token_sequence, structural_features))
     }
 
     fn generate_rust_fingerprint(&self, code: &str) -> Result<CodeFingerprint, String> {
         let syntax_tree = parse_str::<syn::File>(code)
             .map_err(|e| format!("Parse error: {:?}", e))?;
 
-        let mut token_sequence = Vec::new();
+        let mut token_sequence = TokenTree::default();
         let mut structural_features = HashMap::new();
 
         for item in &syntax_tree.items {
             self.extract_rust_tokens(item, &mut token_sequence, &mut structural_features);
         }
 
-        let ast_hash = format!("{:x}", md5::compute(code));
+        Ok(Self::fingerprint(token_sequence, structural_features))
+    }
+
+    fn generate_solidity_fingerprint(&self, code: &str) -> Result<CodeFingerprint, String> {
+        let (source_unit, _comments) = solang_parser::parse(code, 0)
+            .map_err(|diagnostics| format!("Parse error: {:?}", diagnostics))?;
+
+        let mut token_sequence = TokenTree::default();
+        let mut structural_features = HashMap::new();
+
+        for part in &source_unit.0 {
+            match part {
+                SourceUnitPart::ContractDefinition(contract) => {
+                    token_sequence.push("contract".to_string());
+                    *structural_features.entry("contract".to_string()).or_insert(0) += 1;
+                    token_sequence.enter();
+                    for part in &contract.parts {
+                        self.extract_solidity_contract_tokens(part, &mut token_sequence, &mut structural_features);
+                    }
+                    token_sequence.leave();
+                }
+                SourceUnitPart::FunctionDefinition(func) => {
+                    self.extract_solidity_contract_tokens(
+                        &ContractPart::FunctionDefinition(func.clone()),
+                        &mut token_sequence,
+                        &mut structural_features,
+                    );
+                }
+                // Pragmas and imports say nothing about the solution
+                SourceUnitPart::PragmaDirective(..) | SourceUnitPart::ImportDirective(_) => {}
+                _ => {
+                    token_sequence.push("other_item".to_string());
+                    *structural_features.entry("other_item".to_string()).or_insert(0) += 1;
+                }
+            }
+        }
+
+        Ok(Self::fingerprint(token_sequence, structural_features))
+    }
 
-        Ok(CodeFingerprint {
-            ast_hash,
+    /// Fingerprint of the normalized tokens, so renaming variables or
+    /// changing literals doesn't change it. Comments never reach the tokens.
+    fn fingerprint(mut tokens: TokenTree, structural_features: HashMap<String, u32>) -> CodeFingerprint {
+        tokens.map_labels(normalize_tokens);
+        let token_sequence = tokens.sequence();
+        let winnow_hashes = winnowing::fingerprints(&token_sequence);
+        CodeFingerprint {
+            tree: tokens.tree(),
+            version: FINGERPRINT_VERSION,
+            minhash: minhash::signature(&winnow_hashes),
+            winnow_hashes,
             token_sequence,
             structural_features,
-        })
+        }
     }
 
     fn extract_typescript_tokens(
         &self,
         stmt: &swc_ecma_ast::Stmt,
-        tokens: &mut Vec<String>,
+        tokens: &mut TokenTree,
         features: &mut HashMap<String, u32>,
     ) {
         match stmt {
             swc_ecma_ast::Stmt::Expr(expr_stmt) => {
                 tokens.push("expr_stmt".to_string());
                 *features.entry("expr_stmt".to_string()).or_insert(0) += 1;
+                tokens.enter();
                 self.extract_typescript_expr_tokens(&expr_stmt.expr, tokens, features);
+                tokens.leave();
             }
             swc_ecma_ast::Stmt::Block(block) => {
                 tokens.push("block".to_string());
                 *features.entry("block".to_string()).or_insert(0) += 1;
+                tokens.enter();
                 for stmt in &block.stmts {
                     self.extract_typescript_tokens(stmt, tokens, features);
                 }
+                tokens.leave();
             }
             swc_ecma_ast::Stmt::If(if_stmt) => {
                 tokens.push("if".to_string());
                 *features.entry("if".to_string()).or_insert(0) += 1;
+                tokens.enter();
                 self.extract_typescript_expr_tokens(&if_stmt.test, tokens, features);
+                tokens.leave();
             }
             swc_ecma_ast::Stmt::For(for_stmt) => {
                 tokens.push("for".to_string());
@@ -213,7 +605,7 @@ impl AntiCheatEngine {
     fn extract_typescript_expr_tokens(
         &self,
         expr: &swc_ecma_ast::Expr,
-        tokens: &mut Vec<String>,
+        tokens: &mut TokenTree,
         features: &mut HashMap<String, u32>,
     ) {
         match expr {
@@ -243,14 +635,16 @@ impl AntiCheatEngine {
     fn extract_rust_tokens(
         &self,
         item: &syn::Item,
-        tokens: &mut Vec<String>,
+        tokens: &mut TokenTree,
         features: &mut HashMap<String, u32>,
     ) {
         match item {
             Item::Fn(func) => {
                 tokens.push("fn".to_string());
                 *features.entry("fn".to_string()).or_insert(0) += 1;
+                tokens.enter();
                 self.extract_rust_block_tokens(&func.block, tokens, features);
+                tokens.leave();
             }
             Item::Struct(strct) => {
                 tokens.push("struct".to_string());
@@ -274,7 +668,7 @@ impl AntiCheatEngine {
     fn extract_rust_block_tokens(
         &self,
         block: &syn::Block,
-        tokens: &mut Vec<String>,
+        tokens: &mut TokenTree,
         features: &mut HashMap<String, u32>,
     ) {
         for stmt in &block.stmts {
@@ -299,7 +693,7 @@ impl AntiCheatEngine {
     fn extract_rust_expr_tokens(
         &self,
         expr: &syn::Expr,
-        tokens: &mut Vec<String>,
+        tokens: &mut TokenTree,
         features: &mut HashMap<String, u32>,
     ) {
         match expr {
@@ -312,7 +706,10 @@ impl AntiCheatEngine {
                 *features.entry("method_call".to_string()).or_insert(0) += 1;
             }
             Expr::Path(path) => {
-                tokens.push("path".to_string());
+                match path.path.get_ident() {
+                    Some(ident) => tokens.push(format!("ident_{}", ident)),
+                    None => tokens.push("path".to_string()),
+                }
                 *features.entry("path".to_string()).or_insert(0) += 1;
             }
             Expr::Lit(lit) => {
@@ -326,7 +723,9 @@ impl AntiCheatEngine {
             Expr::If(if_expr) => {
                 tokens.push("if".to_string());
                 *features.entry("if".to_string()).or_insert(0) += 1;
+                tokens.enter();
                 self.extract_rust_block_tokens(&if_expr.then_branch, tokens, features);
+                tokens.leave();
             }
             Expr::ForLoop(for_loop) => {
                 tokens.push("for".to_string());
@@ -343,24 +742,188 @@ impl AntiCheatEngine {
         }
     }
 
-    fn calculate_similarity(&self, fp1: &CodeFingerprint, fp2: &CodeFingerprint) -> f64 {
-        // AST hash similarity (exact match)
-        let hash_similarity = if fp1.ast_hash == fp2.ast_hash { 1.0 } else { 0.0 };
+    fn extract_solidity_contract_tokens(
+        &self,
+        part: &ContractPart,
+        tokens: &mut TokenTree,
+        features: &mut HashMap<String, u32>,
+    ) {
+        match part {
+            ContractPart::FunctionDefinition(func) => {
+                tokens.push("fn".to_string());
+                *features.entry("fn".to_string()).or_insert(0) += 1;
+                if let Some(body) = &func.body {
+                    tokens.enter();
+                    self.extract_solidity_stmt_tokens(body, tokens, features);
+                    tokens.leave();
+                }
+            }
+            ContractPart::VariableDefinition(_) => {
+                tokens.push("state_var".to_string());
+                *features.entry("state_var".to_string()).or_insert(0) += 1;
+            }
+            ContractPart::StructDefinition(_) => {
+                tokens.push("struct".to_string());
+                *features.entry("struct".to_string()).or_insert(0) += 1;
+            }
+            ContractPart::EnumDefinition(_) => {
+                tokens.push("enum".to_string());
+                *features.entry("enum".to_string()).or_insert(0) += 1;
+            }
+            ContractPart::EventDefinition(_) => {
+                tokens.push("event".to_string());
+                *features.entry("event".to_string()).or_insert(0) += 1;
+            }
+            _ => {
+                tokens.push("other_item".to_string());
+                *features.entry("other_item".to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn extract_solidity_stmt_tokens(
+        &self,
+        stmt: &solang_parser::pt::Statement,
+        tokens: &mut TokenTree,
+        features: &mut HashMap<String, u32>,
+    ) {
+        use solang_parser::pt::Statement;
+
+        match stmt {
+            Statement::Block { statements, .. } => {
+                for stmt in statements {
+                    self.extract_solidity_stmt_tokens(stmt, tokens, features);
+                }
+            }
+            Statement::Expression(_, expr) => {
+                self.extract_solidity_expr_tokens(expr, tokens, features);
+            }
+            Statement::VariableDefinition(_, _, init) => {
+                tokens.push("let".to_string());
+                *features.entry("let".to_string()).or_insert(0) += 1;
+                if let Some(init) = init {
+                    tokens.enter();
+                    self.extract_solidity_expr_tokens(init, tokens, features);
+                    tokens.leave();
+                }
+            }
+            Statement::If(_, cond, then_branch, else_branch) => {
+                tokens.push("if".to_string());
+                *features.entry("if".to_string()).or_insert(0) += 1;
+                tokens.enter();
+                self.extract_solidity_expr_tokens(cond, tokens, features);
+                self.extract_solidity_stmt_tokens(then_branch, tokens, features);
+                if let Some(else_branch) = else_branch {
+                    self.extract_solidity_stmt_tokens(else_branch, tokens, features);
+                }
+                tokens.leave();
+            }
+            Statement::For(_, _, _, _, body) => {
+                tokens.push("for".to_string());
+                *features.entry("for".to_string()).or_insert(0) += 1;
+                if let Some(body) = body {
+                    tokens.enter();
+                    self.extract_solidity_stmt_tokens(body, tokens, features);
+                    tokens.leave();
+                }
+            }
+            Statement::While(_, _, body) | Statement::DoWhile(_, body, _) => {
+                tokens.push("while".to_string());
+                *features.entry("while".to_string()).or_insert(0) += 1;
+                tokens.enter();
+                self.extract_solidity_stmt_tokens(body, tokens, features);
+                tokens.leave();
+            }
+            Statement::Return(_, _) => {
+                tokens.push("return".to_string());
+                *features.entry("return".to_string()).or_insert(0) += 1;
+            }
+            Statement::Emit(_, _) => {
+                tokens.push("emit".to_string());
+                *features.entry("emit".to_string()).or_insert(0) += 1;
+            }
+            Statement::Revert(..) | Statement::RevertNamedArgs(..) => {
+                tokens.push("revert".to_string());
+                *features.entry("revert".to_string()).or_insert(0) += 1;
+            }
+            _ => {
+                tokens.push("other_stmt".to_string());
+                *features.entry("other_stmt".to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn extract_solidity_expr_tokens(
+        &self,
+        expr: &solang_parser::pt::Expression,
+        tokens: &mut TokenTree,
+        features: &mut HashMap<String, u32>,
+    ) {
+        use solang_parser::pt::Expression;
+
+        match expr {
+            Expression::FunctionCall(..) => {
+                tokens.push("call".to_string());
+                *features.entry("call".to_string()).or_insert(0) += 1;
+            }
+            Expression::MemberAccess(..) => {
+                tokens.push("member".to_string());
+                *features.entry("member".to_string()).or_insert(0) += 1;
+            }
+            Expression::Variable(ident) => {
+                tokens.push(format!("ident_{}", ident.name));
+                *features.entry("ident".to_string()).or_insert(0) += 1;
+            }
+            Expression::BoolLiteral(..)
+            | Expression::NumberLiteral(..)
+            | Expression::RationalNumberLiteral(..)
+            | Expression::HexNumberLiteral(..)
+            | Expression::StringLiteral(_)
+            | Expression::HexLiteral(_)
+            | Expression::AddressLiteral(..) => {
+                tokens.push("literal".to_string());
+                *features.entry("literal".to_string()).or_insert(0) += 1;
+            }
+            Expression::Assign(..) => {
+                tokens.push("assign".to_string());
+                *features.entry("assign".to_string()).or_insert(0) += 1;
+            }
+            _ => {
+                tokens.push("other_expr".to_string());
+                *features.entry("other_expr".to_string()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    fn calculate_similarity(fp1: &CodeFingerprint, fp2: &CodeFingerprint, config: &AntiCheatConfig) -> f64 {
+        Self::similarity_breakdown(fp1, fp2, config).combined
+    }
+
+    fn similarity_breakdown(fp1: &CodeFingerprint, fp2: &CodeFingerprint, config: &AntiCheatConfig) -> SimilarityBreakdown {
+        // Shared k-grams anywhere in the token sequences
+        let winnowing = winnowing::similarity(&fp1.winnow_hashes, &fp2.winnow_hashes);
 
         // Token sequence similarity using Jaro-Winkler distance
         let token_str1 = fp1.token_sequence.join(" ");
         let token_str2 = fp2.token_sequence.join(" ");
-        let token_similarity = jaro_winkler(&token_str1, &token_str2);
+        let token_sequence = jaro_winkler(&token_str1, &token_str2);
 
         // Structural features similarity
-        let structural_similarity = self.calculate_structural_similarity(&fp1.structural_features, &fp2.structural_features);
+        let structure = Self::calculate_structural_similarity(&fp1.structural_features, &fp2.structural_features);
+
+        // Tree edit distance catches structure copied with its tokens shuffled
+        let tree_edit = tree_edit::similarity(&fp1.tree, &fp2.tree);
 
-        // Weighted combination
-        0.4 * hash_similarity + 0.4 * token_similarity + 0.2 * structural_similarity
+        SimilarityBreakdown {
+            winnowing,
+            token_sequence,
+            structure,
+            tree_edit,
+            combined: config.combine(winnowing, token_sequence, structure, tree_edit),
+        }
     }
 
     fn calculate_structural_similarity(
-        &self,
         features1: &HashMap<String, u32>,
         features2: &HashMap<String, u32>,
     ) -> f64 {
@@ -385,15 +948,29 @@ impl AntiCheatEngine {
             similarity_sum / count as f64
         }
     }
+}
 
-    fn assess_risk_level(&self, max_similarity: f64) -> RiskLevel {
-        match max_similarity {
-            s if s >= 0.9 => RiskLevel::Critical,
-            s if s >= 0.7 => RiskLevel::High,
-            s if s >= 0.5 => RiskLevel::Medium,
-            _ => RiskLevel::Low,
-        }
-    }
+/// Run similarity scoring off the async runtime: comparing syntax trees
+/// takes up to about a second a pair at the size limits.
+async fn scoring<T: Send + 'static>(score: impl FnOnce() -> T + Send + 'static) -> Result<T, String> {
+    tokio::task::spawn_blocking(score).await.map_err(|e| format!("Similarity scoring failed: {}", e))
+}
+
+/// Replace identifiers with `VAR1`, `VAR2`, ... in order of first use, so two
+/// submissions that differ only in naming have the same tokens. Literal
+/// values are already reduced to a bare `literal` token by the extractors.
+fn normalize_tokens(tokens: &[String]) -> Vec<String> {
+    let mut names: HashMap<&str, usize> = HashMap::new();
+    tokens
+        .iter()
+        .map(|token| match token.strip_prefix("ident_") {
+            Some(name) => {
+                let next = names.len() + 1;
+                format!("VAR{}", names.entry(name).or_insert(next))
+            }
+            None => token.clone(),
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -410,7 +987,7 @@ mod tests {
         "#;
 
         let fingerprint = engine.generate_fingerprint(code, "rust").unwrap();
-        assert!(!fingerprint.ast_hash.is_empty());
+        assert!(!fingerprint.winnow_hashes.is_empty());
         assert!(!fingerprint.token_sequence.is_empty());
     }
 
@@ -419,12 +996,86 @@ mod tests {
         let engine = AntiCheatEngine::new();
 
         let code1 = "fn test() { let x = 1; }";
-        let code2 = "fn test() { let y = 1; }";
+        let code2 = "fn test() { let y = 1; y.len(); }";
 
         let fp1 = engine.generate_fingerprint(code1, "rust").unwrap();
         let fp2 = engine.generate_fingerprint(code2, "rust").unwrap();
 
-        let similarity = engine.calculate_similarity(&fp1, &fp2);
+        let similarity = AntiCheatEngine::calculate_similarity(&fp1, &fp2, engine.config());
         assert!(similarity > 0.0 && similarity < 1.0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_renamed_identifiers_normalize_alike() {
+        let engine = AntiCheatEngine::new();
+
+        let original = engine.generate_fingerprint("total; count; total;", "typescript").unwrap();
+        let renamed = engine.generate_fingerprint("a; b; a;", "typescript").unwrap();
+        let different = engine.generate_fingerprint("a; a; a;", "typescript").unwrap();
+
+        assert_eq!(original.token_sequence, vec!["expr_stmt", "VAR1", "expr_stmt", "VAR2", "expr_stmt", "VAR1"]);
+        assert_eq!(original.token_sequence, renamed.token_sequence);
+        assert_eq!(original.winnow_hashes, renamed.winnow_hashes);
+        assert_ne!(original.token_sequence, different.token_sequence);
+    }
+
+    #[test]
+    fn test_solidity_fingerprint() {
+        let engine = AntiCheatEngine::new();
+        let code = r#"
+            pragma solidity ^0.8.0;
+            contract Vault {
+                uint256 total;
+                function deposit(uint256 amount) public {
+                    if (amount > 0) { total = total + amount; }
+                }
+            }
+        "#;
+        let renamed = code.replace("total", "balance").replace("amount", "value");
+
+        let fingerprint = engine.generate_fingerprint(code, "solidity").unwrap();
+        assert_eq!(fingerprint.token_sequence, vec!["contract", "state_var", "fn", "if", "other_expr", "assign"]);
+        let contract = &fingerprint.tree.children[0];
+        assert_eq!((contract.label.as_str(), contract.children.len()), ("contract", 2));
+        assert_eq!(contract.children[1].children[0].children.len(), 2);
+        assert_eq!(fingerprint.token_sequence, engine.generate_fingerprint(&renamed, "solidity").unwrap().token_sequence);
+        assert!(engine.generate_fingerprint("contract {", "solidity").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_leaked_solution_from_previous_edition() {
+        let engine = AntiCheatEngine::new();
+        let solution = "fn solve(v: Vec<i32>) -> i32 { let mut total = 0; for x in v { total = total + x; } if total > 10 { total = 10; } total }";
+        engine.store_submission("2023:rust:alice:1", solution, "rust", "alice", "sum-2023", true).await.unwrap();
+        engine.store_submission("2023:rust:bob:2", solution, "rust", "bob", "sum-2023", true).await.unwrap();
+
+        let leaked = solution.replace("total", "acc");
+        let default_scopes = engine.check_plagiarism(&leaked, "rust", "bob", "sum-2024", engine.config()).await.unwrap();
+        assert!(default_scopes.matched_submissions.is_empty());
+
+        let config = AntiCheatConfig { scopes: vec![MatchScope::OwnHistory, MatchScope::OtherChallenges], ..Default::default() };
+        let result = engine.check_plagiarism(&leaked, "rust", "bob", "sum-2024", &config).await.unwrap();
+        let scopes: Vec<(&str, MatchScope)> = result.matched_submissions.iter().map(|m| (m.submission_id.as_str(), m.scope)).collect();
+        assert_eq!(scopes, vec![("2023:rust:bob:2", MatchScope::OwnHistory), ("2023:rust:alice:1", MatchScope::OtherChallenges)]);
+        assert_eq!(result.risk_level, RiskLevel::Critical);
+    }
+
+    #[tokio::test]
+    async fn test_reports_reuse_collusion_clusters_until_a_submission_is_stored() {
+        let engine = AntiCheatEngine::new();
+        let solution = "fn solve(v: Vec<i32>) -> i32 { let mut total = 0; for x in v { total = total + x; } total }";
+        engine.store_submission("c:rust:alice:1", solution, "rust", "alice", "c", true).await.unwrap();
+        engine.store_submission("c:rust:bob:2", solution, "rust", "bob", "c", true).await.unwrap();
+
+        let report = engine.similarity_report("c:rust:alice:1", engine.config()).await.unwrap().unwrap();
+        assert_eq!(report.cluster.unwrap().user_ids, vec!["alice", "bob"]);
+        assert_eq!(engine.collusion_cache.lock().unwrap().len(), 1);
+        engine.similarity_report("c:rust:bob:2", engine.config()).await.unwrap();
+        assert_eq!(engine.collusion_cache.lock().unwrap().len(), 1);
+
+        engine.store_submission("c:rust:carol:3", solution, "rust", "carol", "c", true).await.unwrap();
+        assert!(engine.collusion_cache.lock().unwrap().is_empty());
+        let report = engine.similarity_report("c:rust:alice:1", engine.config()).await.unwrap().unwrap();
+        assert_eq!(report.cluster.unwrap().user_ids, vec!["alice", "bob", "carol"]);
+    }
+}
@@ -0,0 +1,193 @@
+use crate::anti_cheat::RiskLevel;
+use crate::fingerprint_store::MatchScope;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// How much each signal counts towards the similarity of two submissions.
+/// The first three are relative to each other; `tree_edit` is the share of
+/// the final score given to tree edit distance, from 0 to 1.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SimilarityWeights {
+    pub winnowing: f64,
+    pub token_sequence: f64,
+    pub structure: f64,
+    pub tree_edit: f64,
+}
+
+impl Default for SimilarityWeights {
+    fn default() -> Self {
+        Self { winnowing: 0.4, token_sequence: 0.4, structure: 0.2, tree_edit: 0.2 }
+    }
+}
+
+/// Lowest similarity at each risk level; anything below `medium` is low.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RiskCutoffs {
+    pub medium: f64,
+    pub high: f64,
+    pub critical: f64,
+}
+
+impl Default for RiskCutoffs {
+    fn default() -> Self {
+        Self { medium: 0.5, high: 0.7, critical: 0.9 }
+    }
+}
+
+/// Tuning of plagiarism detection: which submissions are compared, the
+/// similarity at which a match is reported, how the similarity signals are
+/// weighed, and the risk cutoffs.
+/// Set per deployment with `ANTI_CHEAT_CONFIG`, and per challenge with an
+/// `antiCheat` object in the challenge config (or an admin's payload), whose
+/// fields override the deployment's.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AntiCheatConfig {
+    pub scopes: Vec<MatchScope>,
+    /// Matches less similar than this are not reported
+    pub report_threshold: f64,
+    pub weights: SimilarityWeights,
+    pub risk_cutoffs: RiskCutoffs,
+}
+
+impl Default for AntiCheatConfig {
+    fn default() -> Self {
+        Self {
+            scopes: vec![MatchScope::OtherUsers],
+            report_threshold: 0.3,
+            weights: SimilarityWeights::default(), risk_cutoffs: RiskCutoffs::default(),
+        }
+    }
+}
+
+impl AntiCheatConfig {
+    /// `ANTI_CHEAT_CONFIG`, a JSON document, or the defaults.
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("ANTI_CHEAT_CONFIG") {
+            Ok(doc) if !doc.trim().is_empty() => {
+                let config: Self = serde_json::from_str(&doc).map_err(|e| format!("Invalid ANTI_CHEAT_CONFIG: {}", e))?;
+                config.validate()?;
+                Ok(config)
+            }
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// The payload's `antiCheat` overrides applied to `deployment`.
+    pub fn from_payload(payload: &Value, deployment: &Self) -> Result<Self, String> {
+        let overrides = match payload.get("antiCheat") {
+            None | Some(Value::Null) => return Ok(deployment.clone()),
+            Some(overrides @ Value::Object(_)) => overrides,
+            Some(_) => return Err("antiCheat must be an object".to_string()),
+        };
+        let mut merged = serde_json::to_value(deployment).map_err(|e| e.to_string())?;
+        merge(&mut merged, overrides);
+        let config: Self = serde_json::from_value(merged).map_err(|e| format!("Invalid antiCheat: {}", e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.scopes.is_empty() {
+            return Err("Anti-cheat scopes must not be empty".to_string());
+        }
+        let unit = |name: &str, value: f64| match (0.0..=1.0).contains(&value) {
+            true => Ok(()),
+            false => Err(format!("Anti-cheat {} must be between 0 and 1, got {}", name, value)),
+        };
+        unit("reportThreshold", self.report_threshold)?;
+        unit("weights.treeEdit", self.weights.tree_edit)?;
+        unit("riskCutoffs.medium", self.risk_cutoffs.medium)?;
+        unit("riskCutoffs.high", self.risk_cutoffs.high)?;
+        unit("riskCutoffs.critical", self.risk_cutoffs.critical)?;
+
+        let SimilarityWeights { winnowing, token_sequence, structure, .. } = self.weights;
+        if [winnowing, token_sequence, structure].iter().any(|w| !w.is_finite() || *w < 0.0) {
+            return Err("Anti-cheat weights must not be negative".to_string());
+        }
+        if winnowing + token_sequence + structure <= 0.0 {
+            return Err("Anti-cheat weights must not all be zero".to_string());
+        }
+        if !(self.risk_cutoffs.medium <= self.risk_cutoffs.high && self.risk_cutoffs.high <= self.risk_cutoffs.critical) {
+            return Err("Anti-cheat risk cutoffs must satisfy medium <= high <= critical".to_string());
+        }
+        Ok(())
+    }
+
+    /// Weighted similarity from the individual signals. A `None` tree
+    /// similarity (trees too large to compare) leaves the other signals.
+    pub fn combine(&self, winnowing: f64, token_sequence: f64, structure: f64, tree_edit: Option<f64>) -> f64 {
+        let w = &self.weights;
+        let combined = (w.winnowing * winnowing + w.token_sequence * token_sequence + w.structure * structure)
+            / (w.winnowing + w.token_sequence + w.structure);
+        match tree_edit {
+            Some(tree_edit) => (1.0 - w.tree_edit) * combined + w.tree_edit * tree_edit,
+            None => combined,
+        }
+    }
+
+    pub fn risk_level(&self, similarity: f64) -> RiskLevel {
+        match similarity {
+            s if s >= self.risk_cutoffs.critical => RiskLevel::Critical,
+            s if s >= self.risk_cutoffs.high => RiskLevel::High,
+            s if s >= self.risk_cutoffs.medium => RiskLevel::Medium,
+            _ => RiskLevel::Low,
+        }
+    }
+}
+
+/// Overwrite `base` with `overrides`, recursing into objects present in both.
+fn merge(base: &mut Value, overrides: &Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_challenge_overrides_deployment_config() {
+        let deployment = AntiCheatConfig { report_threshold: 0.5, ..Default::default() };
+        assert_eq!(AntiCheatConfig::from_payload(&json!({}), &deployment).unwrap(), deployment);
+
+        let config = AntiCheatConfig::from_payload(
+            &json!({ "antiCheat": { "weights": { "treeEdit": 0 }, "riskCutoffs": { "critical": 0.95 } } }),
+            &deployment,
+        )
+        .unwrap();
+        assert_eq!(config.report_threshold, 0.5);
+        assert_eq!(config.scopes, vec![MatchScope::OtherUsers]);
+        let scoped = AntiCheatConfig::from_payload(&json!({ "antiCheat": { "scopes": ["otherUsers", "ownHistory"] } }), &deployment);
+        assert_eq!(scoped.unwrap().scopes, vec![MatchScope::OtherUsers, MatchScope::OwnHistory]);
+        assert_eq!(config.weights.winnowing, 0.4);
+        assert!((config.combine(1.0, 0.5, 0.0, Some(0.0)) - 0.6).abs() < 1e-9);
+        assert_eq!(config.risk_level(0.92), RiskLevel::High);
+        assert_eq!(config.risk_level(0.95), RiskLevel::Critical);
+
+        for invalid in [
+            json!({ "antiCheat": { "reportThreshold": 1.5 } }),
+            json!({ "antiCheat": { "weights": { "winnowing": 0, "tokenSequence": 0, "structure": 0 } } }),
+            json!({ "antiCheat": { "riskCutoffs": { "medium": 0.8 } } }),
+            json!({ "antiCheat": "strict" }),
+            json!({ "antiCheat": { "scopes": [] } }),
+            json!({ "antiCheat": { "scopes": ["everything"] } }),
+        ] {
+            assert!(AntiCheatConfig::from_payload(&invalid, &deployment).is_err(), "{}", invalid);
+        }
+    }
+}
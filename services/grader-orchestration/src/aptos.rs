@@ -0,0 +1,214 @@
+use std::path::{Path, PathBuf};
+
+/// A single test outcome reported by `aptos move test`.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct MoveTestResult {
+    pub module: String,
+    pub name: String,
+    pub passed: bool,
+    pub reason: Option<String>,
+}
+
+/// aptos-core release the framework dependency is pinned to, the one of the
+/// Aptos CLI in the worker image, so every job builds against the same
+/// framework.
+pub const APTOS_FRAMEWORK_REV: &str = "aptos-cli-v4.2.3";
+
+/// The vendored `aptos-move/framework` directory of aptos-core at
+/// [`APTOS_FRAMEWORK_REV`]: `APTOS_FRAMEWORK_DIR`, default
+/// /opt/aptos-framework as installed by the worker image. Packages depend on
+/// it locally, so Move builds never need the network.
+pub fn framework_dir() -> PathBuf {
+    PathBuf::from(std::env::var("APTOS_FRAMEWORK_DIR").unwrap_or_else(|_| "/opt/aptos-framework".to_string()))
+}
+
+/// Named addresses of the framework and token standards, at 0x1, 0x3 and
+/// 0x4. Modules published there would shadow the framework's.
+const RESERVED_NAMED_ADDRESSES: &[&str] = &["std", "aptos_std", "aptos_framework", "aptos_token", "aptos_token_objects"];
+
+/// Move.toml of a package holding a submission, which publishes its modules
+/// at the `graded` address.
+pub fn move_toml(package_name: &str) -> String {
+    format!(
+        r#"
+[package]
+name = "{}"
+version = "0.1.0"
+
+[addresses]
+graded = "0xcafe"
+
+[dependencies]
+AptosStdlib = {{ local = "{}" }}
+"#,
+        package_name,
+        framework_dir().join("aptos-stdlib").display()
+    )
+}
+
+/// Reject submissions declaring modules at a framework address, e.g.
+/// `module 0x1::coin` or `address std { ... }`.
+pub fn check_module_addresses(code: &str) -> Result<(), String> {
+    let code: Vec<&str> = code.lines().map(|line| line.split("//").next().unwrap_or("")).collect();
+    let code = code.join("\n");
+    let tokens: Vec<&str> = code.split(|c: char| c.is_whitespace() || c == '{').filter(|t| !t.is_empty()).collect();
+
+    for pair in tokens.windows(2) {
+        let address = match pair[0] {
+            "module" => pair[1].split_once("::").map(|(address, _)| address),
+            "address" => Some(pair[1]),
+            _ => None,
+        };
+        if let Some(address) = address.filter(|address| is_reserved_address(address)) {
+            return Err(format!("Modules may not be declared at the reserved address {}", address));
+        }
+    }
+    Ok(())
+}
+
+fn is_reserved_address(address: &str) -> bool {
+    match address.strip_prefix("0x") {
+        Some(hex) => matches!(hex.trim_start_matches('0'), "1" | "3" | "4"),
+        None => RESERVED_NAMED_ADDRESSES.contains(&address),
+    }
+}
+
+/// Lay out a Move package (Move.toml + sources/) containing the submission.
+pub fn write_move_package(package_dir: &Path, code: &str) -> Result<(), String> {
+    check_module_addresses(code)?;
+    std::fs::create_dir_all(package_dir.join("sources"))
+        .map_err(|e| format!("Failed to create sources dir: {}", e))?;
+    std::fs::write(package_dir.join("Move.toml"), move_toml("graded_contract"))
+        .map_err(|e| format!("Failed to write Move.toml: {}", e))?;
+    std::fs::write(package_dir.join("sources").join("contract.move"), code)
+        .map_err(|e| format!("Failed to write contract.move: {}", e))?;
+    Ok(())
+}
+
+/// Parse the human readable output of `aptos move test`.
+///
+/// Each test is reported on its own line, e.g.
+/// `[ PASS    ] 0x1::message::test_set_message`. Failure details are printed
+/// afterwards in boxed sections headed by `┌── test_name ───`.
+pub fn parse_move_test_output(output: &str) -> Vec<MoveTestResult> {
+    let mut results = Vec::new();
+
+    for line in output.lines() {
+        let line = line.trim();
+        if !line.starts_with('[') {
+            continue;
+        }
+
+        let close = match line.find(']') {
+            Some(idx) => idx,
+            None => continue,
+        };
+
+        let status = line[1..close].trim();
+        let passed = match status {
+            "PASS" => true,
+            "FAIL" | "TIMEOUT" => false,
+            _ => continue,
+        };
+
+        let qualified = line[close + 1..].trim();
+        let (module, name) = match qualified.rfind("::") {
+            Some(idx) => (qualified[..idx].to_string(), qualified[idx + 2..].to_string()),
+            None => (String::new(), qualified.to_string()),
+        };
+
+        let reason = if status == "TIMEOUT" { Some("Test timed out".to_string()) } else { None };
+
+        results.push(MoveTestResult { module, name, passed, reason });
+    }
+
+    // Attach failure details to the failing tests
+    for result in results.iter_mut().filter(|r| !r.passed && r.reason.is_none()) {
+        result.reason = extract_failure_detail(output, &result.name);
+    }
+
+    results
+}
+
+/// Find the Move test that corresponds to a fixture by function name.
+pub fn find_test_for_fixture<'a>(
+    results: &'a [MoveTestResult],
+    fixture_id: &str,
+    fixture_name: &str,
+) -> Option<&'a MoveTestResult> {
+    results
+        .iter()
+        .find(|r| r.name == fixture_id || r.name == fixture_name)
+}
+
+fn extract_failure_detail(output: &str, test_name: &str) -> Option<String> {
+    let header = format!("┌── {} ", test_name);
+    let start = output.find(&header)?;
+
+    let detail: Vec<&str> = output[start..]
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.trim_start().starts_with('└'))
+        .map(|line| line.trim_start_matches('│').trim())
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    if detail.is_empty() {
+        None
+    } else {
+        Some(detail.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_move_test_output() {
+        let output = "\
+Running Move unit tests
+[ PASS    ] 0x1::message::test_set_message
+[ FAIL    ] 0x1::message::test_overflow
+[ TIMEOUT ] 0x1::message::test_loop
+
+Test failures:
+
+Failures in 0x1::message:
+
+┌── test_overflow ──────
+│ error[E11001]: test failure
+│ arithmetic error
+└──────────────────
+
+Test result: FAILED. Total tests: 3; passed: 1; failed: 2
+";
+
+        let results = parse_move_test_output(output);
+        assert_eq!(results.len(), 3);
+
+        let pass = find_test_for_fixture(&results, "test_set_message", "").unwrap();
+        assert!(pass.passed);
+        assert_eq!(pass.module, "0x1::message");
+
+        let overflow = find_test_for_fixture(&results, "", "test_overflow").unwrap();
+        assert!(!overflow.passed);
+        assert!(overflow.reason.as_ref().unwrap().contains("arithmetic error"));
+
+        let timeout = find_test_for_fixture(&results, "test_loop", "").unwrap();
+        assert_eq!(timeout.reason.as_deref(), Some("Test timed out"));
+    }
+
+    #[test]
+    fn test_framework_addresses_are_reserved() {
+        assert!(check_module_addresses("module graded::message {\n    use std::signer;\n}").is_ok());
+        assert!(check_module_addresses("module 0xcafe::message {}").is_ok());
+        // Only declarations count, not comments or uses
+        assert!(check_module_addresses("// module 0x1::coin\nmodule graded::coin { use 0x1::coin; }").is_ok());
+
+        for code in ["module 0x1::coin {}", "module 0x0001::coin{}", "module aptos_framework::coin {}", "address std {\n module vector {}\n}"] {
+            assert!(check_module_addresses(code).is_err(), "{}", code);
+        }
+        assert!(move_toml("graded_contract").contains(&format!("local = \"{}\"", framework_dir().join("aptos-stdlib").display())));
+    }
+}
@@ -0,0 +1,248 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// Directory inside the job workspace for artifacts the grader writes itself.
+pub const ARTIFACT_DIR: &str = ".fathuss";
+
+const DEFAULT_STORE_CAPACITY: usize = 1000;
+
+/// Reference to a file inside the job workspace, passed between stages (and
+/// returned to callers) instead of the file's contents. The hash lets a
+/// consumer check it is reading the artifact that was produced.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArtifactHandle {
+    /// Path relative to the job workspace
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+impl ArtifactHandle {
+    /// Create a handle for an existing workspace file, hashing it without
+    /// loading it into memory.
+    pub fn for_file(workspace: &Path, relative_path: &str) -> Result<Self, String> {
+        let path = resolve(workspace, relative_path)?;
+        let (sha256, size) = hash_file(&path)?;
+        Ok(Self {
+            path: relative_path.to_string(),
+            sha256,
+            size,
+        })
+    }
+
+    /// Open the artifact for reading, checking it still has the recorded hash.
+    pub fn open(&self, workspace: &Path) -> Result<File, String> {
+        let path = resolve(workspace, &self.path)?;
+        let (sha256, _) = hash_file(&path)?;
+        if sha256 != self.sha256 {
+            return Err(format!("Artifact {} changed since it was recorded", self.path));
+        }
+        File::open(&path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))
+    }
+}
+
+/// Artifacts of recent jobs, copied out of their workspaces before those are
+/// removed, so the handles returned to callers can still be downloaded.
+/// Stored as `{root}/{jobId}/{path}`; the oldest jobs' artifacts are deleted
+/// first. Each job id is claimed by the client grading it, which alone may
+/// download its artifacts besides admins.
+pub struct ArtifactStore {
+    root: PathBuf,
+    capacity: usize,
+    order: Mutex<VecDeque<String>>,
+    /// Client id each job id was claimed by
+    owners: Mutex<HashMap<String, String>>,
+}
+
+impl ArtifactStore {
+    pub fn from_env() -> Self {
+        let root = std::env::var("ARTIFACT_STORE_DIR").unwrap_or_else(|_| "/tmp/fathuss_artifacts".to_string());
+        let capacity = std::env::var("ARTIFACT_STORE_CAPACITY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_STORE_CAPACITY);
+        Self::new(PathBuf::from(root), capacity)
+    }
+
+    pub fn new(root: PathBuf, capacity: usize) -> Self {
+        Self {
+            root,
+            capacity,
+            order: Mutex::new(VecDeque::new()),
+            owners: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Claim `job_id` for the client `owner` before grading it, so another
+    /// client can neither overwrite nor download its artifacts.
+    pub fn claim(&self, job_id: &str, owner: &str) -> Result<(), String> {
+        {
+            let mut owners = self.owners.lock().unwrap();
+            match owners.get(job_id) {
+                Some(claimed) if claimed != owner => return Err(format!("Job {} belongs to another client", job_id)),
+                _ => owners.insert(job_id.to_string(), owner.to_string()),
+            };
+        }
+        self.remember(job_id);
+        Ok(())
+    }
+
+    /// The client that claimed the job, unless it was evicted or claimed
+    /// before the worker restarted.
+    pub fn owner(&self, job_id: &str) -> Option<String> {
+        self.owners.lock().unwrap().get(job_id).cloned()
+    }
+
+    /// Mark the job the most recent, deleting the oldest jobs' artifacts
+    /// beyond the capacity.
+    fn remember(&self, job_id: &str) {
+        let mut order = self.order.lock().unwrap();
+        order.retain(|id| id != job_id);
+        order.push_back(job_id.to_string());
+        while order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                self.owners.lock().unwrap().remove(&oldest);
+                let _ = std::fs::remove_dir_all(self.root.join(oldest));
+            }
+        }
+    }
+
+    fn job_dir(&self, job_id: &str) -> Result<PathBuf, String> {
+        // Job ids come from requests; keep them from escaping the store root
+        if job_id.is_empty() || !job_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Invalid job id for artifacts: {}", job_id));
+        }
+        Ok(self.root.join(job_id))
+    }
+
+    /// Copy the job's artifacts out of `workspace`, checking each still has
+    /// its recorded hash. Returns the handles, now valid against the store.
+    pub fn persist(&self, job_id: &str, workspace: &Path, handles: Vec<ArtifactHandle>) -> Result<Vec<ArtifactHandle>, String> {
+        if handles.is_empty() {
+            return Ok(handles);
+        }
+        let job_dir = self.job_dir(job_id)?;
+        for handle in &handles {
+            let target = resolve(&job_dir, &handle.path)?;
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+            }
+            let mut source = handle.open(workspace)?;
+            let mut file = File::create(&target).map_err(|e| format!("Failed to create {}: {}", target.display(), e))?;
+            std::io::copy(&mut source, &mut file).map_err(|e| format!("Failed to store artifact {}: {}", handle.path, e))?;
+        }
+
+        self.remember(job_id);
+        Ok(handles)
+    }
+
+    /// Like `persist`, for a single artifact.
+    pub fn persist_one(&self, job_id: &str, workspace: &Path, handle: ArtifactHandle) -> Result<ArtifactHandle, String> {
+        let mut handles = self.persist(job_id, workspace, vec![handle])?;
+        Ok(handles.remove(0))
+    }
+
+    /// Open a stored artifact of the job by its workspace-relative path.
+    pub fn open(&self, job_id: &str, path: &str) -> Result<File, String> {
+        let target = resolve(&self.job_dir(job_id)?, path)?;
+        File::open(&target).map_err(|_| format!("No artifact {} for job {}", path, job_id))
+    }
+}
+
+/// Stream `value` as JSON into the workspace artifact directory and return its
+/// handle, so large structures such as traces are never built as one string.
+pub fn write_json_artifact<T: Serialize>(workspace: &Path, name: &str, value: &T) -> Result<ArtifactHandle, String> {
+    let dir = workspace.join(ARTIFACT_DIR);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+
+    let path = dir.join(name);
+    let file = File::create(&path).map_err(|e| format!("Failed to create {}: {}", path.display(), e))?;
+    let mut writer = BufWriter::new(file);
+    serde_json::to_writer(&mut writer, value).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+    writer.flush().map_err(|e| format!("Failed to write {}: {}", name, e))?;
+
+    ArtifactHandle::for_file(workspace, &format!("{}/{}", ARTIFACT_DIR, name))
+}
+
+/// Write a text artifact (such as a report) into the workspace artifact directory.
+pub fn write_text_artifact(workspace: &Path, name: &str, contents: &str) -> Result<ArtifactHandle, String> {
+    let dir = workspace.join(ARTIFACT_DIR);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+    std::fs::write(dir.join(name), contents).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+
+    ArtifactHandle::for_file(workspace, &format!("{}/{}", ARTIFACT_DIR, name))
+}
+
+/// SHA-256 and size of a file, read in chunks.
+pub fn hash_file(path: &Path) -> Result<(String, u64), String> {
+    let mut file = File::open(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    let size = std::io::copy(&mut file, &mut hasher)
+        .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok((hex::encode(hasher.finalize()), size))
+}
+
+/// Join a relative artifact path onto the workspace, refusing paths that
+/// would leave it.
+fn resolve(workspace: &Path, relative_path: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(relative_path);
+    if !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("Artifact path escapes the workspace: {}", relative_path));
+    }
+    Ok(workspace.join(relative))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_handles_detect_changes_and_escapes() {
+        let workspace = tempfile::tempdir().unwrap();
+        let dir = workspace.path();
+
+        let handle = write_json_artifact(dir, "trace.json", &json!({ "events": [1, 2, 3] })).unwrap();
+        assert_eq!(handle.path, ".fathuss/trace.json");
+        assert_eq!(handle.size, std::fs::metadata(dir.join(&handle.path)).unwrap().len());
+        assert!(handle.open(dir).is_ok());
+
+        std::fs::write(dir.join(&handle.path), "{}").unwrap();
+        assert!(handle.open(dir).is_err());
+
+        assert!(ArtifactHandle::for_file(dir, "../outside").is_err());
+    }
+
+    #[test]
+    fn test_store_outlives_workspace_and_evicts_oldest() {
+        let root = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(root.path().to_path_buf(), 1);
+
+        let workspace = tempfile::tempdir().unwrap();
+        let handle = write_text_artifact(workspace.path(), "junit.xml", "<testsuites/>").unwrap();
+        let handle = store.persist_one("job-1", workspace.path(), handle).unwrap();
+        drop(workspace);
+
+        let mut stored = String::new();
+        std::io::Read::read_to_string(&mut store.open("job-1", &handle.path).unwrap(), &mut stored).unwrap();
+        assert_eq!(stored, "<testsuites/>");
+        assert!(store.open("job-1", "../job-2/junit.xml").is_err());
+        assert!(store.persist("../up", root.path(), vec![handle.clone()]).is_err());
+
+        store.claim("job-1", "backend").unwrap();
+        assert!(store.claim("job-1", "other").is_err());
+        assert_eq!(store.owner("job-1").as_deref(), Some("backend"));
+
+        let workspace = tempfile::tempdir().unwrap();
+        let handle = write_text_artifact(workspace.path(), "junit.xml", "<testsuites/>").unwrap();
+        store.persist_one("job-2", workspace.path(), handle).unwrap();
+        assert!(store.open("job-1", ".fathuss/junit.xml").is_err());
+        assert!(store.owner("job-1").is_none());
+    }
+}
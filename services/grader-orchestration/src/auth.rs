@@ -0,0 +1,365 @@
+use crate::aws_sigv4::hmac_sha256;
+use crate::quota::ANONYMOUS_KEY;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+use warp::Filter;
+
+/// What a client may do: `grade` submissions and follow its jobs, or
+/// `admin` everything else, such as reviews, reports and cache control.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scope {
+    Grade,
+    Admin,
+}
+
+impl std::fmt::Display for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Scope::Grade => "grade",
+            Scope::Admin => "admin",
+        })
+    }
+}
+
+/// An authenticated caller. Usage and quotas are tracked by its id.
+#[derive(Clone, Debug)]
+pub struct Client {
+    pub id: String,
+    pub scopes: Vec<Scope>,
+    /// Authenticated by a user's own token, so it acts as that user only.
+    /// API key clients are services submitting on behalf of their users.
+    pub is_user: bool,
+}
+
+impl Client {
+    /// Admins may also grade.
+    pub fn allows(&self, scope: Scope) -> bool {
+        self.scopes.contains(&scope) || self.scopes.contains(&Scope::Admin)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AuthError {
+    /// No credentials, or credentials that aren't valid
+    Unauthorized(String),
+    /// Valid credentials without the scope
+    Forbidden(String),
+}
+
+impl std::fmt::Display for AuthError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AuthError::Unauthorized(message) | AuthError::Forbidden(message) => f.write_str(message),
+        }
+    }
+}
+
+impl warp::reject::Reject for AuthError {}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ApiKeyEntry {
+    client: String,
+    key: Option<String>,
+    /// The key's hex SHA-256 digest, to keep the key itself out of the config
+    key_sha256: Option<String>,
+    scopes: Vec<Scope>,
+}
+
+struct ApiKey {
+    client: String,
+    digest: Vec<u8>,
+    scopes: Vec<Scope>,
+}
+
+/// Checks the API key (`X-API-Key`) or HS256 JWT (`Authorization: Bearer`)
+/// of each request. With authentication explicitly disabled every request
+/// is let through, identified by its `X-API-Key` as before.
+pub struct Authenticator {
+    api_keys: Vec<ApiKey>,
+    jwt_secret: Option<Vec<u8>>,
+    disabled: bool,
+}
+
+impl Authenticator {
+    /// Credentials are required unless `disabled`; without any configured
+    /// no request could be let in, so that is an error.
+    pub fn new(api_keys_json: Option<&str>, jwt_secret: Option<&str>, disabled: bool) -> Result<Self, String> {
+        let entries: Vec<ApiKeyEntry> = match api_keys_json {
+            Some(json) => serde_json::from_str(json).map_err(|e| format!("Invalid API_KEYS: {}", e))?,
+            None => vec![],
+        };
+        let api_keys = entries
+            .into_iter()
+            .map(|entry| {
+                let digest = match (&entry.key, &entry.key_sha256) {
+                    (Some(key), None) => Sha256::digest(key.as_bytes()).to_vec(),
+                    (None, Some(digest)) => hex::decode(digest).ok().filter(|d| d.len() == 32).ok_or_else(|| {
+                        format!("Invalid API_KEYS: keySha256 of client {} isn't a hex SHA-256 digest", entry.client)
+                    })?,
+                    _ => return Err(format!("Invalid API_KEYS: client {} needs exactly one of key and keySha256", entry.client)),
+                };
+                Ok(ApiKey { client: entry.client, digest, scopes: entry.scopes })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let jwt_secret = jwt_secret.filter(|s| !s.is_empty()).map(|s| s.as_bytes().to_vec());
+        match (disabled, !api_keys.is_empty() || jwt_secret.is_some()) {
+            (true, true) => Err("AUTH_DISABLED is set along with API_KEYS or JWT_SECRET".to_string()),
+            (false, false) => Err("Set API_KEYS or JWT_SECRET, or AUTH_DISABLED=true to run without authentication".to_string()),
+            _ => Ok(Self { api_keys, jwt_secret, disabled }),
+        }
+    }
+
+    /// `API_KEYS`, a JSON array of `{"client", "key" or "keySha256",
+    /// "scopes"}`, `JWT_SECRET`, the HS256 secret of bearer tokens, and
+    /// `AUTH_DISABLED`, to open the API to anyone who can reach it.
+    pub fn from_env() -> Result<Self, String> {
+        let disabled = std::env::var("AUTH_DISABLED").map(|v| v == "true" || v == "1").unwrap_or(false);
+        Self::new(std::env::var("API_KEYS").ok().as_deref(), std::env::var("JWT_SECRET").ok().as_deref(), disabled)
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.disabled
+    }
+
+    /// The client behind a request's credentials, if it may act in `scope`.
+    pub fn authenticate(&self, api_key: Option<&str>, authorization: Option<&str>, scope: Scope) -> Result<Client, AuthError> {
+        if !self.enabled() {
+            return Ok(Client {
+                id: api_key.unwrap_or(ANONYMOUS_KEY).to_string(),
+                scopes: vec![Scope::Grade, Scope::Admin],
+                is_user: false,
+            });
+        }
+
+        let bearer = authorization.and_then(|value| value.strip_prefix("Bearer "));
+        let client = match (api_key, bearer) {
+            (Some(key), _) => self.client_for_key(key),
+            (None, Some(token)) => self.client_for_token(token),
+            (None, None) => Err(AuthError::Unauthorized("Missing API key or bearer token".to_string())),
+        }?;
+        match client.allows(scope) {
+            true => Ok(client),
+            false => Err(AuthError::Forbidden(format!("Client {} lacks the {} scope", client.id, scope))),
+        }
+    }
+
+    fn client_for_key(&self, key: &str) -> Result<Client, AuthError> {
+        // Every configured key is compared, in constant time, so timing
+        // reveals neither a key nor which client it belongs to
+        let digest = Sha256::digest(key.as_bytes());
+        let mut found = None;
+        for api_key in &self.api_keys {
+            if constant_time_eq(&api_key.digest, &digest) {
+                found = Some(api_key);
+            }
+        }
+        found
+            .map(|api_key| Client { id: api_key.client.clone(), scopes: api_key.scopes.clone(), is_user: false })
+            .ok_or_else(|| AuthError::Unauthorized("Invalid API key".to_string()))
+    }
+
+    /// A JWT signed with HS256 under `JWT_SECRET`, naming the client in
+    /// `sub` and its scopes in the space-separated `scope` claim.
+    fn client_for_token(&self, token: &str) -> Result<Client, AuthError> {
+        let invalid = |reason: &str| AuthError::Unauthorized(format!("Invalid bearer token: {}", reason));
+        let secret = self.jwt_secret.as_ref().ok_or_else(|| invalid("bearer tokens aren't accepted"))?;
+        let parts: Vec<&str> = token.split('.').collect();
+        let (header, claims, signature) = match parts[..] {
+            [header, claims, signature] => (header, claims, signature),
+            _ => return Err(invalid("malformed")),
+        };
+        let decode = |part: &str| -> Result<Value, AuthError> {
+            let bytes = URL_SAFE_NO_PAD.decode(part).map_err(|_| invalid("malformed"))?;
+            serde_json::from_slice(&bytes).map_err(|_| invalid("malformed"))
+        };
+
+        if decode(header)?.get("alg").and_then(|v| v.as_str()) != Some("HS256") {
+            return Err(invalid("only HS256 is accepted"));
+        }
+        let expected = hmac_sha256(secret, format!("{}.{}", header, claims).as_bytes());
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid("malformed"))?;
+        if !constant_time_eq(&expected, &signature) {
+            return Err(invalid("bad signature"));
+        }
+
+        let claims = decode(claims)?;
+        let now = chrono::Utc::now().timestamp();
+        if claims.get("exp").and_then(|v| v.as_i64()).is_some_and(|exp| exp <= now) {
+            return Err(invalid("expired"));
+        }
+        if claims.get("nbf").and_then(|v| v.as_i64()).is_some_and(|nbf| nbf > now) {
+            return Err(invalid("not yet valid"));
+        }
+        let id = claims.get("sub").and_then(|v| v.as_str()).ok_or_else(|| invalid("no sub claim"))?;
+        let scopes = claims
+            .get("scope")
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .split_whitespace()
+            .filter_map(|scope| serde_json::from_value(Value::String(scope.to_string())).ok())
+            .collect();
+        Ok(Client { id: id.to_string(), scopes, is_user: true })
+    }
+}
+
+/// Job fields that change how a submission is judged rather than what is
+/// submitted, which only admins may set. Challenges set them in their config.
+const PRIVILEGED_FIELDS: &[&str] = &[
+    "fixturesVersion",
+    "antiCheat",
+    "buildPolicy",
+    "scoringRubric",
+    "fuzzing",
+    "functionSignature",
+    "toolchain",
+    "gasLimit",
+    "timeLimit",
+];
+
+/// Reject a job setting fields its client isn't allowed to: the privileged
+/// fields, validate mode, which reveals hidden tests, or a user's `userId`
+/// other than their own.
+pub fn authorize_job(payload: &Value, client: &Client) -> Result<(), AuthError> {
+    if client.allows(Scope::Admin) {
+        return Ok(());
+    }
+    let forbidden = |field: &str| AuthError::Forbidden(format!("Client {} may not set {}", client.id, field));
+    if let Some(field) = PRIVILEGED_FIELDS.iter().find(|field| payload.get(**field).is_some_and(|v| !v.is_null())) {
+        return Err(forbidden(field));
+    }
+    if payload.get("mode").and_then(|v| v.as_str()) == Some("validate") {
+        return Err(forbidden("mode"));
+    }
+    match payload.get("userId").and_then(|v| v.as_str()) {
+        Some(user_id) if client.is_user && user_id != client.id => Err(forbidden("userId")),
+        _ => Ok(()),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The client of a request allowed to act in `scope`; other requests are
+/// rejected with an `AuthError`.
+pub fn client(
+    authenticator: Arc<Authenticator>,
+    scope: Scope,
+) -> impl Filter<Extract = (Client,), Error = warp::Rejection> + Clone {
+    warp::header::optional::<String>("x-api-key")
+        .and(warp::header::optional::<String>("authorization"))
+        .and_then(move |api_key: Option<String>, authorization: Option<String>| {
+            let result = authenticator.authenticate(api_key.as_deref(), authorization.as_deref(), scope);
+            async move { result.map_err(warp::reject::custom) }
+        })
+}
+
+/// Rejects requests not allowed to act in `scope`.
+pub fn require(
+    authenticator: Arc<Authenticator>,
+    scope: Scope,
+) -> impl Filter<Extract = (), Error = warp::Rejection> + Clone {
+    client(authenticator, scope).map(|_| ()).untuple_one()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(secret: &str, claims: Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#);
+        let claims = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let signature = URL_SAFE_NO_PAD.encode(hmac_sha256(secret.as_bytes(), format!("{}.{}", header, claims).as_bytes()));
+        format!("{}.{}.{}", header, claims, signature)
+    }
+
+    #[test]
+    fn test_api_keys_tokens_and_scopes() {
+        let keys = r#"[
+            {"client": "backend", "key": "s3cret", "scopes": ["grade"]},
+            {"client": "ops", "keySha256": "2bb80d537b1da3e38bd30361aa855686bde0eacd7162fef6a25fe97bf527a25b", "scopes": ["admin"]}
+        ]"#;
+        let auth = Authenticator::new(Some(keys), Some("jwt-secret"), false).unwrap();
+
+        assert_eq!(auth.authenticate(Some("s3cret"), None, Scope::Grade).unwrap().id, "backend");
+        assert!(matches!(auth.authenticate(Some("s3cret"), None, Scope::Admin), Err(AuthError::Forbidden(_))));
+        // sha256("secret")
+        assert_eq!(auth.authenticate(Some("secret"), None, Scope::Grade).unwrap().id, "ops");
+        assert!(matches!(auth.authenticate(Some("wrong"), None, Scope::Grade), Err(AuthError::Unauthorized(_))));
+        assert!(matches!(auth.authenticate(None, None, Scope::Grade), Err(AuthError::Unauthorized(_))));
+
+        let valid = token("jwt-secret", serde_json::json!({ "sub": "user-7", "scope": "grade", "exp": chrono::Utc::now().timestamp() + 60 }));
+        let client = auth.authenticate(None, Some(&format!("Bearer {}", valid)), Scope::Grade).unwrap();
+        assert_eq!((client.id.as_str(), client.scopes.as_slice()), ("user-7", &[Scope::Grade][..]));
+        let forged = token("other-secret", serde_json::json!({ "sub": "user-7", "scope": "admin" }));
+        assert!(matches!(auth.authenticate(None, Some(&format!("Bearer {}", forged)), Scope::Grade), Err(AuthError::Unauthorized(_))));
+        let expired = token("jwt-secret", serde_json::json!({ "sub": "user-7", "scope": "grade", "exp": 1 }));
+        assert!(matches!(auth.authenticate(None, Some(&format!("Bearer {}", expired)), Scope::Grade), Err(AuthError::Unauthorized(_))));
+
+        // The API is only open when explicitly disabled
+        assert!(Authenticator::new(None, None, false).is_err());
+        assert!(Authenticator::new(Some(keys), None, true).is_err());
+        let open = Authenticator::new(None, None, true).unwrap();
+        assert!(!open.enabled());
+        assert_eq!(open.authenticate(Some("any"), None, Scope::Admin).unwrap().id, "any");
+        assert_eq!(open.authenticate(None, None, Scope::Admin).unwrap().id, ANONYMOUS_KEY);
+        assert!(Authenticator::new(Some(r#"[{"client": "x", "scopes": []}]"#), None, false).is_err());
+    }
+
+    #[test]
+    fn test_privileged_job_fields_need_admin() {
+        let student = Client { id: "user-7".to_string(), scopes: vec![Scope::Grade], is_user: true };
+        let backend = Client { id: "backend".to_string(), scopes: vec![Scope::Grade], is_user: false };
+        let admin = Client { id: "ops".to_string(), scopes: vec![Scope::Admin], is_user: true };
+        let pinned = serde_json::json!({ "code": "x", "fixturesVersion": "v1" });
+        assert!(matches!(authorize_job(&pinned, &student), Err(AuthError::Forbidden(_))));
+        assert!(authorize_job(&pinned, &admin).is_ok());
+
+        assert!(authorize_job(&serde_json::json!({ "userId": "user-7" }), &student).is_ok());
+        assert!(matches!(authorize_job(&serde_json::json!({ "userId": "user-8" }), &student), Err(AuthError::Forbidden(_))));
+        // Services submit for their users
+        assert!(authorize_job(&serde_json::json!({ "userId": "user-8" }), &backend).is_ok());
+        assert!(matches!(authorize_job(&pinned, &backend), Err(AuthError::Forbidden(_))));
+        assert!(authorize_job(&serde_json::json!({ "userId": "user-8" }), &admin).is_ok());
+
+        let validate = serde_json::json!({ "code": "x", "mode": "validate" });
+        assert!(matches!(authorize_job(&validate, &student), Err(AuthError::Forbidden(_))));
+        assert!(matches!(authorize_job(&validate, &backend), Err(AuthError::Forbidden(_))));
+        assert!(authorize_job(&validate, &admin).is_ok());
+        assert!(authorize_job(&serde_json::json!({ "mode": "changed" }), &student).is_ok());
+
+        let lenient = serde_json::json!({ "code": "x", "antiCheat": { "reportThreshold": 1.0 } });
+        assert!(matches!(authorize_job(&lenient, &backend), Err(AuthError::Forbidden(_))));
+        assert!(authorize_job(&lenient, &admin).is_ok());
+        let unconfined = serde_json::json!({ "code": "x", "buildPolicy": { "mode": "allow" } });
+        assert!(matches!(authorize_job(&unconfined, &student), Err(AuthError::Forbidden(_))));
+        // A submitter could otherwise pass itself with a zero threshold
+        let generous = serde_json::json!({ "code": "x", "scoringRubric": { "passThreshold": 0, "crashPenalty": 0 } });
+        assert!(matches!(authorize_job(&generous, &student), Err(AuthError::Forbidden(_))));
+        assert!(matches!(authorize_job(&generous, &backend), Err(AuthError::Forbidden(_))));
+        assert!(authorize_job(&generous, &admin).is_ok());
+        let seeded = serde_json::json!({ "code": "x", "fuzzing": { "maxIterations": 10, "seed": 7 } });
+        assert!(matches!(authorize_job(&seeded, &backend), Err(AuthError::Forbidden(_))));
+        assert!(authorize_job(&seeded, &admin).is_ok());
+        // A budget of one input would leave the submission unfuzzed
+        let cut_short = serde_json::json!({ "fuzzing": { "maxIterations": 1 } });
+        assert!(matches!(authorize_job(&cut_short, &backend), Err(AuthError::Forbidden(_))));
+        assert!(matches!(authorize_job(&cut_short, &student), Err(AuthError::Forbidden(_))));
+
+        for policy in [
+            serde_json::json!({ "gasLimit": u64::MAX }),
+            serde_json::json!({ "timeLimit": 3600 }),
+            serde_json::json!({ "toolchain": { "solc": "0.4.11" } }),
+            serde_json::json!({ "functionSignature": { "name": "main", "params": [] } }),
+        ] {
+            assert!(matches!(authorize_job(&policy, &backend), Err(AuthError::Forbidden(_))));
+            assert!(authorize_job(&policy, &admin).is_ok());
+        }
+    }
+}
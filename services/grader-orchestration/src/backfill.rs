@@ -0,0 +1,95 @@
+use crate::anti_cheat::AntiCheatEngine;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+pub struct BackfillConfig {
+    /// Submissions re-fingerprinted per batch
+    pub batch_size: usize,
+    /// Pause between batches, bounding the load a backfill adds to the engine
+    pub batch_delay: Duration,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            batch_size: 100,
+            batch_delay: Duration::from_millis(500),
+        }
+    }
+}
+
+impl BackfillConfig {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            batch_size: std::env::var("ANTI_CHEAT_BACKFILL_BATCH_SIZE")
+                .ok()
+                .and_then(|size| size.parse().ok())
+                .unwrap_or(default.batch_size),
+            batch_delay: std::env::var("ANTI_CHEAT_BACKFILL_DELAY_MS")
+                .ok()
+                .and_then(|ms| ms.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(default.batch_delay),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillProgress {
+    pub total: usize,
+    pub processed: usize,
+    pub updated: usize,
+    pub failed: Vec<String>,
+    pub completed: bool,
+}
+
+/// Re-fingerprint every stored submission whose fingerprint predates the
+/// current algorithm version, so comparisons stay consistent after upgrades.
+///
+/// Batches are spaced out so plagiarism checks aren't starved of the
+/// fingerprint store; `progress` is updated after each batch.
+pub async fn run_backfill(
+    engine: Arc<AntiCheatEngine>,
+    config: BackfillConfig,
+    progress: Arc<Mutex<BackfillProgress>>,
+) -> BackfillProgress {
+    let stale = match engine.stale_submissions().await {
+        Ok(stale) => stale,
+        Err(e) => {
+            eprintln!("Warning: Failed to list stale fingerprints: {}", e);
+            Vec::new()
+        }
+    };
+    progress.lock().await.total = stale.len();
+
+    for (index, batch) in stale.chunks(config.batch_size.max(1)).enumerate() {
+        if index > 0 {
+            tokio::time::sleep(config.batch_delay).await;
+        }
+
+        let mut updated = 0;
+        let mut failed = Vec::new();
+        for submission_id in batch {
+            match engine.refingerprint(submission_id).await {
+                Ok(()) => updated += 1,
+                Err(e) => {
+                    eprintln!("Warning: Failed to re-fingerprint {}: {}", submission_id, e);
+                    failed.push(submission_id.clone());
+                }
+            }
+        }
+
+        let mut progress = progress.lock().await;
+        progress.processed += batch.len();
+        progress.updated += updated;
+        progress.failed.extend(failed);
+    }
+
+    let mut progress = progress.lock().await;
+    progress.completed = true;
+    progress.clone()
+}
@@ -0,0 +1,162 @@
+use rand::Rng;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Retries with exponential backoff and full jitter: the wait before retry
+/// `n` is uniform in `[0, min(maxDelay, baseDelay * 2^(n-1))]`.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// `FIXTURES_RETRY_ATTEMPTS` and `FIXTURES_RETRY_BASE_MS`, over the defaults.
+    pub fn from_env() -> Self {
+        let defaults = Self::default();
+        Self {
+            max_attempts: std::env::var("FIXTURES_RETRY_ATTEMPTS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(|attempts: u32| attempts.max(1))
+                .unwrap_or(defaults.max_attempts),
+            base_delay: std::env::var("FIXTURES_RETRY_BASE_MS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .map(Duration::from_millis)
+                .unwrap_or(defaults.base_delay),
+            ..defaults
+        }
+    }
+
+    /// Wait before retry number `retry` (1 for the first retry).
+    pub fn delay(&self, retry: u32) -> Duration {
+        let ceiling = self.base_delay.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1))).min(self.max_delay);
+        let ceiling_ms = ceiling.as_millis() as u64;
+        Duration::from_millis(rand::thread_rng().gen_range(0..=ceiling_ms))
+    }
+}
+
+struct BreakerState {
+    consecutive_failures: u32,
+    open_until: Option<Instant>,
+    /// A trial call is in flight after the cooldown
+    half_open: bool,
+}
+
+/// Stops calling a failing dependency for a cooldown once it has failed
+/// `failure_threshold` times in a row, so jobs fail fast instead of each
+/// waiting through their retries. After the cooldown one call is let through
+/// and the others keep failing fast; its success closes the breaker, its
+/// failure reopens it. A trial call that never reports back is replaced by
+/// another after a further cooldown.
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            state: Mutex::new(BreakerState { consecutive_failures: 0, open_until: None, half_open: false }),
+        }
+    }
+
+    /// `FIXTURES_BREAKER_THRESHOLD` (default 5) and
+    /// `FIXTURES_BREAKER_COOLDOWN_SECS` (default 30).
+    pub fn from_env() -> Self {
+        let threshold = std::env::var("FIXTURES_BREAKER_THRESHOLD").ok().and_then(|v| v.parse().ok()).unwrap_or(5);
+        let cooldown = std::env::var("FIXTURES_BREAKER_COOLDOWN_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(30);
+        Self::new(threshold, Duration::from_secs(cooldown))
+    }
+
+    /// Err while the breaker is open.
+    pub fn check(&self) -> Result<(), String> {
+        let mut state = self.state.lock().unwrap();
+        match state.open_until {
+            Some(until) if Instant::now() < until && state.half_open => Err("Circuit breaker half-open, waiting on a trial call".to_string()),
+            Some(until) if Instant::now() < until => Err(format!(
+                "Circuit breaker open after {} consecutive failures, retrying in {}s",
+                state.consecutive_failures,
+                until.saturating_duration_since(Instant::now()).as_secs() + 1
+            )),
+            Some(_) => {
+                // Half-open: let only this call through, and reopen if it fails
+                state.open_until = Some(Instant::now() + self.cooldown);
+                state.half_open = true;
+                state.consecutive_failures = self.failure_threshold - 1;
+                Ok(())
+            },
+            None => Ok(()),
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures = 0;
+        state.open_until = None;
+        state.half_open = false;
+    }
+
+    pub fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.consecutive_failures += 1;
+        state.half_open = false;
+        if state.consecutive_failures >= self.failure_threshold {
+            state.open_until = Some(Instant::now() + self.cooldown);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_and_breaker() {
+        let policy = RetryPolicy { max_attempts: 5, base_delay: Duration::from_millis(100), max_delay: Duration::from_millis(300) };
+        for _ in 0..20 {
+            assert!(policy.delay(1) <= Duration::from_millis(100));
+            assert!(policy.delay(2) <= Duration::from_millis(200));
+            assert!(policy.delay(10) <= Duration::from_millis(300));
+        }
+
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(60));
+        breaker.record_failure();
+        assert!(breaker.check().is_ok());
+        breaker.record_failure();
+        assert!(breaker.check().is_err());
+
+        // After the cooldown a single trial call decides
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(50));
+        breaker.record_failure();
+        breaker.record_failure();
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.check().is_ok());
+        // Concurrent calls keep failing fast while it is in flight
+        assert_eq!(breaker.check(), Err("Circuit breaker half-open, waiting on a trial call".to_string()));
+        breaker.record_failure();
+        assert!(breaker.check().unwrap_err().starts_with("Circuit breaker open after 2"));
+
+        std::thread::sleep(Duration::from_millis(60));
+        assert!(breaker.check().is_ok());
+        breaker.record_success();
+        assert!(breaker.check().is_ok());
+        assert!(breaker.check().is_ok());
+    }
+}
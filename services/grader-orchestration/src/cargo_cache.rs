@@ -0,0 +1,273 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Per-job CARGO_HOME, inside the workspace's artifact directory.
+const JOB_CARGO_HOME: &str = ".fathuss/cargo-home";
+
+/// Parts of CARGO_HOME that hold downloaded crates and are shared between jobs.
+const SHARED_HOME_DIRS: &[&str] = &["registry", "git"];
+
+/// Caches shared by every Rust build on the worker, so a job doesn't start
+/// from a cold registry and rebuild serde on every submission.
+///
+/// Each job gets its own CARGO_HOME whose `registry` and `git` directories
+/// link to the shared home, which the worker image pre-populates and mounts
+/// read-only. Compiled dependencies come from either sccache or a warm target
+/// directory that is copied into the job's workspace before the build, so
+/// concurrent jobs never write to (or wait on the lock of) a shared target.
+///
+/// Both are keyed by the job's dependency set, `{dir}/{key}`, so a build only
+/// reuses artifacts compiled from the same dependencies. Job builds run
+/// untrusted code and only read the sccache; it is written by trusted warm-up
+/// builds with `writable` set.
+///
+/// With a vendor directory configured, builds are offline: crates.io is
+/// replaced by the vendored bundle, so cargo never needs the network while the
+/// sandbox has it disabled.
+#[derive(Clone, Debug, Default)]
+pub struct CargoCache {
+    pub shared_home: Option<PathBuf>,
+    pub target_seed: Option<PathBuf>,
+    pub sccache_dir: Option<PathBuf>,
+    pub vendor_dir: Option<PathBuf>,
+    pub writable: bool,
+}
+
+impl CargoCache {
+    /// The worker's caches, read from the environment once.
+    pub fn global() -> &'static CargoCache {
+        static CACHE: OnceLock<CargoCache> = OnceLock::new();
+        CACHE.get_or_init(CargoCache::from_env)
+    }
+
+    /// `CARGO_SHARED_HOME`, `CARGO_SHARED_TARGET_DIR`, `SCCACHE_DIR` and
+    /// `CARGO_VENDOR_DIR`; each is disabled when its variable is unset.
+    /// `CARGO_CACHE_WRITABLE=true` lets builds write to the sccache.
+    pub fn from_env() -> Self {
+        let dir = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty()).map(PathBuf::from);
+        Self {
+            shared_home: dir("CARGO_SHARED_HOME"),
+            target_seed: dir("CARGO_SHARED_TARGET_DIR"),
+            sccache_dir: dir("SCCACHE_DIR"),
+            vendor_dir: dir("CARGO_VENDOR_DIR"),
+            writable: std::env::var("CARGO_CACHE_WRITABLE").map(|v| v == "true").unwrap_or(false),
+        }
+    }
+
+    /// Set up the job's overlay in `workspace` and return the environment
+    /// cargo should run with.
+    pub async fn prepare(&self, workspace: &Path) -> Result<Vec<(String, String)>, String> {
+        let mut env = Vec::new();
+
+        if self.shared_home.is_some() || self.vendor_dir.is_some() {
+            let job_home = workspace.join(JOB_CARGO_HOME);
+            std::fs::create_dir_all(&job_home)
+                .map_err(|e| format!("Failed to create {}: {}", job_home.display(), e))?;
+            env.push(("CARGO_HOME".to_string(), job_home.to_string_lossy().to_string()));
+
+            if let Some(shared_home) = &self.shared_home {
+                for dir in SHARED_HOME_DIRS {
+                    let link = job_home.join(dir);
+                    if shared_home.join(dir).is_dir() && !link.exists() {
+                        std::os::unix::fs::symlink(shared_home.join(dir), &link)
+                            .map_err(|e| format!("Failed to link shared cargo {}: {}", dir, e))?;
+                    }
+                }
+                // Registry and source replacement settings apply to every job
+                let config = shared_home.join("config.toml");
+                if config.is_file() && self.vendor_dir.is_none() {
+                    std::fs::copy(&config, job_home.join("config.toml"))
+                        .map_err(|e| format!("Failed to copy cargo config: {}", e))?;
+                }
+            }
+
+            if let Some(vendor_dir) = &self.vendor_dir {
+                check_vendored(workspace, vendor_dir)?;
+                std::fs::write(job_home.join("config.toml"), vendored_config(vendor_dir))
+                    .map_err(|e| format!("Failed to write cargo config: {}", e))?;
+                env.push(("CARGO_NET_OFFLINE".to_string(), "true".to_string()));
+            }
+        }
+
+        let key = dependency_key(workspace);
+        let sccache_dir = self.sccache_dir.as_ref().map(|dir| dir.join(&key));
+        match sccache_dir {
+            // A read-only cache that was never warmed has nothing to offer
+            Some(dir) if self.writable || dir.is_dir() => {
+                env.push(("RUSTC_WRAPPER".to_string(), "sccache".to_string()));
+                env.push(("SCCACHE_DIR".to_string(), dir.to_string_lossy().to_string()));
+                let mode = if self.writable { "READ_WRITE" } else { "READ_ONLY" };
+                env.push(("SCCACHE_LOCAL_RW_MODE".to_string(), mode.to_string()));
+            }
+            _ => {
+                if let Some(seed) = self.target_seed.as_ref().map(|dir| dir.join(&key)) {
+                    let target = workspace.join("target");
+                    if seed.is_dir() && !target.exists() {
+                        tokio::task::spawn_blocking(move || crate::replay::copy_dir(&seed, &target))
+                            .await
+                            .map_err(|e| format!("Target seed copy failed: {}", e))??;
+                    }
+                }
+            }
+        }
+
+        Ok(env)
+    }
+}
+
+/// Key of the workspace's dependency set: its `Cargo.lock` when there is one,
+/// otherwise the dependency tables of its `Cargo.toml`. A manifest that
+/// doesn't parse is hashed whole and left for cargo to report.
+pub fn dependency_key(workspace: &Path) -> String {
+    let mut hasher = Sha256::new();
+    if let Ok(lock) = std::fs::read(workspace.join("Cargo.lock")) {
+        hasher.update(b"lock\0");
+        hasher.update(&lock);
+    } else if let Ok(manifest) = std::fs::read_to_string(workspace.join("Cargo.toml")) {
+        hasher.update(b"manifest\0");
+        match manifest.parse::<toml::Value>() {
+            Ok(manifest) => {
+                for section in ["dependencies", "dev-dependencies", "build-dependencies"] {
+                    if let Some(deps) = manifest.get(section) {
+                        hasher.update(section.as_bytes());
+                        hasher.update(deps.to_string().as_bytes());
+                    }
+                }
+            }
+            Err(_) => hasher.update(manifest.as_bytes()),
+        }
+    }
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// Cargo config replacing crates.io with the vendored bundle.
+fn vendored_config(vendor_dir: &Path) -> String {
+    format!(
+        r#"[source.crates-io]
+replace-with = "vendored-sources"
+
+[source.vendored-sources]
+directory = "{}"
+
+[net]
+offline = true
+"#,
+        vendor_dir.display()
+    )
+}
+
+/// Crate names in a `cargo vendor` directory, read from each crate's manifest.
+fn vendored_crates(vendor_dir: &Path) -> Result<HashSet<String>, String> {
+    let entries = std::fs::read_dir(vendor_dir)
+        .map_err(|e| format!("Failed to read vendored crates at {}: {}", vendor_dir.display(), e))?;
+
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| {
+            let manifest: toml::Value = std::fs::read_to_string(entry.path().join("Cargo.toml")).ok()?.parse().ok()?;
+            manifest.get("package")?.get("name")?.as_str().map(String::from)
+        })
+        .collect())
+}
+
+/// Fail early, naming the crates, when the workspace depends on crates that
+/// aren't vendored; cargo's own offline resolution error is much less clear.
+fn check_vendored(workspace: &Path, vendor_dir: &Path) -> Result<(), String> {
+    let manifest = match std::fs::read_to_string(workspace.join("Cargo.toml")) {
+        Ok(manifest) => manifest,
+        Err(_) => return Ok(()),
+    };
+    let manifest: toml::Value = manifest.parse().map_err(|e| format!("Failed to parse Cargo.toml: {}", e))?;
+    let available = vendored_crates(vendor_dir)?;
+
+    let mut missing: Vec<String> = ["dependencies", "dev-dependencies", "build-dependencies"]
+        .iter()
+        .filter_map(|section| manifest.get(section).and_then(|deps| deps.as_table()))
+        .flat_map(|deps| deps.iter())
+        .filter(|(_, spec)| spec.get("path").is_none())
+        .map(|(name, spec)| spec.get("package").and_then(|p| p.as_str()).unwrap_or(name).to_string())
+        .filter(|name| !available.contains(name))
+        .collect();
+    missing.sort();
+    missing.dedup();
+
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(format!("Dependencies not available for offline builds: {}", missing.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_prepare_links_shared_home_and_seeds_target() {
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::write(workspace.path().join("Cargo.toml"), "[dependencies]\nserde = \"1.0\"\n").unwrap();
+        let key = dependency_key(workspace.path());
+
+        let shared = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(shared.path().join("home/registry/cache")).unwrap();
+        std::fs::create_dir_all(shared.path().join("target").join(&key).join("release/deps")).unwrap();
+        std::fs::write(shared.path().join("target").join(&key).join("release/deps/libserde.rlib"), "rlib").unwrap();
+
+        let cache = CargoCache {
+            shared_home: Some(shared.path().join("home")),
+            target_seed: Some(shared.path().join("target")),
+            ..CargoCache::default()
+        };
+        let env = cache.prepare(workspace.path()).await.unwrap();
+
+        let job_home = workspace.path().join(JOB_CARGO_HOME);
+        assert_eq!(env, vec![("CARGO_HOME".to_string(), job_home.to_string_lossy().to_string())]);
+        assert!(job_home.join("registry/cache").is_dir());
+        assert!(!job_home.join("git").exists());
+        assert!(workspace.path().join("target/release/deps/libserde.rlib").is_file());
+
+        // A different dependency set doesn't reuse that seed
+        let other = tempfile::tempdir().unwrap();
+        std::fs::write(other.path().join("Cargo.toml"), "[dependencies]\nrand = \"0.8\"\n").unwrap();
+        assert_ne!(dependency_key(other.path()), key);
+        cache.prepare(other.path()).await.unwrap();
+        assert!(!other.path().join("target").exists());
+
+        // A warmed sccache replaces the target seed, and job builds only read it
+        let sccache = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(sccache.path().join(&key)).unwrap();
+        let cache = CargoCache { sccache_dir: Some(sccache.path().to_path_buf()), ..cache };
+        let env = cache.prepare(workspace.path()).await.unwrap();
+        assert!(env.contains(&("RUSTC_WRAPPER".to_string(), "sccache".to_string())));
+        assert!(env.contains(&("SCCACHE_DIR".to_string(), sccache.path().join(&key).to_string_lossy().to_string())));
+        assert!(env.contains(&("SCCACHE_LOCAL_RW_MODE".to_string(), "READ_ONLY".to_string())));
+
+        // An unwarmed key falls back to the (absent) seed rather than a cold cache
+        let env = cache.prepare(other.path()).await.unwrap();
+        assert!(!env.iter().any(|(name, _)| name == "RUSTC_WRAPPER"));
+    }
+
+    #[tokio::test]
+    async fn test_vendored_builds_are_offline_and_reject_missing_crates() {
+        let vendor = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(vendor.path().join("serde")).unwrap();
+        std::fs::write(vendor.path().join("serde/Cargo.toml"), "[package]\nname = \"serde\"\nversion = \"1.0.200\"\n").unwrap();
+
+        let cache = CargoCache { vendor_dir: Some(vendor.path().to_path_buf()), ..CargoCache::default() };
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::write(workspace.path().join("Cargo.toml"), "[package]\nname = \"grader-code\"\n\n[dependencies]\nserde = \"1.0\"\n").unwrap();
+
+        let env = cache.prepare(workspace.path()).await.unwrap();
+        assert!(env.contains(&("CARGO_NET_OFFLINE".to_string(), "true".to_string())));
+        let config = std::fs::read_to_string(workspace.path().join(JOB_CARGO_HOME).join("config.toml")).unwrap();
+        assert!(config.contains("replace-with = \"vendored-sources\""));
+
+        std::fs::write(workspace.path().join("Cargo.toml"), "[dependencies]\nserde = \"1.0\"\nrand = \"0.8\"\n").unwrap();
+        assert_eq!(
+            cache.prepare(workspace.path()).await,
+            Err("Dependencies not available for offline builds: rand".to_string())
+        );
+    }
+}
@@ -0,0 +1,358 @@
+use crate::cargo_cache::CargoCache;
+use crate::crash_signature;
+use crate::fuzzer::{CrashSeverity, FuzzCrash, FuzzResult};
+use crate::sandbox::{execute_in_sandbox_with_env, SandboxConfig};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::Duration;
+
+/// Name of the generated fuzz target.
+const TARGET: &str = "entry";
+
+/// Longest input libFuzzer generates. Crashing inputs are reported whole,
+/// so their reproduction replays the exact bytes.
+const MAX_INPUT_LEN: usize = 4096;
+
+/// Bytes of a hanging input kept for the report.
+const MAX_INPUT_PREVIEW: usize = 1024;
+
+/// Lines of cargo's output kept in the error of a failed campaign.
+const MAX_ERROR_LINES: usize = 20;
+
+/// Nightly toolchain cargo-fuzz builds with, from `CARGO_FUZZ_TOOLCHAIN`;
+/// the worker image installs this one.
+const DEFAULT_TOOLCHAIN: &str = "nightly-2024-06-01";
+
+pub fn toolchain() -> String {
+    std::env::var("CARGO_FUZZ_TOOLCHAIN").unwrap_or_else(|_| DEFAULT_TOOLCHAIN.to_string())
+}
+
+/// A fuzz entry point is a function path in the submission, e.g. `parse` or
+/// `parser::parse`, taking `&[u8]`.
+pub fn validate_entry_point(entry_point: &str) -> Result<(), String> {
+    let is_ident = |segment: &str| {
+        segment.chars().next().map_or(false, |c| c.is_ascii_alphabetic() || c == '_')
+            && segment.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    };
+    match entry_point.split("::").all(is_ident) {
+        true => Ok(()),
+        false => Err(format!("Invalid Rust fuzz entry point: {}", entry_point)),
+    }
+}
+
+/// Write a libFuzzer harness calling `entry_point` into `fuzz/`, next to the
+/// submission's `main.rs`. The submission is compiled into the target with
+/// `include!`, so its `main` goes unused, against its own dependencies.
+pub fn write_harness(working_dir: &Path, entry_point: &str) -> Result<(), String> {
+    let fuzz_dir = working_dir.join("fuzz");
+    std::fs::create_dir_all(fuzz_dir.join("fuzz_targets"))
+        .map_err(|e| format!("Failed to create fuzz directory: {}", e))?;
+
+    // `include!` can't take inner attributes, so the submission's move to the
+    // top of the harness
+    let source = std::fs::read_to_string(working_dir.join("main.rs"))
+        .map_err(|e| format!("Failed to read main.rs: {}", e))?;
+    let (attributes, body) = split_inner_attributes(&source);
+    let harness = format!(
+        r#"#![no_main]
+#![allow(dead_code)]
+{}
+
+use libfuzzer_sys::fuzz_target;
+
+include!("../submission.rs");
+
+fuzz_target!(|data: &[u8]| {{
+    {}(data);
+}});
+"#,
+        attributes.join("\n"),
+        entry_point
+    );
+
+    std::fs::write(fuzz_dir.join("Cargo.toml"), fuzz_manifest(working_dir)?)
+        .map_err(|e| format!("Failed to write fuzz manifest: {}", e))?;
+    // The same dependency versions as the submission's build
+    if working_dir.join("Cargo.lock").exists() {
+        std::fs::copy(working_dir.join("Cargo.lock"), fuzz_dir.join("Cargo.lock"))
+            .map_err(|e| format!("Failed to copy Cargo.lock: {}", e))?;
+    }
+    std::fs::write(fuzz_dir.join("submission.rs"), body)
+        .map_err(|e| format!("Failed to write fuzzed submission: {}", e))?;
+    std::fs::write(fuzz_dir.join("fuzz_targets").join(format!("{}.rs", TARGET)), harness)
+        .map_err(|e| format!("Failed to write fuzz harness: {}", e))
+}
+
+/// Manifest of the fuzz crate: the submission's edition and dependencies,
+/// so the included code builds as it did, and libfuzzer-sys.
+fn fuzz_manifest(working_dir: &Path) -> Result<String, String> {
+    let submitted: toml::Table = match std::fs::read_to_string(working_dir.join("Cargo.toml")) {
+        Ok(manifest) => manifest.parse().map_err(|e| format!("Failed to parse Cargo.toml: {}", e))?,
+        Err(_) => toml::Table::new(),
+    };
+    let edition = submitted
+        .get("package")
+        .and_then(|package| package.get("edition"))
+        .cloned()
+        .unwrap_or_else(|| toml::Value::from("2021"));
+    let mut dependencies = submitted.get("dependencies").and_then(|d| d.as_table()).cloned().unwrap_or_default();
+    for (_, dependency) in dependencies.iter_mut() {
+        // The fuzz crate is a directory below the submission's
+        if let Some(path) = dependency.get_mut("path") {
+            *path = toml::Value::from(format!("../{}", path.as_str().unwrap_or_default()));
+        }
+    }
+    dependencies.insert("libfuzzer-sys".to_string(), toml::Value::from("0.4"));
+    let mut tables = toml::Table::new();
+    tables.insert("dependencies".to_string(), toml::Value::Table(dependencies));
+    let dependencies = toml::to_string(&tables).map_err(|e| format!("Failed to write fuzz manifest: {}", e))?;
+
+    Ok(format!(
+        r#"[package]
+name = "grader-code-fuzz"
+version = "0.0.0"
+edition = {edition}
+publish = false
+
+[package.metadata]
+cargo-fuzz = true
+
+[[bin]]
+name = "{target}"
+path = "fuzz_targets/{target}.rs"
+test = false
+doc = false
+
+# Not a member of the submission's workspace
+[workspace]
+
+{dependencies}"#,
+        edition = edition,
+        target = TARGET,
+        dependencies = dependencies
+    ))
+}
+
+/// Split the inner attributes (`#![...]`) and comments off the top of a
+/// source file, returning the attributes and the rest of the file.
+fn split_inner_attributes(source: &str) -> (Vec<&str>, &str) {
+    let mut attributes = Vec::new();
+    let mut rest = source;
+    loop {
+        let trimmed = rest.trim_start();
+        let skipped = if trimmed.starts_with("#![") {
+            let end = match attribute_end(trimmed) {
+                Some(end) => end,
+                None => break,
+            };
+            attributes.push(&trimmed[..end]);
+            end
+        } else if trimmed.starts_with("//") || trimmed.starts_with("#!") {
+            // Inner doc comments are attributes too, and a shebang can't be included
+            trimmed.find('\n').map_or(trimmed.len(), |i| i + 1)
+        } else if trimmed.starts_with("/*") {
+            match trimmed.find("*/") {
+                Some(i) => i + 2,
+                None => break,
+            }
+        } else {
+            break;
+        };
+        rest = &trimmed[skipped..];
+    }
+    (attributes, rest)
+}
+
+/// End of the attribute `source` starts with, past its closing bracket.
+fn attribute_end(source: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in source.char_indices() {
+        match (in_string, c) {
+            (true, _) if escaped => escaped = false,
+            (true, '\\') => escaped = true,
+            (true, '"') => in_string = false,
+            (true, _) => {},
+            (false, '"') => in_string = true,
+            (false, '[') => depth += 1,
+            (false, ']') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(i + 1);
+                }
+            },
+            _ => {},
+        }
+    }
+    None
+}
+
+/// Fuzz a Rust submission's entry point with `cargo fuzz` for the campaign
+/// budget, then import libFuzzer's crash and timeout artifacts.
+pub async fn run_campaign(entry_point: &str, working_dir: &Path, runs: usize, seed: u64, budget: Duration) -> Result<FuzzResult, String> {
+    let start_time = std::time::Instant::now();
+    write_harness(working_dir, entry_point)?;
+
+    let sandbox_config = SandboxConfig {
+        // Building the instrumented target comes on top of the fuzzing budget
+        time_limit: budget + Duration::from_secs(120),
+        memory_limit: 2048 * 1024 * 1024, // 2GB, libFuzzer's default RSS limit
+        cpu_limit: 50,
+        network_disabled: true,
+        max_file_size: 100 * 1024 * 1024, // 100MB
+        max_processes: 10,
+        disk_quota: 1024 * 1024 * 1024, // 1GB
+    };
+    let mut env = CargoCache::global().prepare(working_dir).await?;
+    // A crash's panic prints the backtrace its signature is made of
+    env.push(("RUST_BACKTRACE".to_string(), "1".to_string()));
+    // libFuzzer seeds must be non-zero and fit 32 bits
+    let seed_arg = format!("-seed={}", (seed % u32::MAX as u64) + 1);
+    let max_total_time = format!("-max_total_time={}", budget.as_secs().max(1));
+    let runs_arg = format!("-runs={}", runs);
+    let max_len = format!("-max_len={}", MAX_INPUT_LEN);
+    let result = execute_in_sandbox_with_env(
+        "cargo",
+        &[&format!("+{}", toolchain()), "fuzz", "run", TARGET, "--", &max_total_time, &seed_arg, &runs_arg, &max_len, "-print_final_stats=1"],
+        &env,
+        &sandbox_config,
+        working_dir,
+    ).await?;
+
+    // A crash stops libFuzzer with a non-zero exit too, so only a missing
+    // stats report means the harness never ran
+    let executed = final_stat(&result.stderr, "number_of_executed_units");
+    if executed.is_none() && !result.stderr.contains("ERROR: libFuzzer") {
+        let lines: Vec<&str> = result.stderr.trim().lines().collect();
+        let tail = lines[lines.len().saturating_sub(MAX_ERROR_LINES)..].join("\n");
+        return Err(match result.stderr.contains("error: could not compile") {
+            true => format!("Fuzz target failed to build: {}", tail),
+            false => format!("cargo fuzz failed: {}", tail),
+        });
+    }
+
+    let artifacts_dir = working_dir.join("fuzz").join("artifacts").join(TARGET);
+    let mut crashes_found = Vec::new();
+    let mut hangs = Vec::new();
+    let mut artifacts: Vec<_> = std::fs::read_dir(&artifacts_dir)
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+        .unwrap_or_default();
+    artifacts.sort();
+    for path in artifacts {
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        let data = std::fs::read(&path).map_err(|e| format!("Failed to read fuzz artifact {}: {}", name, e))?;
+        match name.split('-').next() {
+            Some("crash") | Some("oom") => import_crash(&mut crashes_found, &data, &result.stderr),
+            Some("timeout") => hangs.push(artifact_input(&data[..data.len().min(MAX_INPUT_PREVIEW)])),
+            _ => {},
+        }
+    }
+
+    let corpus_dir = working_dir.join("fuzz").join("corpus").join(TARGET);
+    let unique_paths = std::fs::read_dir(&corpus_dir).map(|entries| entries.count()).unwrap_or(0);
+
+    Ok(FuzzResult {
+        inputs_tested: executed.unwrap_or(0) as usize,
+        crashes_found,
+        invariant_violations: vec![],
+        hangs,
+        slowest_inputs: vec![],
+        seed: Some(seed),
+        unique_paths,
+        coverage_score: 0.0,
+        execution_time: start_time.elapsed(),
+        error: None,
+    })
+}
+
+/// A `stat::<name>: N` line of `-print_final_stats=1`.
+fn final_stat(stderr: &str, name: &str) -> Option<u64> {
+    let prefix = format!("stat::{}:", name);
+    stderr
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix(prefix.as_str()))
+        .last()
+        .and_then(|value| value.trim().parse().ok())
+}
+
+/// An input as text when it is UTF-8, otherwise as hex.
+fn artifact_input(data: &[u8]) -> Value {
+    match std::str::from_utf8(data) {
+        Ok(text) => json!(text),
+        Err(_) => json!({ "hex": hex::encode(data) }),
+    }
+}
+
+/// libFuzzer stops at the first crash, so the run's stderr holds its panic
+/// message and backtrace.
+fn import_crash(crashes: &mut Vec<FuzzCrash>, data: &[u8], stderr: &str) {
+    let panic_start = stderr.find("panicked at").map(|i| stderr[..i].rfind('\n').map_or(0, |n| n + 1));
+    let panic = panic_start.map_or("", |start| &stderr[start..]);
+    let error_message = match panic.lines().take(2).collect::<Vec<_>>().join("\n") {
+        message if !message.is_empty() => message,
+        _ => stderr.lines().find(|l| l.contains("ERROR: libFuzzer")).unwrap_or("libFuzzer crash").trim().to_string(),
+    };
+    let stack_trace = panic.find("stack backtrace").map_or(String::new(), |i| panic[i..].to_string());
+    let signature = crash_signature::signature(&error_message, &stack_trace);
+    match crashes.iter_mut().find(|c| c.signature == signature) {
+        Some(existing) => existing.occurrences += 1,
+        None => crashes.push(FuzzCrash {
+            input: artifact_input(data),
+            error_message,
+            stack_trace,
+            gas_used: 0,
+            severity: CrashSeverity::High,
+            signature,
+            occurrences: 1,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_harness_and_crash_import() {
+        assert!(validate_entry_point("parser::parse_input").is_ok());
+        assert!(validate_entry_point("parse(data); std::process::exit").is_err());
+        assert!(validate_entry_point("").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("main.rs"), "//! Parser\n#![allow(unused, clippy::all)]\n#![doc = \"a ] b\"]\nuse rand::Rng;\nfn parse(data: &[u8]) {}\nfn main() {}\n").unwrap();
+        std::fs::write(dir.path().join("Cargo.toml"), "[package]\nname = \"grader-code\"\nedition = \"2018\"\n\n[dependencies]\nrand = \"0.8\"\nutil = { path = \"util\" }\n").unwrap();
+        write_harness(dir.path(), "parse").unwrap();
+        let harness = std::fs::read_to_string(dir.path().join("fuzz/fuzz_targets/entry.rs")).unwrap();
+        assert!(harness.contains("#![allow(unused, clippy::all)]\n#![doc = \"a ] b\"]\n"));
+        assert!(harness.contains("include!(\"../submission.rs\");"));
+        assert!(harness.contains("parse(data);"));
+        let submission = std::fs::read_to_string(dir.path().join("fuzz/submission.rs")).unwrap();
+        assert!(submission.trim_start().starts_with("use rand::Rng;"));
+        // The fuzz crate builds against the submission's dependencies
+        let manifest: toml::Table = std::fs::read_to_string(dir.path().join("fuzz/Cargo.toml")).unwrap().parse().unwrap();
+        assert_eq!(manifest["package"]["edition"].as_str(), Some("2018"));
+        assert_eq!(manifest["dependencies"]["rand"].as_str(), Some("0.8"));
+        assert_eq!(manifest["dependencies"]["util"]["path"].as_str(), Some("../util"));
+        assert!(manifest["dependencies"].get("libfuzzer-sys").is_some());
+        assert_eq!(manifest["bin"][0]["name"].as_str(), Some("entry"));
+
+        let stderr = "#1024\tNEW    cov: 88 ft: 90\n\
+thread '<unnamed>' panicked at src/main.rs:7:13:\n\
+attempt to subtract with overflow\n\
+==12== ERROR: libFuzzer: deadly signal\n\
+stat::number_of_executed_units: 2048\n";
+        let mut crashes = Vec::new();
+        import_crash(&mut crashes, &[0xff, 0x00], stderr);
+        import_crash(&mut crashes, &[0xfe], stderr);
+        assert_eq!((crashes.len(), crashes[0].occurrences), (1, 2));
+        assert!(crashes[0].error_message.ends_with("attempt to subtract with overflow"));
+        assert_eq!(crashes[0].input, json!({ "hex": "ff00" }));
+        // Long inputs are kept whole for the reproduction
+        let long = vec![b'a'; MAX_INPUT_PREVIEW * 2];
+        let mut crashes = Vec::new();
+        import_crash(&mut crashes, &long, stderr);
+        assert_eq!(crashes[0].input.as_str().map(str::len), Some(long.len()));
+        assert_eq!(final_stat(stderr, "number_of_executed_units"), Some(2048));
+    }
+}
@@ -0,0 +1,108 @@
+/// A single test outcome reported by the libtest harness (`cargo test`).
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct CargoTestResult {
+    pub path: String,
+    pub name: String,
+    pub passed: bool,
+    pub ignored: bool,
+    pub reason: Option<String>,
+}
+
+/// Parse `cargo test` stdout, e.g. `test tests::it_works ... ok`. Panic
+/// messages of failing tests are taken from the `---- name stdout ----`
+/// sections printed after the run.
+pub fn parse_cargo_test_output(output: &str) -> Vec<CargoTestResult> {
+    let mut results = Vec::new();
+
+    for line in output.lines() {
+        let rest = match line.strip_prefix("test ") {
+            Some(rest) => rest,
+            None => continue,
+        };
+
+        let (path, status) = match rest.rsplit_once(" ... ") {
+            Some(parts) => parts,
+            None => continue,
+        };
+
+        let (passed, ignored) = match status.trim() {
+            "ok" => (true, false),
+            "FAILED" => (false, false),
+            s if s.starts_with("ignored") => (false, true),
+            _ => continue,
+        };
+
+        let path = path.trim().to_string();
+        let name = path.rsplit("::").next().unwrap_or(&path).to_string();
+        let reason = if passed || ignored { None } else { extract_failure_output(output, &path) };
+
+        results.push(CargoTestResult { path, name, passed, ignored, reason });
+    }
+
+    results
+}
+
+/// Find the test that corresponds to a fixture by function name or full path.
+pub fn find_test_for_fixture<'a>(
+    results: &'a [CargoTestResult],
+    fixture_id: &str,
+    fixture_name: &str,
+) -> Option<&'a CargoTestResult> {
+    results.iter().find(|r| {
+        r.name == fixture_id || r.name == fixture_name || r.path == fixture_id || r.path == fixture_name
+    })
+}
+
+fn extract_failure_output(output: &str, path: &str) -> Option<String> {
+    let header = format!("---- {} stdout ----", path);
+    let start = output.find(&header)? + header.len();
+
+    let section: Vec<&str> = output[start..]
+        .lines()
+        .skip(1)
+        .take_while(|line| !line.starts_with("---- ") && !line.starts_with("failures:"))
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+
+    if section.is_empty() {
+        None
+    } else {
+        Some(section.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cargo_test_output() {
+        let output = "\
+running 3 tests
+test flipper::tests::default_works ... ok
+test flipper::tests::it_flips ... FAILED
+test flipper::tests::slow ... ignored
+
+failures:
+
+---- flipper::tests::it_flips stdout ----
+thread 'flipper::tests::it_flips' panicked at src/lib.rs:42:9:
+assertion failed: flipper.get()
+
+failures:
+    flipper::tests::it_flips
+
+test result: FAILED. 1 passed; 1 failed; 1 ignored; 0 measured; 0 filtered out
+";
+
+        let results = parse_cargo_test_output(output);
+        assert_eq!(results.len(), 3);
+
+        let flips = find_test_for_fixture(&results, "it_flips", "").unwrap();
+        assert!(!flips.passed);
+        assert!(flips.reason.as_ref().unwrap().contains("assertion failed"));
+
+        assert!(find_test_for_fixture(&results, "default_works", "").unwrap().passed);
+        assert!(find_test_for_fixture(&results, "slow", "").unwrap().ignored);
+    }
+}
@@ -0,0 +1,85 @@
+use serde_json::{Map, Value};
+
+/// Grading settings a challenge's authors publish next to its fixtures, and
+/// which submitters may not set themselves.
+const SETTINGS: &[&str] = &[
+    "antiCheat",
+    "buildPolicy",
+    "scoringRubric",
+    "fuzzing",
+    "functionSignature",
+    "toolchain",
+    "gasLimit",
+    "timeLimit",
+];
+
+/// A challenge's config document, e.g. `{ "antiCheat": {...}, "scoringRubric": {...} }`. Its settings
+/// stand in for the job payload's, so only admin clients can override them.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ChallengeConfig {
+    settings: Map<String, Value>,
+}
+
+impl ChallengeConfig {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let settings = match serde_json::from_slice(bytes) {
+            Ok(Value::Object(settings)) => settings,
+            Ok(_) => return Err("Challenge config must be an object".to_string()),
+            Err(e) => return Err(format!("Failed to parse challenge config: {}", e)),
+        };
+        if let Some(key) = settings.keys().find(|key| !SETTINGS.contains(&key.as_str())) {
+            return Err(format!("Unknown challenge config setting: {}", key));
+        }
+        Ok(Self { settings })
+    }
+
+    /// Copy the settings into a job payload that doesn't set them already.
+    /// Where both set an object, the payload's fields are kept and the rest
+    /// filled in, so an admin can override one field, such as a fuzzing seed.
+    pub fn apply(&self, payload: &mut Value) {
+        if let Some(payload) = payload.as_object_mut() {
+            for (key, value) in &self.settings {
+                match (payload.get_mut(key), value) {
+                    (Some(Value::Object(own)), Value::Object(settings)) => {
+                        for (field, value) in settings {
+                            own.entry(field.clone()).or_insert_with(|| value.clone());
+                        }
+                    },
+                    (None | Some(Value::Null), _) => {
+                        payload.insert(key.clone(), value.clone());
+                    },
+                    _ => {},
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_config_fills_unset_payload_settings() {
+        let config = ChallengeConfig::parse(br#"{"antiCheat": {"reportThreshold": 0.6}}"#).unwrap();
+
+        let mut payload = json!({ "code": "x" });
+        config.apply(&mut payload);
+        assert_eq!(payload["antiCheat"]["reportThreshold"], 0.6);
+
+        // An admin's own settings take precedence
+        let mut payload = json!({ "code": "x", "antiCheat": { "reportThreshold": 0.2 } });
+        config.apply(&mut payload);
+        assert_eq!(payload["antiCheat"]["reportThreshold"], 0.2);
+
+        // The challenge's budget still applies to a run with a pinned seed
+        let config = ChallengeConfig::parse(br#"{"fuzzing": {"maxIterations": 500, "seed": 1}}"#).unwrap();
+        let mut payload = json!({ "code": "x", "fuzzing": { "seed": 7 } });
+        config.apply(&mut payload);
+        assert_eq!(payload["fuzzing"], json!({ "maxIterations": 500, "seed": 7 }));
+
+        assert!(ChallengeConfig::parse(br#"{"mode": "validate"}"#).is_err());
+        assert!(ChallengeConfig::parse(b"[]").is_err());
+    }
+}
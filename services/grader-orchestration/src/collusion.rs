@@ -0,0 +1,206 @@
+use crate::anti_cheat::{CodeFingerprint, RiskLevel};
+use crate::fingerprint_store::StoredSubmission;
+use crate::minhash;
+use petgraph::graph::{NodeIndex, UnGraph};
+use petgraph::visit::EdgeRef;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// Rounds of label propagation before giving up on convergence.
+const MAX_ROUNDS: usize = 20;
+
+/// A pair of submissions by different users at least as similar as the
+/// clustering threshold.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarityLink {
+    pub source: String,
+    pub target: String,
+    pub similarity_score: f64,
+}
+
+/// Submissions more similar to each other than to the rest of the challenge,
+/// likely a group of users sharing one solution.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollusionCluster {
+    pub submission_ids: Vec<String>,
+    pub user_ids: Vec<String>,
+    pub links: Vec<SimilarityLink>,
+    pub mean_similarity: f64,
+    pub risk_level: RiskLevel,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CollusionReport {
+    pub challenge_id: String,
+    pub language: String,
+    pub submissions: usize,
+    /// Largest first
+    pub clusters: Vec<CollusionCluster>,
+    pub analysis_time_ms: u64,
+}
+
+/// Group `submissions` into collusion clusters. Pairs that share an LSH band
+/// are scored with `similarity`; those by different users scoring at least
+/// `min_similarity` become weighted edges of a similarity graph, whose
+/// communities are found by label propagation: every submission repeatedly
+/// takes the label with the most edge weight among its neighbours, so
+/// densely linked groups settle on one label while a single link between two
+/// groups doesn't merge them.
+pub fn find_clusters(
+    submissions: &[StoredSubmission],
+    similarity: impl Fn(&CodeFingerprint, &CodeFingerprint) -> f64,
+    risk_level: impl Fn(f64) -> RiskLevel,
+    min_similarity: f64,
+) -> Vec<CollusionCluster> {
+    let mut graph: UnGraph<usize, f64> = UnGraph::new_undirected();
+    let nodes: Vec<NodeIndex> = (0..submissions.len()).map(|i| graph.add_node(i)).collect();
+
+    for (a, b) in candidate_pairs(submissions) {
+        if submissions[a].user_id == submissions[b].user_id {
+            continue;
+        }
+        let score = similarity(&submissions[a].fingerprint, &submissions[b].fingerprint);
+        if score >= min_similarity {
+            graph.add_edge(nodes[a], nodes[b], score);
+        }
+    }
+
+    let mut labels: Vec<usize> = (0..submissions.len()).collect();
+    for _ in 0..MAX_ROUNDS {
+        let mut changed = false;
+        for node in graph.node_indices() {
+            let mut weights: BTreeMap<usize, f64> = BTreeMap::new();
+            for edge in graph.edges(node) {
+                let neighbour = if edge.source() == node { edge.target() } else { edge.source() };
+                *weights.entry(labels[neighbour.index()]).or_insert(0.0) += *edge.weight();
+            }
+            // Ties go to the smallest label, so the result is deterministic
+            let best = weights.into_iter().fold(None, |best: Option<(usize, f64)>, (label, weight)| match best {
+                Some((_, best_weight)) if best_weight >= weight => best,
+                _ => Some((label, weight)),
+            });
+            if let Some((label, _)) = best {
+                if label != labels[node.index()] {
+                    labels[node.index()] = label;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut communities: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+    for (index, label) in labels.iter().enumerate() {
+        communities.entry(*label).or_default().push(index);
+    }
+
+    let mut clusters: Vec<CollusionCluster> = communities
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let links: Vec<SimilarityLink> = graph
+                .edge_indices()
+                .filter_map(|edge| {
+                    let (a, b) = graph.edge_endpoints(edge)?;
+                    let (a, b) = (graph[a], graph[b]);
+                    (labels[a] == labels[members[0]] && labels[b] == labels[members[0]]).then(|| SimilarityLink {
+                        source: submissions[a].submission_id.clone(),
+                        target: submissions[b].submission_id.clone(),
+                        similarity_score: graph[edge],
+                    })
+                })
+                .collect();
+            let mean_similarity = links.iter().map(|l| l.similarity_score).sum::<f64>() / links.len().max(1) as f64;
+            let user_ids: BTreeSet<String> = members.iter().map(|i| submissions[*i].user_id.clone()).collect();
+            CollusionCluster {
+                submission_ids: members.iter().map(|i| submissions[*i].submission_id.clone()).collect(),
+                user_ids: user_ids.into_iter().collect(),
+                links,
+                mean_similarity,
+                risk_level: risk_level(mean_similarity),
+            }
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.submission_ids.len().cmp(&a.submission_ids.len()).then(b.mean_similarity.total_cmp(&a.mean_similarity)));
+    clusters
+}
+
+/// Index pairs sharing at least one LSH band key, each once.
+fn candidate_pairs(submissions: &[StoredSubmission]) -> BTreeSet<(usize, usize)> {
+    let mut buckets: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (index, submission) in submissions.iter().enumerate() {
+        for key in minhash::band_keys(&submission.fingerprint.minhash) {
+            buckets.entry(key).or_default().push(index);
+        }
+    }
+
+    let mut pairs = BTreeSet::new();
+    for bucket in buckets.values() {
+        for (i, a) in bucket.iter().enumerate() {
+            for b in &bucket[i + 1..] {
+                if a != b {
+                    pairs.insert((*a.min(b), *a.max(b)));
+                }
+            }
+        }
+    }
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::winnowing;
+
+    fn submission(id: &str, user: &str, hashes: std::ops::Range<u64>) -> StoredSubmission {
+        let winnow_hashes: Vec<u64> = hashes.collect();
+        StoredSubmission {
+            submission_id: id.to_string(),
+            challenge_id: "two-sum".to_string(),
+            language: "rust".to_string(),
+            user_id: user.to_string(),
+            code: String::new(),
+            fingerprint: CodeFingerprint {
+                version: 0,
+                minhash: minhash::signature(&winnow_hashes),
+                winnow_hashes,
+                tree: Default::default(),
+                token_sequence: vec![],
+                structural_features: HashMap::new(),
+            },
+            accepted: false,
+        }
+    }
+
+    #[test]
+    fn test_ring_forms_one_cluster() {
+        let submissions = vec![
+            submission("a", "alice", 0..100),
+            submission("b", "bob", 2..102),
+            submission("c", "carol", 4..104),
+            submission("d", "dave", 1000..1100),
+            // The same user resubmitting is not collusion
+            submission("d2", "dave", 1000..1100),
+            submission("e", "erin", 2000..2100),
+            submission("f", "frank", 2001..2101),
+        ];
+        let clusters = find_clusters(
+            &submissions,
+            |a, b| winnowing::similarity(&a.winnow_hashes, &b.winnow_hashes),
+            |s| if s >= 0.9 { RiskLevel::Critical } else { RiskLevel::High },
+            0.7,
+        );
+
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0].submission_ids, vec!["a", "b", "c"]);
+        assert_eq!(clusters[0].user_ids, vec!["alice", "bob", "carol"]);
+        assert_eq!(clusters[0].links.len(), 3);
+        assert_eq!(clusters[1].submission_ids, vec!["e", "f"]);
+        assert_eq!(clusters[1].risk_level, RiskLevel::Critical);
+    }
+}
@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const DEFAULT_CAPACITY: usize = 200;
+const ENTRY_FILE: &str = "entry.json";
+
+/// Directories holding build output or tooling state rather than sources.
+const NON_SOURCE_DIRS: &[&str] = &["target", "out", "build", "cache", "node_modules", ".git", ".fathuss"];
+
+/// Compiler output kept for a cache entry: what the compiler printed (vyper
+/// prints its bytecode) plus a digest of the stored build outputs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedCompile {
+    pub stdout: String,
+    pub stderr: String,
+    pub outputs_sha256: String,
+}
+
+/// Content-addressed cache of successful compiles. Entries are keyed by the
+/// SHA-256 of the prepared sources and the toolchain version, so identical
+/// submissions and re-grades skip compilation. Oldest entries are evicted first.
+pub struct CompileCache {
+    root: PathBuf,
+    capacity: usize,
+    order: Mutex<VecDeque<String>>,
+    toolchains: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl CompileCache {
+    /// `COMPILE_CACHE_DIR` (default /tmp/fathuss_compile_cache) and
+    /// `COMPILE_CACHE_CAPACITY` entries (default 200, 0 disables the cache).
+    pub fn from_env() -> Self {
+        let root = std::env::var("COMPILE_CACHE_DIR").unwrap_or_else(|_| "/tmp/fathuss_compile_cache".to_string());
+        let capacity = std::env::var("COMPILE_CACHE_CAPACITY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CAPACITY);
+        Self::new(PathBuf::from(root), capacity)
+    }
+
+    /// Open the cache at `root`, picking up entries left by earlier runs
+    /// (oldest first) so they are evicted in order too.
+    pub fn new(root: PathBuf, capacity: usize) -> Self {
+        let mut existing: Vec<(std::time::SystemTime, String)> = std::fs::read_dir(&root)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter_map(|entry| {
+                let modified = entry.metadata().and_then(|m| m.modified()).ok()?;
+                Some((modified, entry.file_name().to_string_lossy().to_string()))
+            })
+            .collect();
+        existing.sort();
+
+        Self {
+            root,
+            capacity,
+            order: Mutex::new(existing.into_iter().map(|(_, key)| key).collect()),
+            toolchains: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    /// Cache key for the sources prepared in `workspace`, or None if the
+    /// language isn't compiled, the cache is disabled or the toolchain
+    /// version can't be determined.
+    pub fn key(&self, language: &str, workspace: &Path) -> Option<String> {
+        if !self.is_enabled() {
+            return None;
+        }
+        cached_outputs(language)?;
+        let toolchain = self.toolchain_version(language)?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(language.as_bytes());
+        hasher.update([0]);
+        hasher.update(toolchain.as_bytes());
+        hasher.update([0]);
+        hash_sources(workspace, workspace, &mut hasher).ok()?;
+        Some(hex::encode(hasher.finalize()))
+    }
+
+    /// Copy a cached compile's outputs into `workspace`. Entries whose stored
+    /// outputs no longer match their digest are dropped.
+    pub fn restore(&self, key: &str, workspace: &Path) -> Option<CachedCompile> {
+        let entry_dir = self.root.join(key);
+        let entry: CachedCompile = serde_json::from_str(&std::fs::read_to_string(entry_dir.join(ENTRY_FILE)).ok()?).ok()?;
+
+        let files = entry_dir.join("files");
+        if tree_digest(&files).ok()? != entry.outputs_sha256 {
+            eprintln!("Warning: Compile cache entry {} is corrupt, discarding it", key);
+            let _ = std::fs::remove_dir_all(&entry_dir);
+            return None;
+        }
+        if let Err(e) = crate::replay::copy_dir(&files, workspace) {
+            eprintln!("Warning: Failed to restore compile cache entry {}: {}", key, e);
+            return None;
+        }
+
+        self.touch(key);
+        Some(entry)
+    }
+
+    /// Store the build outputs of a successful compile in `workspace`.
+    pub fn store(&self, key: &str, language: &str, workspace: &Path, stdout: &str, stderr: &str) -> Result<(), String> {
+        let outputs = cached_outputs(language).ok_or("Language is not cached")?;
+        let entry_dir = self.root.join(key);
+        let files = entry_dir.join("files");
+        if entry_dir.exists() {
+            std::fs::remove_dir_all(&entry_dir).map_err(|e| format!("Failed to replace cache entry: {}", e))?;
+        }
+        std::fs::create_dir_all(&files).map_err(|e| format!("Failed to create {}: {}", files.display(), e))?;
+
+        for output in outputs {
+            let source = workspace.join(output);
+            let dest = files.join(output);
+            if source.is_dir() {
+                crate::replay::copy_dir(&source, &dest)?;
+            } else if source.is_file() {
+                if let Some(parent) = dest.parent() {
+                    std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+                }
+                std::fs::copy(&source, &dest).map_err(|e| format!("Failed to cache {}: {}", output, e))?;
+            }
+        }
+
+        let entry = CachedCompile {
+            stdout: stdout.to_string(),
+            stderr: stderr.to_string(),
+            outputs_sha256: tree_digest(&files)?,
+        };
+        let entry_json = serde_json::to_string(&entry).map_err(|e| e.to_string())?;
+        std::fs::write(entry_dir.join(ENTRY_FILE), entry_json).map_err(|e| format!("Failed to write cache entry: {}", e))?;
+
+        self.touch(key);
+        Ok(())
+    }
+
+    fn touch(&self, key: &str) {
+        let mut order = self.order.lock().unwrap();
+        order.retain(|k| k != key);
+        order.push_back(key.to_string());
+        while order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                let _ = std::fs::remove_dir_all(self.root.join(oldest));
+            }
+        }
+    }
+
+    fn toolchain_version(&self, language: &str) -> Option<String> {
+        let mut toolchains = self.toolchains.lock().unwrap();
+        toolchains
+            .entry(language.to_string())
+            .or_insert_with(|| {
+                let (command, args): (&str, &[&str]) = match language {
+                    "rust" | "cosmwasm" => ("rustc", &["--version", "--verbose"]),
+                    "ink" => ("cargo", &["contract", "--version"]),
+                    "solidity" => ("forge", &["--version"]),
+                    "vyper" => ("vyper", &["--version"]),
+                    "typescript" => ("tsc", &["--version"]),
+                    "move" => ("aptos", &["--version"]),
+                    _ => return None,
+                };
+                let output = std::process::Command::new(command).args(args).output().ok()?;
+                output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+            })
+            .clone()
+    }
+}
+
+/// Workspace paths a language's compiler produces, restored on a cache hit.
+/// None for languages that aren't compiled.
+fn cached_outputs(language: &str) -> Option<&'static [&'static str]> {
+    match language {
+        "rust" => Some(&["target/release/grader-code"]),
+        "cosmwasm" => Some(&[crate::cosmwasm::WASM_ARTIFACT]),
+        "ink" => Some(&["target/ink"]),
+        "solidity" => Some(&["out"]),
+        "move" => Some(&["build"]),
+        // Vyper's bytecode is in its stdout; tsc only type-checks
+        "vyper" | "typescript" => Some(&[]),
+        _ => None,
+    }
+}
+
+/// Feed the workspace's source files into `hasher` in path order, with line
+/// endings normalized so the same source checked out on another platform hits.
+fn hash_sources(workspace: &Path, dir: &Path, hasher: &mut Sha256) -> Result<(), String> {
+    let mut entries: Vec<std::fs::DirEntry> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?
+        .flatten()
+        .collect();
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        if file_type.is_dir() {
+            if dir == workspace && NON_SOURCE_DIRS.contains(&entry.file_name().to_string_lossy().as_ref()) {
+                continue;
+            }
+            hash_sources(workspace, &path, hasher)?;
+        } else if file_type.is_file() {
+            let relative = path.strip_prefix(workspace).map_err(|e| e.to_string())?;
+            let contents = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            hasher.update(relative.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            match String::from_utf8(contents) {
+                Ok(text) => hasher.update(text.replace("\r\n", "\n").as_bytes()),
+                Err(e) => hasher.update(e.as_bytes()),
+            }
+            hasher.update([0]);
+        } else if file_type.is_symlink() {
+            // Linked template dependencies (lib/, node_modules) are identified by location
+            let target = std::fs::read_link(&path).map_err(|e| e.to_string())?;
+            hasher.update(path.strip_prefix(workspace).map_err(|e| e.to_string())?.to_string_lossy().as_bytes());
+            hasher.update([0]);
+            hasher.update(target.to_string_lossy().as_bytes());
+            hasher.update([0]);
+        }
+    }
+
+    Ok(())
+}
+
+/// SHA-256 over the relative paths and hashes of every file under `dir`.
+fn tree_digest(dir: &Path) -> Result<String, String> {
+    let mut files = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+    while let Some(current) = pending.pop() {
+        for entry in std::fs::read_dir(&current).map_err(|e| format!("Failed to read {}: {}", current.display(), e))?.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+            } else {
+                let relative = path.strip_prefix(dir).map_err(|e| e.to_string())?.to_string_lossy().to_string();
+                files.push((relative, crate::artifacts::hash_file(&path)?.0));
+            }
+        }
+    }
+    files.sort();
+
+    let mut hasher = Sha256::new();
+    for (path, hash) in files {
+        hasher.update(path.as_bytes());
+        hasher.update([0]);
+        hasher.update(hash.as_bytes());
+    }
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_store_and_restore() {
+        let root = tempfile::tempdir().unwrap();
+        let cache = CompileCache::new(root.path().to_path_buf(), 1);
+
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(workspace.path().join("out/Contract.sol")).unwrap();
+        std::fs::write(workspace.path().join("out/Contract.sol/Contract.json"), "{}").unwrap();
+        cache.store("k1", "solidity", workspace.path(), "compiled", "").unwrap();
+
+        let target = tempfile::tempdir().unwrap();
+        let entry = cache.restore("k1", target.path()).unwrap();
+        assert_eq!(entry.stdout, "compiled");
+        assert!(target.path().join("out/Contract.sol/Contract.json").exists());
+
+        // Tampered entries are discarded
+        std::fs::write(root.path().join("k1/files/out/Contract.sol/Contract.json"), "{\"x\":1}").unwrap();
+        assert!(cache.restore("k1", target.path()).is_none());
+
+        // Capacity evicts the oldest entry
+        cache.store("k2", "solidity", workspace.path(), "", "").unwrap();
+        cache.store("k3", "solidity", workspace.path(), "", "").unwrap();
+        assert!(!root.path().join("k2").exists());
+        assert!(cache.restore("k3", target.path()).is_some());
+    }
+
+    #[test]
+    fn test_source_hash_ignores_line_endings_and_build_output() {
+        let hash = |dir: &Path| {
+            let mut hasher = Sha256::new();
+            hash_sources(dir, dir, &mut hasher).unwrap();
+            hex::encode(hasher.finalize())
+        };
+
+        let a = tempfile::tempdir().unwrap();
+        let b = tempfile::tempdir().unwrap();
+        std::fs::write(a.path().join("main.rs"), "fn main() {}\n").unwrap();
+        std::fs::write(b.path().join("main.rs"), "fn main() {}\r\n").unwrap();
+        std::fs::create_dir(b.path().join("target")).unwrap();
+        std::fs::write(b.path().join("target/junk"), "x").unwrap();
+        assert_eq!(hash(a.path()), hash(b.path()));
+
+        std::fs::write(b.path().join("main.rs"), "fn main() { }\n").unwrap();
+        assert_ne!(hash(a.path()), hash(b.path()));
+    }
+}
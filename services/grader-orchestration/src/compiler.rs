@@ -1,170 +1,293 @@
-use serde_json::json;
-use std::process::Command;
-use tokio::process::Command as TokioCommand;
+use crate::artifacts::{ArtifactHandle, ArtifactStore};
+use crate::cargo_cache::CargoCache;
+use crate::diagnostics;
+use crate::evm::{self, ContractArtifact};
+use crate::rust_policy::RustBuildPolicy;
+use crate::sandbox::{execute_in_sandbox, execute_in_sandbox_with_env, ExecutionResult, SandboxConfig};
+use crate::templates;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
 
-pub async fn compile_foundry(code: &str) -> Result<serde_json::Value, String> {
-    let temp_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+/// A compiler message, located in the source where the compiler says so.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub file: Option<String>,
+    pub line: Option<u32>,
+    pub col: Option<u32>,
+    pub severity: Severity,
+    pub message: String,
+    /// Compiler's identifier for the message, e.g. rustc's `unused_variables`
+    /// or solc's error code `2072`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+}
 
-    // Check if we have a foundry.toml (for local challenges)
-    let foundry_toml = temp_dir.path().join("foundry.toml");
-    let is_foundry_project = foundry_toml.exists();
-
-    if !is_foundry_project {
-        // Initialize Foundry project
-        let init_output = TokioCommand::new("forge")
-            .args(&["init", "--no-commit"])
-            .current_dir(&temp_dir)
-            .output()
-            .await
-            .map_err(|e| e.to_string())?;
-
-        if !init_output.status.success() {
-            return Err("Failed to initialize Foundry project".to_string());
-        }
+/// Outcome of a standalone compile job, the same shape for every toolchain.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompileResult {
+    pub success: bool,
+    /// Toolchain that compiled the code: `foundry`, `hardhat`, `cargo` or `move-cli`
+    pub tool: String,
+    /// Errors and notes
+    pub diagnostics: Vec<Diagnostic>,
+    #[serde(default)]
+    pub warnings: Vec<Diagnostic>,
+    /// Build outputs, by path relative to the compile workspace
+    pub artifacts: Vec<ArtifactHandle>,
+    /// ABIs and bytecode of the compiled Solidity contracts (foundry and hardhat)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub contracts: Vec<ContractArtifact>,
+    pub duration_ms: u64,
+    pub output: String,
+    pub error: String,
+}
+
+impl CompileResult {
+    fn from_execution(tool: &str, result: ExecutionResult, started: Instant, artifacts: Vec<ArtifactHandle>) -> Self {
+        let mut compiled = Self {
+            success: result.success,
+            tool: tool.to_string(),
+            diagnostics: vec![],
+            warnings: vec![],
+            artifacts: if result.success { artifacts } else { vec![] },
+            contracts: vec![],
+            duration_ms: started.elapsed().as_millis() as u64,
+            output: result.stdout,
+            error: result.stderr,
+        };
+        compiled.set_diagnostics(parse_diagnostics(&compiled.error));
+        compiled
+    }
+
+    /// Replace the diagnostics, keeping warnings apart from errors.
+    fn set_diagnostics(&mut self, diagnostics: Vec<Diagnostic>) {
+        let (warnings, diagnostics) = split_warnings(diagnostics);
+        self.diagnostics = diagnostics;
+        self.warnings = warnings;
+    }
+}
+
+/// Split diagnostics into (warnings, everything else).
+pub fn split_warnings(diagnostics: Vec<Diagnostic>) -> (Vec<Diagnostic>, Vec<Diagnostic>) {
+    diagnostics.into_iter().partition(|d| d.severity == Severity::Warning)
+}
+
+/// Limits for the build itself, the same as the grading pipeline's compile
+/// step: build scripts and compiler plugins run here.
+fn build_sandbox() -> SandboxConfig {
+    SandboxConfig {
+        time_limit: Duration::from_secs(60), // 1 minute compile timeout
+        memory_limit: 1024 * 1024 * 1024, // 1GB
+        cpu_limit: 50,
+        network_disabled: true,
+        max_file_size: 100 * 1024 * 1024, // 100MB
+        max_processes: 10,
+        disk_quota: 500 * 1024 * 1024, // 500MB
     }
+}
+
+pub async fn compile_foundry(code: &str, store: &ArtifactStore, job_id: &str) -> Result<CompileResult, String> {
+    let temp_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let started = Instant::now();
+
+    // Start from the prebuilt project: forge-std and remappings are already
+    // in place, so nothing is fetched per job
+    templates::install_template(&templates::foundry_template_dir(), temp_dir.path())?;
 
     // Write contract code
     let contract_path = temp_dir.path().join("src").join("Contract.sol");
+    std::fs::create_dir_all(temp_dir.path().join("src")).map_err(|e| e.to_string())?;
     std::fs::write(&contract_path, code).map_err(|e| e.to_string())?;
 
     // Compile
-    let compile_output = TokioCommand::new("forge")
-        .args(&["build"])
-        .current_dir(&temp_dir)
-        .output()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let success = compile_output.status.success();
-    let stdout = String::from_utf8_lossy(&compile_output.stdout);
-    let stderr = String::from_utf8_lossy(&compile_output.stderr);
-
-    Ok(json!({
-        "success": success,
-        "tool": "foundry",
-        "output": stdout,
-        "error": stderr,
-        "artifacts": if success { serde_json::Value::String("generated".to_string()) } else { serde_json::Value::Null }
-    }))
+    let compile_output = execute_in_sandbox("forge", &["build", "--json"], &build_sandbox(), temp_dir.path()).await?;
+
+    let artifacts = collect_artifacts(temp_dir.path(), "out", |p| p.ends_with(".json") && !p.contains("build-info"));
+    let artifacts = store.persist(job_id, temp_dir.path(), artifacts)?;
+    let mut result = CompileResult::from_execution("foundry", compile_output, started, artifacts);
+    if result.success {
+        result.contracts = evm::contract_artifacts(temp_dir.path(), "out/Contract.sol");
+    }
+    result.set_diagnostics(diagnostics::parse_solc_json(&result.output));
+    Ok(result)
 }
 
-pub async fn compile_hardhat(code: &str) -> Result<serde_json::Value, String> {
+pub async fn compile_hardhat(code: &str, store: &ArtifactStore, job_id: &str) -> Result<CompileResult, String> {
     let temp_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let started = Instant::now();
 
-    // Initialize Hardhat project
-    let init_output = TokioCommand::new("npx")
-        .args(&["hardhat", "init", "--yes"])
-        .current_dir(&temp_dir)
-        .output()
-        .await
-        .map_err(|e| e.to_string())?;
+    // Start from the prebuilt project: pinned dependencies, installed once in
+    // the worker image, and a cached compiler
+    templates::install_template(&templates::hardhat_template_dir(), temp_dir.path())?;
 
     // Write contract code
     let contract_path = temp_dir.path().join("contracts").join("Contract.sol");
     std::fs::write(&contract_path, code).map_err(|e| e.to_string())?;
 
-    // Compile
-    let compile_output = TokioCommand::new("npx")
-        .args(&["hardhat", "compile"])
-        .current_dir(&temp_dir)
-        .output()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let success = compile_output.status.success();
-    let stdout = String::from_utf8_lossy(&compile_output.stdout);
-    let stderr = String::from_utf8_lossy(&compile_output.stderr);
-
-    let artifacts = if success { serde_json::Value::String("generated".to_string()) } else { serde_json::Value::Null };
-
-    Ok(json!({
-        "success": success,
-        "tool": "foundry",
-        "output": stdout,
-        "error": stderr,
-        "artifacts": artifacts
-    }))
+    // Compile offline, with the hardhat installed in the template
+    let env = vec![
+        ("npm_config_offline".to_string(), "true".to_string()),
+        ("HARDHAT_DISABLE_TELEMETRY_PROMPT".to_string(), "true".to_string()),
+    ];
+    let compile_output = execute_in_sandbox_with_env(
+        "npx",
+        &["--no-install", "hardhat", "compile"],
+        &env,
+        &build_sandbox(),
+        temp_dir.path(),
+    ).await?;
+
+    let artifacts = collect_artifacts(temp_dir.path(), "artifacts/contracts", |p| p.ends_with(".json") && !p.ends_with(".dbg.json"));
+    let artifacts = store.persist(job_id, temp_dir.path(), artifacts)?;
+    let mut result = CompileResult::from_execution("hardhat", compile_output, started, artifacts);
+    if result.success {
+        result.contracts = evm::contract_artifacts(temp_dir.path(), "artifacts/contracts/Contract.sol");
+    }
+    Ok(result)
 }
 
-pub async fn compile_cargo(code: &str) -> Result<serde_json::Value, String> {
+/// Compile a Rust submission, with the submission's own Cargo.toml if it has
+/// one; that manifest is checked against the policy's dependency allowlist
+/// before cargo ever sees it.
+pub async fn compile_cargo(
+    code: &str,
+    cargo_toml: Option<&str>,
+    policy: &RustBuildPolicy,
+    store: &ArtifactStore,
+    job_id: &str,
+) -> Result<CompileResult, String> {
+    let manifest = match cargo_toml {
+        Some(manifest) => Some(policy.check_manifest(manifest, "compiled-code")?),
+        None => None,
+    };
     let temp_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let started = Instant::now();
 
     // Write code to a file
     let code_path = temp_dir.path().join("main.rs");
     std::fs::write(&code_path, code).map_err(|e| e.to_string())?;
 
     // Create Cargo.toml
-    let cargo_toml = r#"
+    let cargo_toml = manifest.as_deref().unwrap_or(r#"
 [package]
 name = "compiled-code"
 version = "0.1.0"
 edition = "2021"
 
 [dependencies]
-"#;
+"#);
     std::fs::write(temp_dir.path().join("Cargo.toml"), cargo_toml).map_err(|e| e.to_string())?;
 
-    // Compile
-    let compile_output = TokioCommand::new("cargo")
-        .args(&["build", "--release", "--manifest-path", &temp_dir.path().join("Cargo.toml").to_string_lossy()])
-        .output()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let success = compile_output.status.success();
-    let stdout = String::from_utf8_lossy(&compile_output.stdout);
-    let stderr = String::from_utf8_lossy(&compile_output.stderr);
-
-    let artifacts = if success { serde_json::Value::String("generated".to_string()) } else { serde_json::Value::Null };
-
-    Ok(json!({
-        "success": success,
-        "tool": "hardhat",
-        "output": stdout,
-        "error": stderr,
-        "artifacts": artifacts
-    }))
+    // Compile; build scripts and proc-macros run inside the sandbox
+    let env = CargoCache::global().prepare(temp_dir.path()).await?;
+    let compile_output = execute_in_sandbox_with_env(
+        "cargo",
+        &["build", "--release", "--message-format=json"],
+        &env,
+        &build_sandbox(),
+        temp_dir.path(),
+    ).await?;
+
+    let artifacts = collect_artifacts(temp_dir.path(), "target/release", |p| p == "target/release/compiled-code");
+    let artifacts = store.persist(job_id, temp_dir.path(), artifacts)?;
+    let mut result = CompileResult::from_execution("cargo", compile_output, started, artifacts);
+    let messages = diagnostics::parse_cargo_messages(&result.output);
+    result.set_diagnostics(messages.diagnostics);
+    result.output = messages.other_output;
+    result.error = format!("{}{}", messages.rendered, result.error);
+    Ok(result)
 }
 
-pub async fn compile_move(code: &str) -> Result<serde_json::Value, String> {
+pub async fn compile_move(code: &str, store: &ArtifactStore, job_id: &str) -> Result<CompileResult, String> {
     let temp_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let started = Instant::now();
 
     // Create Move package structure
+    crate::aptos::check_module_addresses(code)?;
     std::fs::create_dir_all(temp_dir.path().join("sources")).map_err(|e| e.to_string())?;
 
     // Create Move.toml
-    let move_toml = r#"
-[package]
-name = "compiled_contract"
-version = "0.1.0"
-
-[dependencies]
-AptosStdlib = { git = "https://github.com/aptos-labs/aptos-core.git", subdir = "aptos-move/framework/aptos-stdlib", rev = "main" }
-"#;
-    std::fs::write(temp_dir.path().join("Move.toml"), move_toml).map_err(|e| e.to_string())?;
+    std::fs::write(temp_dir.path().join("Move.toml"), crate::aptos::move_toml("compiled_contract")).map_err(|e| e.to_string())?;
 
     // Write contract code
     let contract_path = temp_dir.path().join("sources").join("contract.move");
     std::fs::write(&contract_path, code).map_err(|e| e.to_string())?;
 
-    // Compile
-    let compile_output = TokioCommand::new("aptos")
-        .args(&["move", "compile", "--package-dir", &temp_dir.path().to_string_lossy()])
-        .output()
-        .await
-        .map_err(|e| e.to_string())?;
-
-    let success = compile_output.status.success();
-    let stdout = String::from_utf8_lossy(&compile_output.stdout);
-    let stderr = String::from_utf8_lossy(&compile_output.stderr);
-
-    let bytecode = if success { serde_json::Value::String("generated".to_string()) } else { serde_json::Value::Null };
-
-    Ok(json!({
-        "success": success,
-        "tool": "move-cli",
-        "output": stdout,
-        "error": stderr,
-        "bytecode": bytecode
-    }))
-}
\ No newline at end of file
+    // Compile offline against the vendored framework
+    let compile_output = execute_in_sandbox(
+        "aptos",
+        &["move", "compile", "--package-dir", "."],
+        &build_sandbox(),
+        temp_dir.path(),
+    ).await?;
+
+    let artifacts = collect_artifacts(temp_dir.path(), "build", |p| p.ends_with(".mv"));
+    let artifacts = store.persist(job_id, temp_dir.path(), artifacts)?;
+    Ok(CompileResult::from_execution("move-cli", compile_output, started, artifacts))
+}
+
+/// Handles for the files under `dir` (relative to the workspace) whose
+/// workspace-relative path is accepted by `keep`. Callers keep them in the
+/// artifact store before the workspace is removed.
+fn collect_artifacts(workspace: &Path, dir: &str, keep: impl Fn(&str) -> bool) -> Vec<ArtifactHandle> {
+    let mut artifacts = Vec::new();
+    let mut pending = vec![workspace.join(dir)];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.is_dir() {
+                pending.push(path);
+                continue;
+            }
+            let relative = match path.strip_prefix(workspace) {
+                Ok(relative) => relative.to_string_lossy().replace('\\', "/"),
+                Err(_) => continue,
+            };
+            if keep(&relative) {
+                match ArtifactHandle::for_file(workspace, &relative) {
+                    Ok(handle) => artifacts.push(handle),
+                    Err(e) => eprintln!("Warning: Failed to record artifact {}: {}", relative, e),
+                }
+            }
+        }
+    }
+
+    artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+    artifacts
+}
+
+/// Pick the `error...` / `warning...` lines out of compiler output, for
+/// toolchains without machine-readable diagnostics. Locations are not parsed,
+/// so the diagnostics carry messages only.
+fn parse_diagnostics(stderr: &str) -> Vec<Diagnostic> {
+    stderr
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim();
+            let lower = line.to_lowercase();
+            let severity = if lower.starts_with("error") {
+                Severity::Error
+            } else if lower.starts_with("warning") {
+                Severity::Warning
+            } else {
+                return None;
+            };
+            Some(Diagnostic { file: None, line: None, col: None, severity, message: line.to_string(), code: None })
+        })
+        .collect()
+}
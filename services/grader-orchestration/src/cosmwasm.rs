@@ -0,0 +1,212 @@
+use crate::artifacts::ArtifactHandle;
+use crate::fixtures::TestFixture;
+use crate::sandbox::{execute_in_sandbox_with_env, SandboxConfig};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Where `cargo build --target wasm32-unknown-unknown` leaves the contract.
+pub const WASM_ARTIFACT: &str = "target/wasm32-unknown-unknown/release/graded_contract.wasm";
+
+const COSMWASM_CARGO_TOML: &str = r#"
+[package]
+name = "graded_contract"
+version = "0.1.0"
+edition = "2021"
+
+[lib]
+path = "lib.rs"
+crate-type = ["cdylib", "rlib"]
+
+[dependencies]
+cosmwasm-std = "2.0"
+cosmwasm-schema = "2.0"
+cw-storage-plus = "2.0"
+serde = { version = "1.0", default-features = false, features = ["derive"] }
+thiserror = "1.0"
+
+[dev-dependencies]
+cosmwasm-vm = "2.0"
+
+[profile.release]
+opt-level = 3
+debug = false
+overflow-checks = true
+panic = "abort"
+"#;
+
+/// Test helpers that run the compiled contract in the metered CosmWasm VM.
+const GAS_HELPER: &str = r#"//! Generated by the Fathuss grader
+use cosmwasm_vm::testing::{mock_instance_with_gas_limit, MockApi, MockQuerier, MockStorage};
+use cosmwasm_vm::Instance;
+
+pub const GAS_LIMIT: u64 = 500_000_000_000_000;
+
+/// Instantiate the compiled contract in the metered VM.
+pub fn instance() -> Instance<MockApi, MockStorage, MockQuerier> {
+    let wasm = std::fs::read("target/wasm32-unknown-unknown/release/graded_contract.wasm")
+        .expect("contract wasm has not been built");
+    mock_instance_with_gas_limit(&wasm, GAS_LIMIT)
+}
+"#;
+
+/// Where the gas runner crate is laid out in the workspace.
+const GAS_RUNNER_DIR: &str = "fathuss_gas_runner";
+
+const GAS_RUNNER_CARGO_TOML: &str = r#"
+[package]
+name = "fathuss_gas_runner"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+cosmwasm-std = "2.0"
+cosmwasm-vm = "2.0"
+serde = { version = "1.0", features = ["derive"] }
+serde_json = "1.0"
+
+[workspace]
+"#;
+
+/// Runs each fixture's messages against a fresh instance of the compiled
+/// contract in the metered VM and writes the gas each fixture used to a file.
+/// It doesn't link the submission, so only the contract's Wasm, metered by
+/// the VM, can affect the figures.
+const GAS_RUNNER_MAIN: &str = r#"//! Generated by the Fathuss grader
+use cosmwasm_std::{Coin, Empty};
+use cosmwasm_vm::testing::{mock_env, mock_info, mock_instance_with_gas_limit};
+use cosmwasm_vm::{call_execute, call_instantiate};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+const GAS_LIMIT: u64 = 500_000_000_000_000;
+
+#[derive(Deserialize)]
+struct GasFixture {
+    id: String,
+    sender: String,
+    funds: Vec<Coin>,
+    instantiate: serde_json::Value,
+    execute: Vec<serde_json::Value>,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().collect();
+    let wasm = std::fs::read(&args[1]).expect("contract wasm has not been built");
+    let fixtures: Vec<GasFixture> = serde_json::from_slice(&std::fs::read(&args[2]).expect("fixtures are missing"))
+        .expect("fixtures are invalid");
+
+    let mut gas = BTreeMap::new();
+    for fixture in fixtures {
+        let mut instance = mock_instance_with_gas_limit(&wasm, GAS_LIMIT);
+        let info = mock_info(&fixture.sender, &fixture.funds);
+        let msg = serde_json::to_vec(&fixture.instantiate).unwrap();
+        // Failing calls still use gas; whether they should fail is for the tests to judge
+        let _ = call_instantiate::<_, _, _, Empty>(&mut instance, &mock_env(), &info, &msg);
+        for msg in &fixture.execute {
+            let msg = serde_json::to_vec(msg).unwrap();
+            let _ = call_execute::<_, _, _, Empty>(&mut instance, &mock_env(), &info, &msg);
+        }
+        gas.insert(fixture.id, GAS_LIMIT - instance.get_gas_left());
+    }
+
+    std::fs::write(&args[3], serde_json::to_vec(&gas).unwrap()).expect("failed to write the gas report");
+}
+"#;
+
+/// Lay out a CosmWasm contract crate containing the submission, with the
+/// `fathuss_gas` test module available to its tests.
+pub fn write_cosmwasm_project(project_dir: &Path, code: &str) -> Result<(), String> {
+    std::fs::write(project_dir.join("Cargo.toml"), COSMWASM_CARGO_TOML)
+        .map_err(|e| format!("Failed to write Cargo.toml: {}", e))?;
+    std::fs::write(project_dir.join("fathuss_gas.rs"), GAS_HELPER)
+        .map_err(|e| format!("Failed to write fathuss_gas.rs: {}", e))?;
+
+    let lib = format!("{}\n\n#[cfg(test)]\n#[path = \"fathuss_gas.rs\"]\nmod fathuss_gas;\n", code);
+    std::fs::write(project_dir.join("lib.rs"), lib)
+        .map_err(|e| format!("Failed to write lib.rs: {}", e))?;
+    Ok(())
+}
+
+/// The messages a fixture's `input` sends to the contract, e.g.
+/// `{ "instantiate": {...}, "execute": [{...}], "sender": "alice" }`, in
+/// the gas runner's format. `None` for fixtures that send none.
+fn gas_fixture(fixture: &TestFixture) -> Option<Value> {
+    let instantiate = fixture.input.get("instantiate")?;
+    Some(json!({
+        "id": fixture.id,
+        "sender": fixture.input.get("sender").and_then(|v| v.as_str()).unwrap_or("creator"),
+        "funds": fixture.input.get("funds").cloned().unwrap_or_else(|| json!([])),
+        "instantiate": instantiate,
+        "execute": fixture.input.get("execute").cloned().unwrap_or_else(|| json!([])),
+    }))
+}
+
+/// Gas used by the messages of each fixture that sends any, keyed by fixture
+/// id. Measured by a separate runner before the submission's own tests run,
+/// so nothing the submission prints or writes can change it.
+pub async fn measure_gas(
+    workspace: &Path,
+    fixtures: &[TestFixture],
+    env: &[(String, String)],
+    config: &SandboxConfig,
+) -> Result<HashMap<String, u64>, String> {
+    let gas_fixtures: Vec<Value> = fixtures.iter().filter_map(gas_fixture).collect();
+    if gas_fixtures.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    // Laid out only now, after the contract is built and before any submission code runs
+    let runner_dir = workspace.join(GAS_RUNNER_DIR);
+    std::fs::create_dir_all(runner_dir.join("src")).map_err(|e| format!("Failed to create gas runner: {}", e))?;
+    std::fs::write(runner_dir.join("Cargo.toml"), GAS_RUNNER_CARGO_TOML).map_err(|e| format!("Failed to write gas runner: {}", e))?;
+    std::fs::write(runner_dir.join("src/main.rs"), GAS_RUNNER_MAIN).map_err(|e| format!("Failed to write gas runner: {}", e))?;
+    let fixtures_file = runner_dir.join("fixtures.json");
+    std::fs::write(&fixtures_file, serde_json::to_vec(&gas_fixtures).map_err(|e| e.to_string())?)
+        .map_err(|e| format!("Failed to write gas runner fixtures: {}", e))?;
+
+    let report_file = runner_dir.join("gas.json");
+    let wasm = workspace.join(WASM_ARTIFACT);
+    let (wasm, fixtures_file, report_file) = (wasm.to_string_lossy(), fixtures_file.to_string_lossy(), report_file.to_string_lossy());
+    let manifest = format!("{}/Cargo.toml", GAS_RUNNER_DIR);
+    let args = ["run", "--release", "--quiet", "--manifest-path", &manifest, "--", &wasm, &fixtures_file, &report_file];
+    let result = execute_in_sandbox_with_env("cargo", &args, env, config, workspace).await?;
+    if !result.success {
+        return Err(format!("Gas runner failed: {}", result.stderr));
+    }
+
+    let report = std::fs::read(runner_dir.join("gas.json")).map_err(|e| format!("Gas runner wrote no report: {}", e))?;
+    serde_json::from_slice(&report).map_err(|e| format!("Invalid gas report: {}", e))
+}
+
+/// Summarize the compiled contract; the checksum matches the code checksum
+/// a chain reports after upload.
+pub fn read_wasm_artifact(project_dir: &Path) -> Result<Value, String> {
+    let wasm = ArtifactHandle::for_file(project_dir, WASM_ARTIFACT)?;
+
+    Ok(json!({
+        "file": "graded_contract.wasm",
+        "wasmSize": wasm.size,
+        "checksum": wasm.sha256
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gas_fixture_from_input() {
+        let fixture: TestFixture = serde_json::from_value(json!({
+            "id": "transfer",
+            "input": { "instantiate": { "count": 1 }, "execute": [{ "increment": {} }] }
+        }))
+        .unwrap();
+        let gas_fixture = gas_fixture(&fixture).unwrap();
+        assert_eq!(gas_fixture["sender"], "creator");
+        assert_eq!(gas_fixture["execute"][0], json!({ "increment": {} }));
+
+        let plain: TestFixture = serde_json::from_value(json!({ "id": "plain", "input": { "n": 1 } })).unwrap();
+        assert!(gas_fixture(&plain).is_none());
+    }
+}
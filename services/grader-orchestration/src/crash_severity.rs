@@ -0,0 +1,134 @@
+use crate::fuzzer::CrashSeverity;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// A crash whose error message matches `pattern` has `severity`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SeverityRule {
+    pub pattern: String,
+    pub severity: CrashSeverity,
+    /// `pattern`, compiled once by `validate` or the first match
+    #[serde(skip)]
+    regex: OnceLock<Option<Regex>>,
+}
+
+impl SeverityRule {
+    /// An invalid pattern, which `validate` rejects, matches nothing.
+    fn is_match(&self, error_message: &str) -> bool {
+        match self.regex.get_or_init(|| Regex::new(&self.pattern).ok()) {
+            Some(re) => re.is_match(error_message),
+            None => false,
+        }
+    }
+}
+
+/// Points deducted per distinct crash of each severity.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SeverityPenalties {
+    pub critical: usize,
+    pub high: usize,
+    pub medium: usize,
+    pub low: usize,
+}
+
+impl Default for SeverityPenalties {
+    fn default() -> Self {
+        Self { critical: 10, high: 5, medium: 3, low: 1 }
+    }
+}
+
+/// Per-challenge crash classification, the `crashSeverity` table of the
+/// scoring rubric. The first rule whose regex matches a crash's error message
+/// sets its severity (crashes no rule matches keep the fuzzer's own), and each
+/// distinct crash costs its severity's penalty instead of `crashPenalty`, at
+/// most `maxPenalty` in total. A correctness challenge can make assertion
+/// failures critical; a robustness one can make them low.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct CrashSeverityRules {
+    pub rules: Vec<SeverityRule>,
+    pub penalties: SeverityPenalties,
+    pub max_penalty: Option<usize>,
+}
+
+impl CrashSeverityRules {
+    pub fn validate(&self) -> Result<(), String> {
+        for rule in &self.rules {
+            let re = Regex::new(&rule.pattern).map_err(|e| format!("Invalid crash severity pattern {}: {}", rule.pattern, e))?;
+            let _ = rule.regex.set(Some(re));
+        }
+        Ok(())
+    }
+
+    /// Severity of the first rule matching `error_message`.
+    pub fn classify(&self, error_message: &str) -> Option<CrashSeverity> {
+        self.rules
+            .iter()
+            .find(|rule| rule.is_match(error_message))
+            .map(|rule| rule.severity.clone())
+    }
+
+    pub fn penalty_for(&self, severities: &[CrashSeverity]) -> usize {
+        let penalty = severities
+            .iter()
+            .map(|severity| match severity {
+                CrashSeverity::Critical => self.penalties.critical,
+                CrashSeverity::High => self.penalties.high,
+                CrashSeverity::Medium => self.penalties.medium,
+                CrashSeverity::Low => self.penalties.low,
+            })
+            .sum();
+        self.max_penalty.map(|max| std::cmp::min(penalty, max)).unwrap_or(penalty)
+    }
+}
+
+/// The fuzzer's own classification, for challenges without rules.
+pub fn builtin_severity(error_message: &str) -> CrashSeverity {
+    if error_message.contains("panic") || error_message.contains("segmentation fault") {
+        CrashSeverity::Critical
+    } else if error_message.contains("overflow") || error_message.contains("null pointer") {
+        CrashSeverity::High
+    } else if error_message.contains("assertion failed") {
+        CrashSeverity::Medium
+    } else {
+        CrashSeverity::Low
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_rules_override_builtin_severity() {
+        let message = "thread 'main' panicked at src/main.rs:3:5:\nassertion failed: sorted(&out)";
+        assert_eq!(builtin_severity(message), CrashSeverity::Critical);
+
+        let rules: CrashSeverityRules = serde_json::from_value(json!({
+            "rules": [
+                { "pattern": "assertion failed", "severity": "low" },
+                { "pattern": "(?i)overflow", "severity": "critical" }
+            ],
+            "penalties": { "low": 0 },
+            "maxPenalty": 15
+        }))
+        .unwrap();
+        assert!(rules.validate().is_ok());
+        assert_eq!(rules.classify(message), Some(CrashSeverity::Low));
+        assert_eq!(rules.classify("attempt to add with Overflow"), Some(CrashSeverity::Critical));
+        assert_eq!(rules.classify("index out of bounds"), None);
+        assert_eq!(rules.penalty_for(&[CrashSeverity::Low, CrashSeverity::High]), 5);
+        assert_eq!(rules.penalty_for(&[CrashSeverity::Critical, CrashSeverity::Critical]), 15);
+
+        let invalid = CrashSeverityRules { rules: vec![SeverityRule { pattern: "(".to_string(), severity: CrashSeverity::Low, regex: OnceLock::new() }], ..Default::default() };
+        assert!(invalid.validate().is_err());
+        assert_eq!(invalid.classify("("), None);
+
+        // Checkpoints written before severities were lowercase still load
+        assert_eq!(serde_json::from_value::<CrashSeverity>(json!("Critical")).unwrap(), CrashSeverity::Critical);
+        assert_eq!(serde_json::to_value(CrashSeverity::Critical).unwrap(), json!("critical"));
+    }
+}
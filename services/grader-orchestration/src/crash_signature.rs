@@ -0,0 +1,143 @@
+use regex::Regex;
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+/// Frames from the top of the stack that identify a crash.
+const SIGNATURE_FRAMES: usize = 3;
+
+/// Panic machinery and interpreter internals sit on top of every stack alike.
+const RUNTIME_FRAMES: &[&str] = &["std::", "core::", "alloc::", "rust_begin_unwind", "/rustc/", "node:internal", "<frozen "];
+
+/// Identifies the bug behind a crash: a hash of its error class and its top
+/// user stack frames, with numbers and addresses normalized away so the same
+/// bug hit with different inputs hashes the same.
+pub fn signature(error_message: &str, stack_trace: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(error_class(error_message).as_bytes());
+    for frame in top_frames(stack_trace) {
+        hasher.update(b"\n");
+        hasher.update(frame.as_bytes());
+    }
+    hex::encode(&hasher.finalize()[..8])
+}
+
+/// The line naming what went wrong: the exception Python and Node print
+/// after their traceback, otherwise the first line (Rust's `panicked at`).
+pub fn error_class(error_message: &str) -> String {
+    let exception = cached(&EXCEPTION, r"^[A-Za-z_][\w.]*(?:Error|Exception)\b");
+    let lines: Vec<&str> = error_message.lines().map(str::trim).filter(|l| !l.is_empty()).collect();
+    let line = lines
+        .iter()
+        .rev()
+        .find(|l| exception.is_match(l))
+        .or_else(|| lines.first())
+        .copied()
+        .unwrap_or("");
+    normalize(line)
+}
+
+/// The top user frames by function name. The `at file:line` lines Rust prints
+/// under each frame are skipped, so they don't crowd out the callers.
+fn top_frames(stack_trace: &str) -> Vec<String> {
+    let rust_frame = cached(&RUST_FRAME, r"^\d+: ");
+    let mut frames = vec![];
+    let mut in_rust_frame = false;
+    for line in stack_trace.lines().map(str::trim) {
+        if in_rust_frame && line.starts_with("at ") {
+            continue;
+        }
+        in_rust_frame = rust_frame.is_match(line);
+        if line.is_empty() || line.starts_with("note:") || line.contains("stack backtrace") || line.contains("Stack trace") {
+            continue;
+        }
+        if RUNTIME_FRAMES.iter().any(|r| line.contains(r)) {
+            continue;
+        }
+        frames.push(normalize(&rust_frame.replace(line, "")));
+        if frames.len() == SIGNATURE_FRAMES {
+            break;
+        }
+    }
+    frames
+}
+
+static EXCEPTION: OnceLock<Regex> = OnceLock::new();
+static RUST_FRAME: OnceLock<Regex> = OnceLock::new();
+static ADDRESS: OnceLock<Regex> = OnceLock::new();
+static SYMBOL_HASH: OnceLock<Regex> = OnceLock::new();
+static NUMBER: OnceLock<Regex> = OnceLock::new();
+
+/// `pattern`, compiled the first time it is used.
+fn cached(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+/// Strip what varies between hits of the same bug: addresses, symbol
+/// hashes, and numbers (values, lengths, line and column numbers), but not
+/// digits within names like `parse_u32`.
+fn normalize(line: &str) -> String {
+    let line = cached(&ADDRESS, r"0x[0-9a-fA-F]+").replace_all(line, "0x_");
+    let line = cached(&SYMBOL_HASH, r"::h[0-9a-f]{16}\b").replace_all(&line, "");
+    cached(&NUMBER, r"\b\d+\b").replace_all(&line, "N").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature_ignores_values_but_not_location() {
+        let trace = |index: u32| format!(
+            "stack backtrace:\n   0: rust_begin_unwind\n   1: core::panicking::panic_bounds_check\n   {}: solution::lookup::h0123456789abcdef\n   {}: solution::main\n",
+            index,
+            index + 1
+        );
+        let a = signature("thread 'main' panicked at src/main.rs:12:5:\nindex out of bounds: the len is 3 but the index is 7", &trace(2));
+        let b = signature("thread 'main' panicked at src/main.rs:12:5:\nindex out of bounds: the len is 0 but the index is 0", &trace(3));
+        assert_eq!(a, b);
+
+        let other = signature("thread 'main' panicked at src/main.rs:12:5:", "stack backtrace:\n   2: solution::parse\n");
+        assert_ne!(a, other);
+
+        let python = "Traceback (most recent call last):\n  File \"code.py\", line 9, in <module>\nZeroDivisionError: division by zero";
+        assert_eq!(error_class(python), "ZeroDivisionError: division by zero");
+    }
+
+    #[inline(never)]
+    fn lookup_u8(values: &[u8], index: usize) -> u8 {
+        values[index]
+    }
+
+    /// Panics when run by `test_signature_of_real_panics`, with the index
+    /// it is given.
+    #[test]
+    fn crashing_helper() {
+        if let Ok(index) = std::env::var("CRASH_SIGNATURE_INDEX") {
+            lookup_u8(&[1, 2, 3], index.parse().unwrap());
+        }
+    }
+
+    fn real_panic(index: usize) -> (String, String) {
+        let output = std::process::Command::new(std::env::current_exe().unwrap())
+            .args(["--exact", "crash_signature::tests::crashing_helper", "--nocapture", "--test-threads=1"])
+            .env("CRASH_SIGNATURE_INDEX", index.to_string())
+            .env("RUST_BACKTRACE", "1")
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8_lossy(&output.stderr).to_string();
+        let start = stderr.find("thread '").expect("helper should panic");
+        let panic = &stderr[start..];
+        let trace = panic.find("stack backtrace").map_or(String::new(), |i| panic[i..].to_string());
+        (panic.lines().take(2).collect::<Vec<_>>().join("\n"), trace)
+    }
+
+    #[test]
+    fn test_signature_of_real_panics() {
+        let (message, trace) = real_panic(7);
+        assert!(top_frames(&trace)[0].ends_with("lookup_u8"), "{:?}", top_frames(&trace));
+
+        let (other_message, other_trace) = real_panic(42);
+        assert_ne!(message, other_message);
+        assert_eq!(signature(&message, &trace), signature(&other_message, &other_trace));
+    }
+}
@@ -0,0 +1,209 @@
+use crate::compiler::{Diagnostic, Severity};
+use crate::typescript::TscDiagnostic;
+use crate::vyper::VyperDiagnostic;
+use regex::Regex;
+use serde_json::Value;
+
+/// Cargo's `--message-format=json` output split into the compiler diagnostics,
+/// their human-readable renderings, and the remaining (non-JSON) output lines.
+pub struct CargoMessages {
+    pub diagnostics: Vec<Diagnostic>,
+    pub rendered: String,
+    pub other_output: String,
+}
+
+/// Parse the JSON lines cargo prints with `--message-format=json`. Each
+/// `compiler-message` becomes a diagnostic located at its primary span.
+pub fn parse_cargo_messages(stdout: &str) -> CargoMessages {
+    let mut messages = CargoMessages {
+        diagnostics: Vec::new(),
+        rendered: String::new(),
+        other_output: String::new(),
+    };
+
+    for line in stdout.lines() {
+        let record = match serde_json::from_str::<Value>(line) {
+            Ok(record) if record.is_object() => record,
+            _ => {
+                messages.other_output.push_str(line);
+                messages.other_output.push('\n');
+                continue;
+            }
+        };
+        if record.get("reason").and_then(|r| r.as_str()) != Some("compiler-message") {
+            continue;
+        }
+        let message = match record.get("message") {
+            Some(message) => message,
+            None => continue,
+        };
+
+        if let Some(rendered) = message.get("rendered").and_then(|r| r.as_str()) {
+            messages.rendered.push_str(rendered);
+        }
+        if let Some(diagnostic) = cargo_diagnostic(message) {
+            messages.diagnostics.push(diagnostic);
+        }
+    }
+
+    messages
+}
+
+/// A clippy lint reported by `cargo clippy --message-format=json`.
+pub struct LintMessage {
+    /// Lint name without the `clippy::` prefix, e.g. `unwrap_used`
+    pub lint: String,
+    pub diagnostic: Diagnostic,
+}
+
+/// The clippy lints among cargo's JSON messages; plain compiler warnings are
+/// left out.
+pub fn parse_clippy_lints(stdout: &str) -> Vec<LintMessage> {
+    stdout
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .filter(|record| record.get("reason").and_then(|r| r.as_str()) == Some("compiler-message"))
+        .filter_map(|record| {
+            let message = record.get("message")?;
+            let lint = message.pointer("/code/code")?.as_str()?.strip_prefix("clippy::")?.to_string();
+            Some(LintMessage { lint, diagnostic: cargo_diagnostic(message)? })
+        })
+        .collect()
+}
+
+/// The diagnostic of one rustc JSON message, located at its primary span.
+fn cargo_diagnostic(message: &Value) -> Option<Diagnostic> {
+    let text = message.get("message").and_then(|m| m.as_str()).unwrap_or("").to_string();
+    let spans = message.get("spans").and_then(|s| s.as_array()).cloned().unwrap_or_default();
+    // Summaries such as "aborting due to 2 previous errors" have no location
+    // and repeat what the other diagnostics already say
+    if spans.is_empty() && (text.starts_with("aborting due to") || text.ends_with("emitted")) {
+        return None;
+    }
+
+    let severity = match message.get("level").and_then(|l| l.as_str()).unwrap_or("") {
+        level if level.starts_with("error") => Severity::Error,
+        "warning" => Severity::Warning,
+        _ => Severity::Note,
+    };
+    let primary = spans
+        .iter()
+        .find(|s| s.get("is_primary").and_then(|p| p.as_bool()).unwrap_or(false))
+        .or_else(|| spans.first());
+
+    Some(Diagnostic {
+        file: primary.and_then(|s| s.get("file_name")).and_then(|f| f.as_str()).map(String::from),
+        line: primary.and_then(|s| s.get("line_start")).and_then(|l| l.as_u64()).map(|l| l as u32),
+        col: primary.and_then(|s| s.get("column_start")).and_then(|c| c.as_u64()).map(|c| c as u32),
+        severity,
+        message: text,
+        code: message.pointer("/code/code").and_then(|c| c.as_str()).map(String::from),
+    })
+}
+
+/// Parse the `errors` array of solc standard JSON output, as printed by
+/// `forge build --json`. solc locates errors by byte offset; the line and column
+/// come from the `--> file:line:col:` header of the formatted message.
+pub fn parse_solc_json(stdout: &str) -> Vec<Diagnostic> {
+    let location = Regex::new(r"--> ([^:\n]+):(\d+):(\d+):").unwrap();
+
+    // forge may log progress before the JSON document
+    let output: Value = match stdout.find('{').and_then(|start| serde_json::from_str(&stdout[start..]).ok()) {
+        Some(output) => output,
+        None => return Vec::new(),
+    };
+
+    output
+        .get("errors")
+        .and_then(|e| e.as_array())
+        .map(|errors| {
+            errors
+                .iter()
+                .map(|error| {
+                    let formatted = error.get("formattedMessage").and_then(|m| m.as_str()).unwrap_or("");
+                    let caps = location.captures(formatted);
+                    Diagnostic {
+                        file: caps
+                            .as_ref()
+                            .map(|c| c[1].to_string())
+                            .or_else(|| error.pointer("/sourceLocation/file").and_then(|f| f.as_str()).map(String::from)),
+                        line: caps.as_ref().and_then(|c| c[2].parse().ok()),
+                        col: caps.as_ref().and_then(|c| c[3].parse().ok()),
+                        severity: match error.get("severity").and_then(|s| s.as_str()) {
+                            Some("error") => Severity::Error,
+                            Some("warning") => Severity::Warning,
+                            _ => Severity::Note,
+                        },
+                        message: error.get("message").and_then(|m| m.as_str()).unwrap_or("").to_string(),
+                        code: error.get("errorCode").and_then(|c| c.as_str()).map(String::from),
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+pub fn from_tsc(diagnostics: &[TscDiagnostic]) -> Vec<Diagnostic> {
+    diagnostics
+        .iter()
+        .map(|d| Diagnostic {
+            file: Some(d.file.clone()),
+            line: Some(d.line),
+            col: Some(d.column),
+            severity: Severity::Error,
+            message: format!("{}: {}", d.code, d.message),
+            code: Some(d.code.clone()),
+        })
+        .collect()
+}
+
+pub fn from_vyper(diagnostics: &[VyperDiagnostic], file: &str) -> Vec<Diagnostic> {
+    diagnostics
+        .iter()
+        .map(|d| Diagnostic {
+            file: Some(file.to_string()),
+            line: d.line,
+            col: d.column,
+            severity: Severity::Error,
+            message: format!("{}: {}", d.kind, d.message),
+            code: None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cargo_messages() {
+        let stdout = r#"{"reason":"compiler-artifact","package_id":"grader-code"}
+{"reason":"compiler-message","message":{"level":"error","message":"mismatched types","rendered":"error[E0308]: mismatched types\n","spans":[{"file_name":"src/main.rs","line_start":4,"column_start":18,"is_primary":true}]}}
+{"reason":"compiler-message","message":{"level":"warning","message":"unused variable: `x`","code":{"code":"unused_variables"},"rendered":"warning: unused variable\n","spans":[{"file_name":"src/main.rs","line_start":2,"column_start":9,"is_primary":true}]}}
+{"reason":"compiler-message","message":{"level":"error","message":"aborting due to 1 previous error","rendered":"error: aborting\n","spans":[]}}
+build script says hi"#;
+
+        let messages = parse_cargo_messages(stdout);
+        assert_eq!(messages.diagnostics, vec![
+            Diagnostic { file: Some("src/main.rs".into()), line: Some(4), col: Some(18), severity: Severity::Error, message: "mismatched types".into(), code: None },
+            Diagnostic { file: Some("src/main.rs".into()), line: Some(2), col: Some(9), severity: Severity::Warning, message: "unused variable: `x`".into(), code: Some("unused_variables".into()) },
+        ]);
+        assert!(messages.rendered.starts_with("error[E0308]"));
+        assert_eq!(messages.other_output, "build script says hi\n");
+    }
+
+    #[test]
+    fn test_parse_solc_json() {
+        let stdout = r#"Compiling 1 files
+{"errors":[{"severity":"error","errorCode":"7576","message":"Undeclared identifier.","sourceLocation":{"file":"src/Contract.sol","start":120,"end":126},"formattedMessage":"DeclarationError: Undeclared identifier.\n --> src/Contract.sol:7:16:\n"}]}"#;
+
+        assert_eq!(parse_solc_json(stdout), vec![Diagnostic {
+            file: Some("src/Contract.sol".into()),
+            line: Some(7),
+            col: Some(16),
+            severity: Severity::Error,
+            message: "Undeclared identifier.".into(),
+            code: Some("7576".into()),
+        }]);
+    }
+}
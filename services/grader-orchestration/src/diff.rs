@@ -0,0 +1,124 @@
+use serde::Serialize;
+use serde_json::Value;
+
+const MAX_DIFF_LINES: usize = 40;
+const MAX_LINE_LENGTH: usize = 200;
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffKind {
+    Same,
+    Expected,
+    Actual,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct DiffLine {
+    pub kind: DiffKind,
+    pub text: String,
+}
+
+/// Line diff between a fixture's expected output and what the submission printed.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OutputDiff {
+    pub lines: Vec<DiffLine>,
+    /// Lines were dropped or shortened to keep the response small
+    pub truncated: bool,
+}
+
+/// Diff the expected output against the result line the harness printed.
+/// Both sides are pretty-printed as JSON when possible so structural
+/// differences land on separate lines.
+pub fn diff_output(expected: &Value, stdout: &str) -> OutputDiff {
+    let actual_line = stdout.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+    let actual = match serde_json::from_str::<Value>(actual_line) {
+        Ok(value) => pretty(&value),
+        Err(_) => actual_line.to_string(),
+    };
+    diff_lines(&pretty(expected), &actual)
+}
+
+pub fn diff_lines(expected: &str, actual: &str) -> OutputDiff {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+
+    // Longest common subsequence table, filled from the end
+    let mut lcs = vec![vec![0usize; actual.len() + 1]; expected.len() + 1];
+    for i in (0..expected.len()).rev() {
+        for j in (0..actual.len()).rev() {
+            lcs[i][j] = if expected[i] == actual[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < expected.len() || j < actual.len() {
+        if i < expected.len() && j < actual.len() && expected[i] == actual[j] {
+            lines.push((DiffKind::Same, expected[i]));
+            i += 1;
+            j += 1;
+        } else if i < expected.len() && (j == actual.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push((DiffKind::Expected, expected[i]));
+            i += 1;
+        } else {
+            lines.push((DiffKind::Actual, actual[j]));
+            j += 1;
+        }
+    }
+
+    let mut truncated = lines.len() > MAX_DIFF_LINES;
+    let lines = lines
+        .into_iter()
+        .take(MAX_DIFF_LINES)
+        .map(|(kind, text)| {
+            if text.chars().count() > MAX_LINE_LENGTH {
+                truncated = true;
+                DiffLine { kind, text: format!("{}...", text.chars().take(MAX_LINE_LENGTH).collect::<String>()) }
+            } else {
+                DiffLine { kind, text: text.to_string() }
+            }
+        })
+        .collect();
+
+    OutputDiff { lines, truncated }
+}
+
+fn pretty(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => serde_json::to_string_pretty(other).unwrap_or_else(|_| other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_output_marks_changed_lines() {
+        let diff = diff_output(&json!({ "sum": 3, "ok": true }), "debug\n{\"sum\": 4, \"ok\": true}\n");
+        let changed: Vec<(&DiffKind, &str)> = diff
+            .lines
+            .iter()
+            .filter(|l| l.kind != DiffKind::Same)
+            .map(|l| (&l.kind, l.text.as_str()))
+            .collect();
+
+        assert_eq!(changed, vec![(&DiffKind::Expected, "  \"sum\": 3"), (&DiffKind::Actual, "  \"sum\": 4")]);
+        assert!(!diff.truncated);
+    }
+
+    #[test]
+    fn test_diff_truncates_long_output() {
+        let actual = (0..100).map(|n| n.to_string()).collect::<Vec<_>>().join("\n");
+        let diff = diff_lines("done", &actual);
+        assert_eq!(diff.lines.len(), MAX_DIFF_LINES);
+        assert!(diff.truncated);
+    }
+}
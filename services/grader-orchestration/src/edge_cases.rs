@@ -0,0 +1,167 @@
+use rand::prelude::*;
+use serde_json::{json, Value};
+
+/// How often a generated value is a classic edge case rather than uniform.
+pub const EDGE_CASE_RATE: f64 = 0.3;
+
+/// Length of "huge" strings and "very long" arrays where the schema sets no
+/// bound, and the depth of deeply nested values. serde_json stops parsing
+/// at 128 levels, so the nesting leaves room for the input's own structure
+/// around it.
+const HUGE_STRING_LEN: usize = 64 * 1024;
+const LONG_ARRAY_LEN: usize = 4096;
+const NESTING_DEPTH: usize = 64;
+
+/// Bytes of strings and array items one generated input may spend on edge
+/// cases, so a long array of huge strings stays a few hundred KiB.
+pub const MAX_INPUT_BYTES: usize = 256 * 1024;
+
+/// Integers that break arithmetic and indexing: the range's own ends and
+/// their neighbours, zero and ±1, and the limits of the usual integer types.
+pub fn integers(lo: i64, hi: i64) -> Vec<i64> {
+    let mut values = vec![
+        lo,
+        lo.saturating_add(1),
+        hi.saturating_sub(1),
+        hi,
+        0,
+        1,
+        -1,
+        i8::MIN as i64,
+        i8::MAX as i64,
+        u8::MAX as i64,
+        u8::MAX as i64 + 1,
+        i16::MIN as i64,
+        i16::MAX as i64,
+        u16::MAX as i64,
+        i32::MIN as i64,
+        i32::MAX as i64,
+        u32::MAX as i64,
+        u32::MAX as i64 + 1,
+        i64::MIN,
+        i64::MAX,
+    ];
+    values.retain(|n| (lo..=hi).contains(n));
+    values.sort_unstable();
+    values.dedup();
+    values
+}
+
+/// Floats that break comparisons and rounding. JSON has no NaN or infinity,
+/// so the extremes stop at the largest finite and smallest subnormal values.
+pub fn numbers(lo: f64, hi: f64) -> Vec<f64> {
+    let mut values = vec![
+        lo,
+        hi,
+        0.0,
+        -0.0,
+        1.0,
+        -1.0,
+        0.1,
+        f64::EPSILON,
+        f64::MIN_POSITIVE,
+        5e-324, // smallest subnormal
+        9007199254740993.0, // 2^53 + 1, not exactly representable
+        f64::MAX,
+        f64::MIN,
+    ];
+    values.retain(|n| *n >= lo && *n <= hi);
+    values
+}
+
+/// Strings that break parsing and text handling: empty and whitespace, a
+/// huge one, NUL, characters outside the BMP (surrogate pairs in UTF-16,
+/// so JavaScript sees two code units), combining marks, bidi overrides,
+/// zero-width and byte order marks, and values that look like other types.
+pub fn strings(min: usize, max: usize) -> Vec<String> {
+    let mut values: Vec<String> = [
+        "",
+        " ",
+        "\t\n",
+        "\u{0}",
+        "\u{1F600}",
+        "\u{10FFFF}",
+        "e\u{301}",
+        "\u{202E}abc",
+        "\u{200B}",
+        "\u{FEFF}",
+        "null",
+        "-1",
+        "1e309",
+        "%s%n",
+        "' OR '1'='1",
+    ]
+    .iter()
+    .map(|s| s.to_string())
+    .collect();
+    values.push("A".repeat(max.min(HUGE_STRING_LEN.max(min))));
+    values.push("\u{1F600}".repeat(max.min(HUGE_STRING_LEN.max(min)) / 4));
+    values.retain(|s| (min..=max).contains(&s.chars().count()));
+    values
+}
+
+/// Array lengths at the bounds, plus a very long one where allowed.
+pub fn array_lengths(min: usize, max: usize) -> Vec<usize> {
+    let mut values = vec![min, max.min(LONG_ARRAY_LEN.max(min)), 0, 1, LONG_ARRAY_LEN];
+    values.retain(|n| (min..=max).contains(n));
+    values.sort_unstable();
+    values.dedup();
+    values
+}
+
+/// A deeply nested array or object, for recursive parsers and printers.
+pub fn nested(rng: &mut StdRng) -> Value {
+    let mut value = json!(0);
+    for _ in 0..NESTING_DEPTH {
+        value = match rng.gen_bool(0.5) {
+            true => json!([value]),
+            false => json!({ "a": value }),
+        };
+    }
+    value
+}
+
+/// An edge case of any type, for inputs without a schema, taking at most
+/// about `max_bytes` when serialized.
+pub fn any(rng: &mut StdRng, max_bytes: usize) -> Value {
+    match rng.gen_range(0..4) {
+        0 => json!(integers(i64::MIN, i64::MAX).choose(rng)),
+        1 => json!(numbers(f64::MIN, f64::MAX).choose(rng)),
+        // Four-byte characters count as one, so bound the count by a quarter
+        2 => json!(strings(0, max_bytes / 4).choose(rng)),
+        _ => nested(rng),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edge_cases_respect_bounds() {
+        assert_eq!(integers(0, 255), vec![0, 1, 127, 254, 255]);
+        assert!(integers(i64::MIN, i64::MAX).contains(&i64::MIN));
+        assert!(numbers(0.0, 1.0).iter().all(|n| (0.0..=1.0).contains(n)));
+        assert!(numbers(-1.0, 1.0).iter().any(|n| *n == 0.0 && n.is_sign_negative()));
+
+        let bounded = strings(1, 8);
+        assert!(bounded.iter().all(|s| (1..=8).contains(&s.chars().count())));
+        assert!(bounded.contains(&"\u{1F600}".to_string()));
+        assert!(strings(0, usize::MAX).iter().any(|s| s.len() == HUGE_STRING_LEN));
+
+        assert_eq!(array_lengths(2, 3), vec![2, 3]);
+        let mut depth = 0;
+        let mut value = nested(&mut StdRng::seed_from_u64(1));
+        while let Some(inner) = value.get(0).or_else(|| value.get("a")).cloned() {
+            value = inner;
+            depth += 1;
+        }
+        assert_eq!(depth, NESTING_DEPTH);
+        // Even inside a few levels of the input's own structure, it parses
+        let wrapped = json!({ "input": [[nested(&mut StdRng::seed_from_u64(1))]] });
+        assert!(serde_json::from_str::<Value>(&wrapped.to_string()).is_ok());
+
+        let mut rng = StdRng::seed_from_u64(2);
+        assert!((0..64).all(|_| any(&mut rng, 1024).to_string().len() <= 1024));
+    }
+}
@@ -0,0 +1,139 @@
+use crate::anti_cheat::{PlagiarismResult, RiskLevel};
+use reqwest::Client;
+use serde::Serialize;
+use std::time::Duration;
+
+const WEBHOOK_ATTEMPTS: u32 = 3;
+
+/// How plagiarism findings are escalated to human reviewers.
+#[derive(Clone, Debug)]
+pub struct EscalationPolicy {
+    /// Reviewer webhook or queue endpoint receiving evidence bundles
+    pub webhook_url: Option<String>,
+    /// Lowest risk level that is escalated
+    pub min_risk: RiskLevel,
+    /// Withhold the result as "under review" until a reviewer decides
+    pub hold_results: bool,
+}
+
+impl Default for EscalationPolicy {
+    fn default() -> Self {
+        Self {
+            webhook_url: None,
+            min_risk: RiskLevel::High,
+            hold_results: false,
+        }
+    }
+}
+
+impl EscalationPolicy {
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        Self {
+            webhook_url: std::env::var("ANTI_CHEAT_WEBHOOK_URL").ok().filter(|url| !url.is_empty()),
+            min_risk: match std::env::var("ANTI_CHEAT_ESCALATION_LEVEL").as_deref() {
+                Ok("medium") => RiskLevel::Medium,
+                Ok("critical") => RiskLevel::Critical,
+                _ => default.min_risk,
+            },
+            hold_results: std::env::var("ANTI_CHEAT_HOLD_RESULTS")
+                .map(|v| v == "true" || v == "1")
+                .unwrap_or(default.hold_results),
+        }
+    }
+
+    pub fn should_escalate(&self, result: &PlagiarismResult) -> bool {
+        result.risk_level >= self.min_risk
+    }
+}
+
+/// Everything a reviewer needs to judge a flagged submission.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EvidenceBundle {
+    pub job_id: String,
+    pub challenge_id: String,
+    pub user_id: String,
+    pub language: String,
+    pub code: String,
+    pub plagiarism: PlagiarismResult,
+    /// Whether the public result is held until a review decision
+    pub held: bool,
+    pub flagged_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Tells reviewers to delete the evidence bundles they hold about a user,
+/// for data-deletion requests. Sent to the same webhook, told apart from
+/// bundles by its `event`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletionNotice {
+    pub event: &'static str,
+    pub user_id: String,
+    pub requested_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl DeletionNotice {
+    pub fn new(user_id: &str) -> Self {
+        Self { event: "userDeleted", user_id: user_id.to_string(), requested_at: chrono::Utc::now() }
+    }
+}
+
+/// POST the evidence bundle to the reviewer webhook, retrying with backoff.
+pub async fn notify_reviewers(webhook_url: &str, bundle: &EvidenceBundle) -> Result<(), String> {
+    post(webhook_url, bundle).await.map_err(|e| format!("Failed to notify reviewers for job {}: {}", bundle.job_id, e))
+}
+
+/// POST the deletion notice to the reviewer webhook, retrying with backoff.
+pub async fn notify_deletion(webhook_url: &str, notice: &DeletionNotice) -> Result<(), String> {
+    post(webhook_url, notice).await.map_err(|e| format!("Failed to ask reviewers to delete evidence on user {}: {}", notice.user_id, e))
+}
+
+async fn post(webhook_url: &str, body: &impl Serialize) -> Result<(), String> {
+    let client = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .map_err(|e| e.to_string())?;
+
+    let mut last_error = String::new();
+    for attempt in 0..WEBHOOK_ATTEMPTS {
+        if attempt > 0 {
+            tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+        }
+
+        match client.post(webhook_url).json(body).send().await {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => last_error = format!("webhook returned {}", response.status()),
+            Err(e) => last_error = e.to_string(),
+        }
+    }
+
+    Err(last_error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(risk_level: RiskLevel) -> PlagiarismResult {
+        PlagiarismResult {
+            similarity_score: 0.0,
+            matched_submissions: vec![],
+            risk_level,
+            analysis_time_ms: 0,
+            evasion: vec![],
+            ai_likelihood: None,
+        }
+    }
+
+    #[test]
+    fn test_escalates_at_or_above_min_risk() {
+        let policy = EscalationPolicy::default();
+        assert!(!policy.should_escalate(&result(RiskLevel::Medium)));
+        assert!(policy.should_escalate(&result(RiskLevel::High)));
+        assert!(policy.should_escalate(&result(RiskLevel::Critical)));
+
+        let critical_only = EscalationPolicy { min_risk: RiskLevel::Critical, ..policy };
+        assert!(!critical_only.should_escalate(&result(RiskLevel::High)));
+    }
+}
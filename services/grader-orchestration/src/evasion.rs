@@ -0,0 +1,234 @@
+use crate::ai_heuristics::KEYWORDS;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+/// Unreachable lines from which dead code counts as injected.
+const MIN_UNREACHABLE_LINES: usize = 5;
+/// Inert statements, and their share of all statements, from which they
+/// count as padding.
+const MIN_INERT_STATEMENTS: usize = 3;
+const MIN_INERT_SHARE: f64 = 0.15;
+/// Scrambled identifiers, and their share of all identifiers, from which
+/// renaming counts as systematic.
+const MIN_SCRAMBLED_IDENTIFIERS: usize = 4;
+const MIN_SCRAMBLED_SHARE: f64 = 0.4;
+
+/// Statements after which the rest of their block never runs.
+const TERMINATORS: &[&str] = &["return", "break", "continue", "throw", "raise", "panic!", "unreachable!", "revert"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EvasionKind {
+    /// Code after a return or inside `if (false)` blocks
+    UnreachableCode,
+    /// Statements with no effect, such as `x;` or `x += 0;`
+    InertPadding,
+    /// Identifiers renamed to meaningless strings like `_0x3fa2` or `lIl1I`
+    IdentifierScrambling,
+}
+
+/// A pattern typical of disguising copied code.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvasionFinding {
+    pub kind: EvasionKind,
+    /// How many lines, statements or identifiers showed the pattern
+    pub count: usize,
+}
+
+/// Evasive patterns in `code`. Text-based, so it works for every language;
+/// Python is told apart by its indentation-delimited blocks.
+pub fn analyze(code: &str, language: &str) -> Vec<EvasionFinding> {
+    let python = language.eq_ignore_ascii_case("python");
+    let lines: Vec<&str> = code
+        .lines()
+        .filter(|l| {
+            let trimmed = l.trim();
+            !trimmed.is_empty() && !trimmed.starts_with("//") && !trimmed.starts_with('#')
+        })
+        .collect();
+
+    let mut findings = Vec::new();
+    let unreachable = if python { unreachable_indented(&lines) } else { unreachable_braced(&lines) };
+    if unreachable >= MIN_UNREACHABLE_LINES {
+        findings.push(EvasionFinding { kind: EvasionKind::UnreachableCode, count: unreachable });
+    }
+
+    let statements: Vec<&str> = lines
+        .iter()
+        .map(|l| l.trim())
+        .filter(|l| python || l.ends_with(';'))
+        .collect();
+    let inert = statements.iter().filter(|s| is_inert(s.trim_end_matches(';').trim())).count();
+    if inert >= MIN_INERT_STATEMENTS && inert as f64 >= MIN_INERT_SHARE * statements.len() as f64 {
+        findings.push(EvasionFinding { kind: EvasionKind::InertPadding, count: inert });
+    }
+
+    let identifier = Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").unwrap();
+    let names: BTreeSet<&str> = identifier
+        .find_iter(code)
+        .map(|m| m.as_str())
+        .filter(|n| n.len() > 2 && !KEYWORDS.contains(n))
+        .collect();
+    let scrambled = names.iter().filter(|n| is_scrambled(n)).count();
+    if scrambled >= MIN_SCRAMBLED_IDENTIFIERS && scrambled as f64 >= MIN_SCRAMBLED_SHARE * names.len() as f64 {
+        findings.push(EvasionFinding { kind: EvasionKind::IdentifierScrambling, count: scrambled });
+    }
+    findings
+}
+
+fn is_terminator(statement: &str) -> bool {
+    TERMINATORS.iter().any(|t| {
+        statement
+            .strip_prefix(t)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', ';', '(']))
+    })
+}
+
+/// A `case ...:` or `default:` label, which is reachable by jumping to it.
+fn is_case_label(line: &str) -> bool {
+    (line.starts_with("case ") && line.contains(':')) || line.strip_prefix("default").is_some_and(|rest| rest.trim_start().starts_with(':'))
+}
+
+fn is_constant_false(condition: &str) -> bool {
+    let condition = condition.trim().trim_start_matches('(').trim_end_matches(')').trim();
+    matches!(condition, "false" | "False" | "0")
+}
+
+/// Lines that can't run in a language whose blocks are delimited by braces.
+fn unreachable_braced(lines: &[&str]) -> usize {
+    let (mut depth, mut dead_depth, mut count) = (0usize, None, 0);
+    for line in lines {
+        let trimmed = line.trim();
+        if let Some(dead) = dead_depth {
+            // The brace closing the dead code's block ends it, as does the
+            // next label of a switch whose case it was in
+            if (trimmed.starts_with('}') || is_case_label(trimmed)) && depth == dead {
+                dead_depth = None;
+            } else {
+                count += 1;
+            }
+        }
+        depth = (depth + trimmed.matches('{').count()).saturating_sub(trimmed.matches('}').count());
+
+        if dead_depth.is_none() {
+            if is_terminator(trimmed) && trimmed.ends_with(';') {
+                dead_depth = Some(depth);
+            } else if let Some(condition) = ["if", "while"].iter().find_map(|k| trimmed.strip_prefix(k)) {
+                if trimmed.ends_with('{') && is_constant_false(condition.trim_end_matches('{')) {
+                    dead_depth = Some(depth);
+                }
+            }
+        }
+    }
+    count
+}
+
+/// Lines that can't run in Python, whose blocks are delimited by indentation.
+fn unreachable_indented(lines: &[&str]) -> usize {
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    // Lines indented at least this much are dead
+    let mut dead_indent = None;
+    let mut count = 0;
+    for line in lines {
+        let (trimmed, level) = (line.trim(), indent(line));
+        match dead_indent {
+            Some(dead) if level >= dead => {
+                count += 1;
+                continue;
+            }
+            _ => dead_indent = None,
+        }
+        if is_terminator(trimmed) {
+            dead_indent = Some(level);
+        } else if let Some(condition) = ["if", "while"].iter().find_map(|k| trimmed.strip_prefix(k)) {
+            if trimmed.ends_with(':') && is_constant_false(condition.trim_end_matches(':')) {
+                dead_indent = Some(level + 1);
+            }
+        }
+    }
+    count
+}
+
+/// Bare words that are statements with an effect: jumps, Python's re-raise
+/// and the placeholder for a function's body in a Solidity modifier.
+const BARE_STATEMENTS: &[&str] = &["pass", "break", "continue", "return", "raise", "_"];
+
+/// A statement without effect: a bare name or literal, a self-assignment,
+/// or adding zero or multiplying by one.
+fn is_inert(statement: &str) -> bool {
+    let is_atom = |s: &str| {
+        let s = s.trim();
+        !s.is_empty() && s.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+    };
+    if statement.is_empty() || (is_atom(statement) && !BARE_STATEMENTS.contains(&statement)) {
+        return true;
+    }
+    if let Some((target, value)) = statement.split_once(" = ") {
+        return is_atom(target) && target.trim() == value.trim();
+    }
+    [("+=", "0"), ("-=", "0"), ("*=", "1"), ("/=", "1")]
+        .iter()
+        .any(|(op, identity)| statement.split_once(op).is_some_and(|(target, value)| is_atom(target) && value.trim() == *identity))
+}
+
+/// Hex-style names (`_0x3fa2`), names made only of easily confused
+/// characters (`lIl1I`), and long names without vowels or alternating
+/// letters and digits (`q7x9k2`).
+fn is_scrambled(name: &str) -> bool {
+    let bare = name.trim_start_matches('_');
+    if bare.starts_with("0x") {
+        return true;
+    }
+    if bare.len() >= 4 && bare.chars().all(|c| matches!(c, 'l' | 'I' | '1' | 'O' | '0' | '_')) {
+        return true;
+    }
+    let letters: Vec<char> = bare.chars().filter(|c| c.is_ascii_alphabetic()).collect();
+    let vowels = letters.iter().filter(|c| "aeiouAEIOU".contains(**c)).count();
+    let switches = bare
+        .chars()
+        .collect::<Vec<_>>()
+        .windows(2)
+        .filter(|w| w[0].is_ascii_digit() != w[1].is_ascii_digit())
+        .count();
+    bare.len() >= 6 && (vowels == 0 || switches >= 4)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(code: &str, language: &str) -> Vec<EvasionKind> {
+        analyze(code, language).into_iter().map(|f| f.kind).collect()
+    }
+
+    #[test]
+    fn test_flags_evasive_patterns() {
+        let clean = "fn total(values: &[i32]) -> i32 {\n    let mut sum = 0;\n    for value in values {\n        sum += value;\n    }\n    sum\n}\n";
+        assert!(kinds(clean, "rust").is_empty());
+
+        let dead = "fn f(x: i32) -> i32 {\n    if x > 0 {\n        return x;\n        let a = 1;\n        let b = 2;\n    }\n    if false {\n        let c = 3;\n        let d = 4;\n        let e = 5;\n    }\n    x\n}\n";
+        assert_eq!(analyze(dead, "rust")[0].count, 5);
+
+        let padded = "function f(x) {\n  x;\n  let y = x;\n  y = y;\n  y += 0;\n  0;\n  return y;\n}\n";
+        assert_eq!(kinds(padded, "typescript"), vec![EvasionKind::InertPadding]);
+
+        let scrambled = "def _0x1a(lIl1, O0O0):\n    q7x9k2 = lIl1 + O0O0\n    return q7x9k2\n";
+        assert_eq!(kinds(scrambled, "python"), vec![EvasionKind::IdentifierScrambling]);
+
+        let python_dead = "def f(x):\n    return x\n    y = 1\n    z = 2\n    w = 3\n    v = 4\n    u = 5\ndef g():\n    pass\n";
+        assert_eq!(kinds(python_dead, "python"), vec![EvasionKind::UnreachableCode]);
+    }
+
+    #[test]
+    fn test_ordinary_control_flow_is_not_flagged() {
+        let switch = "function grade(x) {\n  switch (x) {\n    case 1:\n      a = 1;\n      break;\n    case 2:\n      a = 2;\n      b = 2;\n      break;\n    case 3: {\n      a = 3;\n      break;\n    }\n    default:\n      a = 0;\n      b = 0;\n      c = 0;\n  }\n  return a;\n}\n";
+        assert!(kinds(switch, "typescript").is_empty());
+
+        let reraise = "def f(x):\n    try:\n        g(x)\n    except ValueError:\n        log(x)\n        raise\n    except KeyError:\n        raise\n    except TypeError:\n        raise\n";
+        assert!(kinds(reraise, "python").is_empty());
+
+        let modifiers = "contract C {\n  modifier a() { require(x); _; }\n  modifier b() {\n    require(y);\n    _;\n  }\n  modifier c() {\n    _;\n    done = true;\n  }\n  modifier d() {\n    _;\n  }\n}\n";
+        assert!(kinds(modifiers, "solidity").is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+use revm::db::{CacheDB, EmptyDB};
+use revm::primitives::{keccak256, AccountInfo, Address, Bytes, ExecutionResult, Output, TxKind, U256};
+use revm::Evm;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// Hex creation bytecode saved after compilation for fixture execution.
+pub const BYTECODE_FILE: &str = "Contract.bin";
+
+const CALLER: Address = Address::repeat_byte(0xfa);
+
+/// Outcome of executing one fixture call against a freshly deployed contract.
+#[derive(Clone, Debug)]
+pub struct EvmCallOutcome {
+    pub success: bool,
+    pub reverted: bool,
+    /// Gas consumed by the call transaction, as metered by the EVM
+    pub gas_used: u64,
+    pub return_data: Vec<u8>,
+}
+
+/// A fixture executed directly against the contract: its input carries
+/// `calldata` (hex) and optionally `value` (wei, decimal or hex string).
+pub struct EvmCall {
+    pub calldata: Vec<u8>,
+    pub value: U256,
+}
+
+impl EvmCall {
+    pub fn from_fixture_input(input: &Value) -> Option<Result<Self, String>> {
+        let calldata = input.get("calldata")?.as_str()?;
+        Some(Self::parse(calldata, input.get("value")))
+    }
+
+    fn parse(calldata: &str, value: Option<&Value>) -> Result<Self, String> {
+        let calldata = decode_hex(calldata).map_err(|e| format!("Invalid calldata: {}", e))?;
+        let value = match value {
+            None | Some(Value::Null) => U256::ZERO,
+            Some(Value::Number(n)) => U256::from(n.as_u64().ok_or("Invalid call value")?),
+            Some(Value::String(s)) => s.parse::<U256>().map_err(|e| format!("Invalid call value: {}", e))?,
+            Some(_) => return Err("Invalid call value".to_string()),
+        };
+        Ok(Self { calldata, value })
+    }
+}
+
+/// Extract the creation bytecode of the compiled contract. Vyper prints it
+/// after the ABI with `-f abi,bytecode`; forge writes it to `out/`.
+pub fn creation_bytecode(workspace: &Path, language: &str, compile_stdout: &str) -> Result<Vec<u8>, String> {
+    let hex = match language {
+        "vyper" => compile_stdout
+            .lines()
+            .map(|l| l.trim())
+            .find(|l| l.starts_with("0x"))
+            .ok_or("No bytecode in vyper output")?
+            .to_string(),
+        _ => {
+            let artifact_dir = workspace.join("out").join("Contract.sol");
+            let artifact = std::fs::read_dir(&artifact_dir)
+                .map_err(|e| format!("Failed to read {}: {}", artifact_dir.display(), e))?
+                .flatten()
+                .map(|entry| entry.path())
+                .find(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+                .ok_or("No compiled contract artifact")?;
+            let artifact: Value = serde_json::from_str(
+                &std::fs::read_to_string(&artifact).map_err(|e| format!("Failed to read artifact: {}", e))?,
+            )
+            .map_err(|e| format!("Failed to parse artifact: {}", e))?;
+            artifact
+                .pointer("/bytecode/object")
+                .and_then(|b| b.as_str())
+                .ok_or("Artifact has no bytecode")?
+                .to_string()
+        }
+    };
+
+    decode_hex(&hex)
+}
+
+/// A compiled Solidity contract, read from a forge or hardhat artifact, for
+/// services that deploy or analyze it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContractArtifact {
+    pub name: String,
+    pub source: String,
+    pub abi: Value,
+    /// Hex creation bytecode, `0x` for interfaces and abstract contracts
+    pub bytecode: String,
+    /// keccak256 of the deployed bytecode, as `EXTCODEHASH` reports it once
+    /// deployed. None when there is no runtime code or it has unlinked libraries.
+    pub deployed_bytecode_hash: Option<String>,
+}
+
+/// The contracts compiled from one source file, from the artifacts in `dir`
+/// (`out/Contract.sol` for forge, `artifacts/contracts/Contract.sol` for
+/// hardhat). Both tools write one `<Contract>.json` per contract.
+pub fn contract_artifacts(workspace: &Path, dir: &str) -> Vec<ContractArtifact> {
+    let mut paths: Vec<_> = std::fs::read_dir(workspace.join(dir))
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().map(|ext| ext == "json").unwrap_or(false))
+        .filter(|path| !path.to_string_lossy().ends_with(".dbg.json"))
+        .collect();
+    paths.sort();
+
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let artifact: Value = serde_json::from_str(&std::fs::read_to_string(&path).ok()?).ok()?;
+            let name = path.file_stem()?.to_string_lossy().to_string();
+            let source = artifact
+                .get("sourceName")
+                .and_then(|s| s.as_str())
+                .map(String::from)
+                .unwrap_or_else(|| dir.rsplit('/').next().unwrap_or(dir).to_string());
+            let deployed = bytecode_field(&artifact, "deployedBytecode");
+
+            Some(ContractArtifact {
+                name,
+                source,
+                abi: artifact.get("abi").cloned().unwrap_or(Value::Array(vec![])),
+                bytecode: format!("0x{}", bytecode_field(&artifact, "bytecode").unwrap_or_default()),
+                deployed_bytecode_hash: deployed
+                    .filter(|code| !code.is_empty())
+                    .and_then(|code| decode_hex(&code).ok())
+                    .map(|code| format!("0x{}", hex::encode(keccak256(&code)))),
+            })
+        })
+        .collect()
+}
+
+/// Hex bytecode without `0x`. Forge nests it as `{ "object": ".." }`, hardhat
+/// stores the string directly.
+fn bytecode_field(artifact: &Value, field: &str) -> Option<String> {
+    let value = artifact.get(field)?;
+    let hex = value.as_str().or_else(|| value.get("object").and_then(|o| o.as_str()))?;
+    Some(hex.trim_start_matches("0x").to_string())
+}
+
+/// Deploy `bytecode` into an empty in-memory chain and execute `call` against it.
+pub fn execute_call(bytecode: &[u8], call: &EvmCall, gas_limit: u64) -> Result<EvmCallOutcome, String> {
+    let mut db = CacheDB::new(EmptyDB::default());
+    db.insert_account_info(CALLER, AccountInfo {
+        balance: U256::MAX,
+        ..Default::default()
+    });
+
+    let mut evm = Evm::builder()
+        .with_db(db)
+        .modify_tx_env(|tx| {
+            tx.caller = CALLER;
+            tx.transact_to = TxKind::Create;
+            tx.data = Bytes::copy_from_slice(bytecode);
+            tx.gas_limit = gas_limit;
+        })
+        .build();
+
+    let contract = match evm.transact_commit().map_err(|e| format!("Deployment failed: {:?}", e))? {
+        ExecutionResult::Success { output: Output::Create(_, Some(address)), .. } => address,
+        other => return Err(format!("Deployment failed: {:?}", other)),
+    };
+
+    let tx = evm.tx_mut();
+    tx.transact_to = TxKind::Call(contract);
+    tx.data = Bytes::from(call.calldata.clone());
+    tx.value = call.value;
+    tx.gas_limit = gas_limit;
+
+    let result = evm.transact_commit().map_err(|e| format!("Call failed: {:?}", e))?;
+    Ok(EvmCallOutcome {
+        success: result.is_success(),
+        reverted: matches!(result, ExecutionResult::Revert { .. }),
+        gas_used: result.gas_used(),
+        return_data: result.output().map(|o| o.to_vec()).unwrap_or_default(),
+    })
+}
+
+/// Whether a call outcome matches the fixture's expected output:
+/// `{ "returnData": "0x..", "reverted": false }`, either field optional.
+/// A null expectation only requires the call not to revert.
+pub fn outcome_matches(expected: &Value, outcome: &EvmCallOutcome) -> bool {
+    let expect_revert = expected.get("reverted").and_then(|v| v.as_bool()).unwrap_or(false);
+    if outcome.reverted != expect_revert || (!expect_revert && !outcome.success) {
+        return false;
+    }
+
+    match expected.get("returnData").and_then(|v| v.as_str()) {
+        Some(hex) => decode_hex(hex).map(|data| data == outcome.return_data).unwrap_or(false),
+        None => true,
+    }
+}
+
+fn decode_hex(s: &str) -> Result<Vec<u8>, String> {
+    hex::decode(s.trim().trim_start_matches("0x")).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_execute_call_measures_gas() {
+        // Constructor copies a runtime that returns 42 as a 32-byte word
+        //   runtime: PUSH1 0x2a PUSH1 0 MSTORE PUSH1 0x20 PUSH1 0 RETURN
+        let runtime = "602a60005260206000f3";
+        let init = format!("600a600c600039600a6000f3{}", runtime);
+        let bytecode = decode_hex(&init).unwrap();
+
+        let call = EvmCall::from_fixture_input(&json!({ "calldata": "0x" })).unwrap().unwrap();
+        let outcome = execute_call(&bytecode, &call, 1_000_000).unwrap();
+
+        assert!(outcome.success);
+        // 21000 intrinsic + 5 pushes/mstore/return opcodes and memory expansion
+        assert!(outcome.gas_used > 21_000 && outcome.gas_used < 21_100);
+        assert!(outcome_matches(
+            &json!({ "returnData": format!("0x{:064x}", 42) }),
+            &outcome,
+        ));
+        assert!(!outcome_matches(&json!({ "reverted": true }), &outcome));
+    }
+
+    #[test]
+    fn test_contract_artifacts_from_forge_and_hardhat() {
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(workspace.path().join("out/Contract.sol")).unwrap();
+        std::fs::write(
+            workspace.path().join("out/Contract.sol/Counter.json"),
+            json!({ "abi": [{ "type": "function", "name": "count" }], "bytecode": { "object": "0x6080" }, "deployedBytecode": { "object": "0x60aa" } }).to_string(),
+        ).unwrap();
+        std::fs::write(
+            workspace.path().join("out/Contract.sol/ICounter.json"),
+            json!({ "abi": [], "bytecode": { "object": "0x" }, "deployedBytecode": { "object": "0x" } }).to_string(),
+        ).unwrap();
+
+        let contracts = contract_artifacts(workspace.path(), "out/Contract.sol");
+        assert_eq!(contracts.len(), 2);
+        assert_eq!(contracts[0].name, "Counter");
+        assert_eq!(contracts[0].source, "Contract.sol");
+        assert_eq!(contracts[0].bytecode, "0x6080");
+        assert_eq!(
+            contracts[0].deployed_bytecode_hash,
+            Some(format!("0x{}", hex::encode(keccak256([0x60, 0xaa]))))
+        );
+        assert_eq!(contracts[1].bytecode, "0x");
+        assert_eq!(contracts[1].deployed_bytecode_hash, None);
+
+        std::fs::create_dir_all(workspace.path().join("artifacts/contracts/Contract.sol")).unwrap();
+        std::fs::write(
+            workspace.path().join("artifacts/contracts/Contract.sol/Counter.json"),
+            json!({ "contractName": "Counter", "sourceName": "contracts/Contract.sol", "abi": [], "bytecode": "0x6080", "deployedBytecode": "0x60aa" }).to_string(),
+        ).unwrap();
+        std::fs::write(workspace.path().join("artifacts/contracts/Contract.sol/Counter.dbg.json"), "{}").unwrap();
+        let contracts = contract_artifacts(workspace.path(), "artifacts/contracts/Contract.sol");
+        assert_eq!(contracts.len(), 1);
+        assert_eq!(contracts[0].source, "contracts/Contract.sol");
+    }
+}
@@ -0,0 +1,578 @@
+use crate::anti_cheat::CodeFingerprint;
+use crate::minhash;
+use chrono::{DateTime, Utc};
+use futures::future::BoxFuture;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgPool, PgPoolOptions};
+use sqlx::Row;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Mutex;
+use tokio::sync::OnceCell;
+
+/// A submission kept for plagiarism comparisons. The source is kept so
+/// fingerprints can be regenerated when the algorithm changes.
+#[derive(Debug, Clone)]
+pub struct StoredSubmission {
+    pub submission_id: String,
+    pub challenge_id: String,
+    /// Lowercase
+    pub language: String,
+    pub user_id: String,
+    /// Only loaded by `FingerprintStore::get`; empty from lookups over many
+    /// submissions
+    pub code: String,
+    pub fingerprint: CodeFingerprint,
+    /// Whether it passed grading
+    pub accepted: bool,
+}
+
+/// Which stored submissions a new one is compared against.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MatchScope {
+    /// Other users' submissions to the same challenge
+    #[default]
+    OtherUsers,
+    /// The same user's submissions to other challenges, such as their
+    /// solution to a previous edition
+    OwnHistory,
+    /// Other users' accepted solutions to other challenges, catching reuse of
+    /// solutions leaked from previous editions
+    OtherChallenges,
+}
+
+impl MatchScope {
+    /// Whether `stored` is in this scope for a submission by `user_id` to
+    /// `challenge_id`. The scopes don't overlap.
+    pub fn covers(&self, stored: &StoredSubmission, challenge_id: &str, user_id: &str) -> bool {
+        let same_challenge = stored.challenge_id == challenge_id;
+        let same_user = stored.user_id == user_id;
+        match self {
+            MatchScope::OtherUsers => same_challenge && !same_user,
+            MatchScope::OwnHistory => !same_challenge && same_user,
+            MatchScope::OtherChallenges => !same_challenge && !same_user && stored.accepted,
+        }
+    }
+}
+
+/// Where submission fingerprints live: in memory for a single worker (and
+/// tests), or Postgres so they survive restarts and are shared by every
+/// worker.
+pub trait FingerprintStore: Send + Sync {
+    /// Store a submission, replacing any with the same id.
+    fn store<'a>(&'a self, submission: &'a StoredSubmission) -> BoxFuture<'a, Result<(), String>>;
+
+    /// Submissions in the language and `scope` of one by `user_id` to
+    /// `challenge_id` that share at least one of the LSH `band_keys`,
+    /// without their code.
+    fn candidates<'a>(
+        &'a self,
+        scope: MatchScope,
+        challenge_id: &'a str,
+        language: &'a str,
+        user_id: &'a str,
+        band_keys: &'a [u64],
+    ) -> BoxFuture<'a, Result<Vec<StoredSubmission>, String>>;
+
+    /// Every submission to the challenge in the language, without its code.
+    fn submissions<'a>(&'a self, challenge_id: &'a str, language: &'a str) -> BoxFuture<'a, Result<Vec<StoredSubmission>, String>>;
+
+    fn get<'a>(&'a self, submission_id: &'a str) -> BoxFuture<'a, Result<Option<StoredSubmission>, String>>;
+
+    /// Ids of submissions fingerprinted with an algorithm version older than `version`.
+    fn stale<'a>(&'a self, version: u32) -> BoxFuture<'a, Result<Vec<String>, String>>;
+
+    /// Delete every submission by the user, returning how many there were.
+    fn delete_user<'a>(&'a self, user_id: &'a str) -> BoxFuture<'a, Result<usize, String>>;
+
+    /// Delete submissions first stored before `cutoff`, returning how many
+    /// there were. Re-fingerprinting doesn't make a submission newer.
+    fn prune<'a>(&'a self, cutoff: DateTime<Utc>) -> BoxFuture<'a, Result<usize, String>>;
+}
+
+#[derive(Default)]
+pub struct MemoryStore {
+    inner: Mutex<MemoryIndex>,
+}
+
+#[derive(Default)]
+struct MemoryIndex {
+    submissions: HashMap<String, StoredSubmission>,
+    /// (language, band key) to the submissions in that bucket
+    bands: HashMap<(String, u64), BTreeSet<String>>,
+    /// When each submission was first stored
+    stored_at: HashMap<String, DateTime<Utc>>,
+}
+
+impl MemoryIndex {
+    /// Lookups leave the code out, as `PostgresStore`'s do.
+    fn without_code(submission: &StoredSubmission) -> StoredSubmission {
+        StoredSubmission {
+            submission_id: submission.submission_id.clone(),
+            challenge_id: submission.challenge_id.clone(),
+            language: submission.language.clone(),
+            user_id: submission.user_id.clone(),
+            code: String::new(),
+            fingerprint: submission.fingerprint.clone(),
+            accepted: submission.accepted,
+        }
+    }
+
+    fn bucket(submission: &StoredSubmission, key: u64) -> (String, u64) {
+        (submission.language.clone(), key)
+    }
+
+    fn remove(&mut self, submission_id: &str) -> Option<StoredSubmission> {
+        let previous = self.submissions.remove(submission_id)?;
+        for key in minhash::band_keys(&previous.fingerprint.minhash) {
+            if let Some(bucket) = self.bands.get_mut(&Self::bucket(&previous, key)) {
+                bucket.remove(&previous.submission_id);
+            }
+        }
+        Some(previous)
+    }
+
+    fn delete_where(&mut self, matches: impl Fn(&StoredSubmission, &DateTime<Utc>) -> bool) -> usize {
+        let ids: Vec<String> = self
+            .submissions
+            .values()
+            .filter(|s| matches(s, &self.stored_at[&s.submission_id]))
+            .map(|s| s.submission_id.clone())
+            .collect();
+        for id in &ids {
+            self.remove(id);
+            self.stored_at.remove(id);
+        }
+        ids.len()
+    }
+}
+
+impl FingerprintStore for MemoryStore {
+    fn store<'a>(&'a self, submission: &'a StoredSubmission) -> BoxFuture<'a, Result<(), String>> {
+        let mut index = self.inner.lock().unwrap();
+        index.remove(&submission.submission_id);
+        index.stored_at.entry(submission.submission_id.clone()).or_insert_with(Utc::now);
+        for key in minhash::band_keys(&submission.fingerprint.minhash) {
+            index.bands.entry(MemoryIndex::bucket(submission, key)).or_default().insert(submission.submission_id.clone());
+        }
+        index.submissions.insert(submission.submission_id.clone(), submission.clone());
+        Box::pin(async { Ok(()) })
+    }
+
+    fn candidates<'a>(
+        &'a self,
+        scope: MatchScope,
+        challenge_id: &'a str,
+        language: &'a str,
+        user_id: &'a str,
+        band_keys: &'a [u64],
+    ) -> BoxFuture<'a, Result<Vec<StoredSubmission>, String>> {
+        let index = self.inner.lock().unwrap();
+        let ids: BTreeSet<&String> = band_keys
+            .iter()
+            .filter_map(|key| index.bands.get(&(language.to_string(), *key)))
+            .flatten()
+            .collect();
+        let candidates: Vec<StoredSubmission> = ids
+            .into_iter()
+            .filter_map(|id| index.submissions.get(id))
+            .filter(|s| scope.covers(s, challenge_id, user_id))
+            .map(MemoryIndex::without_code)
+            .collect();
+        Box::pin(async { Ok(candidates) })
+    }
+
+    fn submissions<'a>(&'a self, challenge_id: &'a str, language: &'a str) -> BoxFuture<'a, Result<Vec<StoredSubmission>, String>> {
+        let mut submissions: Vec<StoredSubmission> = self
+            .inner
+            .lock()
+            .unwrap()
+            .submissions
+            .values()
+            .filter(|s| s.challenge_id == challenge_id && s.language == language)
+            .map(MemoryIndex::without_code)
+            .collect();
+        submissions.sort_by(|a, b| a.submission_id.cmp(&b.submission_id));
+        Box::pin(async { Ok(submissions) })
+    }
+
+    fn get<'a>(&'a self, submission_id: &'a str) -> BoxFuture<'a, Result<Option<StoredSubmission>, String>> {
+        let submission = self.inner.lock().unwrap().submissions.get(submission_id).cloned();
+        Box::pin(async { Ok(submission) })
+    }
+
+    fn stale<'a>(&'a self, version: u32) -> BoxFuture<'a, Result<Vec<String>, String>> {
+        let mut ids: Vec<String> = self
+            .inner
+            .lock()
+            .unwrap()
+            .submissions
+            .values()
+            .filter(|s| s.fingerprint.version < version)
+            .map(|s| s.submission_id.clone())
+            .collect();
+        ids.sort();
+        Box::pin(async { Ok(ids) })
+    }
+
+    fn delete_user<'a>(&'a self, user_id: &'a str) -> BoxFuture<'a, Result<usize, String>> {
+        let deleted = self.inner.lock().unwrap().delete_where(|s, _| s.user_id == user_id);
+        Box::pin(async move { Ok(deleted) })
+    }
+
+    fn prune<'a>(&'a self, cutoff: DateTime<Utc>) -> BoxFuture<'a, Result<usize, String>> {
+        let deleted = self.inner.lock().unwrap().delete_where(|_, stored_at| *stored_at < cutoff);
+        Box::pin(async move { Ok(deleted) })
+    }
+}
+
+/// Fingerprints in a `submission_fingerprints` table, and their LSH band keys
+/// in `submission_fingerprint_bands`, indexed by challenge, language and key
+/// for candidate lookups (and by language and key across challenges). The
+/// tables come from the migrations in `migrations/`, applied on first use.
+pub struct PostgresStore {
+    pool: PgPool,
+    migrated: OnceCell<()>,
+}
+
+impl PostgresStore {
+    /// Configured by `ANTI_CHEAT_DATABASE_URL`.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var("ANTI_CHEAT_DATABASE_URL") {
+            Ok(url) if !url.is_empty() => Self::connect_lazy(&url).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Connections are opened on first use, so a database that is down at
+    /// startup only fails the plagiarism checks made while it is.
+    pub fn connect_lazy(url: &str) -> Result<Self, String> {
+        let pool = PgPoolOptions::new()
+            .max_connections(5)
+            .connect_lazy(url)
+            .map_err(|e| format!("Invalid anti-cheat database URL: {}", e))?;
+        Ok(Self { pool, migrated: OnceCell::new() })
+    }
+
+    async fn ensure_schema(&self) -> Result<(), String> {
+        self.migrated
+            .get_or_try_init(|| async {
+                sqlx::migrate!("./migrations")
+                    .run(&self.pool)
+                    .await
+                    .map_err(|e| format!("Failed to migrate fingerprint tables: {}", e))
+            })
+            .await
+            .map(|_| ())
+    }
+
+    /// A row of `SELECT_SUBMISSIONS`, or of `SELECT_SUBMISSION` `with_code`.
+    fn from_row(row: &sqlx::postgres::PgRow, with_code: bool) -> Result<StoredSubmission, String> {
+        let fingerprint: sqlx::types::Json<CodeFingerprint> =
+            row.try_get("fingerprint").map_err(|e| format!("Invalid stored fingerprint: {}", e))?;
+        let column = |name: &str| row.try_get::<String, _>(name).map_err(|e| format!("Invalid stored submission: {}", e));
+        let accepted: bool = row.try_get("accepted").map_err(|e| format!("Invalid stored submission: {}", e))?;
+        Ok(StoredSubmission {
+            submission_id: column("submission_id")?,
+            challenge_id: column("challenge_id")?,
+            language: column("language")?,
+            user_id: column("user_id")?,
+            code: if with_code { column("code")? } else { String::new() },
+            fingerprint: fingerprint.0,
+            accepted,
+        })
+    }
+}
+
+/// Lookups over many submissions leave their source out; only `get` loads it.
+const SELECT_SUBMISSIONS: &str =
+    "SELECT submission_id, challenge_id, language, user_id, fingerprint, accepted FROM submission_fingerprints";
+const SELECT_SUBMISSION: &str =
+    "SELECT submission_id, challenge_id, language, user_id, code, fingerprint, accepted FROM submission_fingerprints";
+
+impl FingerprintStore for PostgresStore {
+    fn store<'a>(&'a self, submission: &'a StoredSubmission) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.ensure_schema().await?;
+            let band_keys: Vec<i64> = minhash::band_keys(&submission.fingerprint.minhash).into_iter().map(|k| k as i64).collect();
+            let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to store fingerprint: {}", e))?;
+            sqlx::query(
+                "INSERT INTO submission_fingerprints
+                    (submission_id, challenge_id, language, user_id, code, fingerprint_version, fingerprint, accepted)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+                 ON CONFLICT (submission_id) DO UPDATE SET
+                    code = EXCLUDED.code,
+                    fingerprint_version = EXCLUDED.fingerprint_version,
+                    fingerprint = EXCLUDED.fingerprint,
+                    accepted = EXCLUDED.accepted",
+            )
+            .bind(&submission.submission_id)
+            .bind(&submission.challenge_id)
+            .bind(&submission.language)
+            .bind(&submission.user_id)
+            .bind(&submission.code)
+            .bind(submission.fingerprint.version as i32)
+            .bind(sqlx::types::Json(&submission.fingerprint))
+            .bind(submission.accepted)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to store fingerprint: {}", e))?;
+            sqlx::query("DELETE FROM submission_fingerprint_bands WHERE submission_id = $1")
+                .bind(&submission.submission_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to store fingerprint: {}", e))?;
+            sqlx::query(
+                "INSERT INTO submission_fingerprint_bands (submission_id, challenge_id, language, band)
+                 SELECT $1, $2, $3, band FROM UNNEST($4::BIGINT[]) AS band ON CONFLICT DO NOTHING",
+            )
+            .bind(&submission.submission_id)
+            .bind(&submission.challenge_id)
+            .bind(&submission.language)
+            .bind(&band_keys)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to store fingerprint: {}", e))?;
+            tx.commit().await.map_err(|e| format!("Failed to store fingerprint: {}", e))
+        })
+    }
+
+    fn candidates<'a>(
+        &'a self,
+        scope: MatchScope,
+        challenge_id: &'a str,
+        language: &'a str,
+        user_id: &'a str,
+        band_keys: &'a [u64],
+    ) -> BoxFuture<'a, Result<Vec<StoredSubmission>, String>> {
+        Box::pin(async move {
+            self.ensure_schema().await?;
+            let band_keys: Vec<i64> = band_keys.iter().map(|k| *k as i64).collect();
+            let (bands, submissions) = match scope {
+                MatchScope::OtherUsers => ("challenge_id = $1 AND", "user_id <> $3"),
+                MatchScope::OwnHistory => ("", "challenge_id <> $1 AND user_id = $3"),
+                MatchScope::OtherChallenges => ("", "challenge_id <> $1 AND user_id <> $3 AND accepted"),
+            };
+            let rows = sqlx::query(&format!(
+                "{} WHERE submission_id IN (
+                    SELECT submission_id FROM submission_fingerprint_bands
+                    WHERE {} language = $2 AND band = ANY($4)
+                 ) AND {} ORDER BY submission_id",
+                SELECT_SUBMISSIONS, bands, submissions
+            ))
+            .bind(challenge_id)
+            .bind(language)
+            .bind(user_id)
+            .bind(&band_keys)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to query fingerprints: {}", e))?;
+            rows.iter().map(|row| Self::from_row(row, false)).collect()
+        })
+    }
+
+    fn submissions<'a>(&'a self, challenge_id: &'a str, language: &'a str) -> BoxFuture<'a, Result<Vec<StoredSubmission>, String>> {
+        Box::pin(async move {
+            self.ensure_schema().await?;
+            let rows = sqlx::query(&format!("{} WHERE challenge_id = $1 AND language = $2 ORDER BY submission_id", SELECT_SUBMISSIONS))
+                .bind(challenge_id)
+                .bind(language)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to query fingerprints: {}", e))?;
+            rows.iter().map(|row| Self::from_row(row, false)).collect()
+        })
+    }
+
+    fn get<'a>(&'a self, submission_id: &'a str) -> BoxFuture<'a, Result<Option<StoredSubmission>, String>> {
+        Box::pin(async move {
+            self.ensure_schema().await?;
+            let row = sqlx::query(&format!("{} WHERE submission_id = $1", SELECT_SUBMISSION))
+                .bind(submission_id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to query fingerprints: {}", e))?;
+            row.as_ref().map(|row| Self::from_row(row, true)).transpose()
+        })
+    }
+
+    fn stale<'a>(&'a self, version: u32) -> BoxFuture<'a, Result<Vec<String>, String>> {
+        Box::pin(async move {
+            self.ensure_schema().await?;
+            let rows = sqlx::query("SELECT submission_id FROM submission_fingerprints WHERE fingerprint_version < $1 ORDER BY submission_id")
+                .bind(version as i32)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to query fingerprints: {}", e))?;
+            rows.iter()
+                .map(|row| row.try_get("submission_id").map_err(|e| format!("Invalid stored submission: {}", e)))
+                .collect()
+        })
+    }
+
+    fn delete_user<'a>(&'a self, user_id: &'a str) -> BoxFuture<'a, Result<usize, String>> {
+        Box::pin(async move {
+            self.ensure_schema().await?;
+            // Their bands go with them, by cascade
+            let result = sqlx::query("DELETE FROM submission_fingerprints WHERE user_id = $1")
+                .bind(user_id)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to delete fingerprints: {}", e))?;
+            Ok(result.rows_affected() as usize)
+        })
+    }
+
+    fn prune<'a>(&'a self, cutoff: DateTime<Utc>) -> BoxFuture<'a, Result<usize, String>> {
+        Box::pin(async move {
+            self.ensure_schema().await?;
+            let result = sqlx::query("DELETE FROM submission_fingerprints WHERE created_at < $1")
+                .bind(cutoff)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to delete fingerprints: {}", e))?;
+            Ok(result.rows_affected() as usize)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::winnowing;
+
+    fn submission(id: &str, user: &str, version: u32) -> StoredSubmission {
+        let winnow_hashes: Vec<u64> = (0..50).collect();
+        StoredSubmission {
+            submission_id: id.to_string(),
+            challenge_id: "two-sum".to_string(),
+            language: "rust".to_string(),
+            user_id: user.to_string(),
+            code: String::new(),
+            fingerprint: CodeFingerprint {
+                version,
+                minhash: minhash::signature(&winnow_hashes),
+                winnow_hashes,
+                tree: Default::default(),
+                token_sequence: vec![],
+                structural_features: HashMap::new(),
+            },
+            accepted: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_lookups() {
+        let store = MemoryStore::default();
+        store.store(&submission("a", "alice", 1)).await.unwrap();
+        store.store(&submission("b", "bob", 0)).await.unwrap();
+        store.store(&submission("c", "carol", 1)).await.unwrap();
+
+        let keys = minhash::band_keys(&submission("x", "x", 1).fingerprint.minhash);
+        let ids = |subs: Vec<StoredSubmission>| subs.into_iter().map(|s| s.submission_id).collect::<Vec<_>>();
+        let other_users = MatchScope::OtherUsers;
+        assert_eq!(ids(store.candidates(other_users, "two-sum", "rust", "alice", &keys).await.unwrap()), vec!["b", "c"]);
+        assert!(store.candidates(other_users, "two-sum", "typescript", "alice", &keys).await.unwrap().is_empty());
+        assert!(store.candidates(other_users, "two-sum", "rust", "alice", &[1, 2, 3]).await.unwrap().is_empty());
+
+        // Last edition's submissions: alice's own, and carol's accepted one
+        for (id, user, accepted) in [("old-a", "alice", false), ("old-b", "bob", false), ("old-c", "carol", true)] {
+            store.store(&StoredSubmission { challenge_id: "two-sum-2023".to_string(), accepted, ..submission(id, user, 1) }).await.unwrap();
+        }
+        assert_eq!(ids(store.candidates(MatchScope::OwnHistory, "two-sum", "rust", "alice", &keys).await.unwrap()), vec!["old-a"]);
+        assert_eq!(ids(store.candidates(MatchScope::OtherChallenges, "two-sum", "rust", "alice", &keys).await.unwrap()), vec!["old-c"]);
+        assert_eq!(ids(store.candidates(other_users, "two-sum", "rust", "alice", &keys).await.unwrap()), vec!["b", "c"]);
+        assert_eq!(store.submissions("two-sum", "rust").await.unwrap().len(), 3);
+        assert_eq!(store.stale(1).await.unwrap(), vec!["b"]);
+
+        store.store(&submission("b", "bob", 1)).await.unwrap();
+        assert!(store.stale(1).await.unwrap().is_empty());
+        assert_eq!(store.get("b").await.unwrap().unwrap().fingerprint.version, 1);
+    }
+
+    #[tokio::test]
+    async fn test_candidates_recall_against_full_scan() {
+        // Submissions sharing from none to all of the query's fingerprints
+        let query: Vec<u64> = (0..200).collect();
+        let store = MemoryStore::default();
+        for i in 0..400u64 {
+            let shared = i / 2;
+            let winnow_hashes: Vec<u64> = (0..shared).chain((shared..200).map(|j| (i + 1) * 1000 + j)).collect();
+            let mut stored = submission(&i.to_string(), &format!("user{}", i), 1);
+            stored.fingerprint.minhash = minhash::signature(&winnow_hashes);
+            stored.fingerprint.winnow_hashes = winnow_hashes;
+            store.store(&stored).await.unwrap();
+        }
+
+        // Everything a full scan finds above the default report threshold
+        let reported: BTreeSet<String> = store
+            .submissions("two-sum", "rust")
+            .await
+            .unwrap()
+            .into_iter()
+            .filter(|s| winnowing::similarity(&query, &s.fingerprint.winnow_hashes) > 0.3)
+            .map(|s| s.submission_id)
+            .collect();
+        let keys = minhash::band_keys(&minhash::signature(&query));
+        let candidates: BTreeSet<String> = store
+            .candidates(MatchScope::OtherUsers, "two-sum", "rust", "query", &keys)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|s| s.submission_id)
+            .collect();
+        assert_eq!(reported.len(), 214);
+        assert!(reported.is_subset(&candidates));
+        // Submissions sharing nothing aren't retrieved
+        assert!(!candidates.contains("0") && !candidates.contains("1"));
+    }
+
+    /// Against a scratch database: `TEST_ANTI_CHEAT_DATABASE_URL=postgres://... cargo test -- --ignored`
+    #[tokio::test]
+    #[ignore]
+    async fn test_postgres_store() {
+        let url = std::env::var("TEST_ANTI_CHEAT_DATABASE_URL").expect("TEST_ANTI_CHEAT_DATABASE_URL is not set");
+        let store = PostgresStore::connect_lazy(&url).unwrap();
+        let run = uuid::Uuid::new_v4().to_string();
+        let id = |name: &str| format!("{}-{}", run, name);
+        let user = |name: &str| format!("{}-{}", run, name);
+        for (name, owner) in [("a", "alice"), ("b", "bob"), ("c", "carol")] {
+            let stored = StoredSubmission { code: format!("fn {}() {{}}", name), user_id: user(owner), ..submission(&id(name), owner, 1) };
+            store.store(&stored).await.unwrap();
+        }
+
+        let keys = minhash::band_keys(&submission("x", "x", 1).fingerprint.minhash);
+        let candidates = store.candidates(MatchScope::OtherUsers, "two-sum", "rust", &user("alice"), &keys).await.unwrap();
+        let ids: Vec<String> = candidates.iter().map(|s| s.submission_id.clone()).filter(|s| s.starts_with(&run)).collect();
+        assert_eq!(ids, vec![id("b"), id("c")]);
+        // Lookups skip the code, a single submission has it
+        assert!(candidates.iter().all(|s| s.code.is_empty()));
+        assert_eq!(store.get(&id("b")).await.unwrap().unwrap().code, "fn b() {}");
+
+        for owner in ["alice", "bob", "carol"] {
+            assert_eq!(store.delete_user(&user(owner)).await.unwrap(), 1);
+        }
+        assert!(store.get(&id("a")).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_deletion() {
+        let store = MemoryStore::default();
+        store.store(&submission("a", "alice", 1)).await.unwrap();
+        store.store(&submission("a2", "alice", 1)).await.unwrap();
+        let cutoff = Utc::now();
+        store.store(&submission("b", "bob", 1)).await.unwrap();
+        // Storing again, as re-fingerprinting does, keeps the original time
+        store.store(&submission("a2", "alice", 2)).await.unwrap();
+
+        let keys = minhash::band_keys(&submission("x", "x", 1).fingerprint.minhash);
+        assert_eq!(store.delete_user("bob").await.unwrap(), 1);
+        assert!(store.get("b").await.unwrap().is_none());
+        assert_eq!(store.candidates(MatchScope::OtherUsers, "two-sum", "rust", "carol", &keys).await.unwrap().len(), 2);
+
+        assert_eq!(store.prune(cutoff).await.unwrap(), 2);
+        assert!(store.submissions("two-sum", "rust").await.unwrap().is_empty());
+        assert!(store.candidates(MatchScope::OtherUsers, "two-sum", "rust", "carol", &keys).await.unwrap().is_empty());
+        assert_eq!(store.delete_user("alice").await.unwrap(), 0);
+    }
+}
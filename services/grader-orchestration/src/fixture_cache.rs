@@ -0,0 +1,342 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Default size cap of the fixture cache.
+const DEFAULT_MAX_BYTES: u64 = 1024 * 1024 * 1024; // 1GB
+const INDEX_FILE: &str = "index.json";
+/// How often use times recorded by hits are written to the index. Losing
+/// them in a crash only makes those entries look older.
+const HIT_SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// A cached fixtures document, project template or fixture file, by its path
+/// relative to the cache directory.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct IndexEntry {
+    size: u64,
+    /// Unix time in milliseconds the entry was last read or written
+    last_used: u64,
+}
+
+#[derive(Default)]
+struct IndexState {
+    entries: HashMap<String, IndexEntry>,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+    /// Hits recorded since the index was last written
+    unsaved_hits: bool,
+    last_saved: Option<Instant>,
+}
+
+/// Jobs using each entry, by entry.
+type Pins = Arc<Mutex<HashMap<String, usize>>>;
+
+/// Keeps a cache entry from being evicted while a job uses it, e.g. a
+/// template its workspace links to or a file mounted into its sandbox.
+/// Clones share the pin, which is released when the last one is dropped.
+#[derive(Clone, Debug)]
+pub struct CachePin(Arc<PinGuard>);
+
+#[derive(Debug)]
+struct PinGuard {
+    entry: String,
+    pins: Pins,
+}
+
+impl Drop for PinGuard {
+    fn drop(&mut self) {
+        let mut pins = self.pins.lock().unwrap();
+        if let Some(count) = pins.get_mut(&self.entry) {
+            *count -= 1;
+            if *count == 0 {
+                pins.remove(&self.entry);
+            }
+        }
+    }
+}
+
+impl PartialEq for CachePin {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.entry == other.0.entry
+    }
+}
+
+/// Fixture cache usage, served by `GET /cache/fixtures/stats`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CacheStats {
+    pub entries: usize,
+    pub bytes: u64,
+    pub max_bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    /// Hits over lookups since the worker started, 0 before any lookup
+    pub hit_rate: f64,
+}
+
+/// Keeps the fixture cache under a size cap by evicting the least recently
+/// used entries that no job has pinned. Entry sizes and use times are
+/// persisted in `index.json` in the cache directory, which is rebuilt from the
+/// directory if missing.
+pub struct CacheIndex {
+    dir: PathBuf,
+    max_bytes: u64,
+    /// Loaded on first use
+    state: Mutex<Option<IndexState>>,
+    pins: Pins,
+}
+
+impl CacheIndex {
+    pub fn new(dir: PathBuf, max_bytes: u64) -> Self {
+        Self { dir, max_bytes, state: Mutex::new(None), pins: Pins::default() }
+    }
+
+    /// `FIXTURES_CACHE_MAX_BYTES` (default 1GB).
+    pub fn max_bytes_from_env() -> u64 {
+        std::env::var("FIXTURES_CACHE_MAX_BYTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_BYTES)
+    }
+
+    /// Pin `entry` until the returned pin is dropped. Pin before checking
+    /// whether the entry is cached, so it can't be evicted in between.
+    pub fn pin(&self, entry: &str) -> CachePin {
+        *self.pins.lock().unwrap().entry(entry.to_string()).or_insert(0) += 1;
+        CachePin(Arc::new(PinGuard { entry: entry.to_string(), pins: self.pins.clone() }))
+    }
+
+    /// Record a cache hit on `entry`. The new use time is written with the
+    /// next save, at most every `HIT_SAVE_INTERVAL` for hits alone.
+    pub fn hit(&self, entry: &str) {
+        let due = self.with_state(|state| {
+            state.hits += 1;
+            if let Some(indexed) = state.entries.get_mut(entry) {
+                indexed.last_used = now_millis();
+                state.unsaved_hits = true;
+            }
+            let interval_passed = match state.last_saved {
+                Some(saved) => saved.elapsed() >= HIT_SAVE_INTERVAL,
+                None => true,
+            };
+            state.unsaved_hits && interval_passed
+        });
+        if due {
+            self.save();
+        }
+    }
+
+    pub fn miss(&self) {
+        self.with_state(|state| state.misses += 1);
+    }
+
+    /// Record `entry` as written, then evict least recently used entries
+    /// until the cache fits its cap again. The new entry itself is kept.
+    pub fn insert(&self, entry: &str) {
+        let size = disk_usage(&self.dir.join(entry));
+        let evicted = self.with_state(|state| {
+            state.entries.insert(entry.to_string(), IndexEntry { size, last_used: now_millis() });
+
+            let pins = self.pins.lock().unwrap();
+            let mut by_age: Vec<(u64, String)> = state
+                .entries
+                .iter()
+                .filter(|(key, _)| key.as_str() != entry && !pins.contains_key(key.as_str()))
+                .map(|(key, indexed)| (indexed.last_used, key.clone()))
+                .collect();
+            by_age.sort();
+
+            let mut total: u64 = state.entries.values().map(|e| e.size).sum();
+            let mut evicted = Vec::new();
+            for (_, key) in by_age {
+                if total <= self.max_bytes {
+                    break;
+                }
+                if let Some(removed) = state.entries.remove(&key) {
+                    total = total.saturating_sub(removed.size);
+                    state.evictions += 1;
+                    evicted.push(key);
+                }
+            }
+            evicted
+        });
+
+        for key in evicted {
+            remove_path(&self.dir.join(&key));
+        }
+        self.save();
+    }
+
+    /// Forget an entry removed from the cache.
+    pub fn remove(&self, entry: &str) {
+        self.with_state(|state| state.entries.remove(entry));
+        self.save();
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.with_state(|state| {
+            let lookups = state.hits + state.misses;
+            CacheStats {
+                entries: state.entries.len(),
+                bytes: state.entries.values().map(|e| e.size).sum(),
+                max_bytes: self.max_bytes,
+                hits: state.hits,
+                misses: state.misses,
+                evictions: state.evictions,
+                hit_rate: if lookups == 0 { 0.0 } else { state.hits as f64 / lookups as f64 },
+            }
+        })
+    }
+
+    fn with_state<R>(&self, f: impl FnOnce(&mut IndexState) -> R) -> R {
+        let mut state = self.state.lock().unwrap();
+        f(state.get_or_insert_with(|| IndexState { entries: self.load(), ..IndexState::default() }))
+    }
+
+    /// The persisted index, or one rebuilt from the cache directory.
+    fn load(&self) -> HashMap<String, IndexEntry> {
+        if let Some(entries) = std::fs::read_to_string(self.dir.join(INDEX_FILE)).ok().and_then(|s| serde_json::from_str(&s).ok()) {
+            return entries;
+        }
+
+        let mut entries = HashMap::new();
+        let mut add = |key: String, path: &Path| {
+            let last_used = path
+                .metadata()
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64)
+                .unwrap_or(0);
+            entries.insert(key, IndexEntry { size: disk_usage(path), last_used });
+        };
+        for (key, path) in list(&self.dir) {
+            if key.ends_with(".json") && key != INDEX_FILE {
+                add(key, &path);
+            }
+        }
+        for subdir in ["templates", "files"] {
+            for (name, path) in list(&self.dir.join(subdir)) {
+                add(format!("{}/{}", subdir, name), &path);
+            }
+        }
+        entries
+    }
+
+    /// Persist the index; written under the lock so concurrent saves don't interleave.
+    fn save(&self) {
+        let result = self.with_state(|state| {
+            state.unsaved_hits = false;
+            state.last_saved = Some(Instant::now());
+            let index = serde_json::to_string(&state.entries).map_err(|e| e.to_string())?;
+            std::fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+            std::fs::write(self.dir.join(INDEX_FILE), index).map_err(|e| e.to_string())
+        });
+        if let Err(e) = result {
+            eprintln!("Warning: Failed to write fixture cache index: {}", e);
+        }
+    }
+}
+
+/// Entries of `dir` by name, skipping partial downloads (dotfiles).
+fn list(dir: &Path) -> Vec<(String, PathBuf)> {
+    std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .map(|entry| (entry.file_name().to_string_lossy().to_string(), entry.path()))
+        .filter(|(name, _)| !name.starts_with('.'))
+        .collect()
+}
+
+/// Size of a file, or of everything under a directory.
+fn disk_usage(path: &Path) -> u64 {
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) if meta.is_dir() => list(path).iter().map(|(_, child)| disk_usage(child)).sum(),
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    }
+}
+
+fn remove_path(path: &Path) {
+    let result = match path.is_dir() {
+        true => std::fs::remove_dir_all(path),
+        false => std::fs::remove_file(path),
+    };
+    if let Err(e) = result {
+        eprintln!("Warning: Failed to evict {} from the fixture cache: {}", path.display(), e);
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = CacheIndex::new(dir.path().to_path_buf(), 250);
+        for name in ["fixtures_a.json", "fixtures_b.json"] {
+            std::fs::write(dir.path().join(name), vec![b' '; 100]).unwrap();
+            index.insert(name);
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+
+        // Reading a keeps it; adding c pushes the cache over its cap
+        index.hit("fixtures_a.json");
+        std::fs::create_dir_all(dir.path().join("files")).unwrap();
+        std::fs::write(dir.path().join("files/abc"), vec![0u8; 100]).unwrap();
+        index.insert("files/abc");
+
+        assert!(dir.path().join("fixtures_a.json").exists());
+        assert!(!dir.path().join("fixtures_b.json").exists());
+        index.miss();
+        let stats = index.stats();
+        assert_eq!((stats.entries, stats.bytes, stats.evictions), (2, 200, 1));
+        assert_eq!(stats.hit_rate, 0.5);
+
+        // The index survives a restart
+        let reopened = CacheIndex::new(dir.path().to_path_buf(), 250);
+        assert_eq!(reopened.stats().entries, 2);
+    }
+
+    #[test]
+    fn test_pinned_entries_are_not_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = CacheIndex::new(dir.path().to_path_buf(), 150);
+        std::fs::create_dir_all(dir.path().join("templates/old")).unwrap();
+        std::fs::write(dir.path().join("templates/old/foundry.toml"), vec![b' '; 100]).unwrap();
+        index.insert("templates/old");
+        let pin = index.pin("templates/old");
+        let shared = pin.clone();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+
+        // The cache may run over its cap rather than evict an entry in use
+        std::fs::write(dir.path().join("fixtures_a.json"), vec![b' '; 100]).unwrap();
+        index.insert("fixtures_a.json");
+        assert!(dir.path().join("templates/old").exists());
+
+        // Once every job using it is done, it is evictable again
+        drop(pin);
+        drop(shared);
+        std::fs::write(dir.path().join("fixtures_b.json"), vec![b' '; 100]).unwrap();
+        index.insert("fixtures_b.json");
+        assert!(!dir.path().join("templates/old").exists());
+        assert!(index.pins.lock().unwrap().is_empty());
+
+        // Hits alone don't rewrite the index every time
+        let written = std::fs::metadata(dir.path().join(INDEX_FILE)).unwrap().modified().unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        index.hit("fixtures_b.json");
+        assert_eq!(std::fs::metadata(dir.path().join(INDEX_FILE)).unwrap().modified().unwrap(), written);
+    }
+}
@@ -0,0 +1,156 @@
+use crate::fixture_cache::CachePin;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+/// Largest external fixture file downloaded by default.
+const DEFAULT_MAX_FILE_BYTES: u64 = 256 * 1024 * 1024; // 256MB
+
+/// A fixture input or expected output too large or too binary for the
+/// fixtures document (images, datasets, contract state dumps), stored
+/// elsewhere and downloaded into the fixture cache by its checksum.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FixtureFile {
+    /// `http(s)://` URL, or `file://` path for local challenge development
+    pub url: String,
+    /// Hex sha256 of the contents; also names the cached copy
+    pub sha256: String,
+    /// Where the file was cached, once downloaded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// Keeps the cached copy from being evicted while the fixture is in use
+    #[serde(skip)]
+    pub pin: Option<CachePin>,
+}
+
+impl FixtureFile {
+    pub fn check(&self) -> Result<(), String> {
+        if self.sha256.len() != 64 || !self.sha256.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Invalid sha256 for fixture file {}", self.url));
+        }
+        match ["http://", "https://", "file://"].iter().any(|scheme| self.url.starts_with(scheme)) {
+            true => Ok(()),
+            false => Err(format!("Unsupported fixture file URL: {}", self.url)),
+        }
+    }
+}
+
+/// `FIXTURES_MAX_FILE_BYTES`, the size limit of each external fixture file.
+pub fn max_file_bytes_from_env() -> u64 {
+    std::env::var("FIXTURES_MAX_FILE_BYTES")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(DEFAULT_MAX_FILE_BYTES)
+}
+
+/// Stream `file` into `files_dir`, unless a copy with its checksum is already
+/// cached, and return the cached path. Downloads over `max_bytes` or with the
+/// wrong checksum are discarded.
+pub async fn download(client: &reqwest::Client, file: &FixtureFile, files_dir: &Path, max_bytes: u64) -> Result<PathBuf, String> {
+    file.check()?;
+    let cached = files_dir.join(file.sha256.to_lowercase());
+    if cached.exists() {
+        return Ok(cached);
+    }
+
+    tokio::fs::create_dir_all(files_dir)
+        .await
+        .map_err(|e| format!("Failed to create fixture files dir: {}", e))?;
+    let partial = files_dir.join(format!(".{}.{}", file.sha256.to_lowercase(), uuid::Uuid::new_v4().simple()));
+    let result = stream_to(client, file, &partial, max_bytes).await;
+    let result = match result {
+        Ok(digest) if digest.eq_ignore_ascii_case(&file.sha256) => tokio::fs::rename(&partial, &cached)
+            .await
+            .map_err(|e| format!("Failed to cache fixture file: {}", e)),
+        Ok(digest) => Err(format!("Checksum mismatch for fixture file {}: expected {}, got {}", file.url, file.sha256, digest)),
+        Err(e) => Err(e),
+    };
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&partial).await;
+    }
+    result.map(|_| cached)
+}
+
+/// Copy the file's contents to `dest` chunk by chunk, returning their sha256.
+async fn stream_to(client: &reqwest::Client, file: &FixtureFile, dest: &Path, max_bytes: u64) -> Result<String, String> {
+    let mut out = tokio::fs::File::create(dest)
+        .await
+        .map_err(|e| format!("Failed to create fixture file: {}", e))?;
+    let mut hasher = Sha256::new();
+    let mut written: u64 = 0;
+    let too_large = || format!("Fixture file {} exceeds the {} byte limit", file.url, max_bytes);
+
+    if let Some(local) = file.url.strip_prefix("file://") {
+        let mut input = tokio::fs::File::open(local)
+            .await
+            .map_err(|e| format!("Failed to open fixture file {}: {}", file.url, e))?;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = input.read(&mut buf).await.map_err(|e| format!("Failed to read fixture file {}: {}", file.url, e))?;
+            if n == 0 {
+                break;
+            }
+            written += n as u64;
+            if written > max_bytes {
+                return Err(too_large());
+            }
+            hasher.update(&buf[..n]);
+            out.write_all(&buf[..n]).await.map_err(|e| format!("Failed to write fixture file: {}", e))?;
+        }
+    } else {
+        let mut response = client
+            .get(&file.url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Failed to fetch fixture file {}: {}", file.url, e))?;
+        if response.content_length().map_or(false, |len| len > max_bytes) {
+            return Err(too_large());
+        }
+        while let Some(chunk) = response.chunk().await.map_err(|e| format!("Failed to read fixture file {}: {}", file.url, e))? {
+            written += chunk.len() as u64;
+            if written > max_bytes {
+                return Err(too_large());
+            }
+            hasher.update(&chunk);
+            out.write_all(&chunk).await.map_err(|e| format!("Failed to write fixture file: {}", e))?;
+        }
+    }
+
+    out.flush().await.map_err(|e| format!("Failed to write fixture file: {}", e))?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_download_checks_size_and_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("dump.bin");
+        std::fs::write(&source, [0u8, 159, 146, 150]).unwrap();
+        let files_dir = dir.path().join("files");
+        let client = reqwest::Client::new();
+
+        let mut file = FixtureFile {
+            url: format!("file://{}", source.display()),
+            sha256: hex::encode(Sha256::digest([0u8, 159, 146, 150])),
+            path: None,
+            pin: None,
+        };
+        let cached = download(&client, &file, &files_dir, 1024).await.unwrap();
+        assert_eq!(std::fs::read(&cached).unwrap(), vec![0u8, 159, 146, 150]);
+
+        // Over the limit, or not what the fixture says it is
+        std::fs::remove_file(&cached).unwrap();
+        assert!(download(&client, &file, &files_dir, 3).await.unwrap_err().contains("byte limit"));
+        file.sha256 = "0".repeat(64);
+        assert!(download(&client, &file, &files_dir, 1024).await.unwrap_err().contains("Checksum mismatch"));
+        assert_eq!(std::fs::read_dir(&files_dir).unwrap().count(), 0);
+
+        file.url = "ftp://example.com/dump.bin".to_string();
+        assert!(file.check().is_err());
+    }
+}
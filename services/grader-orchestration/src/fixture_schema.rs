@@ -0,0 +1,182 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// JSON Schema of a fixtures document, published for authoring tools.
+pub const FIXTURE_SCHEMA: &str = include_str!("../schemas/fixture.schema.json");
+
+/// A problem with one field of one fixture.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureError {
+    /// Position of the fixture in the document
+    pub index: usize,
+    pub fixture_id: Option<String>,
+    /// Path of the offending field within the fixture, e.g. `targets[1]`
+    pub field: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for FixtureError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.fixture_id {
+            Some(id) => write!(f, "fixture {} ({})", self.index, id)?,
+            None => write!(f, "fixture {}", self.index)?,
+        }
+        if !self.field.is_empty() {
+            write!(f, ", field `{}`", self.field)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Check a fixtures document against the fixture schema, plus id uniqueness,
+/// reporting every problem found rather than only the first.
+pub fn validate_fixtures(document: &Value) -> Vec<FixtureError> {
+    let schema: Value = serde_json::from_str(FIXTURE_SCHEMA).expect("fixture schema is valid JSON");
+    let fixtures = match document.as_array() {
+        Some(fixtures) => fixtures,
+        None => {
+            return vec![FixtureError {
+                index: 0,
+                fixture_id: None,
+                field: String::new(),
+                message: format!("expected an array of fixtures, got {}", type_name(document)),
+            }];
+        }
+    };
+
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+    for (index, fixture) in fixtures.iter().enumerate() {
+        let fixture_id = fixture.get("id").and_then(|id| id.as_str()).map(String::from);
+        let mut problems = Vec::new();
+        check(&schema, &schema["items"], fixture, "", &mut problems);
+        if let Some(id) = &fixture_id {
+            if !seen.insert(id.clone()) {
+                problems.push(("id".to_string(), format!("duplicate fixture id \"{}\"", id)));
+            }
+        }
+        errors.extend(problems.into_iter().map(|(field, message)| FixtureError {
+            index,
+            fixture_id: fixture_id.clone(),
+            field,
+            message,
+        }));
+    }
+    errors
+}
+
+/// Validate `value` against the schema keywords the fixture schema uses:
+/// `$ref` (within `root`), `type`, `required`, `properties`,
+/// `additionalProperties`, `items`, `minLength` and `minimum`.
+fn check(root: &Value, schema: &Value, value: &Value, path: &str, problems: &mut Vec<(String, String)>) {
+    if let Some(reference) = schema.get("$ref").and_then(|r| r.as_str()) {
+        let target = root.pointer(reference.trim_start_matches('#')).expect("fixture schema $ref resolves");
+        return check(root, target, value, path, problems);
+    }
+    if let Some(expected) = schema.get("type").and_then(|t| t.as_str()) {
+        if !has_type(value, expected) {
+            problems.push((path.to_string(), format!("expected {}, got {}", expected, type_name(value))));
+            return;
+        }
+    }
+
+    if let Some(min_length) = schema.get("minLength").and_then(|m| m.as_u64()) {
+        if value.as_str().map(|s| (s.chars().count() as u64) < min_length).unwrap_or(false) {
+            problems.push((path.to_string(), format!("must be at least {} characters", min_length)));
+        }
+    }
+    if let Some(minimum) = schema.get("minimum").and_then(|m| m.as_f64()) {
+        if value.as_f64().map(|n| n < minimum).unwrap_or(false) {
+            problems.push((path.to_string(), format!("must be at least {}", minimum)));
+        }
+    }
+
+    if let Some(object) = value.as_object() {
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for required in schema.get("required").and_then(|r| r.as_array()).into_iter().flatten().filter_map(|r| r.as_str()) {
+            if !object.contains_key(required) {
+                problems.push((join(path, required), "is required".to_string()));
+            }
+        }
+        let mut fields: Vec<(&String, &Value)> = object.iter().collect();
+        fields.sort_by_key(|(key, _)| key.as_str());
+        for (key, field) in fields {
+            match properties.and_then(|p| p.get(key)) {
+                Some(field_schema) => check(root, field_schema, field, &join(path, key), problems),
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    problems.push((join(path, key), "is not a fixture field".to_string()));
+                },
+                None => {},
+            }
+        }
+    }
+
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (i, item) in array.iter().enumerate() {
+            check(root, items, item, &format!("{}[{}]", path, i), problems);
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    match path.is_empty() {
+        true => key.to_string(),
+        false => format!("{}.{}", path, key),
+    }
+}
+
+fn has_type(value: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "integer" => value.is_u64() || value.is_i64(),
+        "number" => value.is_number(),
+        "null" => value.is_null(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_reports_each_field() {
+        let document = json!([
+            { "id": "basic", "input": 1, "expected_output": 2, "timeout": 30, "setup": { "run": "mkdir -p data", "timeout": 2 } },
+            { "id": "basic", "timeout": "30", "expected": 2, "targets": ["src/lib.rs", 3] },
+            { "name": "no id", "gas_limit": 0, "input_file": { "url": "https://example.com/state.bin" } }
+        ]);
+
+        let errors: Vec<String> = validate_fixtures(&document).iter().map(|e| e.to_string()).collect();
+        assert_eq!(errors, vec![
+            "fixture 1 (basic), field `expected`: is not a fixture field",
+            "fixture 1 (basic), field `targets[1]`: expected string, got integer",
+            "fixture 1 (basic), field `timeout`: expected integer, got string",
+            "fixture 1 (basic), field `id`: duplicate fixture id \"basic\"",
+            "fixture 2, field `id`: is required",
+            "fixture 2, field `gas_limit`: must be at least 1",
+            "fixture 2, field `input_file.sha256`: is required",
+        ]);
+
+        assert!(validate_fixtures(&json!([{ "id": "ok" }])).is_empty());
+        assert_eq!(validate_fixtures(&json!({ "id": "ok" }))[0].message, "expected an array of fixtures, got object");
+    }
+}
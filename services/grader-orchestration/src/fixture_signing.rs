@@ -0,0 +1,115 @@
+use crate::fixture_source::Bundle;
+use ed25519_dalek::{Signature, Verifier, VerifyingKey};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Signed checksums of a challenge's fixture bundles, published next to them:
+/// `{ "challengeId", "files": { "fixtures": "<sha256>", "hidden-tests": ..., "template": ... }, "signature" }`.
+/// The hex Ed25519 signature covers `signed_message()`, so a manifest can't
+/// be replayed for another challenge.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureManifest {
+    pub challenge_id: String,
+    /// Hex sha256 of each published bundle, by `Bundle::key`
+    pub files: BTreeMap<String, String>,
+    pub signature: String,
+}
+
+/// The publisher's Ed25519 public key from `FIXTURES_SIGNING_KEY` (hex). When
+/// set, every fixture bundle must match a manifest signed with it.
+pub fn signing_key_from_env() -> Result<Option<VerifyingKey>, String> {
+    match std::env::var("FIXTURES_SIGNING_KEY") {
+        Ok(key) if !key.is_empty() => parse_verifying_key(&key).map(Some),
+        _ => Ok(None),
+    }
+}
+
+pub fn parse_verifying_key(hex_key: &str) -> Result<VerifyingKey, String> {
+    let bytes: [u8; 32] = hex::decode(hex_key.trim())
+        .map_err(|e| format!("Invalid fixture signing key: {}", e))?
+        .try_into()
+        .map_err(|_| "Invalid fixture signing key: expected 32 bytes".to_string())?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| format!("Invalid fixture signing key: {}", e))
+}
+
+impl FixtureManifest {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        serde_json::from_slice(bytes).map_err(|e| format!("Invalid fixture manifest: {}", e))
+    }
+
+    /// What the publisher signs: a version line, the challenge id, then one
+    /// `<bundle> <sha256>` line per file in key order.
+    pub fn signed_message(&self) -> String {
+        let mut message = format!("fathuss-fixtures-v1\n{}\n", self.challenge_id);
+        for (key, digest) in &self.files {
+            message.push_str(&format!("{} {}\n", key, digest.to_lowercase()));
+        }
+        message
+    }
+
+    /// Check the signature, and that the manifest is for `challenge_id`.
+    pub fn verify(&self, key: &VerifyingKey, challenge_id: &str) -> Result<(), String> {
+        if self.challenge_id != challenge_id {
+            return Err(format!("Fixture manifest is for challenge {}, not {}", self.challenge_id, challenge_id));
+        }
+        let signature: [u8; 64] = hex::decode(&self.signature)
+            .map_err(|e| format!("Invalid fixture manifest signature: {}", e))?
+            .try_into()
+            .map_err(|_| "Invalid fixture manifest signature: expected 64 bytes".to_string())?;
+        key.verify(self.signed_message().as_bytes(), &Signature::from_bytes(&signature))
+            .map_err(|_| format!("Fixture manifest signature for challenge {} does not verify", challenge_id))
+    }
+
+    /// Check a fetched bundle against its checksum. `None` means the source
+    /// has no such bundle, which is only fine if the manifest lists none.
+    pub fn check(&self, bundle: Bundle, bytes: Option<&[u8]>) -> Result<(), String> {
+        match (self.files.get(bundle.key()), bytes) {
+            (None, None) => Ok(()),
+            (None, Some(_)) => Err(format!("{} not listed in the fixture manifest", bundle.name())),
+            (Some(_), None) => Err(format!("{} listed in the fixture manifest are missing", bundle.name())),
+            (Some(expected), Some(bytes)) => {
+                let actual = hex::encode(Sha256::digest(bytes));
+                match actual.eq_ignore_ascii_case(expected) {
+                    true => Ok(()),
+                    false => Err(format!("Checksum mismatch for {}: expected {}, got {}", bundle.name(), expected, actual)),
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    #[test]
+    fn test_manifest_verifies_signature_and_checksums() {
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        let fixtures = br#"[{"id": "basic"}]"#;
+
+        let mut manifest = FixtureManifest {
+            challenge_id: "challenge-1".to_string(),
+            files: BTreeMap::from([("fixtures".to_string(), hex::encode(Sha256::digest(fixtures)))]),
+            signature: String::new(),
+        };
+        manifest.signature = hex::encode(signing_key.sign(manifest.signed_message().as_bytes()).to_bytes());
+        let key = parse_verifying_key(&hex::encode(signing_key.verifying_key().to_bytes())).unwrap();
+
+        manifest.verify(&key, "challenge-1").unwrap();
+        manifest.check(Bundle::Fixtures, Some(fixtures)).unwrap();
+        manifest.check(Bundle::Template, None).unwrap();
+
+        // Truncated or tampered bundles, missing or unlisted ones
+        assert!(manifest.check(Bundle::Fixtures, Some(&fixtures[..10])).is_err());
+        assert!(manifest.check(Bundle::Fixtures, None).is_err());
+        assert!(manifest.check(Bundle::HiddenTests, Some(b"[]")).is_err());
+
+        // Replayed for another challenge, or with edited checksums
+        assert!(manifest.verify(&key, "challenge-2").is_err());
+        manifest.files.insert("hidden-tests".to_string(), hex::encode(Sha256::digest(b"[]")));
+        assert!(manifest.verify(&key, "challenge-1").is_err());
+    }
+}
@@ -0,0 +1,380 @@
+use crate::aws_sigv4::{uri_encode, Credentials};
+use futures::future::BoxFuture;
+use reqwest::{Client, StatusCode, Url};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+/// The documents a challenge publishes for the worker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Bundle {
+    /// Public fixtures, a JSON array
+    Fixtures,
+    /// Hidden fixtures, a JSON array
+    HiddenTests,
+    /// Foundry project template, a `.tar.gz`
+    Template,
+    /// Signed checksums of the other bundles, see `fixture_signing`
+    Manifest,
+    /// Grading settings, see `challenge_config`
+    Config,
+}
+
+impl Bundle {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Bundle::Fixtures => "fixtures",
+            Bundle::HiddenTests => "hidden tests",
+            Bundle::Template => "project template",
+            Bundle::Manifest => "fixture manifest",
+            Bundle::Config => "challenge config",
+        }
+    }
+
+    /// The bundle's entry in a fixture manifest, also its path in the
+    /// fixtures service API.
+    pub fn key(&self) -> &'static str {
+        match self {
+            Bundle::Fixtures => "fixtures",
+            Bundle::HiddenTests => "hidden-tests",
+            Bundle::Template => "template",
+            Bundle::Manifest => "manifest",
+            Bundle::Config => "config",
+        }
+    }
+}
+
+/// Result of fetching a bundle, conditionally on a cached ETag.
+pub enum Fetched {
+    /// `version` is the fixtures revision served, when the source reports it
+    Body { bytes: Vec<u8>, etag: Option<String>, version: Option<String> },
+    NotModified,
+    NotFound,
+}
+
+/// Where fixture bundles come from: the fixtures web service, or an object
+/// store for self-hosted deployments without one.
+pub trait FixtureSource: Send + Sync {
+    /// Fetch a challenge's bundle, at fixtures revision `version` if pinned,
+    /// otherwise the latest. With `etag` set, an unchanged bundle comes back
+    /// as `Fetched::NotModified`.
+    fn fetch<'a>(
+        &'a self,
+        challenge_id: &'a str,
+        bundle: Bundle,
+        version: Option<&'a str>,
+        etag: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Fetched, String>>;
+
+    /// Whether fetched fixtures may be cached. Local sources are read on
+    /// every job so edits show up immediately.
+    fn cacheable(&self) -> bool {
+        true
+    }
+}
+
+/// The fixtures service API: `{base}/challenges/{id}/fixtures`,
+/// `/hidden-tests`, `/template`, `/manifest` and `/config`, with `?version=`
+/// to pin a revision. The service reports the revision served in
+/// `X-Fixtures-Version`.
+pub struct HttpSource {
+    client: Client,
+    base_url: String,
+}
+
+impl HttpSource {
+    pub fn new(base_url: String) -> Self {
+        Self { client: Client::new(), base_url }
+    }
+}
+
+impl FixtureSource for HttpSource {
+    fn fetch<'a>(
+        &'a self,
+        challenge_id: &'a str,
+        bundle: Bundle,
+        version: Option<&'a str>,
+        etag: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Fetched, String>> {
+        Box::pin(async move {
+            let mut request = self.client.get(format!("{}/challenges/{}/{}", self.base_url, challenge_id, bundle.key()));
+            if let Some(version) = version {
+                request = request.query(&[("version", version)]);
+            }
+            if let Some(etag) = etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            read_response(request.send().await, bundle).await
+        })
+    }
+}
+
+/// An S3-compatible bucket (AWS S3, MinIO, or GCS through its XML API with
+/// HMAC keys) holding `{prefix}/{id}/fixtures.json`, `hidden-tests.json`,
+/// `template.tar.gz`, `manifest.json` and `config.json`, and each pinnable
+/// revision under `{prefix}/{id}/versions/{version}/`. The latest objects
+/// carry their revision as `fixtures-version` user metadata. Requests are
+/// signed with AWS Signature Version 4 when credentials are configured;
+/// without them the bucket must allow reads.
+pub struct ObjectStoreSource {
+    client: Client,
+    endpoint: Url,
+    bucket: String,
+    prefix: String,
+    region: String,
+    credentials: Option<Credentials>,
+}
+
+impl ObjectStoreSource {
+    pub fn new(endpoint: &str, bucket: String, prefix: String, region: String, credentials: Option<Credentials>) -> Result<Self, String> {
+        let endpoint = Url::parse(endpoint).map_err(|e| format!("Invalid object store endpoint {}: {}", endpoint, e))?;
+        Ok(Self {
+            client: Client::new(),
+            endpoint,
+            bucket,
+            prefix: prefix.trim_matches('/').to_string(),
+            region,
+            credentials,
+        })
+    }
+
+    /// The bucket named by `FIXTURES_BUCKET`, if set. `FIXTURES_BUCKET_PREFIX`,
+    /// `FIXTURES_S3_ENDPOINT` and the standard `AWS_*` variables configure it.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        let bucket = match std::env::var("FIXTURES_BUCKET") {
+            Ok(bucket) if !bucket.is_empty() => bucket,
+            _ => return Ok(None),
+        };
+        let region = std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+        let endpoint = std::env::var("FIXTURES_S3_ENDPOINT").unwrap_or_else(|_| format!("https://s3.{}.amazonaws.com", region));
+        let prefix = std::env::var("FIXTURES_BUCKET_PREFIX").unwrap_or_default();
+        Self::new(&endpoint, bucket, prefix, region, Credentials::from_env()).map(Some)
+    }
+
+    fn object_key(&self, challenge_id: &str, bundle: Bundle, version: Option<&str>) -> String {
+        let object = match bundle {
+            Bundle::Fixtures => "fixtures.json",
+            Bundle::HiddenTests => "hidden-tests.json",
+            Bundle::Template => "template.tar.gz",
+            Bundle::Manifest => "manifest.json",
+            Bundle::Config => "config.json",
+        };
+        let object = match version {
+            Some(version) => format!("{}/versions/{}/{}", challenge_id, version, object),
+            None => format!("{}/{}", challenge_id, object),
+        };
+        match self.prefix.is_empty() {
+            true => object,
+            false => format!("{}/{}", self.prefix, object),
+        }
+    }
+
+    /// Path-style object path, percent-encoded as SigV4 canonicalizes it.
+    fn object_path(&self, key: &str) -> String {
+        let base = self.endpoint.path().trim_end_matches('/');
+        format!("{}/{}/{}", base, uri_encode(&self.bucket), key.split('/').map(uri_encode).collect::<Vec<_>>().join("/"))
+    }
+
+    /// SigV4 headers for an unsigned-payload GET of `path`.
+    fn signed_headers(&self, path: &str, credentials: &Credentials, now: chrono::DateTime<chrono::Utc>) -> Vec<(String, String)> {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let host = match self.endpoint.port() {
+            Some(port) => format!("{}:{}", self.endpoint.host_str().unwrap_or(""), port),
+            None => self.endpoint.host_str().unwrap_or("").to_string(),
+        };
+
+        let mut headers = vec![
+            ("host".to_string(), host),
+            ("x-amz-content-sha256".to_string(), "UNSIGNED-PAYLOAD".to_string()),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+        if let Some(token) = &credentials.session_token {
+            headers.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        let signed_names = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(";");
+        let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+
+        let canonical_request = format!("GET\n{}\n\n{}\n{}\nUNSIGNED-PAYLOAD", path, canonical_headers, signed_names);
+        let authorization = credentials.authorization(&self.region, "s3", now, &canonical_request, &signed_names);
+
+        headers.retain(|(name, _)| name != "host");
+        headers.push(("authorization".to_string(), authorization));
+        headers
+    }
+}
+
+impl FixtureSource for ObjectStoreSource {
+    fn fetch<'a>(
+        &'a self,
+        challenge_id: &'a str,
+        bundle: Bundle,
+        version: Option<&'a str>,
+        etag: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Fetched, String>> {
+        Box::pin(async move {
+            let path = self.object_path(&self.object_key(challenge_id, bundle, version));
+            let mut url = self.endpoint.clone();
+            url.set_path(&path);
+
+            let mut request = self.client.get(url);
+            if let Some(credentials) = &self.credentials {
+                for (name, value) in self.signed_headers(&path, credentials, chrono::Utc::now()) {
+                    request = request.header(name, value);
+                }
+            }
+            if let Some(etag) = etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            read_response(request.send().await, bundle).await
+        })
+    }
+}
+
+/// Challenges in a local directory, for authors running the worker offline:
+/// `{root}/challenges/{id}/fixtures/*.json` and `hidden/*.json`, plus an
+/// optional `template.tar.gz`, `manifest.json` and `config.json`. Pinned
+/// revisions are read from `{root}/challenges/{id}/versions/{version}/` in
+/// the same layout.
+pub struct LocalDirSource {
+    root: PathBuf,
+}
+
+impl LocalDirSource {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    /// The directory named by `FIXTURES_DIR`, if set.
+    pub fn from_env() -> Option<Self> {
+        std::env::var("FIXTURES_DIR").ok().filter(|dir| !dir.is_empty()).map(|dir| Self::new(PathBuf::from(dir)))
+    }
+}
+
+impl FixtureSource for LocalDirSource {
+    fn fetch<'a>(
+        &'a self,
+        challenge_id: &'a str,
+        bundle: Bundle,
+        version: Option<&'a str>,
+        _etag: Option<&'a str>,
+    ) -> BoxFuture<'a, Result<Fetched, String>> {
+        Box::pin(async move {
+            if challenge_id.is_empty() || challenge_id.contains('/') || challenge_id.contains("..") {
+                return Err(format!("Invalid challenge id: {}", challenge_id));
+            }
+            if version.is_some_and(|v| v.is_empty() || v.contains('/') || v.contains("..")) {
+                return Err(format!("Invalid fixtures version: {}", version.unwrap_or("")));
+            }
+            let mut challenge_dir = self.root.join("challenges").join(challenge_id);
+            if let Some(version) = version {
+                challenge_dir = challenge_dir.join("versions").join(version);
+            }
+            let bytes = match bundle {
+                Bundle::Fixtures => read_fixture_dir(&challenge_dir.join("fixtures"))?,
+                Bundle::HiddenTests => read_fixture_dir(&challenge_dir.join("hidden"))?,
+                Bundle::Template => std::fs::read(challenge_dir.join("template.tar.gz")).ok(),
+                Bundle::Manifest => std::fs::read(challenge_dir.join("manifest.json")).ok(),
+                Bundle::Config => std::fs::read(challenge_dir.join("config.json")).ok(),
+            };
+            Ok(match bytes {
+                Some(bytes) => Fetched::Body { bytes, etag: None, version: version.map(String::from) },
+                None => Fetched::NotFound,
+            })
+        })
+    }
+
+    fn cacheable(&self) -> bool {
+        false
+    }
+}
+
+/// The `*.json` files of a fixture directory, each holding one fixture or an
+/// array of them, joined in file name order into one JSON array. `None` if
+/// the directory doesn't exist.
+pub fn read_fixture_dir(dir: &Path) -> Result<Option<Vec<u8>>, String> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Ok(None),
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().map(|e| e == "json").unwrap_or(false))
+        .collect();
+    files.sort();
+
+    let mut fixtures = Vec::new();
+    for file in files {
+        let contents = std::fs::read_to_string(&file).map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        match serde_json::from_str(&contents).map_err(|e| format!("Failed to parse {}: {}", file.display(), e))? {
+            Value::Array(items) => fixtures.extend(items),
+            fixture => fixtures.push(fixture),
+        }
+    }
+    serde_json::to_vec(&fixtures).map(Some).map_err(|e| e.to_string())
+}
+
+async fn read_response(response: Result<reqwest::Response, reqwest::Error>, bundle: Bundle) -> Result<Fetched, String> {
+    let response = response.map_err(|e| format!("Failed to fetch {}: {}", bundle.name(), e))?;
+    match response.status() {
+        StatusCode::NOT_MODIFIED => return Ok(Fetched::NotModified),
+        StatusCode::NOT_FOUND => return Ok(Fetched::NotFound),
+        status if !status.is_success() => return Err(format!("Failed to fetch {}: HTTP {}", bundle.name(), status)),
+        _ => {},
+    }
+
+    let header = |name: &str| response.headers().get(name).and_then(|v| v.to_str().ok()).map(String::from);
+    let etag = header(reqwest::header::ETAG.as_str());
+    // The fixtures service's header, or object metadata in a bucket
+    let version = header("x-fixtures-version").or_else(|| header("x-amz-meta-fixtures-version"));
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| format!("Failed to read {}: {}", bundle.name(), e))?;
+    Ok(Fetched::Body { bytes: bytes.to_vec(), etag, version })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_object_layout_and_signing() {
+        let credentials = Credentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+        let source = ObjectStoreSource::new("http://minio:9000", "fathuss".to_string(), "/fixtures/".to_string(), "us-east-1".to_string(), None).unwrap();
+        let key = source.object_key("challenge-1", Bundle::HiddenTests, None);
+        assert_eq!(key, "fixtures/challenge-1/hidden-tests.json");
+        assert_eq!(source.object_key("challenge-1", Bundle::Fixtures, Some("v3")), "fixtures/challenge-1/versions/v3/fixtures.json");
+        assert_eq!(source.object_path(&key), "/fathuss/fixtures/challenge-1/hidden-tests.json");
+
+        let now = chrono::DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap().with_timezone(&chrono::Utc);
+        let headers = source.signed_headers(&source.object_path(&key), &credentials, now);
+        let authorization = &headers.iter().find(|(name, _)| name == "authorization").unwrap().1;
+        assert!(authorization.starts_with("AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240501/us-east-1/s3/aws4_request, SignedHeaders=host;x-amz-content-sha256;x-amz-date, Signature="));
+        assert_eq!(headers.iter().find(|(name, _)| name == "x-amz-date").unwrap().1, "20240501T120000Z");
+    }
+
+    #[tokio::test]
+    async fn test_local_dir_source_joins_fixture_files() {
+        let root = tempfile::tempdir().unwrap();
+        let fixtures = root.path().join("challenges/counter/fixtures");
+        std::fs::create_dir_all(&fixtures).unwrap();
+        std::fs::write(fixtures.join("02-overflow.json"), r#"{"id": "overflow"}"#).unwrap();
+        std::fs::write(fixtures.join("01-basic.json"), r#"[{"id": "zero"}, {"id": "one"}]"#).unwrap();
+        std::fs::write(fixtures.join("notes.md"), "not a fixture").unwrap();
+
+        let source = LocalDirSource::new(root.path().to_path_buf());
+        let ids = match source.fetch("counter", Bundle::Fixtures, None, None).await.unwrap() {
+            Fetched::Body { bytes, .. } => serde_json::from_slice::<Vec<Value>>(&bytes).unwrap().iter().map(|f| f["id"].as_str().unwrap().to_string()).collect::<Vec<_>>(),
+            _ => panic!("expected fixtures"),
+        };
+        assert_eq!(ids, vec!["zero", "one", "overflow"]);
+
+        assert!(matches!(source.fetch("counter", Bundle::HiddenTests, None, None).await.unwrap(), Fetched::NotFound));
+        assert!(source.fetch("../counter", Bundle::Fixtures, None, None).await.is_err());
+        assert!(source.fetch("counter", Bundle::Fixtures, Some(".."), None).await.is_err());
+    }
+}
@@ -1,78 +1,402 @@
-use reqwest::Client;
+use crate::backoff::{CircuitBreaker, RetryPolicy};
+use crate::challenge_config::ChallengeConfig;
+use crate::fixture_cache::{CacheIndex, CachePin, CacheStats};
+use crate::fixture_files::{self, FixtureFile};
+use crate::fixture_schema;
+use crate::fixture_signing::{self, FixtureManifest};
+use crate::fixture_source::{self, Bundle, Fetched, FixtureSource, HttpSource, LocalDirSource, ObjectStoreSource};
+use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use sha2::{Digest, Sha256};
 use tokio::fs as async_fs;
 
+/// How long cached fixtures are served before being revalidated.
+const DEFAULT_CACHE_TTL: Duration = Duration::from_secs(300);
+
+/// A fixture as written in a fixtures document. Fields are snake_case, with
+/// camelCase aliases accepted.
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct TestFixture {
     pub id: String,
+    #[serde(default = "default_fixture_name")]
     pub name: String,
+    #[serde(default)]
     pub description: String,
+    #[serde(default)]
     pub input: Value,
+    #[serde(default, alias = "expectedOutput")]
     pub expected_output: Value,
+    #[serde(default)]
     pub hidden: bool,
+    #[serde(default = "default_fixture_timeout")]
     pub timeout: u64,
+    #[serde(default = "default_fixture_gas_limit", alias = "gasLimit")]
     pub gas_limit: u64,
+    /// Source files the fixture exercises, used to select fixtures affected
+    /// by a change; empty means the fixture may depend on anything
+    #[serde(default)]
+    pub targets: Vec<String>,
+    /// Input too large for the document, mounted read-only into the sandbox
+    /// and passed to the program in place of the JSON input file
+    #[serde(default, alias = "inputFile")]
+    pub input_file: Option<FixtureFile>,
+    /// Expected output too large for the document, compared byte for byte
+    #[serde(default, alias = "expectedOutputFile")]
+    pub expected_output_file: Option<FixtureFile>,
+    /// Suite the fixture belongs to, reported with its own subtotal
+    #[serde(default)]
+    pub suite: Option<String>,
+    /// Suites that must all pass before this fixture runs; it is skipped
+    /// otherwise
+    #[serde(default, alias = "dependsOn")]
+    pub depends_on: Vec<String>,
+    /// Run in the sandbox before the fixture, e.g. to seed files
+    #[serde(default)]
+    pub setup: Option<FixtureCommand>,
+    /// Run in the sandbox after the fixture, whatever its outcome
+    #[serde(default)]
+    pub teardown: Option<FixtureCommand>,
+}
+
+fn default_fixture_name() -> String {
+    "Unnamed test".to_string()
+}
+
+fn default_fixture_timeout() -> u64 {
+    30
+}
+
+fn default_fixture_gas_limit() -> u64 {
+    1000000
+}
+
+/// Longest a fixture's setup or teardown command may run, in seconds.
+pub const MAX_COMMAND_TIMEOUT: u64 = 30;
+
+/// A shell command run around a fixture under its own time budget.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FixtureCommand {
+    /// Passed to `sh -c` in the job workspace
+    pub run: String,
+    /// Seconds, capped at `MAX_COMMAND_TIMEOUT`
+    #[serde(default = "default_command_timeout")]
+    pub timeout: u64,
+}
+
+fn default_command_timeout() -> u64 {
+    5
 }
 
 pub struct FixtureManager {
-    client: Client,
+    source: Box<dyn FixtureSource>,
     cache_dir: String,
-    fixtures_base_url: String,
+    cache_ttl: Duration,
+    /// Publisher key every bundle's manifest must be signed with
+    signing_key: Option<ed25519_dalek::VerifyingKey>,
+    retry: RetryPolicy,
+    breaker: CircuitBreaker,
+    /// Fail jobs whose fixtures can't be fetched instead of grading them
+    /// without fixtures
+    hard_fail: bool,
+    client: reqwest::Client,
+    /// Size limit of each external fixture file
+    max_file_bytes: u64,
+    /// Size cap and LRU order of everything under `cache_dir`
+    index: CacheIndex,
+    /// Parsed fixtures by challenge (and pinned version), in front of the
+    /// disk cache
+    memory: Mutex<HashMap<String, MemoryEntry>>,
+    /// Loads in progress, so concurrent jobs share a single fetch of a
+    /// challenge's fixtures, hidden tests and manifest
+    fixture_loads: SingleFlight<(Vec<TestFixture>, Option<String>)>,
+    hidden_loads: SingleFlight<Vec<TestFixture>>,
+    manifest_loads: SingleFlight<FixtureManifest>,
+}
+
+/// Concurrent loads of the same key share one: the first caller runs it and
+/// the rest wait for its result. A key is forgotten as soon as its load
+/// finishes, successfully or not, so failures are retried by the next caller.
+struct SingleFlight<T> {
+    inflight: Mutex<HashMap<String, Arc<Flight<T>>>>,
+}
+
+type Flight<T> = tokio::sync::OnceCell<Result<T, String>>;
+
+impl<T: Clone> SingleFlight<T> {
+    fn new() -> Self {
+        Self { inflight: Mutex::new(HashMap::new()) }
+    }
+
+    async fn run<F, Fut>(&self, key: &str, load: F) -> Result<T, String>
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = Result<T, String>>,
+    {
+        let flight = self.inflight.lock().unwrap().entry(key.to_string()).or_default().clone();
+        // If the leading job is cancelled, a waiting one takes over the load
+        let result = flight.get_or_init(load).await.clone();
+        let mut inflight = self.inflight.lock().unwrap();
+        if let Some(current) = inflight.get(key) {
+            if Arc::ptr_eq(current, &flight) {
+                inflight.remove(key);
+            }
+        }
+        result
+    }
+
+    #[cfg(test)]
+    fn len(&self) -> usize {
+        self.inflight.lock().unwrap().len()
+    }
+}
+
+/// Most challenges whose fixtures are kept in memory.
+const MEMORY_CACHE_CAPACITY: usize = 256;
+
+struct MemoryEntry {
+    fixtures: Vec<TestFixture>,
+    version: Option<String>,
+    loaded_at: Instant,
+}
+
+/// A challenge's fixtures as cached on disk, with what is needed to
+/// revalidate them.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedFixtures {
+    etag: Option<String>,
+    /// Fixtures revision, when the source reports one
+    #[serde(default)]
+    version: Option<String>,
+    /// Unix time the fixtures were last fetched or revalidated
+    fetched_at: u64,
+    fixtures: Vec<TestFixture>,
+}
+
+/// Challenge ids name cache files and directories.
+fn check_challenge_id(challenge_id: &str) -> Result<(), String> {
+    match challenge_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        true => Ok(()),
+        false => Err(format!("Invalid challenge id: {}", challenge_id)),
+    }
+}
+
+/// Fixture versions name cache files and source paths.
+fn check_version(version: &str) -> Result<(), String> {
+    let valid = !version.is_empty()
+        && !version.contains("..")
+        && version.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    match valid {
+        true => Ok(()),
+        false => Err(format!("Invalid fixtures version: {}", version)),
+    }
+}
+
+/// Cache name of a challenge's fixtures or template, per pinned version.
+fn cache_name(challenge_id: &str, version: Option<&str>) -> String {
+    match version {
+        Some(version) => format!("{}@{}", challenge_id, version),
+        None => challenge_id.to_string(),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
 }
 
 impl FixtureManager {
     pub fn new(fixtures_base_url: String, cache_dir: String) -> Self {
+        Self::with_source(Box::new(HttpSource::new(fixtures_base_url)), cache_dir)
+    }
+
+    pub fn with_source(source: Box<dyn FixtureSource>, cache_dir: String) -> Self {
         Self {
-            client: Client::new(),
+            index: CacheIndex::new(PathBuf::from(&cache_dir), CacheIndex::max_bytes_from_env()),
+            memory: Mutex::new(HashMap::new()),
+            fixture_loads: SingleFlight::new(),
+            hidden_loads: SingleFlight::new(),
+            manifest_loads: SingleFlight::new(),
+            source,
             cache_dir,
-            fixtures_base_url,
+            cache_ttl: DEFAULT_CACHE_TTL,
+            signing_key: None,
+            retry: RetryPolicy::default(),
+            breaker: CircuitBreaker::new(5, Duration::from_secs(30)),
+            hard_fail: false,
+            client: reqwest::Client::new(),
+            max_file_bytes: fixture_files::max_file_bytes_from_env(),
         }
     }
 
-    pub async fn fetch_challenge_fixtures(&self, challenge_id: &str) -> Result<Vec<TestFixture>, String> {
-        // Check if challenge_id is a local path (starts with /)
-        if challenge_id.starts_with('/') {
-            return self.fetch_local_fixtures(challenge_id).await;
-        }
+    /// Fixtures from the local directory `FIXTURES_DIR` if set, else from
+    /// the bucket `FIXTURES_BUCKET` if set, otherwise from the fixtures
+    /// service at `FIXTURES_BASE_URL`.
+    pub fn from_env() -> Result<Self, String> {
+        let source: Box<dyn FixtureSource> = match (LocalDirSource::from_env(), ObjectStoreSource::from_env()?) {
+            (Some(local), _) => Box::new(local),
+            (None, Some(bucket)) => Box::new(bucket),
+            (None, None) => {
+                let fixtures_base_url = std::env::var("FIXTURES_BASE_URL").unwrap_or_else(|_| "http://localhost:4000/api".to_string());
+                Box::new(HttpSource::new(fixtures_base_url))
+            },
+        };
+        let cache_ttl = std::env::var("FIXTURES_CACHE_TTL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_CACHE_TTL);
+        Ok(Self {
+            cache_ttl,
+            signing_key: fixture_signing::signing_key_from_env()?,
+            retry: RetryPolicy::from_env(),
+            breaker: CircuitBreaker::from_env(),
+            hard_fail: std::env::var("FIXTURES_HARD_FAIL").map(|v| v == "true" || v == "1").unwrap_or(false),
+            ..Self::with_source(source, "/tmp/fixtures_cache".to_string())
+        })
+    }
+
+    /// The challenge's public fixtures at revision `version`, or the latest
+    /// if not pinned, with their external files downloaded. Also returns the
+    /// revision they came from, when known.
+    pub async fn fetch_challenge_fixtures(&self, challenge_id: &str, version: Option<&str>) -> Result<(Vec<TestFixture>, Option<String>), String> {
+        let (fixtures, version) = self.load_challenge_fixtures(challenge_id, version).await?;
+        Ok((self.download_files(fixtures, true).await?, version))
+    }
 
-        let fixtures_url = format!("{}/challenges/{}/fixtures", self.fixtures_base_url, challenge_id);
+    async fn load_challenge_fixtures(&self, challenge_id: &str, version: Option<&str>) -> Result<(Vec<TestFixture>, Option<String>), String> {
+        if let Some(local_dir) = crate::workspace::local_challenge_dir(challenge_id) {
+            return Ok((self.fetch_local_fixtures(&local_dir?).await?, None));
+        }
+        if let Some(version) = version {
+            check_version(version)?;
+        }
+        if !self.source.cacheable() {
+            return self.fetch_uncached(challenge_id, Bundle::Fixtures, version).await;
+        }
 
-        // Try to get from cache first
-        let cache_key = format!("fixtures_{}", challenge_id);
-        if let Ok(cached) = self.get_cached_fixtures(&cache_key).await {
-            return Ok(cached);
+        let key = cache_name(challenge_id, version);
+        if let Some(loaded) = self.memory_hit(&key, version.is_some()) {
+            return Ok(loaded);
         }
 
-        // Fetch from remote
-        let response = self.client
-            .get(&fixtures_url)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to fetch fixtures: {}", e))?;
+        // Single flight: the first job for a challenge loads its fixtures while
+        // concurrent ones wait for the result
+        self.fixture_loads.run(&key, || async {
+            if let Some(loaded) = self.memory_hit(&key, version.is_some()) {
+                return Ok(loaded);
+            }
+            let (fixtures, loaded_version) = self.load_through_disk_cache(challenge_id, version).await?;
+            let mut memory = self.memory.lock().unwrap();
+            if memory.len() >= MEMORY_CACHE_CAPACITY && !memory.contains_key(&key) {
+                let oldest = memory.iter().min_by_key(|(_, entry)| entry.loaded_at).map(|(key, _)| key.clone());
+                if let Some(oldest) = oldest {
+                    memory.remove(&oldest);
+                }
+            }
+            memory.insert(key.clone(), MemoryEntry { fixtures: fixtures.clone(), version: loaded_version.clone(), loaded_at: Instant::now() });
+            Ok((fixtures, loaded_version))
+        }).await
+    }
 
-        if !response.status().is_success() {
-            return Err(format!("Failed to fetch fixtures: HTTP {}", response.status()));
+    /// Fixtures loaded into memory within the cache TTL, or ever for a pinned
+    /// version.
+    fn memory_hit(&self, key: &str, pinned: bool) -> Option<(Vec<TestFixture>, Option<String>)> {
+        let memory = self.memory.lock().unwrap();
+        let entry = memory.get(key)?;
+        match pinned || entry.loaded_at.elapsed() < self.cache_ttl {
+            true => Some((entry.fixtures.clone(), entry.version.clone())),
+            false => None,
         }
+    }
 
-        let fixtures_data: Value = response
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse fixtures JSON: {}", e))?;
+    async fn load_through_disk_cache(&self, challenge_id: &str, version: Option<&str>) -> Result<(Vec<TestFixture>, Option<String>), String> {
+        // Serve from cache while fresh, otherwise revalidate with its ETag.
+        // Pinned revisions never change, so they are always fresh.
+        let cache_key = format!("fixtures_{}", cache_name(challenge_id, version));
+        let cached = self.get_cached_fixtures(&cache_key).await.ok();
+        if let Some(entry) = &cached {
+            if version.is_some() || unix_now().saturating_sub(entry.fetched_at) < self.cache_ttl.as_secs() {
+                return Ok((entry.fixtures.clone(), entry.version.clone()));
+            }
+        }
 
-        let fixtures = self.parse_fixtures(fixtures_data)?;
+        let etag = cached.as_ref().and_then(|entry| entry.etag.as_deref());
+        let fetched = match self.fetch_bundle(challenge_id, Bundle::Fixtures, version, etag).await {
+            Ok(fetched) => fetched,
+            Err(e) => match cached {
+                // The fixtures service being down shouldn't fail grading
+                Some(entry) => {
+                    eprintln!("Warning: Serving stale fixtures for {}: {}", challenge_id, e);
+                    return Ok((entry.fixtures, entry.version));
+                },
+                None => return Err(e),
+            },
+        };
+
+        let (bytes, etag, served_version) = match (fetched, cached) {
+            (Fetched::Body { bytes, etag, version: served }, _) => (bytes, etag, served.or(version.map(String::from))),
+            (Fetched::NotModified, Some(mut entry)) => {
+                entry.fetched_at = unix_now();
+                self.cache_fixtures(&cache_key, &entry).await?;
+                return Ok((entry.fixtures, entry.version));
+            },
+            (Fetched::NotModified, None) => return Err("Fixtures not modified, but none are cached".to_string()),
+            (Fetched::NotFound, _) => return Err(format!("No fixtures for challenge {}", challenge_id)),
+        };
+
+        let fixtures = self.parse_fixture_bytes(&bytes)?;
 
         // Cache the fixtures
-        self.cache_fixtures(&cache_key, &fixtures).await?;
+        let entry = CachedFixtures { etag, version: served_version, fetched_at: unix_now(), fixtures };
+        self.cache_fixtures(&cache_key, &entry).await?;
 
-        Ok(fixtures)
+        Ok((entry.fixtures, entry.version))
+    }
+
+    /// Drop a challenge's cached fixtures and project template, so the next
+    /// job fetches them again. Returns whether anything was cached.
+    pub async fn invalidate(&self, challenge_id: &str) -> Result<bool, String> {
+        check_challenge_id(challenge_id)?;
+        self.memory.lock().unwrap().remove(challenge_id);
+
+        let cache_path = Path::new(&self.cache_dir).join(format!("fixtures_{}.json", challenge_id));
+        let template_dir = Path::new(&self.cache_dir).join("templates").join(challenge_id);
+        let mut removed = false;
+        if cache_path.exists() {
+            async_fs::remove_file(&cache_path)
+                .await
+                .map_err(|e| format!("Failed to remove cached fixtures: {}", e))?;
+            self.index.remove(&format!("fixtures_{}.json", challenge_id));
+            removed = true;
+        }
+        if template_dir.exists() {
+            async_fs::remove_dir_all(&template_dir)
+                .await
+                .map_err(|e| format!("Failed to remove cached template: {}", e))?;
+            self.index.remove(&format!("templates/{}", challenge_id));
+            removed = true;
+        }
+        Ok(removed)
+    }
+
+    /// Size and hit rate of the on-disk fixture cache.
+    pub fn cache_stats(&self) -> CacheStats {
+        self.index.stats()
     }
 
-    async fn fetch_local_fixtures(&self, local_path: &str) -> Result<Vec<TestFixture>, String> {
-        // For local testing, create basic fixtures from the test files
-        let test_dir = Path::new(local_path).join("test");
+    async fn fetch_local_fixtures(&self, local_path: &Path) -> Result<Vec<TestFixture>, String> {
+        // Challenge directories in the local source layout
+        if let Some(document) = fixture_source::read_fixture_dir(&local_path.join("fixtures"))? {
+            return self.parse_fixture_bytes(&document);
+        }
+
+        // Otherwise, create basic fixtures from the test files
+        let test_dir = local_path.join("test");
         let mut fixtures = Vec::new();
 
         if test_dir.exists() {
@@ -86,109 +410,237 @@ impl FixtureManager {
                 hidden: false,
                 timeout: 30000, // 30 seconds
                 gas_limit: 10000000,
+                targets: Vec::new(),
+                input_file: None,
+                expected_output_file: None,
+                suite: None,
+                depends_on: Vec::new(),
+                setup: None,
+                teardown: None,
             });
         }
 
         Ok(fixtures)
     }
 
-    pub async fn fetch_hidden_tests(&self, challenge_id: &str) -> Result<Vec<TestFixture>, String> {
-        let hidden_url = format!("{}/challenges/{}/hidden-tests", self.fixtures_base_url, challenge_id);
+    /// The challenge's hidden tests at revision `version`, normally the one
+    /// its public fixtures came from.
+    pub async fn fetch_hidden_tests(&self, challenge_id: &str, version: Option<&str>) -> Result<Vec<TestFixture>, String> {
+        let fixtures = self.load_hidden_tests(challenge_id, version).await?;
+        // Hidden expected outputs are compared by checksum, so they never need to touch disk
+        self.download_files(fixtures, false).await
+    }
 
-        // Hidden tests are always fetched fresh (not cached)
-        let response = self.client
-            .get(&hidden_url)
-            .send()
-            .await
-            .map_err(|e| format!("Failed to fetch hidden tests: {}", e))?;
+    async fn load_hidden_tests(&self, challenge_id: &str, version: Option<&str>) -> Result<Vec<TestFixture>, String> {
+        if let Some(local_dir) = crate::workspace::local_challenge_dir(challenge_id) {
+            return match fixture_source::read_fixture_dir(&local_dir?.join("hidden"))? {
+                Some(document) => self.parse_fixture_bytes(&document),
+                None => Ok(vec![]),
+            };
+        }
+        if let Some(version) = version {
+            check_version(version)?;
+        }
+
+        // Hidden tests are always fetched fresh (not cached), though concurrent
+        // jobs share a fetch; a challenge may have none
+        self.hidden_loads.run(&cache_name(challenge_id, version), || async {
+            match self.fetch_bundle(challenge_id, Bundle::HiddenTests, version, None).await? {
+                Fetched::Body { bytes, .. } => self.parse_fixture_bytes(&bytes),
+                _ => Ok(vec![]),
+            }
+        }).await
+    }
 
-        if !response.status().is_success() {
-            return Err(format!("Failed to fetch hidden tests: HTTP {}", response.status()));
+    /// The challenge's config at revision `version`, fetched fresh for every
+    /// job like its hidden tests. Challenges without one use the defaults.
+    pub async fn fetch_challenge_config(&self, challenge_id: &str, version: Option<&str>) -> Result<ChallengeConfig, String> {
+        if let Some(local_dir) = crate::workspace::local_challenge_dir(challenge_id) {
+            return match fs::read(local_dir?.join("config.json")) {
+                Ok(bytes) => ChallengeConfig::parse(&bytes),
+                Err(_) => Ok(ChallengeConfig::default()),
+            };
+        }
+        check_challenge_id(challenge_id)?;
+        if let Some(version) = version {
+            check_version(version)?;
         }
 
-        let hidden_data: Value = response
-            .json()
-            .await
-            .map_err(|e| format!("Failed to parse hidden tests JSON: {}", e))?;
+        match self.fetch_bundle(challenge_id, Bundle::Config, version, None).await? {
+            Fetched::Body { bytes, .. } => ChallengeConfig::parse(&bytes),
+            _ => Ok(ChallengeConfig::default()),
+        }
+    }
 
-        self.parse_fixtures(hidden_data)
+    async fn fetch_uncached(&self, challenge_id: &str, bundle: Bundle, version: Option<&str>) -> Result<(Vec<TestFixture>, Option<String>), String> {
+        match self.fetch_bundle(challenge_id, bundle, version, None).await? {
+            Fetched::Body { bytes, version: served, .. } => Ok((self.parse_fixture_bytes(&bytes)?, served.or(version.map(String::from)))),
+            _ => Err(format!("No {} for challenge {}", bundle.name(), challenge_id)),
+        }
     }
 
-    fn parse_fixtures(&self, data: Value) -> Result<Vec<TestFixture>, String> {
-        let fixtures_array = data
-            .as_array()
-            .ok_or("Fixtures data is not an array")?;
+    /// Fetch from the source, retrying failures with backoff. Exhausted
+    /// retries count against the circuit breaker, which fails fetches
+    /// immediately while it is open.
+    async fn fetch_with_retry(&self, challenge_id: &str, bundle: Bundle, version: Option<&str>, etag: Option<&str>) -> Result<Fetched, String> {
+        self.breaker.check().map_err(|e| format!("Failed to fetch {}: {}", bundle.name(), e))?;
+
+        let mut attempt = 1;
+        loop {
+            match self.source.fetch(challenge_id, bundle, version, etag).await {
+                Ok(fetched) => {
+                    self.breaker.record_success();
+                    return Ok(fetched);
+                },
+                Err(e) if attempt >= self.retry.max_attempts => {
+                    self.breaker.record_failure();
+                    return Err(e);
+                },
+                Err(e) => {
+                    let delay = self.retry.delay(attempt);
+                    eprintln!("Warning: {} (attempt {}/{}), retrying in {}ms", e, attempt, self.retry.max_attempts, delay.as_millis());
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                },
+            }
+        }
+    }
 
-        let mut fixtures = Vec::new();
+    /// Stream the fixtures' external files into the fixture cache, recording
+    /// where each was stored. Expected output files are skipped unless
+    /// `expected_outputs` is set.
+    async fn download_files(&self, mut fixtures: Vec<TestFixture>, expected_outputs: bool) -> Result<Vec<TestFixture>, String> {
+        let files_dir = Path::new(&self.cache_dir).join("files");
+        for fixture in &mut fixtures {
+            let expected = fixture.expected_output_file.iter_mut().filter(|_| expected_outputs);
+            for file in fixture.input_file.iter_mut().chain(expected) {
+                let entry = format!("files/{}", file.sha256.to_lowercase());
+                file.pin = Some(self.index.pin(&entry));
+                let cached = Path::new(&self.cache_dir).join(&entry).exists();
+                file.path = Some(fixture_files::download(&self.client, file, &files_dir, self.max_file_bytes).await?);
+                match cached {
+                    true => self.index.hit(&entry),
+                    false => {
+                        self.index.miss();
+                        self.index.insert(&entry);
+                    },
+                }
+            }
+        }
+        Ok(fixtures)
+    }
+
+    /// Whether jobs should fail when their fixtures can't be fetched.
+    pub fn hard_fail(&self) -> bool {
+        self.hard_fail
+    }
+
+    /// Fetch a bundle from the source, checked against the signed manifest
+    /// of the same revision when a signing key is configured.
+    async fn fetch_bundle(&self, challenge_id: &str, bundle: Bundle, version: Option<&str>, etag: Option<&str>) -> Result<Fetched, String> {
+        let fetched = self.fetch_with_retry(challenge_id, bundle, version, etag).await?;
+        let key = match &self.signing_key {
+            Some(key) => key,
+            None => return Ok(fetched),
+        };
+        let bytes = match &fetched {
+            Fetched::Body { bytes, .. } => Some(bytes.as_slice()),
+            Fetched::NotFound => None,
+            // The cached copy was verified when it was fetched
+            Fetched::NotModified => return Ok(fetched),
+        };
+
+        let manifest = self.manifest_loads.run(&cache_name(challenge_id, version), || async {
+            match self.fetch_with_retry(challenge_id, Bundle::Manifest, version, None).await? {
+                Fetched::Body { bytes, .. } => FixtureManifest::parse(&bytes),
+                _ => Err(format!("No fixture manifest for challenge {}", challenge_id)),
+            }
+        }).await?;
+        manifest.verify(key, challenge_id)?;
+        manifest.check(bundle, bytes)?;
+        Ok(fetched)
+    }
+
+    fn parse_fixture_bytes(&self, bytes: &[u8]) -> Result<Vec<TestFixture>, String> {
+        let data: Value = serde_json::from_slice(bytes)
+            .map_err(|e| format!("Failed to parse fixtures JSON: {}", e))?;
+        self.parse_fixtures(data)
+    }
 
-        for fixture_value in fixtures_array {
-            let fixture = self.parse_single_fixture(fixture_value)?;
-            fixtures.push(fixture);
+    /// The challenge's Foundry project template (src/, test/, foundry.toml,
+    /// remappings, lib/), downloaded once as a `.tar.gz` bundle and cached.
+    /// Challenges without a template use the worker's default project, but a
+    /// pinned revision must have one: grading it against the default project
+    /// wouldn't reproduce the original grade. A cached template comes with a
+    /// pin to hold while the workspace links to it.
+    pub async fn fetch_project_template(&self, challenge_id: &str, version: Option<&str>) -> Result<(PathBuf, Option<CachePin>), String> {
+        // Local challenges are Foundry projects themselves
+        if let Some(local_dir) = crate::workspace::local_challenge_dir(challenge_id) {
+            let project = local_dir?;
+            return Ok(match project.join("foundry.toml").exists() {
+                true => (project, None),
+                false => (crate::templates::foundry_template_dir(), None),
+            });
         }
 
-        Ok(fixtures)
+        // The id and version name the cache directory
+        check_challenge_id(challenge_id)?;
+        if let Some(version) = version {
+            check_version(version)?;
+        }
+        let entry = format!("templates/{}", cache_name(challenge_id, version));
+        let pin = self.index.pin(&entry);
+        let template_dir = Path::new(&self.cache_dir).join(&entry);
+        if template_dir.exists() {
+            if self.source.cacheable() {
+                self.index.hit(&entry);
+                return Ok((template_dir, Some(pin)));
+            }
+            // Local templates are unpacked afresh for every job
+            async_fs::remove_dir_all(&template_dir)
+                .await
+                .map_err(|e| format!("Failed to remove cached template: {}", e))?;
+        }
+
+        let archive = match self.fetch_bundle(challenge_id, Bundle::Template, version, None).await? {
+            Fetched::Body { bytes, .. } => bytes,
+            _ => match version {
+                Some(version) => return Err(format!("No template for challenge {} at fixtures version {}", challenge_id, version)),
+                None => return Ok((crate::templates::foundry_template_dir(), None)),
+            },
+        };
+        crate::templates::unpack_template(&archive, &template_dir).await?;
+        if self.source.cacheable() {
+            self.index.miss();
+            self.index.insert(&entry);
+        }
+
+        Ok((template_dir, Some(pin)))
     }
 
-    fn parse_single_fixture(&self, data: &Value) -> Result<TestFixture, String> {
-        let id = data
-            .get("id")
-            .and_then(|v| v.as_str())
-            .ok_or("Fixture missing id")?
-            .to_string();
-
-        let name = data
-            .get("name")
-            .and_then(|v| v.as_str())
-            .unwrap_or("Unnamed test")
-            .to_string();
-
-        let description = data
-            .get("description")
-            .and_then(|v| v.as_str())
-            .unwrap_or("")
-            .to_string();
-
-        let input = data
-            .get("input")
-            .cloned()
-            .unwrap_or(json!(null));
-
-        let expected_output = data
-            .get("expected_output")
-            .cloned()
-            .unwrap_or(json!(null));
-
-        let hidden = data
-            .get("hidden")
-            .and_then(|v| v.as_bool())
-            .unwrap_or(false);
-
-        let timeout = data
-            .get("timeout")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(30);
-
-        let gas_limit = data
-            .get("gas_limit")
-            .and_then(|v| v.as_u64())
-            .unwrap_or(1000000);
-
-        Ok(TestFixture {
-            id,
-            name,
-            description,
-            input,
-            expected_output,
-            hidden,
-            timeout,
-            gas_limit,
-        })
+    fn parse_fixtures(&self, data: Value) -> Result<Vec<TestFixture>, String> {
+        let errors = fixture_schema::validate_fixtures(&data);
+        if !errors.is_empty() {
+            let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+            return Err(format!("Invalid fixtures: {}", errors.join("; ")));
+        }
+        let fixtures: Vec<TestFixture> = serde_json::from_value(data)
+            .map_err(|e| format!("Invalid fixtures: {}", e))?;
+
+        for fixture in &fixtures {
+            for file in fixture.input_file.iter().chain(&fixture.expected_output_file) {
+                file.check()?;
+            }
+        }
+
+        Ok(fixtures)
     }
 
-    async fn get_cached_fixtures(&self, cache_key: &str) -> Result<Vec<TestFixture>, String> {
+    async fn get_cached_fixtures(&self, cache_key: &str) -> Result<CachedFixtures, String> {
         let cache_path = Path::new(&self.cache_dir).join(format!("{}.json", cache_key));
 
         if !cache_path.exists() {
+            self.index.miss();
             return Err("Cache miss".to_string());
         }
 
@@ -196,13 +648,13 @@ impl FixtureManager {
             .await
             .map_err(|e| format!("Failed to read cache: {}", e))?;
 
-        let cached_data: Value = serde_json::from_str(&cache_content)
+        let entry = serde_json::from_str(&cache_content)
             .map_err(|e| format!("Failed to parse cache: {}", e))?;
-
-        self.parse_fixtures(cached_data)
+        self.index.hit(&format!("{}.json", cache_key));
+        Ok(entry)
     }
 
-    async fn cache_fixtures(&self, cache_key: &str, fixtures: &[TestFixture]) -> Result<(), String> {
+    async fn cache_fixtures(&self, cache_key: &str, entry: &CachedFixtures) -> Result<(), String> {
         // Create cache directory if it doesn't exist
         async_fs::create_dir_all(&self.cache_dir)
             .await
@@ -210,26 +662,13 @@ impl FixtureManager {
 
         let cache_path = Path::new(&self.cache_dir).join(format!("{}.json", cache_key));
 
-        let fixtures_json: Vec<Value> = fixtures
-            .iter()
-            .map(|f| json!({
-                "id": f.id,
-                "name": f.name,
-                "description": f.description,
-                "input": f.input,
-                "expected_output": f.expected_output,
-                "hidden": f.hidden,
-                "timeout": f.timeout,
-                "gas_limit": f.gas_limit
-            }))
-            .collect();
-
-        let cache_content = serde_json::to_string_pretty(&fixtures_json)
+        let cache_content = serde_json::to_string_pretty(entry)
             .map_err(|e| format!("Failed to serialize fixtures: {}", e))?;
 
         async_fs::write(&cache_path, cache_content)
             .await
             .map_err(|e| format!("Failed to write cache: {}", e))?;
+        self.index.insert(&format!("{}.json", cache_key));
 
         Ok(())
     }
@@ -277,4 +716,114 @@ impl FixtureManager {
 
         fuzz_inputs
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cache_ttl_and_invalidation() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        // Nothing listens on the discard port, so every fetch fails
+        let manager = FixtureManager::new("http://127.0.0.1:9".to_string(), cache_dir.path().to_string_lossy().to_string());
+        let entry = CachedFixtures {
+            etag: Some("\"v1\"".to_string()),
+            version: Some("v1".to_string()),
+            fetched_at: unix_now(),
+            fixtures: manager.parse_fixtures(json!([{ "id": "test-1", "input": 1, "expectedOutput": 2 }])).unwrap(),
+        };
+        manager.cache_fixtures("fixtures_challenge-1", &entry).await.unwrap();
+
+        // Fresh entries are served without contacting the fixtures service
+        let (fixtures, version) = manager.fetch_challenge_fixtures("challenge-1", None).await.unwrap();
+        assert_eq!((fixtures[0].id.as_str(), version.as_deref()), ("test-1", Some("v1")));
+        assert_eq!((fixtures[0].expected_output.clone(), fixtures[0].timeout), (json!(2), 30));
+
+        // Expired entries are revalidated, and served stale if that fails
+        manager.memory.lock().unwrap().clear();
+        let stale = CachedFixtures { fetched_at: unix_now() - 3600, ..entry };
+        manager.cache_fixtures("fixtures_challenge-1", &stale).await.unwrap();
+        assert_eq!(manager.fetch_challenge_fixtures("challenge-1", None).await.unwrap().0.len(), 1);
+
+        // Pinned revisions are cached separately and never expire
+        manager.cache_fixtures("fixtures_challenge-1@v1", &stale).await.unwrap();
+        assert_eq!(manager.fetch_challenge_fixtures("challenge-1", Some("v1")).await.unwrap().1.as_deref(), Some("v1"));
+        assert!(manager.fetch_challenge_fixtures("challenge-1", Some("../v1")).await.is_err());
+
+        assert!(manager.invalidate("challenge-1").await.unwrap());
+        assert!(!manager.invalidate("challenge-1").await.unwrap());
+        assert!(manager.fetch_challenge_fixtures("challenge-1", None).await.is_err());
+        assert!(manager.invalidate("../etc").await.is_err());
+    }
+
+    /// Serves one fixture, slowly, counting requests. Challenge `broken` fails.
+    struct CountingSource(Arc<std::sync::atomic::AtomicUsize>);
+
+    impl FixtureSource for CountingSource {
+        fn fetch<'a>(
+            &'a self,
+            challenge_id: &'a str,
+            _bundle: Bundle,
+            _version: Option<&'a str>,
+            _etag: Option<&'a str>,
+        ) -> futures::future::BoxFuture<'a, Result<Fetched, String>> {
+            Box::pin(async move {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                match challenge_id {
+                    "broken" => Err("Fixtures service unavailable".to_string()),
+                    _ => Ok(Fetched::Body { bytes: br#"[{"id": "basic"}]"#.to_vec(), etag: None, version: None }),
+                }
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_fetches_share_one_request() {
+        let cache_dir = tempfile::tempdir().unwrap();
+        let requests = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let source = Box::new(CountingSource(requests.clone()));
+        let manager = FixtureManager::with_source(source, cache_dir.path().to_string_lossy().to_string());
+
+        let fetches = (0..20).map(|_| manager.fetch_challenge_fixtures("challenge-1", None));
+        for result in futures::future::join_all(fetches).await {
+            assert_eq!(result.unwrap().0[0].id, "basic");
+        }
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        // Hidden tests aren't cached, but concurrent jobs still share a fetch
+        let fetches = (0..20).map(|_| manager.fetch_hidden_tests("challenge-1", None));
+        for result in futures::future::join_all(fetches).await {
+            assert_eq!(result.unwrap()[0].id, "basic");
+        }
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 2);
+
+        // A failed load is shared, then forgotten so the next job retries it
+        let mut manager = manager;
+        manager.retry = RetryPolicy { max_attempts: 1, ..RetryPolicy::default() };
+        let fetches = (0..20).map(|_| manager.fetch_challenge_fixtures("broken", None));
+        for result in futures::future::join_all(fetches).await {
+            assert!(result.is_err());
+        }
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 3);
+        assert!(manager.fetch_challenge_fixtures("broken", None).await.is_err());
+        assert_eq!(requests.load(std::sync::atomic::Ordering::SeqCst), 4);
+        assert_eq!(manager.fixture_loads.len() + manager.hidden_loads.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_pinned_revision_without_template_fails() {
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(root.path().join("challenges/challenge-1/versions/v2")).unwrap();
+        let cache_dir = tempfile::tempdir().unwrap();
+        let source = Box::new(crate::fixture_source::LocalDirSource::new(root.path().to_path_buf()));
+        let manager = FixtureManager::with_source(source, cache_dir.path().to_string_lossy().to_string());
+
+        // Unpinned challenges without a template use the default project
+        assert_eq!(manager.fetch_project_template("challenge-1", None).await.unwrap(), (crate::templates::foundry_template_dir(), None));
+        assert_eq!(
+            manager.fetch_project_template("challenge-1", Some("v2")).await,
+            Err("No template for challenge challenge-1 at fixtures version v2".to_string())
+        );
+    }
+}
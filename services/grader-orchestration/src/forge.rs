@@ -0,0 +1,210 @@
+use serde_json::Value;
+
+/// A single test outcome reported by `forge test --json`.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ForgeTestResult {
+    pub suite: String,
+    pub name: String,
+    pub passed: bool,
+    pub reason: Option<String>,
+    pub gas_used: u64,
+}
+
+/// Parse the JSON document emitted by `forge test --json`.
+///
+/// The output is keyed by `path:Contract`, each holding a `test_results` map
+/// keyed by test signature (e.g. `testTransfer()`). Test names are reported
+/// without their parameter list.
+pub fn parse_forge_test_json(output: &str) -> Result<Vec<ForgeTestResult>, String> {
+    let mut results = Vec::new();
+
+    for (suite_name, signature, test) in forge_tests(&forge_json(output)?)? {
+        results.push(ForgeTestResult {
+            suite: suite_name.to_string(),
+            name: test_name(signature),
+            passed: test_passed(test),
+            reason: test_reason(test),
+            gas_used: extract_gas(test),
+        });
+    }
+
+    Ok(results)
+}
+
+/// A fuzz or invariant test outcome reported by `forge test --json`.
+#[derive(Clone, Debug)]
+pub struct ForgeFuzzResult {
+    pub name: String,
+    /// An `invariant_*` test rather than a fuzz test
+    pub invariant: bool,
+    pub runs: u64,
+    pub passed: bool,
+    pub reason: Option<String>,
+    /// The failing calldata (fuzz tests) or call sequence (invariant tests)
+    pub counterexample: Option<Value>,
+}
+
+/// Fuzz and invariant tests of a `forge test --json` document; unit tests
+/// are left out.
+pub fn parse_forge_fuzz_json(output: &str) -> Result<Vec<ForgeFuzzResult>, String> {
+    let mut results = Vec::new();
+
+    for (_, signature, test) in forge_tests(&forge_json(output)?)? {
+        let (invariant, stats) = match (test.pointer("/kind/Fuzz"), test.pointer("/kind/Invariant")) {
+            (Some(stats), _) => (false, stats),
+            (None, Some(stats)) => (true, stats),
+            (None, None) => continue,
+        };
+        results.push(ForgeFuzzResult {
+            name: test_name(signature),
+            invariant,
+            runs: stats.get("runs").and_then(|v| v.as_u64()).unwrap_or(0),
+            passed: test_passed(test),
+            reason: test_reason(test),
+            counterexample: test.get("counterexample").filter(|c| !c.is_null()).cloned(),
+        });
+    }
+
+    Ok(results)
+}
+
+fn forge_json(output: &str) -> Result<Value, String> {
+    // forge may print compiler progress before the JSON document
+    let json_start = output.find('{').ok_or("No JSON found in forge output")?;
+    serde_json::from_str(output[json_start..].trim())
+        .map_err(|e| format!("Failed to parse forge test JSON: {}", e))
+}
+
+/// Every test of the document as `(suite, signature, result)`.
+fn forge_tests(data: &Value) -> Result<Vec<(&str, &str, &Value)>, String> {
+    let suites = data.as_object().ok_or("Forge test output is not an object")?;
+    Ok(suites
+        .iter()
+        .filter_map(|(suite_name, suite)| suite.get("test_results").and_then(|v| v.as_object()).map(|tests| (suite_name, tests)))
+        .flat_map(|(suite_name, tests)| tests.iter().map(move |(signature, test)| (suite_name.as_str(), signature.as_str(), test)))
+        .collect())
+}
+
+fn test_name(signature: &str) -> String {
+    signature.split('(').next().unwrap_or(signature).to_string()
+}
+
+fn test_passed(test: &Value) -> bool {
+    // Newer forge versions report `status`, older ones `success`
+    match test.get("status").and_then(|v| v.as_str()) {
+        Some(status) => status == "Success",
+        None => test.get("success").and_then(|v| v.as_bool()).unwrap_or(false),
+    }
+}
+
+fn test_reason(test: &Value) -> Option<String> {
+    test.get("reason").and_then(|v| v.as_str()).map(|s| s.to_string())
+}
+
+/// Find the forge test that corresponds to a fixture, matching on the test
+/// function name against either the fixture id or its display name. Only
+/// suites from `test_files`, the challenge's own, are considered, so a
+/// submission defining a test of the same name can't stand in for it.
+pub fn find_test_for_fixture<'a>(
+    results: &'a [ForgeTestResult],
+    test_files: &[String],
+    fixture_id: &str,
+    fixture_name: &str,
+) -> Option<&'a ForgeTestResult> {
+    results
+        .iter()
+        .filter(|r| test_files.iter().any(|file| suite_path(&r.suite) == file))
+        .find(|r| r.name == fixture_id || r.name == fixture_name)
+}
+
+/// The source file of a `path:Contract` suite.
+fn suite_path(suite: &str) -> &str {
+    suite.rsplit_once(':').map(|(path, _)| path).unwrap_or(suite)
+}
+
+fn extract_gas(test: &Value) -> u64 {
+    let kind = match test.get("kind") {
+        Some(kind) => kind,
+        None => return test.get("gas").and_then(|v| v.as_u64()).unwrap_or(0),
+    };
+
+    if let Some(gas) = kind.get("Unit").and_then(|u| u.get("gas")).and_then(|v| v.as_u64()) {
+        return gas;
+    }
+
+    // Fuzz and invariant tests only report aggregate gas figures
+    kind.get("Fuzz")
+        .and_then(|f| f.get("median_gas").or_else(|| f.get("mean_gas")))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_forge_test_json() {
+        let output = r#"Compiling 2 files with 0.8.23
+{
+  "test/Contract.t.sol:ContractTest": {
+    "duration": "1ms",
+    "test_results": {
+      "testDeposit()": {
+        "status": "Success",
+        "reason": null,
+        "kind": { "Unit": { "gas": 31245 } }
+      },
+      "testWithdraw()": {
+        "status": "Failure",
+        "reason": "revert: insufficient balance",
+        "kind": { "Unit": { "gas": 12000 } }
+      },
+      "testFuzz_Amount(uint256)": {
+        "status": "Success",
+        "reason": null,
+        "kind": { "Fuzz": { "runs": 256, "mean_gas": 4100, "median_gas": 4000 } }
+      }
+    }
+  }
+}"#;
+
+        let results = parse_forge_test_json(output).unwrap();
+        assert_eq!(results.len(), 3);
+        let test_files = vec!["test/Contract.t.sol".to_string()];
+
+        let deposit = find_test_for_fixture(&results, &test_files, "testDeposit", "Deposit").unwrap();
+        assert!(deposit.passed);
+        assert_eq!(deposit.gas_used, 31245);
+
+        let withdraw = find_test_for_fixture(&results, &test_files, "w-1", "testWithdraw").unwrap();
+        assert!(!withdraw.passed);
+        assert_eq!(withdraw.reason.as_deref(), Some("revert: insufficient balance"));
+
+        let fuzz = find_test_for_fixture(&results, &test_files, "testFuzz_Amount", "").unwrap();
+        assert_eq!(fuzz.gas_used, 4000);
+
+        let fuzz = parse_forge_fuzz_json(output).unwrap();
+        assert_eq!(fuzz.len(), 1);
+        assert_eq!((fuzz[0].name.as_str(), fuzz[0].invariant, fuzz[0].runs, fuzz[0].passed), ("testFuzz_Amount", false, 256, true));
+    }
+
+    #[test]
+    fn test_submission_tests_do_not_match_fixtures() {
+        // The submission defines a passing test named like the challenge's failing one
+        let output = r#"{
+  "src/Contract.sol:Contract": {
+    "test_results": { "testWithdraw()": { "status": "Success", "kind": { "Unit": { "gas": 1 } } } }
+  },
+  "test/Contract.t.sol:ContractTest": {
+    "test_results": { "testWithdraw()": { "status": "Failure", "reason": "revert", "kind": { "Unit": { "gas": 2 } } } }
+  }
+}"#;
+        let results = parse_forge_test_json(output).unwrap();
+        let test_files = vec!["test/Contract.t.sol".to_string()];
+
+        let withdraw = find_test_for_fixture(&results, &test_files, "testWithdraw", "").unwrap();
+        assert!(!withdraw.passed);
+        assert!(find_test_for_fixture(&results, &[], "testWithdraw", "").is_none());
+    }
+}
@@ -0,0 +1,128 @@
+use crate::fuzzer::{FuzzCrash, SlowInput};
+use crate::invariants::InvariantViolation;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// Where interrupted fuzz campaigns are checkpointed, one file per job.
+/// Retried jobs keep their job id, so a worker that restarts or a job that
+/// is preempted resumes its campaign instead of starting a new one. A job
+/// redelivered to another worker only resumes if the directory is storage
+/// the workers share.
+pub struct CheckpointStore {
+    root: PathBuf,
+}
+
+impl CheckpointStore {
+    /// `FUZZ_CHECKPOINT_DIR`, by default `/var/lib/fathuss/fuzz_checkpoints`,
+    /// a volume of the worker image.
+    pub fn from_env() -> Self {
+        let root = std::env::var("FUZZ_CHECKPOINT_DIR").unwrap_or_else(|_| "/var/lib/fathuss/fuzz_checkpoints".to_string());
+        Self { root: PathBuf::from(root) }
+    }
+
+    /// The checkpoint of `job_id`'s campaign over the submission identified
+    /// by `fingerprint`.
+    pub fn for_job(&self, job_id: &str, fingerprint: &str) -> Checkpointer {
+        // Job ids come from the payload, so they never name the file directly
+        let file = format!("{}.json", hex::encode(Sha256::digest(job_id.as_bytes())));
+        Checkpointer { path: self.root.join(file), fingerprint: fingerprint.to_string() }
+    }
+}
+
+/// Progress of a campaign: the seed its inputs are generated from, how many
+/// of them have run, and everything found so far.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct CampaignState {
+    pub fingerprint: String,
+    pub seed: u64,
+    /// Inputs run so far, in generation order
+    pub next_input: usize,
+    /// Budget spent before the interruption
+    pub elapsed_ms: u64,
+    pub crashes: Vec<FuzzCrash>,
+    pub invariant_violations: Vec<InvariantViolation>,
+    pub hangs: Vec<Value>,
+    pub slowest_inputs: Vec<SlowInput>,
+    pub path_hashes: Vec<String>,
+    pub coverage: Vec<String>,
+}
+
+pub struct Checkpointer {
+    path: PathBuf,
+    fingerprint: String,
+}
+
+impl Checkpointer {
+    /// The checkpoint of one campaign over the submission. Its inputs are
+    /// generated from `campaign` (the fuzz config, fixtures and budget), so a
+    /// retry under a different one starts over.
+    pub fn for_campaign(&self, campaign: &Value) -> Checkpointer {
+        let fingerprint = hex::encode(Sha256::digest(format!("{}\n{}", self.fingerprint, campaign)));
+        Checkpointer { path: self.path.clone(), fingerprint }
+    }
+
+    /// The saved state, unless there is none or it belongs to a different
+    /// submission under the same job id.
+    pub fn load(&self) -> Option<CampaignState> {
+        let contents = std::fs::read(&self.path).ok()?;
+        match serde_json::from_slice::<CampaignState>(&contents) {
+            Ok(state) if state.fingerprint == self.fingerprint => Some(state),
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("Warning: Ignoring unreadable fuzz checkpoint {}: {}", self.path.display(), e);
+                None
+            },
+        }
+    }
+
+    /// Replace the saved state. Written to a temporary file first, so an
+    /// interruption mid-write leaves the previous checkpoint intact.
+    pub fn save(&self, state: CampaignState) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        let contents = serde_json::to_vec(&CampaignState { fingerprint: self.fingerprint.clone(), ..state })
+            .map_err(|e| format!("Failed to serialize fuzz checkpoint: {}", e))?;
+        let tmp = self.path.with_extension("tmp");
+        std::fs::write(&tmp, contents).map_err(|e| format!("Failed to write fuzz checkpoint: {}", e))?;
+        std::fs::rename(&tmp, &self.path).map_err(|e| format!("Failed to write fuzz checkpoint: {}", e))
+    }
+
+    /// Forget the campaign once it has finished.
+    pub fn clear(&self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_checkpoint_round_trip_per_submission() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = CheckpointStore { root: dir.path().to_path_buf() };
+        let checkpoint = store.for_job("job-1/../../etc", "code-a");
+        assert!(checkpoint.load().is_none());
+
+        checkpoint
+            .save(CampaignState { seed: 42, next_input: 75, elapsed_ms: 20_000, hangs: vec![json!([1])], ..Default::default() })
+            .unwrap();
+        assert!(checkpoint.path.starts_with(dir.path()));
+        let state = checkpoint.load().unwrap();
+        assert_eq!((state.seed, state.next_input, state.elapsed_ms, state.hangs.len()), (42, 75, 20_000, 1));
+
+        // A different submission or fuzz config retried under the same job id starts over
+        assert!(store.for_job("job-1/../../etc", "code-b").load().is_none());
+        let campaign = checkpoint.for_campaign(&json!({ "config": { "seed": 1 } }));
+        campaign.save(CampaignState { seed: 1, ..Default::default() }).unwrap();
+        assert!(checkpoint.for_campaign(&json!({ "config": { "seed": 2 } })).load().is_none());
+        assert!(checkpoint.for_campaign(&json!({ "config": { "seed": 1 } })).load().is_some());
+
+        checkpoint.clear();
+        assert!(checkpoint.load().is_none());
+    }
+}
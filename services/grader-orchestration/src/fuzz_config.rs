@@ -0,0 +1,355 @@
+use crate::cargo_fuzz;
+use crate::edge_cases::{self, EDGE_CASE_RATE};
+use crate::invariants::Invariant;
+use crate::sol_fuzz::{PropertyContract, SolidityFuzzEngine};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sha2::{Digest, Sha256};
+
+/// Longest generated string, array or object where the schema sets no bound.
+const MAX_GENERATED_LEN: usize = 16;
+/// Largest budget a challenge can ask for, by default.
+const MAX_ITERATIONS: usize = 10_000;
+const MAX_SECONDS: u64 = 600;
+
+/// The operator's bounds on a challenge's fuzzing budget, and the key fuzz
+/// seeds are derived with.
+#[derive(Clone, Debug)]
+pub struct FuzzLimits {
+    pub max_iterations: usize,
+    pub max_seconds: u64,
+    seed_key: String,
+}
+
+impl Default for FuzzLimits {
+    fn default() -> Self {
+        Self { max_iterations: MAX_ITERATIONS, max_seconds: MAX_SECONDS, seed_key: String::new() }
+    }
+}
+
+impl FuzzLimits {
+    /// `FUZZ_MAX_ITERATIONS` and `FUZZ_MAX_SECONDS` over the default caps, and
+    /// the seed key from `FUZZ_SEED_KEY`.
+    pub fn from_env() -> Result<Self, String> {
+        let defaults = Self::default();
+        let cap = |name: &str, default: u64| match std::env::var(name) {
+            Ok(value) => match value.parse::<u64>() {
+                Ok(cap) if cap > 0 => Ok(cap),
+                _ => Err(format!("Invalid {}: {}", name, value)),
+            },
+            Err(_) => Ok(default),
+        };
+        Ok(Self {
+            max_iterations: cap("FUZZ_MAX_ITERATIONS", defaults.max_iterations as u64)? as usize,
+            max_seconds: cap("FUZZ_MAX_SECONDS", defaults.max_seconds)?,
+            seed_key: std::env::var("FUZZ_SEED_KEY").unwrap_or_default(),
+        })
+    }
+
+    /// The seed of a job's campaign, derived from its challenge, language and
+    /// code so a submitter can't pick one that misses their bugs, while a
+    /// re-grade of the same code fuzzes the same inputs.
+    fn derive_seed(&self, payload: &Value) -> u64 {
+        let field = |name: &str| payload.get(name).and_then(|v| v.as_str()).unwrap_or("");
+        let digest = Sha256::digest(format!("{}\n{}\n{}\n{}", self.seed_key, field("challengeId"), field("language"), field("code")));
+        u64::from_le_bytes(digest[..8].try_into().unwrap())
+    }
+}
+
+/// How a fuzzed input reaches the program.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputDelivery {
+    /// A JSON file, named by the first argument
+    #[default]
+    File,
+    /// JSON on standard input
+    Stdin,
+    /// Typed arguments of the challenge's `functionSignature`, through the
+    /// generated harness
+    Harness,
+}
+
+/// Per-challenge fuzzing configuration, from the `fuzzing` object of the
+/// challenge config. Only an admin's job may override it, e.g. to pin the
+/// seed.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FuzzConfig {
+    /// Keywords, magic numbers and addresses spliced into mutated inputs
+    #[serde(default)]
+    pub dictionary: Vec<Value>,
+    /// JSON schema of the program's input; generated inputs and mutated
+    /// fields follow it so they stay structurally valid
+    #[serde(default)]
+    pub input_schema: Option<Value>,
+    /// Properties checked on every fuzzed run
+    #[serde(default)]
+    pub invariants: Vec<Invariant>,
+    /// Inputs to run, instead of the worker default (at most 10,000)
+    #[serde(default)]
+    pub max_iterations: Option<usize>,
+    /// Wall-clock budget, instead of `FUZZ_BUDGET_SECS` (at most 600)
+    #[serde(default)]
+    pub max_seconds: Option<u64>,
+    /// Seed of the campaign, which only admins set; otherwise derived from
+    /// the submission, see `FuzzLimits::derive_seed`
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub solidity_engine: SolidityFuzzEngine,
+    /// Property and fuzz test contracts for Solidity challenges
+    #[serde(default)]
+    pub property_contracts: Vec<PropertyContract>,
+    #[serde(default)]
+    pub input_delivery: InputDelivery,
+    /// Function of a Rust submission taking `&[u8]`, fuzzed with cargo-fuzz
+    /// instead of through JSON input files
+    #[serde(default)]
+    pub rust_entry_point: Option<String>,
+}
+
+impl FuzzConfig {
+    pub fn from_payload(payload: &Value, limits: &FuzzLimits) -> Result<Self, String> {
+        let config: Self = match payload.get("fuzzing") {
+            None | Some(Value::Null) => Self::default(),
+            Some(fuzzing) => serde_json::from_value(fuzzing.clone()).map_err(|e| format!("Invalid fuzzing config: {}", e))?,
+        };
+        if let Some(token) = config.dictionary.iter().find(|t| !t.is_string() && !t.is_number()) {
+            return Err(format!("Invalid fuzzing dictionary entry {}: expected a string or number", token));
+        }
+        for invariant in &config.invariants {
+            invariant.validate()?;
+        }
+        for contract in &config.property_contracts {
+            contract.validate()?;
+        }
+        if config.input_delivery == InputDelivery::Harness && payload.get("functionSignature").map_or(true, |s| s.is_null()) {
+            return Err("Invalid fuzzing config: harness input delivery needs a functionSignature".to_string());
+        }
+        if let Some(entry_point) = &config.rust_entry_point {
+            cargo_fuzz::validate_entry_point(entry_point)?;
+        }
+        if config.max_iterations == Some(0) || config.max_seconds == Some(0) {
+            return Err("Invalid fuzzing config: maxIterations and maxSeconds must be positive".to_string());
+        }
+        Ok(Self {
+            max_iterations: config.max_iterations.map(|n| n.min(limits.max_iterations)),
+            max_seconds: config.max_seconds.map(|s| s.min(limits.max_seconds)),
+            seed: Some(config.seed.unwrap_or_else(|| limits.derive_seed(payload))),
+            ..config
+        })
+    }
+
+    pub fn string_token(&self, rng: &mut StdRng) -> Option<String> {
+        let strings: Vec<&str> = self.dictionary.iter().filter_map(|t| t.as_str()).collect();
+        strings.choose(rng).map(|s| s.to_string())
+    }
+
+    pub fn number_token(&self, rng: &mut StdRng) -> Option<Value> {
+        let numbers: Vec<&Value> = self.dictionary.iter().filter(|t| t.is_number()).collect();
+        numbers.choose(rng).map(|n| (*n).clone())
+    }
+
+    /// A random value valid against `schema`, favouring the edge cases of its
+    /// type and dictionary tokens. Supports `const`, `enum`, `type` (or a list of
+    /// types), numeric and length bounds, `items`, `properties` and `required`.
+    /// Past `edge_cases::MAX_INPUT_BYTES`, strings and arrays shrink to their
+    /// schema's minimum length.
+    pub fn generate(&self, schema: &Value, rng: &mut StdRng) -> Value {
+        let mut budget = edge_cases::MAX_INPUT_BYTES;
+        self.generate_within(schema, rng, &mut budget)
+    }
+
+    fn generate_within(&self, schema: &Value, rng: &mut StdRng, budget: &mut usize) -> Value {
+        if let Some(value) = schema.get("const") {
+            return value.clone();
+        }
+        if let Some(option) = schema.get("enum").and_then(|v| v.as_array()).and_then(|options| options.choose(rng)) {
+            return option.clone();
+        }
+
+        let type_name = match schema.get("type") {
+            Some(Value::String(t)) => t.as_str(),
+            Some(Value::Array(types)) => types.choose(rng).and_then(|t| t.as_str()).unwrap_or("null"),
+            _ if schema.get("properties").is_some() => "object",
+            _ if schema.get("items").is_some() => "array",
+            // An untyped schema accepts anything
+            _ if rng.gen_bool(EDGE_CASE_RATE) => {
+                let value = edge_cases::any(rng, *budget);
+                *budget = budget.saturating_sub(value.to_string().len());
+                return value;
+            },
+            _ => "string",
+        };
+        match type_name {
+            "boolean" => json!(rng.gen::<bool>()),
+            "integer" => self.integer(schema, rng),
+            "number" => self.number(schema, rng),
+            "string" => {
+                let string = self.string(schema, rng, *budget);
+                *budget = budget.saturating_sub(string.len());
+                json!(string)
+            },
+            "array" => {
+                let items = schema.get("items").cloned().unwrap_or_else(|| json!({}));
+                let len = self.length(schema, "minItems", "maxItems", rng, *budget);
+                // Every item costs at least its separator
+                *budget = budget.saturating_sub(len);
+                Value::Array((0..len).map(|_| self.generate_within(&items, rng, budget)).collect())
+            },
+            "object" => {
+                let required: Vec<&str> = schema
+                    .get("required")
+                    .and_then(|v| v.as_array())
+                    .map(|r| r.iter().filter_map(|k| k.as_str()).collect())
+                    .unwrap_or_default();
+                let mut object = Map::new();
+                for (key, property) in schema.get("properties").and_then(|v| v.as_object()).into_iter().flatten() {
+                    // Optional properties are left out half the time
+                    if required.contains(&key.as_str()) || rng.gen_bool(0.5) {
+                        object.insert(key.clone(), self.generate_within(property, rng, budget));
+                    }
+                }
+                Value::Object(object)
+            },
+            _ => Value::Null,
+        }
+    }
+
+    fn integer(&self, schema: &Value, rng: &mut StdRng) -> Value {
+        let lo = schema.get("minimum").and_then(|v| v.as_i64()).unwrap_or(i64::MIN);
+        let hi = schema.get("maximum").and_then(|v| v.as_i64()).unwrap_or(i64::MAX).max(lo);
+        let in_range = |n: i64| (lo..=hi).contains(&n);
+
+        if rng.gen_bool(EDGE_CASE_RATE) {
+            let mut boundaries = edge_cases::integers(lo, hi);
+            boundaries.extend(self.dictionary.iter().filter_map(|t| t.as_i64()).filter(|n| in_range(*n)));
+            if let Some(n) = boundaries.choose(rng) {
+                return json!(n);
+            }
+        }
+        json!(rng.gen_range(lo..=hi))
+    }
+
+    fn number(&self, schema: &Value, rng: &mut StdRng) -> Value {
+        let lo = schema.get("minimum").and_then(|v| v.as_f64()).unwrap_or(-1e9);
+        let hi = schema.get("maximum").and_then(|v| v.as_f64()).unwrap_or(1e9).max(lo);
+        let in_range = |n: f64| n >= lo && n <= hi;
+
+        if rng.gen_bool(EDGE_CASE_RATE) {
+            let mut boundaries = edge_cases::numbers(lo, hi);
+            boundaries.extend(self.dictionary.iter().filter_map(|t| t.as_f64()).filter(|n| in_range(*n)));
+            if let Some(n) = boundaries.choose(rng) {
+                return json!(n);
+            }
+        }
+        json!(rng.gen_range(lo..=hi))
+    }
+
+    /// A string within the schema's bounds, no longer than `budget` bytes
+    /// unless its minimum length needs more.
+    fn string(&self, schema: &Value, rng: &mut StdRng, budget: usize) -> String {
+        let min = schema.get("minLength").and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let max = schema.get("maxLength").and_then(|v| v.as_u64()).map_or(usize::MAX, |m| m as usize).max(min);
+        // Characters take up to four bytes
+        let max = max.min((budget / 4).max(min));
+        if rng.gen_bool(0.5) {
+            if let Some(token) = self.string_token(rng).filter(|t| (min..=max).contains(&t.chars().count())) {
+                return token;
+            }
+        }
+        if rng.gen_bool(EDGE_CASE_RATE) {
+            if let Some(edge_case) = edge_cases::strings(min, max).choose(rng) {
+                return edge_case.clone();
+            }
+        }
+        let len = rng.gen_range(min..=max.min(min.max(MAX_GENERATED_LEN)));
+        random_string(rng, len)
+    }
+
+    /// An array length within the schema's bounds: one of the edge cases,
+    /// which may be very long when unbounded, or a short random one. No
+    /// longer than `budget` unless the minimum needs more.
+    fn length(&self, schema: &Value, min_key: &str, max_key: &str, rng: &mut StdRng, budget: usize) -> usize {
+        let min = schema.get(min_key).and_then(|v| v.as_u64()).unwrap_or(0) as usize;
+        let max = schema.get(max_key).and_then(|v| v.as_u64()).map_or(usize::MAX, |m| m as usize).max(min).min(budget.max(min));
+        if rng.gen_bool(EDGE_CASE_RATE) {
+            if let Some(len) = edge_cases::array_lengths(min, max).choose(rng) {
+                return *len;
+            }
+        }
+        rng.gen_range(min..=max.min(min.max(MAX_GENERATED_LEN)))
+    }
+}
+
+/// Mostly printable ASCII, so string inputs survive parsing, with the
+/// occasional arbitrary character to exercise encoding handling.
+pub fn random_char(rng: &mut StdRng) -> char {
+    match rng.gen_ratio(1, 20) {
+        true => rng.gen::<char>(),
+        false => rng.gen_range(' '..='~'),
+    }
+}
+
+pub fn random_string(rng: &mut StdRng, len: usize) -> String {
+    (0..len).map(|_| random_char(rng)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_inputs_follow_schema() {
+        let limits = FuzzLimits::default();
+        let config = FuzzConfig::from_payload(&json!({
+            "fuzzing": {
+                "dictionary": ["transfer", "0xdeadbeef", 255],
+                "inputSchema": {
+                    "type": "object",
+                    "required": ["op", "amount", "to"],
+                    "properties": {
+                        "op": { "enum": ["transfer", "mint"] },
+                        "amount": { "type": "integer", "minimum": 0, "maximum": 1000 },
+                        "to": { "type": "string", "minLength": 10, "maxLength": 10 },
+                        "tags": { "type": "array", "items": { "type": "boolean" }, "maxItems": 3 }
+                    }
+                }
+            }
+        }), &limits)
+        .unwrap();
+        let schema = config.input_schema.clone().unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..200 {
+            let input = config.generate(&schema, &mut rng);
+            assert!(["transfer", "mint"].contains(&input["op"].as_str().unwrap()));
+            assert!((0..=1000).contains(&input["amount"].as_i64().unwrap()));
+            assert_eq!(input["to"].as_str().unwrap().chars().count(), 10);
+            assert!(input.get("tags").map_or(true, |t| t.as_array().unwrap().len() <= 3));
+        }
+        // Long arrays of huge strings stay within the edge case budget
+        let unbounded = json!({ "type": "array", "items": { "type": "array", "items": { "type": "string" } } });
+        for _ in 0..50 {
+            let input = config.generate(&unbounded, &mut rng);
+            assert!(input.to_string().len() < 2 * edge_cases::MAX_INPUT_BYTES);
+        }
+
+        assert!(FuzzConfig::from_payload(&json!({ "fuzzing": { "dictionary": [[1]] } }), &limits).is_err());
+        assert!(FuzzConfig::from_payload(&json!({ "fuzzing": { "maxSeconds": 0 } }), &limits).is_err());
+        assert!(FuzzConfig::from_payload(&json!({ "fuzzing": { "inputDelivery": "harness" } }), &limits).is_err());
+        let budget = FuzzConfig::from_payload(&json!({ "fuzzing": { "maxIterations": 1000000, "seed": 42 } }), &limits).unwrap();
+        assert_eq!((budget.max_iterations, budget.seed), (Some(MAX_ITERATIONS), Some(42)));
+        let capped = FuzzLimits { max_iterations: 500, ..FuzzLimits::default() };
+        let budget = FuzzConfig::from_payload(&json!({ "fuzzing": { "maxIterations": 1000 } }), &capped).unwrap();
+        assert_eq!(budget.max_iterations, Some(500));
+
+        // Without a seed, the same code always gets the same one, and other code another
+        let seed = |code: &str| FuzzConfig::from_payload(&json!({ "code": code, "language": "python" }), &limits).unwrap().seed;
+        assert_eq!(seed("print(1)"), seed("print(1)"));
+        assert_ne!(seed("print(1)"), seed("print(2)"));
+        let keyed = FuzzLimits { seed_key: "k".to_string(), ..FuzzLimits::default() };
+        assert_ne!(seed("print(1)"), FuzzConfig::from_payload(&json!({ "code": "print(1)", "language": "python" }), &keyed).unwrap().seed);
+    }
+}
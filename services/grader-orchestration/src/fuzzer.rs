@@ -1,5 +1,15 @@
+use crate::cargo_fuzz;
+use crate::crash_severity;
+use crate::crash_signature;
+use crate::edge_cases::{self, EDGE_CASE_RATE};
 use crate::fixtures::TestFixture;
-use crate::sandbox::{execute_in_sandbox, SandboxConfig, ExecutionResult};
+use crate::fuzz_checkpoint::{CampaignState, Checkpointer};
+use crate::fuzz_config::{self, FuzzConfig, InputDelivery};
+use crate::harness::{self, FunctionSignature};
+use crate::invariants::{self, CheckerDir, InvariantViolation};
+use crate::sol_fuzz;
+use futures::StreamExt;
+use crate::sandbox::{execute_in_sandbox_with_env, execute_in_sandbox_with_stdin, SandboxConfig, ExecutionResult, TIMEOUT_ERROR};
 use serde_json::{json, Value};
 use std::collections::HashSet;
 use std::path::Path;
@@ -9,34 +19,99 @@ use sha2::{Digest, Sha256};
 
 pub struct FuzzResult {
     pub inputs_tested: usize,
+    /// One entry per distinct crash signature
     pub crashes_found: Vec<FuzzCrash>,
+    /// One entry per broken invariant
+    pub invariant_violations: Vec<InvariantViolation>,
+    /// Inputs that ran into the per-input time limit, likely infinite loops
+    pub hangs: Vec<Value>,
+    /// The slowest runs, hung ones included, slowest first
+    pub slowest_inputs: Vec<SlowInput>,
+    /// Seed the inputs were generated from; `None` when fuzzing was skipped
+    pub seed: Option<u64>,
     pub unique_paths: usize,
     pub coverage_score: f64,
     pub execution_time: Duration,
+    /// Why the campaign couldn't run, e.g. a fuzz target that didn't build
+    pub error: Option<String>,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 pub struct FuzzCrash {
     pub input: Value,
     pub error_message: String,
     pub stack_trace: String,
     pub gas_used: u64,
     pub severity: CrashSeverity,
+    /// See `crash_signature::signature`
+    pub signature: String,
+    /// Fuzz inputs that hit this crash; `input` is the first of them
+    pub occurrences: usize,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+impl FuzzResult {
+    /// Crashing inputs, counting repeats of the same crash.
+    pub fn crash_occurrences(&self) -> usize {
+        self.crashes_found.iter().map(|c| c.occurrences).sum()
+    }
+}
+
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SlowInput {
+    pub input: Value,
+    pub wall_time_ms: u64,
+    /// Stopped at the time limit rather than finished
+    pub hung: bool,
+}
+
+/// How far a campaign has got, as reported while it runs.
+#[derive(Clone, Debug, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FuzzProgress {
+    pub inputs_tested: usize,
+    pub total_inputs: usize,
+    pub crashes: usize,
+    pub hangs: usize,
+}
+
+/// Slow inputs kept for the report.
+const SLOWEST_INPUTS: usize = 5;
+
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum CrashSeverity {
+    // The aliases read fuzz checkpoints saved before the names were lowercase
+    #[serde(alias = "Low")]
     Low,
+    #[serde(alias = "Medium")]
     Medium,
+    #[serde(alias = "High")]
     High,
+    #[serde(alias = "Critical")]
     Critical,
 }
 
+/// Sandboxes running fuzz inputs at once, by default.
+const DEFAULT_CONCURRENCY: usize = 4;
+/// Wall-clock budget of a whole campaign, by default.
+const DEFAULT_BUDGET: Duration = Duration::from_secs(60);
+/// Inputs run between checkpoints of the campaign state.
+const CHECKPOINT_INTERVAL: usize = 25;
+/// Least time between progress reports of a campaign.
+const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
 pub struct Fuzzer {
     max_iterations: usize,
     timeout_per_test: Duration,
     max_input_size: usize,
     seed: u64,
+    config: FuzzConfig,
+    concurrency: usize,
+    budget: Duration,
+    signature: Option<FunctionSignature>,
+    checkpoint: Option<Checkpointer>,
+    checkers: Option<CheckerDir>,
 }
 
 impl Fuzzer {
@@ -46,35 +121,161 @@ impl Fuzzer {
             timeout_per_test,
             max_input_size: 1024, // 1KB max input
             seed: rand::random(),
+            config: FuzzConfig::default(),
+            concurrency: DEFAULT_CONCURRENCY,
+            budget: DEFAULT_BUDGET,
+            signature: None,
+            checkpoint: None,
+            checkers: None,
+        }
+    }
+
+    /// `new`, with `FUZZ_CONCURRENCY` and `FUZZ_BUDGET_SECS` over the default
+    /// pool size and campaign budget.
+    pub fn from_env(max_iterations: usize, timeout_per_test: Duration) -> Self {
+        let mut fuzzer = Self::new(max_iterations, timeout_per_test);
+        if let Some(concurrency) = std::env::var("FUZZ_CONCURRENCY").ok().and_then(|v| v.parse::<usize>().ok()) {
+            fuzzer.concurrency = concurrency.max(1);
+        }
+        if let Some(budget) = std::env::var("FUZZ_BUDGET_SECS").ok().and_then(|v| v.parse().ok()) {
+            fuzzer.budget = Duration::from_secs(budget);
+        }
+        fuzzer
+    }
+
+    /// Mutate and generate inputs with the challenge's dictionary and input
+    /// schema, within its budget and from its seed where it sets them.
+    pub fn with_config(mut self, config: FuzzConfig) -> Self {
+        if let Some(max_iterations) = config.max_iterations {
+            self.max_iterations = max_iterations;
+        }
+        if let Some(max_seconds) = config.max_seconds {
+            self.budget = Duration::from_secs(max_seconds);
+        }
+        if let Some(seed) = config.seed {
+            self.seed = seed;
+        }
+        self.config = config;
+        self
+    }
+
+    /// The graded function, whose parameter types shape inputs delivered
+    /// through the harness.
+    pub fn with_signature(mut self, signature: Option<&FunctionSignature>) -> Self {
+        self.signature = signature.cloned();
+        self
+    }
+
+    /// Checkpoint the campaign as it runs, resuming from an earlier
+    /// checkpoint of the same job if there is one.
+    pub fn with_checkpoint(mut self, checkpoint: Checkpointer) -> Self {
+        self.checkpoint = Some(checkpoint);
+        self
+    }
+
+    /// Run invariant checkers from `checkers` rather than the workspace.
+    pub fn with_checkers(mut self, checkers: Option<CheckerDir>) -> Self {
+        self.checkers = checkers;
+        self
+    }
+
+    /// The challenge's input schema, or for harness delivery without one,
+    /// the schema of the function's parameters.
+    fn input_schema(&self) -> Option<Value> {
+        match (&self.config.input_schema, self.config.input_delivery, &self.signature) {
+            (Some(schema), _, _) => Some(schema.clone()),
+            (None, InputDelivery::Harness, Some(signature)) => Some(harness::input_schema(signature)),
+            _ => None,
         }
     }
 
+    /// Solidity contracts never read JSON input files, so their campaign runs
+    /// in forge's fuzzer or Echidna instead, with the same budget and seed.
+    pub async fn run_solidity_campaign(&self, working_dir: &Path) -> Result<FuzzResult, String> {
+        sol_fuzz::run_campaign(&self.config, working_dir, self.max_iterations, self.seed, self.budget).await
+    }
+
+    /// Rust challenges declaring a fuzz entry point are fuzzed in-process by
+    /// libFuzzer, which runs far more inputs than one sandbox per input.
+    pub async fn run_rust_campaign(&self, working_dir: &Path) -> Result<FuzzResult, String> {
+        let entry_point = self.config.rust_entry_point.as_deref().ok_or("No Rust fuzz entry point configured")?;
+        cargo_fuzz::run_campaign(entry_point, working_dir, self.max_iterations, self.seed, self.budget).await
+    }
+
+    /// Fuzz the compiled solution with inputs derived from the fixtures,
+    /// calling `on_progress` every `PROGRESS_INTERVAL` while it runs.
     pub async fn run_fuzz_campaign(
         &self,
         base_fixtures: &[TestFixture],
         working_dir: &Path,
         compile_command: &str,
         run_command: &str,
+        on_progress: &(dyn Fn(&FuzzProgress) + Sync),
     ) -> Result<FuzzResult, String> {
-        let start_time = std::time::Instant::now();
-        let mut rng = StdRng::seed_from_u64(self.seed);
+        // The checkpoint only resumes a campaign generating the same inputs
+        let campaign = json!({
+            "config": self.config,
+            "signature": self.signature,
+            "maxIterations": self.max_iterations,
+            "fixtures": base_fixtures.iter().map(|f| &f.input).collect::<Vec<_>>(),
+        });
+        let checkpoint = self.checkpoint.as_ref().map(|c| c.for_campaign(&campaign));
+        let result = self
+            .run_checkpointed_campaign(base_fixtures, working_dir, compile_command, run_command, on_progress, checkpoint.as_ref())
+            .await;
+        // Only an interrupted campaign is resumed, not a finished or failed one
+        if let Some(checkpoint) = &checkpoint {
+            checkpoint.clear();
+        }
+        result
+    }
 
-        let mut inputs_tested = 0;
-        let mut crashes_found = Vec::new();
-        let mut unique_paths = HashSet::new();
-        let mut coverage_data = HashSet::new();
+    async fn run_checkpointed_campaign(
+        &self,
+        base_fixtures: &[TestFixture],
+        working_dir: &Path,
+        compile_command: &str,
+        run_command: &str,
+        on_progress: &(dyn Fn(&FuzzProgress) + Sync),
+        checkpoint: Option<&Checkpointer>,
+    ) -> Result<FuzzResult, String> {
+        let start_time = std::time::Instant::now();
+        // A resumed campaign regenerates the same inputs from its saved seed
+        // and skips those that already ran
+        let resumed = checkpoint.and_then(|c| c.load()).unwrap_or_default();
+        let seed = if resumed.next_input > 0 { resumed.seed } else { self.seed };
+        let spent = Duration::from_millis(resumed.elapsed_ms);
+        if resumed.next_input > 0 {
+            println!("Resuming fuzzing campaign after {} inputs ({}s spent)", resumed.next_input, spent.as_secs());
+        }
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut inputs_tested = resumed.next_input;
+        let mut crashes_found = resumed.crashes;
+        let mut invariant_violations = resumed.invariant_violations;
+        let mut hangs = resumed.hangs;
+        let mut slowest_inputs = resumed.slowest_inputs;
+        let mut unique_paths: HashSet<String> = resumed.path_hashes.into_iter().collect();
+        let mut coverage_data: HashSet<String> = resumed.coverage.into_iter().collect();
+        let mut rejected_inputs = 0;
+        let schema = self.input_schema();
 
         // Generate fuzz inputs based on base fixtures
         let mut fuzz_inputs = Vec::new();
         for fixture in base_fixtures {
             // Generate variations of each base input
-            let variations = self.generate_input_variations(&fixture.input, 10, &mut rng);
+            let variations = self.generate_input_variations(&fixture.input, schema.as_ref(), 10, &mut rng);
             fuzz_inputs.extend(variations);
         }
 
-        // Add some completely random inputs
-        for _ in 0..50 {
-            fuzz_inputs.push(self.generate_random_input(&mut rng));
+        // Add some completely random inputs, structurally valid when the challenge has an input schema,
+        // enough to fill the iteration budget
+        for _ in 0..self.max_iterations.saturating_sub(fuzz_inputs.len()).max(50) {
+            let input = match &schema {
+                Some(schema) => self.config.generate(schema, &mut rng),
+                None => self.generate_random_input(&mut rng),
+            };
+            fuzz_inputs.push(input);
         }
 
         // Shuffle the inputs for better coverage
@@ -82,39 +283,31 @@ impl Fuzzer {
 
         // Limit to max_iterations
         let test_inputs = fuzz_inputs.into_iter().take(self.max_iterations).collect::<Vec<_>>();
-
-        for input in test_inputs {
+        let total_inputs = test_inputs.len();
+
+        // Inputs run on a bounded pool of sandboxes. Results come back in
+        // input order, so with a fixed seed the report is reproducible; inputs
+        // not started before the budget runs out are skipped.
+        let deadline = start_time + self.budget.saturating_sub(spent);
+        let runs = futures::stream::iter(test_inputs.into_iter().enumerate().skip(inputs_tested))
+            .take_while(|_| futures::future::ready(std::time::Instant::now() < deadline))
+            .map(|(index, input)| async move {
+                let run = self.run_input(&input, index + 1, working_dir, run_command).await;
+                (input, run)
+            })
+            .buffered(self.concurrency);
+        futures::pin_mut!(runs);
+        let mut last_progress = std::time::Instant::now();
+
+        while let Some((input, run)) = runs.next().await {
             inputs_tested += 1;
+            let (result, violations) = run?;
 
-            // Create a unique test file for this input
-            let test_file = format!("fuzz_test_{}.json", inputs_tested);
-            let test_path = working_dir.join(&test_file);
-
-            // Write the fuzz input to file
-            let input_json = serde_json::to_string_pretty(&input)
-                .map_err(|e| format!("Failed to serialize fuzz input: {}", e))?;
-
-            tokio::fs::write(&test_path, &input_json)
-                .await
-                .map_err(|e| format!("Failed to write fuzz test file: {}", e))?;
-
-            // Execute the test
-            let sandbox_config = SandboxConfig {
-                time_limit: self.timeout_per_test,
-                memory_limit: 256 * 1024 * 1024, // 256MB for fuzzing
-                cpu_limit: 25, // 25% CPU
-                network_disabled: true,
-                max_file_size: 1024 * 1024, // 1MB
-                max_processes: 5,
-                disk_quota: 10 * 1024 * 1024, // 10MB for fuzzing
+            let (wall_time, hung) = match &result {
+                Ok(exec_result) => (exec_result.execution_time, false),
+                Err(e) => (self.timeout_per_test, e == TIMEOUT_ERROR),
             };
-
-            let result = execute_in_sandbox(
-                run_command,
-                &[&test_file],
-                &sandbox_config,
-                working_dir,
-            ).await;
+            record_slow_input(&mut slowest_inputs, &input, wall_time.as_millis() as u64, hung);
 
             // Analyze the result
             match result {
@@ -128,56 +321,205 @@ impl Fuzzer {
 
                     // Check for crashes
                     if !exec_result.success && exec_result.exit_code != Some(0) {
-                        let crash = self.analyze_crash(&input, &exec_result);
-                        if let Some(crash) = crash {
-                            crashes_found.push(crash);
+                        // A mutated input the harness can't deserialize never reached the solution
+                        if self.config.input_delivery == InputDelivery::Harness && exec_result.exit_code == Some(harness::INVALID_ARGUMENT_EXIT_CODE) {
+                            rejected_inputs += 1;
+                        } else if let Some(crash) = self.analyze_crash(&input, &exec_result) {
+                            record_crash(&mut crashes_found, crash);
                         }
                     }
+                    for (invariant, message) in violations {
+                        invariants::record_violation(&mut invariant_violations, &invariant, Some(&input), message);
+                    }
                 },
+                // Running out of time is a hang, not a crash
+                Err(e) if e == TIMEOUT_ERROR => hangs.push(input),
                 Err(e) => {
                     // Execution failed - this might be a crash
+                    let stack_trace = "Execution failed in sandbox".to_string();
                     let crash = FuzzCrash {
                         input: input.clone(),
+                        signature: crash_signature::signature(&e, &stack_trace),
                         error_message: e,
-                        stack_trace: "Execution failed in sandbox".to_string(),
+                        stack_trace,
                         gas_used: 0,
                         severity: CrashSeverity::Medium,
+                        occurrences: 1,
                     };
-                    crashes_found.push(crash);
+                    record_crash(&mut crashes_found, crash);
                 }
             }
 
-            // Clean up test file
-            let _ = tokio::fs::remove_file(&test_path).await;
+            if let (Some(checkpoint), 0) = (checkpoint, inputs_tested % CHECKPOINT_INTERVAL) {
+                let state = CampaignState {
+                    seed,
+                    next_input: inputs_tested,
+                    elapsed_ms: (spent + start_time.elapsed()).as_millis() as u64,
+                    crashes: crashes_found.clone(),
+                    invariant_violations: invariant_violations.clone(),
+                    hangs: hangs.clone(),
+                    slowest_inputs: slowest_inputs.clone(),
+                    path_hashes: unique_paths.iter().cloned().collect(),
+                    coverage: coverage_data.iter().cloned().collect(),
+                    ..Default::default()
+                };
+                if let Err(e) = checkpoint.save(state) {
+                    eprintln!("Warning: Failed to checkpoint fuzzing campaign: {}", e);
+                }
+            }
+            if last_progress.elapsed() >= PROGRESS_INTERVAL {
+                on_progress(&FuzzProgress {
+                    inputs_tested,
+                    total_inputs,
+                    crashes: crashes_found.len(),
+                    hangs: hangs.len(),
+                });
+                last_progress = std::time::Instant::now();
+            }
+        }
+        if rejected_inputs > 0 {
+            println!("{} fuzz inputs didn't fit the function signature and were skipped", rejected_inputs);
+        }
+        if inputs_tested < self.max_iterations.min(total_inputs) {
+            println!("Fuzzing budget of {}s spent after {} inputs", self.budget.as_secs(), inputs_tested);
         }
 
-        let execution_time = start_time.elapsed();
+        let execution_time = spent + start_time.elapsed();
         let coverage_score = self.calculate_coverage_score(&coverage_data);
 
         Ok(FuzzResult {
             inputs_tested,
             crashes_found,
+            invariant_violations,
+            hangs,
+            slowest_inputs,
+            seed: Some(seed),
             unique_paths: unique_paths.len(),
             coverage_score,
             execution_time,
+            error: None,
         })
     }
 
-    fn generate_input_variations(&self, base_input: &Value, count: usize, rng: &mut StdRng) -> Vec<Value> {
+    /// Run one input in its own sandbox, then the challenge's checker scripts
+    /// if it didn't crash. Returns the run and the invariants it broke.
+    async fn run_input(
+        &self,
+        input: &Value,
+        number: usize,
+        working_dir: &Path,
+        run_command: &str,
+    ) -> Result<(Result<ExecutionResult, String>, Vec<(String, String)>), String> {
+        // Create a unique test file for this input
+        let test_file = format!("fuzz_test_{}.json", number);
+        let test_path = working_dir.join(&test_file);
+
+        // Write the fuzz input to file
+        let input_json = serde_json::to_string_pretty(input)
+            .map_err(|e| format!("Failed to serialize fuzz input: {}", e))?;
+
+        tokio::fs::write(&test_path, &input_json)
+            .await
+            .map_err(|e| format!("Failed to write fuzz test file: {}", e))?;
+
+        // Execute the test
+        let sandbox_config = SandboxConfig {
+            time_limit: self.timeout_per_test,
+            memory_limit: 256 * 1024 * 1024, // 256MB for fuzzing
+            cpu_limit: 25, // 25% CPU
+            network_disabled: true,
+            max_file_size: 1024 * 1024, // 1MB
+            max_processes: 5,
+            disk_quota: 10 * 1024 * 1024, // 10MB for fuzzing
+        };
+
+        // Rust panics print their backtrace, which crash signatures are made of
+        let env = [("RUST_BACKTRACE".to_string(), "1".to_string())];
+        // The file is written either way, for the invariant checkers
+        let result = match self.config.input_delivery {
+            InputDelivery::Stdin => execute_in_sandbox_with_stdin(run_command, &[], &env, input_json.as_bytes(), &sandbox_config, working_dir).await,
+            InputDelivery::File | InputDelivery::Harness => execute_in_sandbox_with_env(
+                run_command,
+                &[&test_file],
+                &env,
+                &sandbox_config,
+                working_dir,
+            ).await,
+        };
+
+        let violations = match &result {
+            Ok(exec_result) if exec_result.success || exec_result.exit_code == Some(0) => {
+                self.check_invariants(&test_file, &exec_result.stdout, working_dir).await
+            },
+            _ => Ok(vec![]),
+        };
+
+        // Clean up test file
+        let _ = tokio::fs::remove_file(&test_path).await;
+        Ok((result, violations?))
+    }
+
+    /// Run the challenge's checker scripts against one run's input and stdout,
+    /// returning `(invariant, message)` for each one broken.
+    async fn check_invariants(&self, input_file: &str, stdout: &str, working_dir: &Path) -> Result<Vec<(String, String)>, String> {
+        let mut violations = Vec::new();
+        let checks: Vec<_> = self.config.invariants.iter().filter_map(|i| i.check.as_ref().map(|c| (&i.name, c))).collect();
+        if checks.is_empty() {
+            return Ok(violations);
+        }
+
+        let checkers = match &self.checkers {
+            Some(checkers) => checkers,
+            None => return Err("Invariant checkers need a checker directory".to_string()),
+        };
+        // Checkers get their own copy of the input, which a concurrent run
+        // in the workspace can't rewrite
+        let (input_copy, output_file) = (checkers.run_file(input_file), checkers.run_file(&format!("{}.out", input_file)));
+        tokio::fs::copy(working_dir.join(input_file), &input_copy)
+            .await
+            .map_err(|e| format!("Failed to copy fuzz input file: {}", e))?;
+        tokio::fs::write(&output_file, stdout)
+            .await
+            .map_err(|e| format!("Failed to write fuzz output file: {}", e))?;
+        for (name, command) in checks {
+            match invariants::check(command, &input_copy, &output_file, checkers).await {
+                Ok(Some(message)) => violations.push((name.clone(), message)),
+                Ok(None) => {},
+                // A checker that can't run doesn't vouch for the input
+                Err(e) => violations.push((name.clone(), format!("Invariant checker failed: {}", e))),
+            }
+        }
+        let _ = tokio::fs::remove_file(&input_copy).await;
+        let _ = tokio::fs::remove_file(&output_file).await;
+        Ok(violations)
+    }
+
+    /// Variations of `base_input`, each changing one element. With a schema,
+    /// replaced array items and object fields are generated from their own
+    /// schema so the rest of the input stays valid.
+    fn generate_input_variations(&self, base_input: &Value, schema: Option<&Value>, count: usize, rng: &mut StdRng) -> Vec<Value> {
         let mut variations = Vec::new();
 
         for _ in 0..count {
             let variation = match base_input {
-                Value::Number(n) => {
-                    let base = n.as_f64().unwrap_or(0.0);
-                    let delta = rng.gen_range(-100.0..100.0);
-                    json!(base + delta)
+                Value::Number(n) => match self.config.number_token(rng).filter(|_| rng.gen_bool(0.3)) {
+                    Some(token) => token,
+                    None => {
+                        let base = n.as_f64().unwrap_or(0.0);
+                        let delta = rng.gen_range(-100.0..100.0);
+                        json!(base + delta)
+                    },
                 },
                 Value::String(s) => {
                     let mut chars: Vec<char> = s.chars().collect();
-                    if !chars.is_empty() {
+                    if let Some(token) = self.config.string_token(rng).filter(|_| rng.gen_bool(0.5)) {
+                        // Splice a dictionary token in
+                        let idx = rng.gen_range(0..=chars.len());
+                        chars.splice(idx..idx, token.chars());
+                        json!(chars.into_iter().collect::<String>())
+                    } else if !chars.is_empty() {
                         let idx = rng.gen_range(0..chars.len());
-                        chars[idx] = rng.gen::<char>();
+                        chars[idx] = fuzz_config::random_char(rng);
                         json!(chars.into_iter().collect::<String>())
                     } else {
                         json!(self.generate_random_string(rng, 10))
@@ -187,7 +529,7 @@ impl Fuzzer {
                     let mut new_arr = arr.clone();
                     if !new_arr.is_empty() {
                         let idx = rng.gen_range(0..new_arr.len());
-                        new_arr[idx] = self.generate_random_value(rng);
+                        new_arr[idx] = self.generate_value_for(schema.and_then(|s| s.get("items")), rng);
                     }
                     json!(new_arr)
                 },
@@ -196,11 +538,12 @@ impl Fuzzer {
                     let keys: Vec<&String> = obj.keys().collect();
                     if !keys.is_empty() {
                         let key = keys[rng.gen_range(0..keys.len())];
-                        new_obj.insert(key.clone(), self.generate_random_value(rng));
+                        let property = schema.and_then(|s| s.get("properties")).and_then(|p| p.get(key.as_str()));
+                        new_obj.insert(key.clone(), self.generate_value_for(property, rng));
                     }
                     json!(new_obj)
                 },
-                _ => self.generate_random_value(rng),
+                _ => self.generate_value_for(schema, rng),
             };
             variations.push(variation);
         }
@@ -209,10 +552,30 @@ impl Fuzzer {
     }
 
     fn generate_random_input(&self, rng: &mut StdRng) -> Value {
-        self.generate_random_value(rng)
+        let mut budget = edge_cases::MAX_INPUT_BYTES;
+        self.generate_random_value(rng, &mut budget)
+    }
+
+    fn generate_value_for(&self, schema: Option<&Value>, rng: &mut StdRng) -> Value {
+        match schema {
+            Some(schema) => self.config.generate(schema, rng),
+            None => self.generate_random_input(rng),
+        }
     }
 
-    fn generate_random_value(&self, rng: &mut StdRng) -> Value {
+    /// Like `FuzzConfig::generate`, edge cases share a budget of
+    /// `edge_cases::MAX_INPUT_BYTES` across the whole value.
+    fn generate_random_value(&self, rng: &mut StdRng, budget: &mut usize) -> Value {
+        if !self.config.dictionary.is_empty() && rng.gen_bool(0.3) {
+            if let Some(token) = self.config.dictionary.choose(rng) {
+                return token.clone();
+            }
+        }
+        if rng.gen_bool(EDGE_CASE_RATE) {
+            let value = edge_cases::any(rng, *budget);
+            *budget = budget.saturating_sub(value.to_string().len());
+            return value;
+        }
         match rng.gen_range(0..5) {
             0 => json!(rng.gen::<i64>()),
             1 => json!(rng.gen::<f64>()),
@@ -223,7 +586,7 @@ impl Fuzzer {
             3 => {
                 let len = rng.gen_range(0..10);
                 let arr: Vec<Value> = (0..len)
-                    .map(|_| self.generate_random_value(rng))
+                    .map(|_| self.generate_random_value(rng, budget))
                     .collect();
                 json!(arr)
             },
@@ -233,7 +596,7 @@ impl Fuzzer {
                 for _ in 0..num_fields {
                     let key_len = rng.gen_range(1..10);
                     let key = self.generate_random_string(rng, key_len);
-                    let value = self.generate_random_value(rng);
+                    let value = self.generate_random_value(rng, budget);
                     obj.insert(key, value);
                 }
                 json!(obj)
@@ -242,9 +605,7 @@ impl Fuzzer {
     }
 
     fn generate_random_string(&self, rng: &mut StdRng, len: usize) -> String {
-        (0..len)
-            .map(|_| rng.gen::<char>())
-            .collect()
+        fuzz_config::random_string(rng, len)
     }
 
     fn calculate_path_hash(&self, result: &ExecutionResult) -> String {
@@ -282,26 +643,20 @@ impl Fuzzer {
             "Unknown crash".to_string()
         };
 
-        // Determine severity based on error patterns
-        let severity = if error_message.contains("panic") || error_message.contains("segmentation fault") {
-            CrashSeverity::Critical
-        } else if error_message.contains("overflow") || error_message.contains("null pointer") {
-            CrashSeverity::High
-        } else if error_message.contains("assertion failed") {
-            CrashSeverity::Medium
-        } else {
-            CrashSeverity::Low
-        };
+        // Challenges can reclassify crashes with their own severity rules
+        let severity = crash_severity::builtin_severity(&error_message);
 
         // Extract stack trace (simplified)
         let stack_trace = self.extract_stack_trace(&result.stderr);
 
         Some(FuzzCrash {
             input: input.clone(),
+            signature: crash_signature::signature(&error_message, &stack_trace),
             error_message,
             stack_trace,
             gas_used: result.gas_used,
             severity,
+            occurrences: 1,
         })
     }
 
@@ -331,4 +686,23 @@ impl Fuzzer {
             stack_trace
         }
     }
-}
\ No newline at end of file
+}
+
+/// Count a crash against an earlier one with the same signature, so one bug
+/// is reported (and penalized) once however many inputs hit it.
+fn record_crash(crashes: &mut Vec<FuzzCrash>, crash: FuzzCrash) {
+    match crashes.iter_mut().find(|c| c.signature == crash.signature) {
+        Some(existing) => existing.occurrences += 1,
+        None => crashes.push(crash),
+    }
+}
+
+/// Keep the `SLOWEST_INPUTS` slowest runs, slowest first.
+fn record_slow_input(slowest: &mut Vec<SlowInput>, input: &Value, wall_time_ms: u64, hung: bool) {
+    if slowest.len() == SLOWEST_INPUTS && slowest.last().map_or(false, |s| s.wall_time_ms >= wall_time_ms) {
+        return;
+    }
+    let index = slowest.iter().position(|s| s.wall_time_ms < wall_time_ms).unwrap_or(slowest.len());
+    slowest.insert(index, SlowInput { input: input.clone(), wall_time_ms, hung });
+    slowest.truncate(SLOWEST_INPUTS);
+}
@@ -0,0 +1,169 @@
+use serde::Deserialize;
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+use tokio::process::Command as TokioCommand;
+use tokio::time::timeout;
+
+const CLONE_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Hosts repositories may be fetched from: `GIT_SOURCE_ALLOWED_HOSTS`, a
+/// comma-separated list, default github.com and gitlab.com.
+fn allowed_hosts() -> Vec<String> {
+    std::env::var("GIT_SOURCE_ALLOWED_HOSTS")
+        .unwrap_or_else(|_| "github.com,gitlab.com".to_string())
+        .split(',')
+        .map(|host| host.trim().to_ascii_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
+/// A submission given as a commit of a git repository instead of inline code.
+#[derive(Clone, Debug, Deserialize)]
+pub struct GitSource {
+    pub url: String,
+    pub commit: String,
+}
+
+impl GitSource {
+    /// Read the optional `repository` field (`{ "url", "commit" }`) of the job payload.
+    pub fn from_payload(payload: &Value) -> Result<Option<Self>, String> {
+        let source: Self = match payload.get("repository") {
+            None | Some(Value::Null) => return Ok(None),
+            Some(repository) => serde_json::from_value(repository.clone())
+                .map_err(|e| format!("Invalid repository: {}", e))?,
+        };
+        source.validate(&allowed_hosts())?;
+        Ok(Some(source))
+    }
+
+    fn validate(&self, allowed_hosts: &[String]) -> Result<(), String> {
+        // Only remote https repositories; file://, ssh and git:// would reach
+        // the worker's filesystem or credentials
+        let rest = self
+            .url
+            .strip_prefix("https://")
+            .ok_or_else(|| format!("Repository URL must use https: {}", self.url))?;
+        // Hosts are compared as given, so user info and ports are refused too
+        let host = rest.split('/').next().unwrap_or_default().to_ascii_lowercase();
+        if !allowed_hosts.contains(&host) {
+            return Err(format!("Repository host {} is not allowed", host));
+        }
+        if self.commit.len() != 40 || !self.commit.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!("Repository commit must be a full 40-character SHA: {}", self.commit));
+        }
+        Ok(())
+    }
+
+    /// Shallow-fetch exactly the requested commit into `workspace` and check
+    /// it out. This is the only network access of the job; everything after
+    /// runs in the sandbox against the checked-out tree. Trees with a `.cargo`
+    /// directory are refused, as its config would change how cargo builds.
+    pub async fn checkout(&self, workspace: &Path) -> Result<(), String> {
+        run_git(workspace, &["init", "--quiet"]).await?;
+        run_git(workspace, &["remote", "add", "origin", &self.url]).await?;
+        run_git(workspace, &["fetch", "--depth", "1", "--no-tags", "--no-recurse-submodules", "origin", &self.commit]).await?;
+        run_git(workspace, &["checkout", "--quiet", "--detach", "FETCH_HEAD"]).await?;
+
+        let head = run_git(workspace, &["rev-parse", "HEAD"]).await?;
+        if !head.trim().eq_ignore_ascii_case(&self.commit) {
+            return Err(format!("Checked out commit {} does not match requested {}", head.trim(), self.commit));
+        }
+
+        if let Some(path) = find_cargo_config(workspace) {
+            return Err(format!("Repository may not contain {}", path));
+        }
+
+        Ok(())
+    }
+}
+
+/// The first `.cargo` entry of the tree, relative to `root`; cargo reads its
+/// config from every directory it runs in and their parents.
+fn find_cargo_config(root: &Path) -> Option<String> {
+    fn walk(root: &Path, dir: &Path) -> Option<String> {
+        for entry in std::fs::read_dir(dir).ok()?.flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            if name == ".cargo" {
+                return Some(path.strip_prefix(root).unwrap_or(&path).display().to_string());
+            }
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if is_dir && name != ".git" {
+                if let Some(found) = walk(root, &path) {
+                    return Some(found);
+                }
+            }
+        }
+        None
+    }
+
+    walk(root, root)
+}
+
+async fn run_git(workspace: &Path, args: &[&str]) -> Result<String, String> {
+    let output = timeout(
+        CLONE_TIMEOUT,
+        TokioCommand::new("git")
+            // Never run repository hooks, prompt for credentials, follow local transports
+            // or be redirected off the allowed host
+            .args(["-c", "core.hooksPath=/dev/null", "-c", "protocol.file.allow=never", "-c", "credential.helper="])
+            .args(["-c", "http.followRedirects=false"])
+            .args(args)
+            .current_dir(workspace)
+            .env("GIT_TERMINAL_PROMPT", "0")
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .output(),
+    )
+    .await
+    .map_err(|_| format!("git {} timed out", args[0]))?
+    .map_err(|e| format!("Failed to run git: {}", e))?;
+
+    if !output.status.success() {
+        return Err(format!("git {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_repository_payload_validation() {
+        let commit = "3f1c0a9b2d4e5f60718293a4b5c6d7e8f9012345";
+        let source = GitSource::from_payload(&json!({
+            "repository": { "url": "https://github.com/example/project.git", "commit": commit }
+        }))
+        .unwrap()
+        .unwrap();
+        assert_eq!(source.commit, commit);
+
+        assert!(GitSource::from_payload(&json!({})).unwrap().is_none());
+        assert!(GitSource::from_payload(&json!({
+            "repository": { "url": "file:///etc", "commit": commit }
+        })).is_err());
+        assert!(GitSource::from_payload(&json!({
+            "repository": { "url": "https://github.com/example/project.git", "commit": "main" }
+        })).is_err());
+
+        let hosts = vec!["github.com".to_string()];
+        let at = |url: &str| GitSource { url: url.to_string(), commit: commit.to_string() }.validate(&hosts);
+        assert!(at("https://GitHub.com/example/project.git").is_ok());
+        assert!(at("https://gitlab.com/example/project.git").is_err());
+        assert!(at("https://github.com@evil.example/project.git").is_err());
+        assert!(at("https://github.com:8443/example/project.git").is_err());
+    }
+
+    #[test]
+    fn test_cargo_config_is_found() {
+        let tree = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(tree.path().join("src")).unwrap();
+        std::fs::create_dir_all(tree.path().join(".git/.cargo")).unwrap();
+        assert_eq!(find_cargo_config(tree.path()), None);
+
+        std::fs::create_dir_all(tree.path().join("src/.cargo")).unwrap();
+        assert_eq!(find_cargo_config(tree.path()).as_deref(), Some("src/.cargo"));
+    }
+}
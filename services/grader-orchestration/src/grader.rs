@@ -26,7 +26,9 @@ pub async fn grade_code(code: &str, language: &str, public_test_cases: &[Value],
     let result = match language {
         "rust" => grade_rust(code, &all_test_cases, gas_limit, time_limit, &mut execution_trace).await,
         "solidity" => grade_solidity(code, &all_test_cases).await,
+        "vyper" => grade_vyper(code, &all_test_cases).await,
         "javascript" => grade_javascript(code, &all_test_cases).await,
+        "typescript" => grade_typescript(code, &all_test_cases).await,
         "python" => grade_python(code, &all_test_cases).await,
         "move" => grade_move(code, &all_test_cases).await,
         _ => Err(format!("Unsupported language: {}", language)),
@@ -38,7 +40,8 @@ pub async fn grade_code(code: &str, language: &str, public_test_cases: &[Value],
         Ok(mut result_json) => {
             // Add gas and time usage to result
             if let Some(obj) = result_json.as_object_mut() {
-                obj.insert("gasUsed".to_string(), json!(gas_limit.saturating_sub(1000))); // Simplified gas calculation
+                // Only language graders that execute on a gas meter report gas
+                obj.entry("gasUsed").or_insert(json!(0));
                 obj.insert("timeUsed".to_string(), json!(execution_time));
                 obj.insert("publicTestsPassed".to_string(), json!(public_test_cases.len())); // Simplified - should count actual passes
                 obj.insert("hiddenTestsPassed".to_string(), json!(hidden_test_cases.len())); // Simplified - should count actual passes
@@ -80,7 +83,7 @@ serde_json = "1.0"
                 "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis(),
                 "eventType": "compilation_start",
                 "data": { "language": "rust" },
-                "gasUsed": 100
+                "gasUsed": 0
             }));
         }
     }
@@ -107,7 +110,7 @@ serde_json = "1.0"
                 "timestamp": std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_millis(),
                 "eventType": "execution_complete",
                 "data": { "success": success },
-                "gasUsed": 500
+                "gasUsed": 0
             }));
         }
     }
@@ -162,6 +165,33 @@ async fn grade_solidity(code: &str, test_cases: &[Value]) -> Result<Value, Strin
     }))
 }
 
+async fn grade_vyper(code: &str, test_cases: &[Value]) -> Result<Value, String> {
+    let temp_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+
+    let contract_path = temp_dir.path().join("Contract.vy");
+    std::fs::write(&contract_path, code).map_err(|e| e.to_string())?;
+
+    let output = TokioCommand::new("vyper")
+        .args(&["-f", "abi,bytecode"])
+        .arg(&contract_path)
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    let success = output.status.success();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    Ok(json!({
+        "success": success,
+        "score": if success { 100 } else { 0 },
+        "output": stdout,
+        "error": stderr,
+        "diagnostics": crate::vyper::parse_vyper_diagnostics(&stderr),
+        "language": "vyper"
+    }))
+}
+
 async fn grade_javascript(code: &str, test_cases: &[Value]) -> Result<Value, String> {
     // Simple JavaScript execution (in a real implementation, you'd want sandboxing)
     let temp_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
@@ -188,14 +218,36 @@ async fn grade_javascript(code: &str, test_cases: &[Value]) -> Result<Value, Str
     }))
 }
 
-async fn grade_python(code: &str, test_cases: &[Value]) -> Result<Value, String> {
+async fn grade_typescript(code: &str, test_cases: &[Value]) -> Result<Value, String> {
     let temp_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
 
-    let code_path = temp_dir.path().join("code.py");
-    std::fs::write(&code_path, code).map_err(|e| e.to_string())?;
+    std::fs::write(temp_dir.path().join("code.ts"), code).map_err(|e| e.to_string())?;
+    crate::typescript::write_deno_shim(temp_dir.path())?;
 
-    let output = TokioCommand::new("python3")
-        .arg(&code_path)
+    // Type errors fail the submission before anything runs
+    let check_output = TokioCommand::new("tsc")
+        .args(crate::typescript::tsc_check_args(&["code.ts"]))
+        .current_dir(temp_dir.path())
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !check_output.status.success() {
+        let stdout = String::from_utf8_lossy(&check_output.stdout);
+        return Ok(json!({
+            "success": false,
+            "score": 0,
+            "output": stdout,
+            "error": String::from_utf8_lossy(&check_output.stderr),
+            "diagnostics": crate::typescript::parse_tsc_diagnostics(&stdout),
+            "stage": "compilation",
+            "language": "typescript"
+        }));
+    }
+
+    let output = TokioCommand::new("deno")
+        .args(&["run", "--no-prompt", "code.ts"])
+        .current_dir(temp_dir.path())
         .output()
         .await
         .map_err(|e| e.to_string())?;
@@ -209,18 +261,18 @@ async fn grade_python(code: &str, test_cases: &[Value]) -> Result<Value, String>
         "score": if success { 100 } else { 0 },
         "output": stdout,
         "error": stderr,
-        "language": "python"
+        "language": "typescript"
     }))
 }
 
-async fn grade_move(code: &str, test_cases: &[Value]) -> Result<Value, String> {
+async fn grade_python(code: &str, test_cases: &[Value]) -> Result<Value, String> {
     let temp_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
 
-    let code_path = temp_dir.path().join("contract.move");
+    let code_path = temp_dir.path().join("code.py");
     std::fs::write(&code_path, code).map_err(|e| e.to_string())?;
 
-    let output = TokioCommand::new("aptos")
-        .args(&["move", "compile", "--package-dir", &temp_dir.path().to_string_lossy()])
+    let output = TokioCommand::new("python3")
+        .arg(&code_path)
         .output()
         .await
         .map_err(|e| e.to_string())?;
@@ -234,6 +286,77 @@ async fn grade_move(code: &str, test_cases: &[Value]) -> Result<Value, String> {
         "score": if success { 100 } else { 0 },
         "output": stdout,
         "error": stderr,
+        "language": "python"
+    }))
+}
+
+async fn grade_move(code: &str, test_cases: &[Value]) -> Result<Value, String> {
+    let temp_dir = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let package_dir = temp_dir.path().to_string_lossy().to_string();
+
+    crate::aptos::write_move_package(temp_dir.path(), code)?;
+
+    let output = TokioCommand::new("aptos")
+        .args(&["move", "compile", "--package-dir", &package_dir])
+        .output()
+        .await
+        .map_err(|e| e.to_string())?;
+
+    if !output.status.success() {
+        return Ok(json!({
+            "success": false,
+            "score": 0,
+            "output": String::from_utf8_lossy(&output.stdout),
+            "error": String::from_utf8_lossy(&output.stderr),
+            "language": "move"
+        }));
+    }
+
+    // Run the package's unit tests in the sandbox
+    let sandbox_config = SandboxConfig {
+        time_limit: Duration::from_secs(300),
+        memory_limit: 1024 * 1024 * 1024, // 1GB
+        cpu_limit: 50,
+        network_disabled: true,
+        max_file_size: 100 * 1024 * 1024, // 100MB
+        max_processes: 10,
+        disk_quota: 500 * 1024 * 1024, // 500MB
+    };
+
+    let test_result = execute_in_sandbox("aptos", &["move", "test", "--package-dir", &package_dir], &sandbox_config, temp_dir.path()).await?;
+    let move_results = crate::aptos::parse_move_test_output(&test_result.stdout);
+
+    // Map Move unit tests onto the challenge's test cases; without test cases
+    // every unit test in the package counts
+    let test_results: Vec<Value> = if test_cases.is_empty() {
+        move_results.iter().map(|t| json!({
+            "name": t.name,
+            "passed": t.passed,
+            "error": t.reason
+        })).collect()
+    } else {
+        test_cases.iter().map(|case| {
+            let id = case.get("id").and_then(|v| v.as_str()).unwrap_or("");
+            let name = case.get("name").and_then(|v| v.as_str()).unwrap_or("");
+            match crate::aptos::find_test_for_fixture(&move_results, id, name) {
+                Some(t) => json!({ "id": id, "name": name, "passed": t.passed, "error": t.reason }),
+                None => json!({ "id": id, "name": name, "passed": false, "error": "No matching Move unit test" }),
+            }
+        }).collect()
+    };
+
+    let total = test_results.len();
+    let passed = test_results.iter().filter(|t| t["passed"].as_bool().unwrap_or(false)).count();
+    let score = if total > 0 { (passed * 100) / total } else { 0 };
+
+    Ok(json!({
+        "success": total > 0 && passed == total,
+        "score": score,
+        "passedTests": passed,
+        "totalTests": total,
+        "testResults": test_results,
+        "output": test_result.stdout,
+        "error": test_result.stderr,
         "language": "move"
     }))
-}
\ No newline at end of file
+}
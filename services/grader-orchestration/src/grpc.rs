@@ -0,0 +1,304 @@
+use crate::anti_cheat::{AntiCheatEngine, PlagiarismResult, RiskLevel};
+use crate::anti_cheat_config::AntiCheatConfig;
+use crate::auth::{AuthError, Authenticator, Client, Scope};
+use crate::job_slots::{JobSlots, Saturated};
+use crate::quota::{ActiveJob, QuotaExceeded, UsageTracker};
+use futures::future::BoxFuture;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+use tonic::{Request, Response, Status};
+
+pub mod proto {
+    tonic::include_proto!("fathuss.grader.v1");
+}
+
+use proto::grader_server::{Grader, GraderServer};
+use proto::{
+    CheckPlagiarismRequest, CheckPlagiarismResponse, CompileRequest, CompileResponse, Diagnostic, GradeRequest, GradeResponse,
+    MatchedSubmission, TestResult,
+};
+
+/// The worker's grading and compile jobs, as run for the HTTP API, so both
+/// APIs go through the same pipeline.
+pub trait GradingPipeline: Send + Sync {
+    /// The `/grade` response to a `/grade` body, with usage counted
+    /// against `client`.
+    fn grade(&self, payload: Value, client: Client, job: ActiveJob) -> BoxFuture<'static, Value>;
+
+    /// The `/compile` response to a `/compile` body.
+    fn compile(&self, payload: Value, client: Client) -> BoxFuture<'static, Value>;
+}
+
+/// The `Grader` gRPC service of `proto/grader.proto`. Requests are turned
+/// into the JSON bodies of the HTTP API and responses read back from its
+/// JSON responses, which are also returned whole as `result_json`.
+pub struct GraderService {
+    pipeline: Arc<dyn GradingPipeline>,
+    anti_cheat: Arc<AntiCheatEngine>,
+    authenticator: Arc<Authenticator>,
+    usage: Arc<UsageTracker>,
+    job_slots: Arc<JobSlots>,
+}
+
+impl GraderService {
+    pub fn new(
+        pipeline: Arc<dyn GradingPipeline>,
+        anti_cheat: Arc<AntiCheatEngine>,
+        authenticator: Arc<Authenticator>,
+        usage: Arc<UsageTracker>,
+        job_slots: Arc<JobSlots>,
+    ) -> Self {
+        Self { pipeline, anti_cheat, authenticator, usage, job_slots }
+    }
+
+    /// The caller, from the same `x-api-key` or `authorization` as over
+    /// HTTP, sent as request metadata.
+    fn client<T>(&self, request: &Request<T>, scope: Scope) -> Result<Client, AuthError> {
+        let metadata = |name: &str| request.metadata().get(name).and_then(|v| v.to_str().ok());
+        self.authenticator.authenticate(metadata("x-api-key"), metadata("authorization"), scope)
+    }
+}
+
+impl From<AuthError> for Status {
+    fn from(error: AuthError) -> Self {
+        match error {
+            AuthError::Unauthorized(message) => Status::unauthenticated(message),
+            AuthError::Forbidden(message) => Status::permission_denied(message),
+        }
+    }
+}
+
+impl From<QuotaExceeded> for Status {
+    fn from(exceeded: QuotaExceeded) -> Self {
+        Status::resource_exhausted(exceeded.to_string())
+    }
+}
+
+impl From<Saturated> for Status {
+    fn from(saturated: Saturated) -> Self {
+        Status::unavailable(saturated.to_string())
+    }
+}
+
+/// Serve the gRPC API on `port` until the server fails.
+pub async fn serve(port: u16, service: GraderService) -> Result<(), String> {
+    tonic::transport::Server::builder()
+        .add_service(GraderServer::new(service))
+        .serve(([0, 0, 0, 0], port).into())
+        .await
+        .map_err(|e| format!("gRPC server failed: {}", e))
+}
+
+#[tonic::async_trait]
+impl Grader for GraderService {
+    async fn grade(&self, request: Request<GradeRequest>) -> Result<Response<GradeResponse>, Status> {
+        let client = self.client(&request, Scope::Grade)?;
+        let ticket = self.job_slots.admit()?;
+        let job = self.usage.admit(&client.id)?;
+        let payload = grade_payload(request.get_ref()).map_err(Status::invalid_argument)?;
+        let _slot = ticket.slot().await;
+        let result = self.pipeline.grade(payload, client, job).await;
+        Ok(Response::new(grade_response(&result)))
+    }
+
+    async fn compile(&self, request: Request<CompileRequest>) -> Result<Response<CompileResponse>, Status> {
+        let client = self.client(&request, Scope::Grade)?;
+        let request = request.into_inner();
+        let mut payload = options(&request.options_json).map_err(Status::invalid_argument)?;
+        payload.insert("language".to_string(), json!(request.language));
+        payload.insert("code".to_string(), json!(request.code));
+        let result = self.pipeline.compile(Value::Object(payload), client).await;
+        Ok(Response::new(CompileResponse {
+            success: result.get("success").and_then(|v| v.as_bool()).unwrap_or(false),
+            output: text(&result, "output"),
+            error: text(&result, "error"),
+            diagnostics: diagnostics(&result["diagnostics"]),
+            warnings: diagnostics(&result["warnings"]),
+            time_used_ms: number(&result, "timeUsed"),
+            result_json: result.to_string(),
+        }))
+    }
+
+    async fn check_plagiarism(&self, request: Request<CheckPlagiarismRequest>) -> Result<Response<CheckPlagiarismResponse>, Status> {
+        // Matches name other users' submissions
+        self.client(&request, Scope::Admin)?;
+        let request = request.into_inner();
+        let overrides = json!({ "antiCheat": Value::Object(options(&request.anti_cheat_json).map_err(Status::invalid_argument)?) });
+        let config = AntiCheatConfig::from_payload(&overrides, self.anti_cheat.config()).map_err(Status::invalid_argument)?;
+        let plagiarism = self
+            .anti_cheat
+            .check_plagiarism(&request.code, &request.language, &request.user_id, &request.challenge_id, &config)
+            .await
+            .map_err(Status::internal)?;
+        Ok(Response::new(plagiarism_response(&plagiarism)))
+    }
+}
+
+/// A request's JSON options, as the object its own fields are added to.
+fn options(options_json: &str) -> Result<Map<String, Value>, String> {
+    if options_json.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    match serde_json::from_str(options_json) {
+        Ok(Value::Object(options)) => Ok(options),
+        _ => Err("Options must be a JSON object".to_string()),
+    }
+}
+
+/// The `/grade` body of a request. Fields that are set take precedence
+/// over the same fields in `options_json`.
+fn grade_payload(request: &GradeRequest) -> Result<Value, String> {
+    let mut payload = options(&request.options_json)?;
+    let fields = [
+        ("jobId", &request.job_id),
+        ("challengeId", &request.challenge_id),
+        ("language", &request.language),
+        ("code", &request.code),
+        ("userId", &request.user_id),
+        ("contestId", &request.contest_id),
+        ("fixturesVersion", &request.fixtures_version),
+    ];
+    for (name, value) in fields {
+        if !value.is_empty() {
+            payload.insert(name.to_string(), json!(value));
+        }
+    }
+    if let Some(gas_limit) = request.gas_limit {
+        payload.insert("gasLimit".to_string(), json!(gas_limit));
+    }
+    if let Some(time_limit) = request.time_limit {
+        payload.insert("timeLimit".to_string(), json!(time_limit));
+    }
+    if request.check_plagiarism {
+        payload.insert("checkPlagiarism".to_string(), json!(true));
+    }
+    Ok(Value::Object(payload))
+}
+
+fn grade_response(result: &Value) -> GradeResponse {
+    let test_results = |suite: &str| -> Vec<TestResult> {
+        result["testResults"][suite]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|test| TestResult {
+                fixture_id: text(test, "fixtureId"),
+                name: text(test, "name"),
+                passed: test.get("passed").and_then(|v| v.as_bool()).unwrap_or(false),
+                gas_used: number(test, "gasUsed"),
+                wall_time_ms: number(test, "wallTimeMs"),
+                timed_out: test.get("timedOut").and_then(|v| v.as_bool()).unwrap_or(false),
+                error: text(test, "error"),
+            })
+            .collect()
+    };
+    GradeResponse {
+        job_id: text(result, "jobId"),
+        // Only failed and held jobs have a status
+        status: result.get("status").and_then(|v| v.as_str()).unwrap_or("completed").to_string(),
+        success: result.get("success").and_then(|v| v.as_bool()).unwrap_or(false),
+        score: number(result, "score") as u32,
+        passed_tests: number(result, "passedTests") as u32,
+        total_tests: number(result, "totalTests") as u32,
+        gas_used: number(result, "gasUsed"),
+        time_used_ms: number(result, "timeUsed"),
+        error: text(result, "error"),
+        diagnostics: diagnostics(&result["diagnostics"]),
+        warnings: diagnostics(&result["warnings"]),
+        public_tests: test_results("public"),
+        hidden_tests: test_results("hidden"),
+        result_json: result.to_string(),
+    }
+}
+
+fn plagiarism_response(plagiarism: &PlagiarismResult) -> CheckPlagiarismResponse {
+    let risk_level = match plagiarism.risk_level {
+        RiskLevel::Low => proto::RiskLevel::Low,
+        RiskLevel::Medium => proto::RiskLevel::Medium,
+        RiskLevel::High => proto::RiskLevel::High,
+        RiskLevel::Critical => proto::RiskLevel::Critical,
+    };
+    CheckPlagiarismResponse {
+        similarity_score: plagiarism.similarity_score,
+        risk_level: risk_level as i32,
+        matches: plagiarism
+            .matched_submissions
+            .iter()
+            .map(|m| MatchedSubmission {
+                submission_id: m.submission_id.clone(),
+                similarity_score: m.similarity_score,
+                scope: json!(m.scope).as_str().unwrap_or_default().to_string(),
+            })
+            .collect(),
+        analysis_time_ms: plagiarism.analysis_time_ms,
+    }
+}
+
+fn diagnostics(value: &Value) -> Vec<Diagnostic> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|d| Diagnostic {
+            file: text(d, "file"),
+            line: number(d, "line") as u32,
+            col: number(d, "col") as u32,
+            severity: text(d, "severity"),
+            message: text(d, "message"),
+            code: text(d, "code"),
+        })
+        .collect()
+}
+
+fn text(value: &Value, key: &str) -> String {
+    value.get(key).and_then(|v| v.as_str()).unwrap_or_default().to_string()
+}
+
+fn number(value: &Value, key: &str) -> u64 {
+    value.get(key).and_then(|v| v.as_u64()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_and_responses_map_to_http_bodies() {
+        let request = GradeRequest {
+            challenge_id: "two-sum".to_string(),
+            language: "rust".to_string(),
+            code: "fn main() {}".to_string(),
+            time_limit: Some(10),
+            options_json: r#"{"language": "python", "cargoToml": "[package]"}"#.to_string(),
+            ..Default::default()
+        };
+        let payload = grade_payload(&request).unwrap();
+        assert_eq!(
+            payload,
+            json!({ "challengeId": "two-sum", "language": "rust", "code": "fn main() {}", "timeLimit": 10, "cargoToml": "[package]" })
+        );
+        let invalid = GradeRequest { options_json: "[1]".to_string(), ..Default::default() };
+        assert_eq!(grade_payload(&invalid), Err("Options must be a JSON object".to_string()));
+
+        let result = json!({
+            "jobId": "job-1",
+            "success": false,
+            "score": 40,
+            "passedTests": 1,
+            "totalTests": 2,
+            "timeUsed": 1200,
+            "error": "",
+            "warnings": [{ "file": "src/main.rs", "line": 3, "col": 9, "severity": "warning", "message": "unused variable", "code": "unused_variables" }],
+            "testResults": {
+                "public": [{ "fixtureId": "t1", "name": "small", "passed": true, "gasUsed": 0, "wallTimeMs": 12, "timedOut": false, "error": null }],
+                "hidden": [{ "fixtureId": "h1", "passed": false, "timedOut": true }]
+            }
+        });
+        let response = grade_response(&result);
+        assert_eq!((response.status.as_str(), response.score, response.time_used_ms), ("completed", 40, 1200));
+        assert_eq!(response.warnings[0].code, "unused_variables");
+        assert_eq!(response.public_tests[0].name, "small");
+        assert!(response.hidden_tests[0].timed_out && response.hidden_tests[0].name.is_empty());
+        assert_eq!(grade_response(&json!({ "error": "Unsupported worker type", "status": "failed" })).status, "failed");
+    }
+}
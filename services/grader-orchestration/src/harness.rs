@@ -0,0 +1,353 @@
+use crate::result_protocol::RESULT_FILE;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+
+/// Signature of the function a challenge grades, taken from its manifest.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FunctionSignature {
+    pub name: String,
+    #[serde(default)]
+    pub params: Vec<FunctionParam>,
+    #[serde(default)]
+    pub returns: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FunctionParam {
+    pub name: String,
+    /// Rust type of the parameter; interpreted languages ignore it
+    #[serde(rename = "type", default)]
+    pub ty: String,
+}
+
+/// Exit code of a Rust harness given an argument that doesn't deserialize
+/// into its parameter's type. The solution never ran, whatever it prints.
+pub const INVALID_ARGUMENT_EXIT_CODE: i32 = 87;
+
+/// A file to write into the workspace.
+pub struct HarnessFile {
+    pub path: String,
+    pub contents: String,
+}
+
+impl FunctionSignature {
+    /// Read the optional `functionSignature` field of the job payload.
+    pub fn from_payload(payload: &Value) -> Result<Option<Self>, String> {
+        match payload.get("functionSignature") {
+            Some(Value::Null) | None => Ok(None),
+            Some(signature) => serde_json::from_value(signature.clone())
+                .map(Some)
+                .map_err(|e| format!("Invalid functionSignature: {}", e)),
+        }
+    }
+}
+
+/// Generate the files for a single-function submission wrapped in a harness
+/// that reads the fixture input (JSON from the file named by the first
+/// argument, or stdin), calls the function and reports its result as JSON,
+/// both on stdout and through the result protocol file.
+///
+/// Fixture inputs may be an object keyed by parameter name, an array of
+/// positional arguments, or the bare value of a single parameter.
+pub fn generate_harness(language: &str, signature: &FunctionSignature, code: &str) -> Result<Vec<HarnessFile>, String> {
+    match language {
+        "rust" => Ok(vec![HarnessFile {
+            path: "main.rs".to_string(),
+            contents: format!("{}\n\n{}", code, rust_harness(signature)?),
+        }]),
+        "javascript" => Ok(vec![
+            HarnessFile { path: "solution.js".to_string(), contents: code.to_string() },
+            HarnessFile { path: "code.js".to_string(), contents: javascript_harness(signature) },
+        ]),
+        "typescript" => Ok(vec![
+            HarnessFile { path: "solution.ts".to_string(), contents: code.to_string() },
+            HarnessFile { path: "code.ts".to_string(), contents: typescript_harness(signature) },
+        ]),
+        "python" => Ok(vec![
+            HarnessFile { path: "solution.py".to_string(), contents: code.to_string() },
+            HarnessFile { path: "code.py".to_string(), contents: python_harness(signature) },
+        ]),
+        _ => Err(format!("No harness available for language: {}", language)),
+    }
+}
+
+/// JSON schema of the harness's input: an object keyed by parameter name,
+/// each following its declared Rust type. Parameters without a type (or of
+/// a type not understood here, such as a struct) accept any value.
+pub fn input_schema(signature: &FunctionSignature) -> Value {
+    let properties: Map<String, Value> = signature.params.iter().map(|p| (p.name.clone(), type_schema(&p.ty))).collect();
+    let required: Vec<&str> = signature.params.iter().map(|p| p.name.as_str()).collect();
+    json!({ "type": "object", "properties": properties, "required": required })
+}
+
+fn type_schema(ty: &str) -> Value {
+    let ty = ty.trim().trim_start_matches('&').trim();
+    let ty = ty.strip_prefix("mut ").unwrap_or(ty).trim();
+    if let Some(item) = ["Vec", "VecDeque", "HashSet", "BTreeSet"].iter().find_map(|c| generic_arg(ty, c)) {
+        return json!({ "type": "array", "items": type_schema(item) });
+    }
+    // Option arguments are always given; null is left to the fixtures
+    if let Some(inner) = ["Option", "Box"].iter().find_map(|c| generic_arg(ty, c)) {
+        return type_schema(inner);
+    }
+    if let Some(inner) = ty.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        return match inner.rsplit_once(';').map(|(item, len)| (item, len.trim().parse::<usize>())) {
+            Some((item, Ok(len))) => json!({ "type": "array", "items": type_schema(item), "minItems": len, "maxItems": len }),
+            _ => json!({ "type": "array", "items": type_schema(inner) }),
+        };
+    }
+    let bounds = match ty {
+        "i8" => Some((i8::MIN as i64, i8::MAX as i64)),
+        "i16" => Some((i16::MIN as i64, i16::MAX as i64)),
+        "i32" => Some((i32::MIN as i64, i32::MAX as i64)),
+        "i64" | "i128" | "isize" => Some((i64::MIN, i64::MAX)),
+        "u8" => Some((0, u8::MAX as i64)),
+        "u16" => Some((0, u16::MAX as i64)),
+        "u32" => Some((0, u32::MAX as i64)),
+        // Generated integers stay within i64
+        "u64" | "u128" | "usize" => Some((0, i64::MAX)),
+        _ => None,
+    };
+    match (ty, bounds) {
+        (_, Some((minimum, maximum))) => json!({ "type": "integer", "minimum": minimum, "maximum": maximum }),
+        ("bool", _) => json!({ "type": "boolean" }),
+        ("f32" | "f64", _) => json!({ "type": "number" }),
+        ("char", _) => json!({ "type": "string", "minLength": 1, "maxLength": 1 }),
+        ("String" | "str", _) => json!({ "type": "string" }),
+        _ => json!({}),
+    }
+}
+
+/// `T` of `Container<T>`.
+fn generic_arg<'a>(ty: &'a str, container: &str) -> Option<&'a str> {
+    ty.strip_prefix(container)?.trim_start().strip_prefix('<')?.strip_suffix('>')
+}
+
+/// Whether a harness run's stdout matches the fixture's expected output. A
+/// null expectation accepts any output.
+pub fn output_matches(expected: &Value, stdout: &str) -> bool {
+    if expected.is_null() {
+        return true;
+    }
+
+    // The harness prints the result as the last line of output
+    let last_line = stdout.lines().rev().find(|l| !l.trim().is_empty()).unwrap_or("").trim();
+    match serde_json::from_str::<Value>(last_line) {
+        Ok(actual) => &actual == expected,
+        Err(_) => expected.as_str().map(|s| s == last_line).unwrap_or(false),
+    }
+}
+
+/// Whether a result reported through the result protocol matches the
+/// fixture's expected output.
+pub fn result_matches(expected: &Value, actual: &Value) -> bool {
+    expected.is_null() || expected == actual
+}
+
+fn params_list(signature: &FunctionSignature) -> String {
+    signature
+        .params
+        .iter()
+        .map(|p| format!("{:?}", p.name))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn rust_harness(signature: &FunctionSignature) -> Result<String, String> {
+    let mut args = Vec::new();
+    for (index, param) in signature.params.iter().enumerate() {
+        if param.ty.is_empty() {
+            return Err(format!("Parameter `{}` has no Rust type", param.name));
+        }
+        args.push(format!("__fathuss_arg::<{}>(&input, {:?}, {})", param.ty, param.name, index));
+    }
+
+    Ok(format!(
+        r#"// ---- Generated by the Fathuss grader ----
+const __FATHUSS_PARAM_COUNT: usize = {count};
+
+fn __fathuss_read_input() -> serde_json::Value {{
+    use std::io::Read;
+    let mut raw = String::new();
+    match std::env::args().nth(1) {{
+        Some(path) => raw = std::fs::read_to_string(path).expect("failed to read input file"),
+        None => {{ std::io::stdin().read_to_string(&mut raw).expect("failed to read stdin"); }}
+    }}
+    serde_json::from_str(&raw).expect("input is not valid JSON")
+}}
+
+fn __fathuss_arg<T: serde::de::DeserializeOwned>(input: &serde_json::Value, name: &str, index: usize) -> T {{
+    let value = match input {{
+        serde_json::Value::Object(map) if map.contains_key(name) => map[name].clone(),
+        serde_json::Value::Array(items) if __FATHUSS_PARAM_COUNT > 1 => items.get(index).cloned().unwrap_or(serde_json::Value::Null),
+        other => other.clone(),
+    }};
+    serde_json::from_value(value).unwrap_or_else(|e| {{
+        eprintln!("invalid argument `{{}}`: {{}}", name, e);
+        std::process::exit({invalid_argument_exit_code})
+    }})
+}}
+
+fn main() {{
+    let input = __fathuss_read_input();
+    let result = {name}({args});
+    println!("{{}}", serde_json::to_string(&result).expect("result is not serializable"));
+    let _ = std::fs::write({result_file:?}, serde_json::json!({{ "result": &result }}).to_string());
+}}
+"#,
+        result_file = RESULT_FILE,
+        invalid_argument_exit_code = INVALID_ARGUMENT_EXIT_CODE,
+        count = signature.params.len(),
+        name = signature.name,
+        args = args.join(", "),
+    ))
+}
+
+fn javascript_harness(signature: &FunctionSignature) -> String {
+    format!(
+        r#"// ---- Generated by the Fathuss grader ----
+const fs = require('fs');
+const mod = require('./solution.js');
+const fn = typeof mod === 'function' ? mod : (mod[{name:?}] || mod.default);
+const params = [{params}];
+const input = JSON.parse(fs.readFileSync(process.argv[2] || 0, 'utf8'));
+
+let args;
+if (input !== null && typeof input === 'object' && !Array.isArray(input) && params.every((p) => p in input)) {{
+  args = params.map((p) => input[p]);
+}} else if (Array.isArray(input) && params.length > 1) {{
+  args = input;
+}} else {{
+  args = [input];
+}}
+
+Promise.resolve(fn(...args)).then((result) => {{
+  console.log(JSON.stringify(result));
+  fs.writeFileSync({result_file:?}, JSON.stringify({{ result }}));
+}}).catch((err) => {{
+  console.error(err && err.stack ? err.stack : err);
+  fs.writeFileSync({result_file:?}, JSON.stringify({{ verdict: 'error', message: String(err) }}));
+  process.exit(1);
+}});
+"#,
+        result_file = RESULT_FILE,
+        name = signature.name,
+        params = params_list(signature),
+    )
+}
+
+fn typescript_harness(signature: &FunctionSignature) -> String {
+    format!(
+        r#"// ---- Generated by the Fathuss grader ----
+import {{ {name} as solutionFn }} from "./solution.ts";
+
+const params: string[] = [{params}];
+const raw = Deno.args.length > 0
+  ? Deno.readTextFileSync(Deno.args[0])
+  : new TextDecoder().decode(await new Response(Deno.stdin.readable).arrayBuffer());
+const input = JSON.parse(raw);
+
+let args: unknown[];
+if (input !== null && typeof input === "object" && !Array.isArray(input) && params.every((p) => p in input)) {{
+  args = params.map((p) => input[p]);
+}} else if (Array.isArray(input) && params.length > 1) {{
+  args = input;
+}} else {{
+  args = [input];
+}}
+
+// deno-lint-ignore no-explicit-any
+const result = await (solutionFn as (...a: any[]) => unknown)(...args);
+console.log(JSON.stringify(result));
+Deno.writeTextFileSync({result_file:?}, JSON.stringify({{ result }}));
+"#,
+        result_file = RESULT_FILE,
+        name = signature.name,
+        params = params_list(signature),
+    )
+}
+
+fn python_harness(signature: &FunctionSignature) -> String {
+    format!(
+        r#"# ---- Generated by the Fathuss grader ----
+import json
+import sys
+
+from solution import {name} as solution_fn
+
+PARAMS = [{params}]
+
+with (open(sys.argv[1]) if len(sys.argv) > 1 else sys.stdin) as f:
+    data = json.load(f)
+
+if isinstance(data, dict) and all(p in data for p in PARAMS):
+    args = [data[p] for p in PARAMS]
+elif isinstance(data, list) and len(PARAMS) > 1:
+    args = data
+else:
+    args = [data]
+
+try:
+    result = solution_fn(*args)
+except Exception as exc:
+    with open({result_file:?}, "w") as f:
+        json.dump({{"verdict": "error", "message": repr(exc)}}, f)
+    raise
+
+print(json.dumps(result))
+with open({result_file:?}, "w") as f:
+    json.dump({{"result": result}}, f)
+"#,
+        result_file = RESULT_FILE,
+        name = signature.name,
+        params = params_list(signature),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_generate_rust_harness() {
+        let signature = FunctionSignature::from_payload(&json!({
+            "functionSignature": {
+                "name": "solve",
+                "params": [{ "name": "nums", "type": "Vec<i64>" }, { "name": "target", "type": "i64" }],
+                "returns": "bool"
+            }
+        }))
+        .unwrap()
+        .unwrap();
+
+        let files = generate_harness("rust", &signature, "fn solve(nums: Vec<i64>, target: i64) -> bool { true }").unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].contents.contains("std::process::exit(87)"));
+        assert!(files[0].contents.contains("let result = solve(__fathuss_arg::<Vec<i64>>(&input, \"nums\", 0), __fathuss_arg::<i64>(&input, \"target\", 1));"));
+
+        assert!(output_matches(&json!(true), "debug line\ntrue\n"));
+        assert!(!output_matches(&json!([1, 2]), "[2, 1]"));
+        assert!(output_matches(&Value::Null, "anything"));
+    }
+
+    #[test]
+    fn test_input_schema_follows_param_types() {
+        let signature: FunctionSignature = serde_json::from_value(json!({
+            "name": "route",
+            "params": [
+                { "name": "hops", "type": "Vec<u8>" },
+                { "name": "key", "type": "[u8; 4]" },
+                { "name": "label", "type": "&str" },
+                { "name": "graph", "type": "Graph" }
+            ]
+        }))
+        .unwrap();
+        let schema = input_schema(&signature);
+        assert_eq!(schema["required"], json!(["hops", "key", "label", "graph"]));
+        assert_eq!(schema["properties"]["hops"], json!({ "type": "array", "items": { "type": "integer", "minimum": 0, "maximum": 255 } }));
+        assert_eq!((schema["properties"]["key"]["minItems"].clone(), schema["properties"]["key"]["maxItems"].clone()), (json!(4), json!(4)));
+        assert_eq!(schema["properties"]["label"], json!({ "type": "string" }));
+        assert_eq!(schema["properties"]["graph"], json!({}));
+    }
+}
@@ -0,0 +1,388 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const DEFAULT_CAPACITY: usize = 1000;
+const FROZEN_MARKER: &str = "frozen";
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JobRecord {
+    pub job_id: String,
+    pub challenge_id: String,
+    pub contest_id: Option<String>,
+    /// Who submitted the job, so their results can be deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+    /// The client that submitted the job, the only one besides admins that may read it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client_id: Option<String>,
+    pub completed_at: chrono::DateTime<chrono::Utc>,
+    pub result: Value,
+    /// Computed during a contest freeze and withheld until the contest unfreezes
+    pub embargoed: bool,
+    /// Withheld pending an anti-cheat review decision
+    pub under_review: bool,
+}
+
+/// Recently completed grading jobs, kept so results can be inspected after
+/// the grading response has been returned. Oldest jobs are evicted first.
+pub struct JobHistory {
+    capacity: usize,
+    inner: Mutex<HistoryInner>,
+    embargo: Option<EmbargoStore>,
+}
+
+/// Contest freezes and the results they embargo, kept on disk so they
+/// outlive history eviction and restarts: `{root}/{contestId}/frozen` marks
+/// a frozen contest, `{root}/{contestId}/{jobId}.json` holds a result.
+struct EmbargoStore {
+    root: PathBuf,
+}
+
+impl EmbargoStore {
+    fn contest_dir(&self, contest_id: &str) -> Result<PathBuf, String> {
+        // Contest ids come from requests; keep them from escaping the root
+        if contest_id.is_empty() || !contest_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Invalid contest id: {}", contest_id));
+        }
+        Ok(self.root.join(contest_id))
+    }
+
+    fn frozen_contests(&self) -> HashSet<String> {
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(_) => return HashSet::new(),
+        };
+        entries
+            .flatten()
+            .filter(|entry| entry.path().join(FROZEN_MARKER).exists())
+            .map(|entry| entry.file_name().to_string_lossy().into_owned())
+            .collect()
+    }
+
+    fn freeze(&self, contest_id: &str) -> Result<(), String> {
+        let dir = self.contest_dir(contest_id)?;
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {}", dir.display(), e))?;
+        std::fs::write(dir.join(FROZEN_MARKER), b"").map_err(|e| format!("Failed to freeze contest {}: {}", contest_id, e))
+    }
+
+    fn store(&self, record: &JobRecord) -> Result<(), String> {
+        let contest_id = record.contest_id.as_deref().unwrap_or_default();
+        if !record.job_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Invalid job id: {}", record.job_id));
+        }
+        let path = self.contest_dir(contest_id)?.join(format!("{}.json", record.job_id));
+        let json = serde_json::to_vec(record).map_err(|e| e.to_string())?;
+        std::fs::write(&path, json).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    /// Delete the user's embargoed results in every contest, returning
+    /// their job ids.
+    fn delete_user(&self, user_id: &str) -> Result<Vec<String>, String> {
+        let contests = match std::fs::read_dir(&self.root) {
+            Ok(contests) => contests,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Failed to read {}: {}", self.root.display(), e)),
+        };
+        let mut deleted = Vec::new();
+        for contest in contests.flatten().map(|entry| entry.path()) {
+            let entries = match std::fs::read_dir(&contest) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for path in entries.flatten().map(|entry| entry.path()).filter(|path| path.extension().is_some_and(|e| e == "json")) {
+                let record = std::fs::read(&path).ok().and_then(|json| serde_json::from_slice::<JobRecord>(&json).ok());
+                if let Some(record) = record.filter(|r| r.user_id.as_deref() == Some(user_id)) {
+                    std::fs::remove_file(&path).map_err(|e| format!("Failed to delete {}: {}", path.display(), e))?;
+                    deleted.push(record.job_id);
+                }
+            }
+        }
+        Ok(deleted)
+    }
+
+    /// The contest's embargoed results, removed from the store along with
+    /// its freeze.
+    fn release(&self, contest_id: &str) -> Result<Vec<JobRecord>, String> {
+        let dir = self.contest_dir(contest_id)?;
+        let mut records = Vec::new();
+        if let Ok(entries) = std::fs::read_dir(&dir) {
+            for path in entries.flatten().map(|entry| entry.path()).filter(|path| path.extension().is_some_and(|e| e == "json")) {
+                let json = std::fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                match serde_json::from_slice(&json) {
+                    Ok(record) => records.push(record),
+                    Err(e) => eprintln!("Warning: Skipping corrupt embargoed result {}: {}", path.display(), e),
+                }
+            }
+        }
+        match std::fs::remove_dir_all(&dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(format!("Failed to remove {}: {}", dir.display(), e)),
+            _ => {}
+        }
+        Ok(records)
+    }
+}
+
+#[derive(Default)]
+struct HistoryInner {
+    records: HashMap<String, JobRecord>,
+    order: VecDeque<String>,
+    frozen_contests: HashSet<String>,
+}
+
+impl JobHistory {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            inner: Mutex::new(HistoryInner::default()),
+            embargo: None,
+        }
+    }
+
+    /// History whose contest freezes are kept in `CONTEST_EMBARGO_DIR`.
+    pub fn from_env() -> Self {
+        let root = std::env::var("CONTEST_EMBARGO_DIR").unwrap_or_else(|_| "/tmp/fathuss_embargo".to_string());
+        Self::new().with_embargo_dir(PathBuf::from(root))
+    }
+
+    /// Keep contest freezes and embargoed results in `root`, resuming the
+    /// freezes already there.
+    pub fn with_embargo_dir(mut self, root: PathBuf) -> Self {
+        let store = EmbargoStore { root };
+        self.inner.get_mut().unwrap().frozen_contests = store.frozen_contests();
+        self.embargo = Some(store);
+        self
+    }
+
+    /// Store a completed job, returning whether it was embargoed by a contest freeze.
+    pub fn record(
+        &self,
+        job_id: &str,
+        challenge_id: &str,
+        contest_id: Option<&str>,
+        user_id: Option<&str>,
+        client_id: Option<&str>,
+        result: Value,
+    ) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        let embargoed = contest_id
+            .map(|id| inner.frozen_contests.contains(id))
+            .unwrap_or(false);
+
+        if !inner.records.contains_key(job_id) {
+            inner.order.push_back(job_id.to_string());
+        }
+        let record = JobRecord {
+            job_id: job_id.to_string(),
+            challenge_id: challenge_id.to_string(),
+            contest_id: contest_id.map(|id| id.to_string()),
+            user_id: user_id.map(|id| id.to_string()),
+            client_id: client_id.map(|id| id.to_string()),
+            completed_at: chrono::Utc::now(),
+            result,
+            embargoed,
+            under_review: false,
+        };
+        if let (true, Some(store)) = (embargoed, &self.embargo) {
+            if let Err(e) = store.store(&record) {
+                eprintln!("Warning: Embargoed result of job {} only kept in memory: {}", job_id, e);
+            }
+        }
+        inner.records.insert(job_id.to_string(), record);
+
+        while inner.order.len() > self.capacity {
+            if let Some(oldest) = inner.order.pop_front() {
+                inner.records.remove(&oldest);
+            }
+        }
+
+        embargoed
+    }
+
+    /// Start a scoreboard freeze: results for the contest are stored but embargoed.
+    pub fn freeze(&self, contest_id: &str) -> Result<(), String> {
+        if let Some(store) = &self.embargo {
+            store.freeze(contest_id)?;
+        }
+        self.inner.lock().unwrap().frozen_contests.insert(contest_id.to_string());
+        Ok(())
+    }
+
+    pub fn is_frozen(&self, contest_id: &str) -> bool {
+        self.inner.lock().unwrap().frozen_contests.contains(contest_id)
+    }
+
+    /// End a freeze and release every embargoed result of the contest in bulk,
+    /// including those evicted from the history or embargoed before a
+    /// restart. Returns the released jobs in completion order.
+    pub fn unfreeze(&self, contest_id: &str) -> Result<Vec<JobRecord>, String> {
+        let mut inner = self.inner.lock().unwrap();
+        let stored = match &self.embargo {
+            Some(store) => store.release(contest_id)?,
+            None => Vec::new(),
+        };
+        inner.frozen_contests.remove(contest_id);
+
+        let mut released: HashMap<String, JobRecord> = stored.into_iter().map(|record| (record.job_id.clone(), record)).collect();
+        for record in inner.records.values_mut() {
+            if record.embargoed && record.contest_id.as_deref() == Some(contest_id) {
+                record.embargoed = false;
+                released.insert(record.job_id.clone(), record.clone());
+            }
+        }
+
+        // Results under review stay withheld until the review decides
+        let mut released: Vec<JobRecord> = released
+            .into_values()
+            .filter(|record| !record.under_review)
+            .map(|record| JobRecord { embargoed: false, ..record })
+            .collect();
+        released.sort_by_key(|record| record.completed_at);
+        Ok(released)
+    }
+
+    /// Withhold a job's result until `resolve_review` is called. Returns false
+    /// if the job is unknown.
+    pub fn hold_for_review(&self, job_id: &str) -> bool {
+        match self.inner.lock().unwrap().records.get_mut(job_id) {
+            Some(record) => {
+                record.under_review = true;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Record a reviewer's decision on a held job. A confirmed violation
+    /// disqualifies the result; otherwise it is released unchanged.
+    pub fn resolve_review(&self, job_id: &str, violation_confirmed: bool) -> Option<JobRecord> {
+        let mut inner = self.inner.lock().unwrap();
+        let record = inner.records.get_mut(job_id).filter(|r| r.under_review)?;
+
+        record.under_review = false;
+        if violation_confirmed {
+            if let Some(obj) = record.result.as_object_mut() {
+                obj.insert("success".to_string(), Value::Bool(false));
+                obj.insert("score".to_string(), Value::from(0));
+                obj.insert("disqualified".to_string(), Value::Bool(true));
+            }
+        }
+        Some(record.clone())
+    }
+
+    /// Delete the user's results, embargoed ones included, returning how
+    /// many jobs they were from.
+    pub fn delete_user(&self, user_id: &str) -> Result<usize, String> {
+        let mut inner = self.inner.lock().unwrap();
+        let mut deleted: HashSet<String> = match &self.embargo {
+            Some(store) => store.delete_user(user_id)?.into_iter().collect(),
+            None => HashSet::new(),
+        };
+        inner.records.retain(|job_id, record| {
+            let owned = record.user_id.as_deref() == Some(user_id);
+            if owned {
+                deleted.insert(job_id.clone());
+            }
+            !owned
+        });
+        inner.order.retain(|job_id| !deleted.contains(job_id));
+        Ok(deleted.len())
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<JobRecord> {
+        self.inner.lock().unwrap().records.get(job_id).cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_history_evicts_oldest() {
+        let history = JobHistory::with_capacity(2);
+        history.record("job-1", "c", None, None, None, json!({"score": 1}));
+        history.record("job-2", "c", None, None, None, json!({"score": 2}));
+        history.record("job-3", "c", None, None, Some("backend"), json!({"score": 3}));
+
+        assert!(history.get("job-1").is_none());
+        assert_eq!(history.get("job-3").unwrap().result["score"], 3);
+        assert_eq!(history.get("job-3").unwrap().client_id.as_deref(), Some("backend"));
+    }
+
+    #[test]
+    fn test_contest_freeze_embargoes_until_unfreeze() {
+        let history = JobHistory::new();
+        assert!(!history.record("before", "c", Some("contest-1"), None, None, json!({})));
+
+        history.freeze("contest-1").unwrap();
+        assert!(history.record("during", "c", Some("contest-1"), None, None, json!({})));
+        assert!(!history.record("other", "c", Some("contest-2"), None, None, json!({})));
+        assert!(history.get("during").unwrap().embargoed);
+
+        let released = history.unfreeze("contest-1").unwrap();
+        assert_eq!(released.len(), 1);
+        assert_eq!(released[0].job_id, "during");
+        assert!(!history.get("during").unwrap().embargoed);
+        assert!(!history.is_frozen("contest-1"));
+    }
+
+    #[test]
+    fn test_embargo_survives_eviction_and_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = JobHistory::with_capacity(1).with_embargo_dir(dir.path().to_path_buf());
+        history.freeze("contest-1").unwrap();
+        assert!(history.freeze("../contest").is_err());
+        history.record("job-1", "c", Some("contest-1"), None, None, json!({ "score": 70 }));
+        history.record("job-2", "c", Some("contest-1"), None, None, json!({ "score": 90 }));
+        assert!(history.get("job-1").is_none());
+
+        // A restarted worker is still frozen and releases both results
+        let restarted = JobHistory::new().with_embargo_dir(dir.path().to_path_buf());
+        assert!(restarted.is_frozen("contest-1"));
+        assert!(restarted.record("job-3", "c", Some("contest-1"), None, None, json!({ "score": 40 })));
+        let released = restarted.unfreeze("contest-1").unwrap();
+        let scores: Vec<_> = released.iter().map(|r| r.result["score"].as_u64().unwrap()).collect();
+        assert_eq!(scores, vec![70, 90, 40]);
+        assert!(released.iter().all(|r| !r.embargoed));
+        assert!(!JobHistory::new().with_embargo_dir(dir.path().to_path_buf()).is_frozen("contest-1"));
+    }
+
+    #[test]
+    fn test_review_hold_and_resolution() {
+        let history = JobHistory::new();
+        history.record("job-1", "c", None, None, None, json!({ "success": true, "score": 90 }));
+
+        assert!(history.resolve_review("job-1", true).is_none());
+        assert!(history.hold_for_review("job-1"));
+        assert!(history.get("job-1").unwrap().under_review);
+
+        let resolved = history.resolve_review("job-1", true).unwrap();
+        assert!(!resolved.under_review);
+        assert_eq!(resolved.result["score"], 0);
+        assert_eq!(resolved.result["disqualified"], true);
+    }
+
+    #[test]
+    fn test_delete_user_removes_embargoed_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let history = JobHistory::new().with_embargo_dir(dir.path().to_path_buf());
+        history.freeze("contest-1").unwrap();
+        history.record("job-1", "c", Some("contest-1"), Some("alice"), None, json!({}));
+        history.record("job-2", "c", None, Some("alice"), None, json!({}));
+        history.record("job-3", "c", Some("contest-1"), Some("bob"), None, json!({}));
+
+        assert_eq!(history.delete_user("alice").unwrap(), 2);
+        assert!(history.get("job-1").is_none() && history.get("job-2").is_none());
+        let released = JobHistory::new().with_embargo_dir(dir.path().to_path_buf()).unfreeze("contest-1").unwrap();
+        assert_eq!(released.iter().map(|r| r.job_id.as_str()).collect::<Vec<_>>(), vec!["job-3"]);
+    }
+}
@@ -0,0 +1,182 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+/// Stages of the grading pipeline that hooks run around.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Stage {
+    FetchFixtures,
+    Compile,
+    Lint,
+    SecurityAnalysis,
+    PublicTests,
+    HiddenTests,
+    Fuzz,
+    Plagiarism,
+}
+
+impl std::fmt::Display for Stage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Stage::FetchFixtures => "fetch fixtures",
+            Stage::Compile => "compile",
+            Stage::Lint => "lint",
+            Stage::SecurityAnalysis => "security analysis",
+            Stage::PublicTests => "public tests",
+            Stage::HiddenTests => "hidden tests",
+            Stage::Fuzz => "fuzz",
+            Stage::Plagiarism => "plagiarism",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The job a hook is called for.
+pub struct StageContext<'a> {
+    pub job_id: &'a str,
+    pub challenge_id: &'a str,
+    pub language: &'a str,
+    pub workspace: &'a Path,
+}
+
+/// What a stage produced, as passed to `after_stage`.
+pub struct StageOutcome {
+    pub elapsed: Duration,
+    /// Stage-specific summary, e.g. `{"passed": 3, "total": 4}` for test stages
+    pub summary: Value,
+}
+
+/// Extension point around each pipeline stage, for deployments that want
+/// custom logging, extra analyses or policy checks. Returning an error aborts
+/// the job with that reason. Hooks run while the job holds the worker, so
+/// they should be quick.
+pub trait PipelineHooks: Send + Sync {
+    fn before_stage(&self, _stage: Stage, _ctx: &StageContext) -> Result<(), String> {
+        Ok(())
+    }
+
+    fn after_stage(&self, _stage: Stage, _ctx: &StageContext, _outcome: &StageOutcome) -> Result<(), String> {
+        Ok(())
+    }
+
+    /// Progress within a stage as it happens, e.g. each test's result or the
+    /// fuzzing campaign's counts. Progress can't abort the job.
+    fn progress(&self, _stage: Stage, _ctx: &StageContext, _detail: &Value) {}
+}
+
+/// Hooks registered with the worker, called in registration order. The first
+/// hook to abort stops the chain.
+#[derive(Default)]
+pub struct HookChain {
+    hooks: Vec<Box<dyn PipelineHooks>>,
+}
+
+impl HookChain {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&mut self, hook: Box<dyn PipelineHooks>) {
+        self.hooks.push(hook);
+    }
+
+    /// The worker's hooks: the stage timing logger if `PIPELINE_STAGE_TIMINGS`
+    /// is enabled.
+    pub fn from_env() -> Self {
+        let mut chain = Self::new();
+        if std::env::var("PIPELINE_STAGE_TIMINGS").map(|v| v == "true").unwrap_or(false) {
+            chain.register(Box::new(StageTimingLogger));
+        }
+        chain
+    }
+}
+
+impl PipelineHooks for HookChain {
+    fn before_stage(&self, stage: Stage, ctx: &StageContext) -> Result<(), String> {
+        for hook in &self.hooks {
+            hook.before_stage(stage, ctx)
+                .map_err(|e| format!("Aborted before {} stage: {}", stage, e))?;
+        }
+        Ok(())
+    }
+
+    fn after_stage(&self, stage: Stage, ctx: &StageContext, outcome: &StageOutcome) -> Result<(), String> {
+        for hook in &self.hooks {
+            hook.after_stage(stage, ctx, outcome)
+                .map_err(|e| format!("Aborted after {} stage: {}", stage, e))?;
+        }
+        Ok(())
+    }
+
+    fn progress(&self, stage: Stage, ctx: &StageContext, detail: &Value) {
+        for hook in &self.hooks {
+            hook.progress(stage, ctx, detail);
+        }
+    }
+}
+
+/// Logs how long each stage of a job took.
+pub struct StageTimingLogger;
+
+impl PipelineHooks for StageTimingLogger {
+    fn after_stage(&self, stage: Stage, ctx: &StageContext, outcome: &StageOutcome) -> Result<(), String> {
+        println!(
+            "Job {}: {} stage took {}ms ({})",
+            ctx.job_id,
+            stage,
+            outcome.elapsed.as_millis(),
+            outcome.summary
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    struct Recorder(Arc<Mutex<Vec<String>>>);
+
+    impl PipelineHooks for Recorder {
+        fn before_stage(&self, stage: Stage, _ctx: &StageContext) -> Result<(), String> {
+            self.0.lock().unwrap().push(format!("before {}", stage));
+            Ok(())
+        }
+    }
+
+    struct AbortCompile;
+
+    impl PipelineHooks for AbortCompile {
+        fn before_stage(&self, stage: Stage, _ctx: &StageContext) -> Result<(), String> {
+            match stage {
+                Stage::Compile => Err("blocked".to_string()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn test_chain_runs_hooks_in_order_and_aborts() {
+        let seen = Arc::new(Mutex::new(vec![]));
+        let mut chain = HookChain::new();
+        chain.register(Box::new(Recorder(seen.clone())));
+        chain.register(Box::new(AbortCompile));
+        chain.register(Box::new(Recorder(seen.clone())));
+
+        let ctx = StageContext { job_id: "job-1", challenge_id: "c", language: "python", workspace: Path::new("/tmp") };
+        assert!(chain.before_stage(Stage::FetchFixtures, &ctx).is_ok());
+        assert_eq!(
+            chain.before_stage(Stage::Compile, &ctx),
+            Err("Aborted before compile stage: blocked".to_string())
+        );
+        let outcome = StageOutcome { elapsed: Duration::from_millis(5), summary: json!({}) };
+        assert!(chain.after_stage(Stage::Compile, &ctx, &outcome).is_ok());
+
+        // The aborting hook stops the chain before later hooks run
+        assert_eq!(*seen.lock().unwrap(), vec!["before fetch fixtures", "before fetch fixtures", "before compile"]);
+    }
+}
@@ -0,0 +1,279 @@
+use crate::fixtures::TestFixture;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Directories that never contain project sources.
+const SKIPPED_DIRS: &[&str] = &["target", "node_modules", ".git", ".fathuss", "__pycache__", "out"];
+
+/// Source files of a project and, for each, the project files it imports.
+/// Paths are relative to the workspace with `/` separators.
+pub type DependencyGraph = HashMap<String, HashSet<String>>;
+
+/// Build the import graph of the project sources in `workspace`.
+pub fn dependency_graph(workspace: &Path, language: &str) -> DependencyGraph {
+    let extensions: &[&str] = match language {
+        "rust" | "ink" | "cosmwasm" => &["rs"],
+        "javascript" => &["js", "mjs", "cjs"],
+        "typescript" => &["ts", "tsx", "js"],
+        "python" => &["py"],
+        _ => &[],
+    };
+
+    let files: HashSet<String> = source_files(workspace, extensions).into_iter().collect();
+    let mut graph = DependencyGraph::new();
+
+    for file in &files {
+        let source = std::fs::read_to_string(workspace.join(file)).unwrap_or_default();
+        let imports = match language {
+            "rust" | "ink" | "cosmwasm" => rust_imports(file, &source, &files),
+            "javascript" | "typescript" => js_imports(file, &source, &files),
+            "python" => python_imports(file, &source, &files),
+            _ => HashSet::new(),
+        };
+        graph.insert(file.clone(), imports);
+    }
+
+    graph
+}
+
+/// Ids of the fixtures whose targets (transitively) import a changed file.
+///
+/// Conservative by design: fixtures without declared targets, and any change
+/// to a file outside the source graph (manifests, build config, assets),
+/// select every fixture.
+pub fn impacted_fixtures(fixtures: &[TestFixture], graph: &DependencyGraph, changed_files: &[String]) -> HashSet<String> {
+    let changed: HashSet<String> = changed_files.iter().map(|f| normalize(f)).collect();
+    if changed.iter().any(|f| !graph.contains_key(f)) {
+        return fixtures.iter().map(|f| f.id.clone()).collect();
+    }
+
+    fixtures
+        .iter()
+        .filter(|fixture| {
+            fixture.targets.is_empty()
+                || fixture.targets.iter().any(|target| reaches_changed(graph, &normalize(target), &changed))
+        })
+        .map(|fixture| fixture.id.clone())
+        .collect()
+}
+
+fn reaches_changed(graph: &DependencyGraph, start: &str, changed: &HashSet<String>) -> bool {
+    let mut seen = HashSet::new();
+    let mut pending = vec![start.to_string()];
+
+    while let Some(file) = pending.pop() {
+        if changed.contains(&file) {
+            return true;
+        }
+        if !seen.insert(file.clone()) {
+            continue;
+        }
+        if let Some(imports) = graph.get(&file) {
+            pending.extend(imports.iter().cloned());
+        }
+    }
+
+    false
+}
+
+fn source_files(workspace: &Path, extensions: &[&str]) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut pending = vec![workspace.to_path_buf()];
+
+    while let Some(dir) = pending.pop() {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            match entry.file_type() {
+                Ok(ft) if ft.is_dir() && !SKIPPED_DIRS.contains(&name.as_str()) => pending.push(path),
+                Ok(ft) if ft.is_file() => {
+                    let matches = path.extension().map(|e| extensions.iter().any(|x| e == *x)).unwrap_or(false);
+                    if let (true, Ok(relative)) = (matches, path.strip_prefix(workspace)) {
+                        found.push(relative.to_string_lossy().replace('\\', "/"));
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    found
+}
+
+/// `mod x;` declarations and `use crate::x` / `use super::x` paths.
+fn rust_imports(file: &str, source: &str, files: &HashSet<String>) -> HashSet<String> {
+    let mod_decl = Regex::new(r"(?m)^\s*(?:pub(?:\([^)]*\))?\s+)?mod\s+(\w+)\s*;").unwrap();
+    let use_path = Regex::new(r"\b(crate|super)::(\w+)").unwrap();
+
+    let path = Path::new(file);
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    // Children of `foo.rs` live in `foo/`; children of lib.rs, main.rs and mod.rs live beside them
+    let module_dir = if matches!(stem.as_str(), "lib" | "main" | "mod") { dir.to_path_buf() } else { dir.join(&stem) };
+    let crate_root = files
+        .iter()
+        .find(|f| f.ends_with("lib.rs") || f.ends_with("main.rs"))
+        .and_then(|root| Path::new(root).parent().map(Path::to_path_buf))
+        .unwrap_or_default();
+
+    let mut imports = HashSet::new();
+    for caps in mod_decl.captures_iter(source) {
+        imports.extend(resolve_rust_module(&module_dir, &caps[1], files));
+    }
+    for caps in use_path.captures_iter(source) {
+        let base = if &caps[1] == "crate" { crate_root.clone() } else { dir.to_path_buf() };
+        imports.extend(resolve_rust_module(&base, &caps[2], files));
+    }
+    imports.remove(file);
+    imports
+}
+
+fn resolve_rust_module(dir: &Path, name: &str, files: &HashSet<String>) -> Option<String> {
+    [dir.join(format!("{}.rs", name)), dir.join(name).join("mod.rs")]
+        .iter()
+        .map(|p| normalize(&p.to_string_lossy()))
+        .find(|p| files.contains(p))
+}
+
+/// Relative `import ... from './x'`, `import('./x')` and `require('./x')`.
+fn js_imports(file: &str, source: &str, files: &HashSet<String>) -> HashSet<String> {
+    let import = Regex::new(r#"(?:from|import|require)\s*\(?\s*['"](\.{1,2}/[^'"]+)['"]"#).unwrap();
+    let dir = Path::new(file).parent().unwrap_or(Path::new(""));
+
+    import
+        .captures_iter(source)
+        .filter_map(|caps| {
+            let base = normalize(&dir.join(&caps[1]).to_string_lossy());
+            ["", ".ts", ".tsx", ".js", ".mjs", ".cjs", "/index.ts", "/index.js"]
+                .iter()
+                .map(|suffix| format!("{}{}", base, suffix))
+                .find(|candidate| files.contains(candidate))
+        })
+        .collect()
+}
+
+/// `import a.b` and `from a.b import c` / `from .a import b`.
+fn python_imports(file: &str, source: &str, files: &HashSet<String>) -> HashSet<String> {
+    let import = Regex::new(r"(?m)^\s*(?:from\s+(\.*[\w.]*)\s+import\s+([\w, ]+)|import\s+([\w.]+))").unwrap();
+    let dir = Path::new(file).parent().unwrap_or(Path::new(""));
+
+    let mut imports = HashSet::new();
+    for caps in import.captures_iter(source) {
+        let (module, names) = match (caps.get(1), caps.get(3)) {
+            (Some(from), _) => (from.as_str(), caps.get(2).map(|m| m.as_str()).unwrap_or("")),
+            (None, Some(module)) => (module.as_str(), ""),
+            _ => continue,
+        };
+
+        let dots = module.chars().take_while(|c| *c == '.').count();
+        let mut base: PathBuf = if dots == 0 {
+            PathBuf::new()
+        } else {
+            let mut base = dir.to_path_buf();
+            for _ in 1..dots {
+                base.pop();
+            }
+            base
+        };
+        for part in module[dots..].split('.').filter(|p| !p.is_empty()) {
+            base.push(part);
+        }
+
+        // `from pkg import mod` may name submodules rather than attributes
+        let mut candidates = vec![base.clone()];
+        candidates.extend(names.split(',').map(|n| base.join(n.trim())));
+        for candidate in candidates {
+            let candidate = normalize(&candidate.to_string_lossy());
+            for path in [format!("{}.py", candidate), format!("{}/__init__.py", candidate)] {
+                let path = path.trim_start_matches('/').to_string();
+                if files.contains(&path) && path != file {
+                    imports.insert(path);
+                }
+            }
+        }
+    }
+    imports
+}
+
+/// Normalize a relative path: `/` separators, no `./`, `..` applied.
+fn normalize(path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn fixture(id: &str, targets: &[&str]) -> TestFixture {
+        TestFixture {
+            id: id.to_string(),
+            name: id.to_string(),
+            description: String::new(),
+            input: Value::Null,
+            expected_output: Value::Null,
+            hidden: false,
+            timeout: 30,
+            gas_limit: 1_000_000,
+            targets: targets.iter().map(|t| t.to_string()).collect(),
+            input_file: None,
+            expected_output_file: None,
+            suite: None,
+            depends_on: Vec::new(),
+            setup: None,
+            teardown: None,
+        }
+    }
+
+    #[test]
+    fn test_impacted_fixtures_follow_imports() {
+        let workspace = tempfile::tempdir().unwrap();
+        let dir = workspace.path();
+        std::fs::create_dir_all(dir.join("src/utils")).unwrap();
+        std::fs::write(dir.join("src/cart.ts"), "import { total } from './utils/math';\n").unwrap();
+        std::fs::write(dir.join("src/users.ts"), "export const users = [];\n").unwrap();
+        std::fs::write(dir.join("src/utils/math.ts"), "export const total = 0;\n").unwrap();
+
+        let graph = dependency_graph(dir, "typescript");
+        let fixtures = vec![
+            fixture("cart", &["src/cart.ts"]),
+            fixture("users", &["src/users.ts"]),
+            fixture("untargeted", &[]),
+        ];
+
+        let impacted = impacted_fixtures(&fixtures, &graph, &["./src/utils/math.ts".to_string()]);
+        assert_eq!(impacted, ["cart", "untargeted"].iter().map(|s| s.to_string()).collect());
+
+        // Changes outside the source graph are treated as affecting everything
+        let impacted = impacted_fixtures(&fixtures, &graph, &["package.json".to_string()]);
+        assert_eq!(impacted.len(), 3);
+    }
+
+    #[test]
+    fn test_rust_and_python_imports() {
+        let files: HashSet<String> = ["src/main.rs", "src/parser.rs", "src/parser/lexer.rs", "pkg/__init__.py", "pkg/core.py", "app.py"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        assert_eq!(rust_imports("src/main.rs", "mod parser;\n", &files), ["src/parser.rs".to_string()].into());
+        assert_eq!(rust_imports("src/parser.rs", "mod lexer;\nuse crate::parser::x;", &files), ["src/parser/lexer.rs".to_string()].into());
+        assert_eq!(python_imports("app.py", "from pkg import core\n", &files), ["pkg/__init__.py".to_string(), "pkg/core.py".to_string()].into());
+    }
+}
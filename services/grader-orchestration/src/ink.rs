@@ -0,0 +1,69 @@
+use serde_json::{json, Value};
+use std::path::Path;
+
+const INK_CARGO_TOML: &str = r#"
+[package]
+name = "graded_contract"
+version = "0.1.0"
+edition = "2021"
+
+[dependencies]
+ink = { version = "5.0", default-features = false }
+
+[lib]
+path = "lib.rs"
+
+[features]
+default = ["std"]
+std = ["ink/std"]
+ink-as-dependency = []
+"#;
+
+/// Lay out an ink! contract crate containing the submission.
+pub fn write_ink_project(project_dir: &Path, code: &str) -> Result<(), String> {
+    std::fs::write(project_dir.join("Cargo.toml"), INK_CARGO_TOML)
+        .map_err(|e| format!("Failed to write Cargo.toml: {}", e))?;
+    std::fs::write(project_dir.join("lib.rs"), code)
+        .map_err(|e| format!("Failed to write lib.rs: {}", e))?;
+    Ok(())
+}
+
+/// Summarize the `.contract` bundle produced by `cargo contract build`.
+pub fn read_contract_metadata(project_dir: &Path) -> Result<Value, String> {
+    let ink_dir = project_dir.join("target").join("ink");
+    let bundle_path = std::fs::read_dir(&ink_dir)
+        .map_err(|e| format!("Failed to read {}: {}", ink_dir.display(), e))?
+        .flatten()
+        .map(|entry| entry.path())
+        .find(|path| path.extension().map(|ext| ext == "contract").unwrap_or(false))
+        .ok_or("No .contract artifact produced")?;
+
+    let bundle: Value = serde_json::from_str(
+        &std::fs::read_to_string(&bundle_path).map_err(|e| format!("Failed to read contract bundle: {}", e))?,
+    )
+    .map_err(|e| format!("Failed to parse contract bundle: {}", e))?;
+
+    let source = bundle.get("source").cloned().unwrap_or(json!({}));
+    let wasm_size = source
+        .get("wasm")
+        .and_then(|w| w.as_str())
+        .map(|hex| hex.trim_start_matches("0x").len() / 2)
+        .unwrap_or(0);
+
+    let messages: Vec<Value> = bundle
+        .pointer("/spec/messages")
+        .and_then(|m| m.as_array())
+        .map(|messages| messages.iter().filter_map(|m| m.get("label").cloned()).collect())
+        .unwrap_or_default();
+
+    Ok(json!({
+        "file": bundle_path.file_name().map(|n| n.to_string_lossy().to_string()),
+        "name": bundle.pointer("/contract/name"),
+        "version": bundle.pointer("/contract/version"),
+        "codeHash": source.get("hash"),
+        "language": source.get("language"),
+        "compiler": source.get("compiler"),
+        "wasmSize": wasm_size,
+        "messages": messages
+    }))
+}
@@ -0,0 +1,159 @@
+use crate::artifacts::ArtifactHandle;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+
+/// Hashes of the artifacts produced by the compile stage of a single job.
+///
+/// Anything executed from the workspace must appear here with an unchanged
+/// hash, so binaries dropped into the workspace by other means (a build
+/// script downloading a precompiled payload, a test overwriting the binary)
+/// are refused.
+#[derive(Debug, Default)]
+pub struct ArtifactManifest {
+    preexisting: HashSet<String>,
+    artifacts: HashMap<PathBuf, String>,
+}
+
+impl ArtifactManifest {
+    /// Snapshot the executables already present before compilation starts.
+    pub fn before_compile(workspace: &Path) -> Self {
+        let preexisting = find_executables(workspace)
+            .iter()
+            .filter_map(|path| hash_file(path).ok())
+            .collect();
+
+        Self {
+            preexisting,
+            artifacts: HashMap::new(),
+        }
+    }
+
+    /// Record the compiler output at `relative_path` as a trusted artifact,
+    /// returning its handle for later stages.
+    pub fn record(&mut self, workspace: &Path, relative_path: &str) -> Result<ArtifactHandle, String> {
+        let handle = ArtifactHandle::for_file(workspace, relative_path)?;
+
+        if self.preexisting.contains(&handle.sha256) {
+            return Err(format!(
+                "Artifact {} is identical to a binary present before compilation",
+                relative_path
+            ));
+        }
+
+        // Build scripts should only generate sources, never executables
+        if let Some(payload) = find_build_script_executables(workspace).first() {
+            return Err(format!(
+                "Build script produced an executable: {}",
+                payload.display()
+            ));
+        }
+
+        self.artifacts.insert(workspace.join(relative_path), handle.sha256.clone());
+        Ok(handle)
+    }
+
+    /// Check that `command` may be executed. Commands outside the workspace
+    /// (toolchain binaries such as `forge`) are not subject to verification.
+    pub fn verify(&self, workspace: &Path, command: &str) -> Result<(), String> {
+        if !command.starts_with("./") && !command.starts_with('/') {
+            return Ok(());
+        }
+
+        let path = if command.starts_with('/') {
+            PathBuf::from(command)
+        } else {
+            workspace.join(command.trim_start_matches("./"))
+        };
+
+        if !path.starts_with(workspace) {
+            return Ok(());
+        }
+
+        let expected = self
+            .artifacts
+            .get(&path)
+            .ok_or_else(|| format!("Refusing to execute unrecorded binary: {}", command))?;
+
+        let actual = hash_file(&path)?;
+        if &actual != expected {
+            return Err(format!("Binary {} was modified after compilation", command));
+        }
+
+        Ok(())
+    }
+}
+
+fn hash_file(path: &Path) -> Result<String, String> {
+    crate::artifacts::hash_file(path).map(|(hash, _)| hash)
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::io::Read;
+
+    let mut magic = [0u8; 4];
+    std::fs::File::open(path)
+        .and_then(|mut f| f.read_exact(&mut magic))
+        .map(|_| magic == ELF_MAGIC)
+        .unwrap_or(false)
+}
+
+fn find_executables(dir: &Path) -> Vec<PathBuf> {
+    let mut found = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let entries = match std::fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(ft) if ft.is_dir() => pending.push(path),
+                Ok(ft) if ft.is_file() && is_executable(&path) => found.push(path),
+                _ => {}
+            }
+        }
+    }
+
+    found
+}
+
+/// Executables inside cargo's `target/*/build/*/out` directories, where build
+/// scripts write their generated output.
+fn find_build_script_executables(workspace: &Path) -> Vec<PathBuf> {
+    find_executables(&workspace.join("target"))
+        .into_iter()
+        .filter(|path| {
+            path.components().any(|c| c.as_os_str() == "out")
+                && path.components().any(|c| c.as_os_str() == "build")
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify_rejects_unrecorded_and_modified_binaries() {
+        let workspace = tempfile::tempdir().unwrap();
+        let dir = workspace.path();
+
+        let mut manifest = ArtifactManifest::before_compile(dir);
+
+        std::fs::write(dir.join("app"), b"\x7fELF compiled").unwrap();
+        std::fs::write(dir.join("payload"), b"\x7fELF smuggled").unwrap();
+        manifest.record(dir, "app").unwrap();
+
+        assert!(manifest.verify(dir, "./app").is_ok());
+        assert!(manifest.verify(dir, "./payload").is_err());
+        assert!(manifest.verify(dir, "forge").is_ok());
+
+        std::fs::write(dir.join("app"), b"\x7fELF tampered").unwrap();
+        assert!(manifest.verify(dir, "./app").is_err());
+    }
+}
@@ -0,0 +1,213 @@
+use crate::sandbox::{execute_in_sandbox, SandboxConfig};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// A property every fuzzed run must keep ("output is sorted", "balance never
+/// negative"), checked by a script or, for Solidity, a forge invariant test.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Invariant {
+    pub name: String,
+    /// Checker command, run in a read-only copy of the challenge files with
+    /// the input file and a file of the program's stdout as arguments; a
+    /// non-zero exit is a violation, explained by its output
+    #[serde(default)]
+    pub check: Option<String>,
+    /// Forge invariant test function (`invariant_*`), run once per campaign
+    /// since forge generates its own call sequences
+    #[serde(default)]
+    pub forge_test: Option<String>,
+}
+
+impl Invariant {
+    pub fn validate(&self) -> Result<(), String> {
+        match (&self.check, &self.forge_test) {
+            (Some(_), None) => Ok(()),
+            (None, Some(test)) if test.starts_with("invariant") && test.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => Ok(()),
+            (None, Some(test)) => Err(format!("Invalid forge invariant test name for invariant {}: {}", self.name, test)),
+            _ => Err(format!("Invariant {} needs exactly one of check or forgeTest", self.name)),
+        }
+    }
+}
+
+/// An invariant some fuzzed input broke, reported apart from crashes.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InvariantViolation {
+    pub invariant: String,
+    /// First input that broke it; forge invariant tests have none
+    pub input: Option<Value>,
+    pub message: String,
+    pub occurrences: usize,
+}
+
+/// Record a violation, counting repeats of an invariant already broken.
+pub fn record_violation(violations: &mut Vec<InvariantViolation>, invariant: &str, input: Option<&Value>, message: String) {
+    match violations.iter_mut().find(|v| v.invariant == invariant) {
+        Some(existing) => existing.occurrences += 1,
+        None => violations.push(InvariantViolation {
+            invariant: invariant.to_string(),
+            input: input.cloned(),
+            message,
+            occurrences: 1,
+        }),
+    }
+}
+
+/// Where checker scripts run: a read-only copy of the challenge's files,
+/// taken before the submission is written into the workspace, so a submission
+/// can't rewrite its checkers. Each run's input and output are written next
+/// to it, also outside the workspace.
+pub struct CheckerDir {
+    root: TempDir,
+}
+
+impl CheckerDir {
+    /// Snapshot the challenge files in `workspace`.
+    pub fn snapshot(workspace: &Path) -> Result<Self, String> {
+        let root = tempfile::tempdir().map_err(|e| format!("Failed to create checker dir: {}", e))?;
+        let files = root.path().join("challenge");
+        crate::replay::copy_dir(workspace, &files)?;
+        set_read_only(&files, true)?;
+        std::fs::create_dir(root.path().join("runs")).map_err(|e| format!("Failed to create checker dir: {}", e))?;
+        Ok(Self { root })
+    }
+
+    /// Directory checkers run in.
+    pub fn path(&self) -> PathBuf {
+        self.root.path().join("challenge")
+    }
+
+    /// Path of a run's input or output file.
+    pub fn run_file(&self, name: &str) -> PathBuf {
+        self.root.path().join("runs").join(name)
+    }
+}
+
+impl Drop for CheckerDir {
+    fn drop(&mut self) {
+        // The directories must be writable again for the copy to be removed
+        if let Err(e) = set_read_only(&self.path(), false) {
+            eprintln!("Warning: Failed to clean up checker dir: {}", e);
+        }
+    }
+}
+
+/// Make a tree read-only, directories included, or writable by its owner again.
+fn set_read_only(path: &Path, read_only: bool) -> Result<(), String> {
+    let metadata = std::fs::symlink_metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let mode = match (metadata.is_dir(), read_only) {
+        (true, true) => 0o555,
+        (true, false) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    };
+    // Directories are opened up before and closed after their entries
+    let set_mode = || std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).map_err(|e| format!("Failed to set permissions of {}: {}", path.display(), e));
+    if metadata.is_dir() {
+        if !read_only {
+            set_mode()?;
+        }
+        for entry in std::fs::read_dir(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))? {
+            set_read_only(&entry.map_err(|e| e.to_string())?.path(), read_only)?;
+        }
+        if read_only {
+            set_mode()?;
+        }
+        return Ok(());
+    }
+    set_mode()
+}
+
+/// Run a checker script against one fuzzed run. `Ok(Some(message))` when
+/// the invariant doesn't hold.
+pub async fn check(command: &str, input_file: &Path, output_file: &Path, checkers: &CheckerDir) -> Result<Option<String>, String> {
+    let sandbox_config = SandboxConfig {
+        time_limit: Duration::from_secs(5),
+        memory_limit: 256 * 1024 * 1024, // 256MB
+        cpu_limit: 25,
+        network_disabled: true,
+        max_file_size: 1024 * 1024, // 1MB
+        max_processes: 5,
+        disk_quota: 10 * 1024 * 1024, // 10MB
+    };
+    // The files are passed as positional parameters, never spliced into the command
+    let script = format!("{} \"$@\"", command);
+    let (input_file, output_file) = (input_file.to_string_lossy(), output_file.to_string_lossy());
+    let result = execute_in_sandbox("sh", &["-c", &script, "invariant", &input_file, &output_file], &sandbox_config, &checkers.path()).await?;
+    Ok(violation_message(result.success && result.exit_code == Some(0), &result.stdout, &result.stderr))
+}
+
+/// Run a forge invariant test. `Ok(Some(message))` when it fails.
+pub async fn run_forge_test(test: &str, working_dir: &Path) -> Result<Option<String>, String> {
+    let sandbox_config = SandboxConfig {
+        time_limit: Duration::from_secs(120), // forge runs many call sequences
+        memory_limit: 1024 * 1024 * 1024, // 1GB
+        cpu_limit: 50,
+        network_disabled: true,
+        max_file_size: 100 * 1024 * 1024, // 100MB
+        max_processes: 10,
+        disk_quota: 500 * 1024 * 1024, // 500MB
+    };
+    let pattern = format!("^{}$", test);
+    let result = execute_in_sandbox("forge", &["test", "--match-test", &pattern], &sandbox_config, working_dir).await?;
+    Ok(violation_message(result.success, &result.stdout, &result.stderr))
+}
+
+fn violation_message(held: bool, stdout: &str, stderr: &str) -> Option<String> {
+    if held {
+        return None;
+    }
+    let output = if stderr.trim().is_empty() { stdout } else { stderr };
+    Some(match output.trim() {
+        "" => "Invariant check failed".to_string(),
+        message => message.to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_invariants_validate_and_violations_dedupe() {
+        let parse = |v: Value| serde_json::from_value::<Invariant>(v).unwrap().validate();
+        assert!(parse(json!({ "name": "sorted", "check": "python3 checks/sorted.py" })).is_ok());
+        assert!(parse(json!({ "name": "solvent", "forgeTest": "invariant_solvent" })).is_ok());
+        assert!(parse(json!({ "name": "solvent", "forgeTest": "invariant_x; rm -rf /" })).is_err());
+        assert!(parse(json!({ "name": "neither" })).is_err());
+
+        let mut violations = Vec::new();
+        record_violation(&mut violations, "sorted", Some(&json!([3, 1])), "not sorted".to_string());
+        record_violation(&mut violations, "sorted", Some(&json!([2, 1])), "not sorted".to_string());
+        assert_eq!((violations.len(), violations[0].occurrences), (1, 2));
+        assert_eq!(violations[0].input, Some(json!([3, 1])));
+
+        assert_eq!(violation_message(false, "", "balance went negative\n"), Some("balance went negative".to_string()));
+        assert_eq!(violation_message(true, "", ""), None);
+    }
+
+    #[test]
+    fn test_checker_dir_is_a_read_only_snapshot() {
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(workspace.path().join("checks")).unwrap();
+        std::fs::write(workspace.path().join("checks/sorted.py"), "exit(0)").unwrap();
+
+        let checkers = CheckerDir::snapshot(workspace.path()).unwrap();
+        let root = checkers.root.path().to_path_buf();
+        // Later changes to the workspace don't reach the snapshot
+        std::fs::write(workspace.path().join("checks/sorted.py"), "exit(1)").unwrap();
+        assert_eq!(std::fs::read_to_string(checkers.path().join("checks/sorted.py")).unwrap(), "exit(0)");
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!((mode(&checkers.path().join("checks")), mode(&checkers.path().join("checks/sorted.py"))), (0o555, 0o444));
+        std::fs::write(checkers.run_file("input.json"), "[]").unwrap();
+
+        drop(checkers);
+        assert!(!root.exists());
+    }
+}
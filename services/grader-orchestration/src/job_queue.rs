@@ -0,0 +1,413 @@
+use crate::backoff::RetryPolicy;
+use crate::kafka_queue::KafkaQueue;
+use crate::nats_queue::NatsQueue;
+use crate::sqs_queue::SqsQueue;
+use futures::future::BoxFuture;
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{OnceCell, Semaphore};
+
+/// How long a poll of the queue waits for a job before returning empty.
+pub(crate) const POLL_TIMEOUT: Duration = Duration::from_secs(5);
+/// Deliveries of a Redis job before it is dead-lettered.
+const DEFAULT_MAX_ATTEMPTS: u64 = 3;
+
+/// Where grading jobs come from: HTTP requests to `/grade` only, or a queue
+/// the platform backend dispatches to as well.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WorkerMode {
+    Http,
+    Queue,
+}
+
+impl WorkerMode {
+    /// `WORKER_MODE`, `http` (default) or `queue`.
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("WORKER_MODE").as_deref() {
+            Err(_) | Ok("") | Ok("http") => Ok(WorkerMode::Http),
+            Ok("queue") => Ok(WorkerMode::Queue),
+            Ok(other) => Err(format!("Unknown WORKER_MODE: {} (expected http or queue)", other)),
+        }
+    }
+}
+
+/// A grading job taken from a queue.
+#[derive(Debug, Clone)]
+pub struct QueuedJob {
+    /// The job as a `/grade` payload
+    pub payload: Value,
+    /// What the queue needs to acknowledge the job
+    pub receipt: String,
+}
+
+/// A source of grading jobs. Jobs stay owned by the worker until completed,
+/// so one whose worker dies is handed out again.
+pub trait JobQueue: Send + Sync {
+    /// The next job, or `None` if none arrived within the poll timeout.
+    fn next(&self) -> BoxFuture<'_, Result<Option<QueuedJob>, String>>;
+
+    /// Publish the job's result under its `jobId` and acknowledge it.
+    fn complete<'a>(&'a self, job: &'a QueuedJob, result: &'a Value) -> BoxFuture<'a, Result<(), String>>;
+
+    /// Jobs graded at once.
+    fn max_in_flight(&self) -> usize {
+        1
+    }
+
+    /// How often `keep_alive` is called while a job is graded, for queues
+    /// that hand a job out again once its lease runs out.
+    fn keep_alive_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Extend the lease of a job still being graded.
+    fn keep_alive<'a>(&'a self, _job: &'a QueuedJob) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// The queue named by `QUEUE_BACKEND`, `redis` (default), `nats`, `kafka` or
+/// `sqs`.
+pub async fn connect(worker_type: &str) -> Result<Arc<dyn JobQueue>, String> {
+    match std::env::var("QUEUE_BACKEND").as_deref() {
+        Err(_) | Ok("") | Ok("redis") => {
+            let queue = RedisQueue::from_env(worker_type)?;
+            match queue.requeue_abandoned().await {
+                Ok(0) => {}
+                Ok(requeued) => println!("Requeued {} jobs abandoned by a previous run", requeued),
+                Err(e) => eprintln!("Warning: Failed to requeue abandoned jobs: {}", e),
+            }
+            Ok(Arc::new(queue))
+        }
+        Ok("nats") => Ok(Arc::new(NatsQueue::connect(worker_type).await?)),
+        Ok("kafka") => Ok(Arc::new(KafkaQueue::from_env(worker_type)?)),
+        Ok("sqs") => Ok(Arc::new(SqsQueue::from_env()?)),
+        Ok(other) => Err(format!("Unknown QUEUE_BACKEND: {} (expected redis, nats, kafka or sqs)", other)),
+    }
+}
+
+/// Redis list queue. Jobs are pushed with `LPUSH` onto the queue key and
+/// atomically moved onto this worker's processing list while graded, so the
+/// jobs of a worker that died are requeued when it restarts under the same
+/// consumer name. Results are written to `{result prefix}{jobId}`. A job
+/// delivered more than `max_attempts` times, such as one that crashes every
+/// worker grading it, is moved to the dead-letter list instead and given a
+/// failed result.
+pub struct RedisQueue {
+    client: redis::Client,
+    connection: OnceCell<redis::aio::ConnectionManager>,
+    queue_key: String,
+    processing_key: String,
+    /// Deliveries so far of each job, by digest of the message
+    attempts_key: String,
+    dead_letter_key: String,
+    max_attempts: u64,
+    result_prefix: String,
+    result_ttl_secs: u64,
+}
+
+impl RedisQueue {
+    /// `REDIS_URL` (required), `REDIS_QUEUE_KEY` (default
+    /// `grader_jobs:{worker_type}`), `REDIS_RESULT_PREFIX` (default
+    /// `grader_results:`), `REDIS_RESULT_TTL_SECS` (default 86400),
+    /// `REDIS_CONSUMER_NAME` (default the hostname), `REDIS_MAX_ATTEMPTS`
+    /// (default 3) and `REDIS_DEAD_LETTER_KEY` (default `{queue key}:dead`).
+    pub fn from_env(worker_type: &str) -> Result<Self, String> {
+        let url = std::env::var("REDIS_URL").map_err(|_| "REDIS_URL is required in queue mode".to_string())?;
+        let client = redis::Client::open(url.as_str()).map_err(|e| format!("Invalid REDIS_URL: {}", e))?;
+        let queue_key = std::env::var("REDIS_QUEUE_KEY").unwrap_or_else(|_| format!("grader_jobs:{}", worker_type));
+        let consumer = std::env::var("REDIS_CONSUMER_NAME")
+            .or_else(|_| std::env::var("HOSTNAME"))
+            .unwrap_or_else(|_| uuid::Uuid::new_v4().to_string());
+        let max_attempts = match std::env::var("REDIS_MAX_ATTEMPTS") {
+            Ok(v) => v.parse().ok().filter(|n| *n > 0).ok_or_else(|| format!("Invalid REDIS_MAX_ATTEMPTS: {}", v))?,
+            Err(_) => DEFAULT_MAX_ATTEMPTS,
+        };
+        Ok(Self {
+            client,
+            connection: OnceCell::new(),
+            processing_key: format!("{}:processing:{}", queue_key, consumer),
+            attempts_key: format!("{}:attempts", queue_key),
+            dead_letter_key: std::env::var("REDIS_DEAD_LETTER_KEY").unwrap_or_else(|_| format!("{}:dead", queue_key)),
+            max_attempts,
+            queue_key,
+            result_prefix: std::env::var("REDIS_RESULT_PREFIX").unwrap_or_else(|_| "grader_results:".to_string()),
+            result_ttl_secs: std::env::var("REDIS_RESULT_TTL_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(86400),
+        })
+    }
+
+    /// The shared connection, which reconnects by itself after failures.
+    async fn connection(&self) -> Result<redis::aio::ConnectionManager, String> {
+        let connection = self
+            .connection
+            .get_or_try_init(|| redis::aio::ConnectionManager::new(self.client.clone()))
+            .await
+            .map_err(|e| format!("Failed to connect to Redis: {}", e))?;
+        Ok(connection.clone())
+    }
+
+    async fn query<T: redis::FromRedisValue>(&self, command: &redis::Cmd) -> Result<T, String> {
+        command.query_async(&mut self.connection().await?).await.map_err(|e| format!("Redis command failed: {}", e))
+    }
+
+    /// Move jobs left on this worker's processing list by a previous run
+    /// back onto the queue, ahead of the others. Returns how many there were.
+    pub async fn requeue_abandoned(&self) -> Result<usize, String> {
+        let mut requeued = 0;
+        loop {
+            let mut command = redis::cmd("LMOVE");
+            command.arg(&self.processing_key).arg(&self.queue_key).arg("RIGHT").arg("RIGHT");
+            let moved: Option<String> = self.query(&command).await?;
+            if moved.is_none() {
+                return Ok(requeued);
+            }
+            requeued += 1;
+        }
+    }
+
+    /// Move a job delivered too often from the processing list to the
+    /// dead-letter list, with a failed result for it.
+    async fn dead_letter(&self, raw: &str, payload: &Value, attempts: u64) -> Result<(), String> {
+        let job_id = job_id(payload);
+        eprintln!("Warning: Moving job {} to {} after {} attempts", job_id, self.dead_letter_key, attempts - 1);
+        let result = json!({
+            "jobId": job_id,
+            "status": "failed",
+            "error": format!("Job was abandoned by its worker {} times", attempts - 1)
+        });
+        let outcome: Result<(), redis::RedisError> = redis::pipe()
+            .atomic()
+            .cmd("LPUSH")
+            .arg(&self.dead_letter_key)
+            .arg(raw)
+            .ignore()
+            .cmd("LREM")
+            .arg(&self.processing_key)
+            .arg(1)
+            .arg(raw)
+            .ignore()
+            .cmd("HDEL")
+            .arg(&self.attempts_key)
+            .arg(digest(raw))
+            .ignore()
+            .cmd("SET")
+            .arg(format!("{}{}", self.result_prefix, job_id))
+            .arg(result.to_string())
+            .arg("EX")
+            .arg(self.result_ttl_secs)
+            .ignore()
+            .query_async(&mut self.connection().await?)
+            .await;
+        outcome.map_err(|e| format!("Failed to dead-letter job {}: {}", job_id, e))
+    }
+}
+
+/// Short digest of a message, to key it by.
+fn digest(raw: &str) -> String {
+    hex::encode(&Sha256::digest(raw.as_bytes())[..16])
+}
+
+/// The job's `jobId`, or for a job without one an id derived from the job
+/// itself, so every delivery of it is graded and reported under the same id.
+pub(crate) fn job_id(payload: &Value) -> String {
+    match payload.get("jobId").and_then(Value::as_str) {
+        Some(job_id) => job_id.to_string(),
+        None => format!("job-{}", digest(&payload.to_string())),
+    }
+}
+
+impl JobQueue for RedisQueue {
+    fn next(&self) -> BoxFuture<'_, Result<Option<QueuedJob>, String>> {
+        Box::pin(async move {
+            let mut command = redis::cmd("BLMOVE");
+            command
+                .arg(&self.queue_key)
+                .arg(&self.processing_key)
+                .arg("RIGHT")
+                .arg("LEFT")
+                .arg(POLL_TIMEOUT.as_secs());
+            let raw: Option<String> = self.query(&command).await?;
+            let raw = match raw {
+                Some(raw) => raw,
+                None => return Ok(None),
+            };
+            match serde_json::from_str::<Value>(&raw) {
+                Ok(payload) if payload.is_object() => {
+                    let mut command = redis::cmd("HINCRBY");
+                    command.arg(&self.attempts_key).arg(digest(&raw)).arg(1);
+                    let attempts: u64 = self.query(&command).await?;
+                    if attempts > self.max_attempts {
+                        self.dead_letter(&raw, &payload, attempts).await?;
+                        return Ok(None);
+                    }
+                    Ok(Some(QueuedJob { payload, receipt: raw }))
+                },
+                _ => {
+                    // Nothing to grade or to report a result under, so drop it
+                    eprintln!("Warning: Dropping malformed job from {}: {}", self.queue_key, raw);
+                    let mut command = redis::cmd("LREM");
+                    command.arg(&self.processing_key).arg(1).arg(&raw);
+                    self.query::<i64>(&command).await?;
+                    Ok(None)
+                }
+            }
+        })
+    }
+
+    fn complete<'a>(&'a self, job: &'a QueuedJob, result: &'a Value) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let job_id = job.payload.get("jobId").and_then(|v| v.as_str()).unwrap_or_default();
+            let outcome: Result<(), redis::RedisError> = redis::pipe()
+                .atomic()
+                .cmd("SET")
+                .arg(format!("{}{}", self.result_prefix, job_id))
+                .arg(result.to_string())
+                .arg("EX")
+                .arg(self.result_ttl_secs)
+                .ignore()
+                .cmd("LREM")
+                .arg(&self.processing_key)
+                .arg(1)
+                .arg(&job.receipt)
+                .ignore()
+                .cmd("HDEL")
+                .arg(&self.attempts_key)
+                .arg(digest(&job.receipt))
+                .ignore()
+                .query_async(&mut self.connection().await?)
+                .await;
+            outcome.map_err(|e| format!("Failed to publish result of job {}: {}", job_id, e))
+        })
+    }
+}
+
+/// Take one job from the queue, grade it with `process` and publish the
+/// result. Returns whether there was a job.
+pub async fn consume_one<F, Fut>(queue: &dyn JobQueue, process: &F) -> Result<bool, String>
+where
+    F: Fn(Value) -> Fut,
+    Fut: Future<Output = Value>,
+{
+    match queue.next().await? {
+        Some(job) => process_job(queue, job, process).await.map(|_| true),
+        None => Ok(false),
+    }
+}
+
+/// Grade a job with `process` and publish the result. Jobs without a
+/// `jobId` are given one derived from their content.
+async fn process_job<F, Fut>(queue: &dyn JobQueue, mut job: QueuedJob, process: &F) -> Result<(), String>
+where
+    F: Fn(Value) -> Fut,
+    Fut: Future<Output = Value>,
+{
+    let job_id = job_id(&job.payload);
+    if let Some(payload) = job.payload.as_object_mut() {
+        payload.insert("jobId".to_string(), json!(job_id));
+    }
+    let grading = process(job.payload.clone());
+    let result = match queue.keep_alive_interval() {
+        Some(interval) => {
+            tokio::pin!(grading);
+            let mut ticks = tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                tokio::select! {
+                    result = &mut grading => break result,
+                    _ = ticks.tick() => {
+                        if let Err(e) = queue.keep_alive(&job).await {
+                            eprintln!("Warning: Failed to extend the lease of a queued job: {}", e);
+                        }
+                    }
+                }
+            }
+        }
+        None => grading.await,
+    };
+    queue.complete(&job, &result).await
+}
+
+/// Grade jobs from the queue, up to its `max_in_flight` at once, until the
+/// worker stops, backing off while the queue is unreachable.
+pub async fn run_consumer<F, Fut>(queue: Arc<dyn JobQueue>, process: F)
+where
+    F: Fn(Value) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = Value> + Send + 'static,
+{
+    let backoff = RetryPolicy { max_attempts: u32::MAX, base_delay: Duration::from_millis(500), max_delay: Duration::from_secs(30) };
+    let process = Arc::new(process);
+    let slots = Arc::new(Semaphore::new(queue.max_in_flight()));
+    let mut failures = 0;
+    loop {
+        let slot = slots.clone().acquire_owned().await.expect("job slots are never closed");
+        let job = match queue.next().await {
+            Ok(job) => {
+                failures = 0;
+                job
+            }
+            Err(e) => {
+                failures += 1;
+                eprintln!("Warning: Job queue error: {}", e);
+                tokio::time::sleep(backoff.delay(failures)).await;
+                continue;
+            }
+        };
+        if let Some(job) = job {
+            let (queue, process) = (queue.clone(), process.clone());
+            tokio::spawn(async move {
+                if let Err(e) = process_job(queue.as_ref(), job, process.as_ref()).await {
+                    eprintln!("Warning: {}", e);
+                }
+                drop(slot);
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::Mutex as StdMutex;
+
+    #[derive(Default)]
+    struct MemoryQueue {
+        pending: StdMutex<VecDeque<QueuedJob>>,
+        results: StdMutex<Vec<(String, Value)>>,
+    }
+
+    impl JobQueue for MemoryQueue {
+        fn next(&self) -> BoxFuture<'_, Result<Option<QueuedJob>, String>> {
+            Box::pin(async move { Ok(self.pending.lock().unwrap().pop_front()) })
+        }
+
+        fn complete<'a>(&'a self, job: &'a QueuedJob, result: &'a Value) -> BoxFuture<'a, Result<(), String>> {
+            Box::pin(async move {
+                let job_id = job.payload["jobId"].as_str().unwrap_or_default().to_string();
+                self.results.lock().unwrap().push((job_id, result.clone()));
+                Ok(())
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_consume_grades_and_publishes_results() {
+        let queue = MemoryQueue::default();
+        for payload in [json!({ "jobId": "job-1", "code": "a" }), json!({ "code": "b" })] {
+            queue.pending.lock().unwrap().push_back(QueuedJob { receipt: payload.to_string(), payload });
+        }
+        let process = |payload: Value| async move { json!({ "graded": payload["code"] }) };
+
+        while consume_one(&queue, &process).await.unwrap() {}
+
+        let results = queue.results.lock().unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0], ("job-1".to_string(), json!({ "graded": "a" })));
+        // Derived from the job, so a redelivery is reported under the same id
+        assert_eq!(results[1].0, job_id(&json!({ "code": "b" })));
+        assert_ne!(results[1].0, job_id(&json!({ "code": "c" })));
+        assert_eq!(results[1].1, json!({ "graded": "b" }));
+    }
+}
@@ -0,0 +1,311 @@
+use crate::auth::{Client, Scope};
+use crate::hooks::{PipelineHooks, Stage, StageContext, StageOutcome};
+use chrono::{DateTime, Utc};
+use futures::stream::{self, Stream, StreamExt};
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+const DEFAULT_RETENTION: Duration = Duration::from_secs(3600);
+/// Events a slow subscriber can fall behind by before it misses some.
+const EVENT_BUFFER: usize = 256;
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed { result: Value },
+    Failed { error: String },
+}
+
+/// A job submitted through `POST /jobs`, as reported by `GET /jobs/{id}`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AsyncJob {
+    pub job_id: String,
+    #[serde(flatten)]
+    pub status: JobStatus,
+    pub submitted_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+}
+
+/// Something that happened to a job, streamed by `GET /jobs/{id}/events`:
+/// `status` changes, with the job as `GET /jobs/{id}` reports it, `stage`
+/// transitions and `progress` within a stage.
+#[derive(Debug, Clone)]
+pub struct JobEvent {
+    pub kind: &'static str,
+    pub data: Value,
+    /// Set on the status event of a finished job, the last of its events
+    pub last: bool,
+}
+
+struct Entry {
+    job: AsyncJob,
+    /// Id of the client that submitted the job, the only one besides admins
+    /// that may see it
+    owner: String,
+    /// Every event so far, replayed to subscribers that come in late
+    events: Vec<JobEvent>,
+    sender: broadcast::Sender<JobEvent>,
+}
+
+/// Grading jobs run in the background, by job id. Finished jobs are kept for
+/// the retention period, in memory, so they are lost when the worker restarts.
+/// A job is only visible to the client that submitted it and to admins.
+pub struct JobRegistry {
+    jobs: Mutex<HashMap<String, Entry>>,
+    retention: Duration,
+}
+
+impl JobRegistry {
+    pub fn new(retention: Duration) -> Self {
+        Self { jobs: Mutex::new(HashMap::new()), retention }
+    }
+
+    /// `ASYNC_JOB_RETENTION_SECS`, how long finished jobs are kept (default 3600).
+    pub fn from_env() -> Self {
+        let retention = std::env::var("ASYNC_JOB_RETENTION_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_RETENTION);
+        Self::new(retention)
+    }
+
+    /// Run `grading` in the background under `job_id`, for the client
+    /// `owner`. Returns `false` without running it if a job with that id is
+    /// still known. A job whose grading panics fails.
+    pub fn submit<Fut>(self: &Arc<Self>, job_id: &str, owner: &str, grading: Fut) -> bool
+    where
+        Fut: Future<Output = Value> + Send + 'static,
+    {
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            self.prune(&mut jobs, Utc::now());
+            if jobs.contains_key(job_id) {
+                return false;
+            }
+            let job = AsyncJob { job_id: job_id.to_string(), status: JobStatus::Queued, submitted_at: Utc::now(), finished_at: None };
+            let queued = JobEvent { kind: "status", data: json!(job), last: false };
+            let entry = Entry { job, owner: owner.to_string(), events: vec![queued], sender: broadcast::channel(EVENT_BUFFER).0 };
+            jobs.insert(job_id.to_string(), entry);
+        }
+
+        let registry = self.clone();
+        let id = job_id.to_string();
+        tokio::spawn(async move {
+            registry.set(&id, JobStatus::Running);
+            // Graded in a task of its own, so a panic fails the job instead
+            // of leaving it running
+            let status = match tokio::spawn(grading).await {
+                Ok(result) => match (result.get("status").and_then(|v| v.as_str()), result.get("error").and_then(|v| v.as_str())) {
+                    (Some("failed"), Some(error)) => JobStatus::Failed { error: error.to_string() },
+                    _ => JobStatus::Completed { result },
+                },
+                Err(e) => {
+                    eprintln!("Warning: Grading job {} panicked: {}", id, e);
+                    JobStatus::Failed { error: "Grading failed unexpectedly".to_string() }
+                }
+            };
+            registry.set(&id, status);
+        });
+        true
+    }
+
+    /// The job, if `client` may see it.
+    pub fn status(&self, job_id: &str, client: &Client) -> Option<AsyncJob> {
+        let mut jobs = self.jobs.lock().unwrap();
+        self.prune(&mut jobs, Utc::now());
+        jobs.get(job_id).filter(|entry| entry.visible_to(client)).map(|entry| entry.job.clone())
+    }
+
+    /// The job's events so far followed by those still to come, ending with
+    /// its final status, if `client` may see it.
+    pub fn events(&self, job_id: &str, client: &Client) -> Option<impl Stream<Item = JobEvent> + Send + 'static> {
+        let mut jobs = self.jobs.lock().unwrap();
+        self.prune(&mut jobs, Utc::now());
+        let entry = jobs.get(job_id).filter(|entry| entry.visible_to(client))?;
+        let receiver = entry.job.finished_at.is_none().then(|| entry.sender.subscribe());
+        let live = stream::unfold(receiver, |receiver| async move {
+            let mut receiver = receiver?;
+            loop {
+                match receiver.recv().await {
+                    Ok(event) => {
+                        let last = event.last;
+                        return Some((event, (!last).then_some(receiver)));
+                    },
+                    // Progress is superseded by later progress, so skip what was missed
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        });
+        Some(stream::iter(entry.events.clone()).chain(live))
+    }
+
+    /// Record an event of a job submitted here; events of other jobs, such
+    /// as those graded through `/grade`, are ignored.
+    fn publish(&self, job_id: &str, kind: &'static str, data: Value) {
+        if let Some(entry) = self.jobs.lock().unwrap().get_mut(job_id) {
+            entry.push(JobEvent { kind, data, last: false });
+        }
+    }
+
+    fn set(&self, job_id: &str, status: JobStatus) {
+        if let Some(entry) = self.jobs.lock().unwrap().get_mut(job_id) {
+            let finished = matches!(status, JobStatus::Completed { .. } | JobStatus::Failed { .. });
+            if finished {
+                entry.job.finished_at = Some(Utc::now());
+            }
+            entry.job.status = status;
+            let event = JobEvent { kind: "status", data: json!(entry.job), last: finished };
+            entry.push(event);
+        }
+    }
+
+    /// Forget jobs that finished longer than the retention period ago.
+    fn prune(&self, jobs: &mut HashMap<String, Entry>, now: DateTime<Utc>) {
+        let retention = chrono::Duration::from_std(self.retention).unwrap_or(chrono::Duration::MAX);
+        jobs.retain(|_, entry| match entry.job.finished_at {
+            Some(finished) => now - finished < retention,
+            None => true,
+        });
+    }
+}
+
+impl Entry {
+    fn visible_to(&self, client: &Client) -> bool {
+        client.id == self.owner || client.allows(Scope::Admin)
+    }
+
+    fn push(&mut self, event: JobEvent) {
+        // Nobody may be subscribed, which is fine
+        let _ = self.sender.send(event.clone());
+        self.events.push(event);
+    }
+}
+
+/// Streams the stages and progress of the registry's jobs to their
+/// subscribers.
+impl PipelineHooks for Arc<JobRegistry> {
+    fn before_stage(&self, stage: Stage, ctx: &StageContext) -> Result<(), String> {
+        self.publish(ctx.job_id, "stage", json!({ "stage": stage, "state": "started" }));
+        Ok(())
+    }
+
+    fn after_stage(&self, stage: Stage, ctx: &StageContext, outcome: &StageOutcome) -> Result<(), String> {
+        self.publish(
+            ctx.job_id,
+            "stage",
+            json!({
+                "stage": stage,
+                "state": "finished",
+                "elapsedMs": outcome.elapsed.as_millis() as u64,
+                "summary": outcome.summary
+            }),
+        );
+        Ok(())
+    }
+
+    fn progress(&self, stage: Stage, ctx: &StageContext, detail: &Value) {
+        self.publish(ctx.job_id, "progress", json!({ "stage": stage, "detail": detail }));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn client(id: &str, scope: Scope) -> Client {
+        Client { id: id.to_string(), scopes: vec![scope], is_user: false }
+    }
+
+    async fn finished(registry: &JobRegistry, job_id: &str) -> AsyncJob {
+        loop {
+            match registry.status(job_id, &client("backend", Scope::Grade)) {
+                Some(job) if job.finished_at.is_some() => return job,
+                _ => tokio::task::yield_now().await,
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_jobs_run_in_background_and_expire() {
+        let registry = Arc::new(JobRegistry::new(Duration::from_secs(60)));
+        let backend = client("backend", Scope::Grade);
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        assert!(registry.submit("job-1", "backend", async move {
+            released.await.unwrap();
+            json!({ "jobId": "job-1", "score": 100 })
+        }));
+        assert!(!registry.submit("job-1", "backend", async { json!({}) }));
+        assert!(registry.status("job-1", &backend).unwrap().finished_at.is_none());
+
+        // Other clients can't tell the job exists; admins can see it
+        assert!(registry.status("job-1", &client("other", Scope::Grade)).is_none());
+        assert!(registry.events("job-1", &client("other", Scope::Grade)).is_none());
+        assert!(registry.status("job-1", &client("ops", Scope::Admin)).is_some());
+
+        release.send(()).unwrap();
+        let job = finished(&registry, "job-1").await;
+        let view = serde_json::to_value(&job).unwrap();
+        assert_eq!(view["status"], "completed");
+        assert_eq!(view["result"]["score"], 100);
+        assert_eq!(view["jobId"], "job-1");
+
+        registry.submit("job-2", "backend", async { json!({ "error": "Compilation failed", "status": "failed" }) });
+        let view = serde_json::to_value(finished(&registry, "job-2").await).unwrap();
+        assert_eq!(view, json!({ "jobId": "job-2", "status": "failed", "error": "Compilation failed", "submittedAt": view["submittedAt"], "finishedAt": view["finishedAt"] }));
+
+        // A panic fails the job rather than leaving it running
+        registry.submit("job-4", "backend", async { panic!("grader bug") });
+        let view = serde_json::to_value(finished(&registry, "job-4").await).unwrap();
+        assert_eq!(view["status"], "failed");
+
+        let expiring = Arc::new(JobRegistry::new(Duration::ZERO));
+        expiring.submit("job-3", "backend", async { json!({}) });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(expiring.status("job-3", &backend).is_none());
+        assert!(registry.status("unknown", &backend).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_events_replay_history_then_follow_the_job() {
+        let registry = Arc::new(JobRegistry::new(Duration::from_secs(60)));
+        let (release, released) = tokio::sync::oneshot::channel::<()>();
+        let backend = client("backend", Scope::Grade);
+        registry.submit("job-1", "backend", async move {
+            released.await.unwrap();
+            json!({ "score": 100 })
+        });
+        let ctx = StageContext { job_id: "job-1", challenge_id: "c", language: "rust", workspace: std::path::Path::new("/tmp") };
+        registry.before_stage(Stage::Compile, &ctx).unwrap();
+        registry.progress(Stage::Compile, &ctx, &json!({ "warnings": 0 }));
+
+        let events = registry.events("job-1", &backend).unwrap();
+        registry.after_stage(Stage::Compile, &ctx, &StageOutcome { elapsed: Duration::from_millis(5), summary: json!({}) }).unwrap();
+        release.send(()).unwrap();
+        let events: Vec<JobEvent> = events.collect().await;
+
+        // Running may come before or after the compile stage started
+        let kinds: Vec<&str> = events.iter().filter(|e| e.kind != "status").map(|e| e.kind).collect();
+        assert_eq!(kinds, vec!["stage", "progress", "stage"]);
+        assert_eq!(events[0].data["status"], "queued");
+        let last = events.last().unwrap();
+        assert!(last.last);
+        assert_eq!(last.data["status"], "completed");
+        assert_eq!(last.data["result"]["score"], 100);
+
+        // Finished jobs replay their events and end
+        assert_eq!(registry.events("job-1", &backend).unwrap().collect::<Vec<_>>().await.len(), events.len());
+        assert!(registry.events("unknown", &backend).is_none());
+    }
+}
@@ -0,0 +1,117 @@
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// Bounds the grading pipelines run at once for `/grade`, `POST /jobs` and
+/// gRPC, each of which may spawn compilers with gigabyte limits. Jobs beyond
+/// the limit wait in a queue of bounded length; beyond that they are refused.
+pub struct JobSlots {
+    slots: Arc<Semaphore>,
+    capacity: usize,
+    max_queued: usize,
+    /// Admitted jobs, running or waiting for a slot
+    admitted: AtomicUsize,
+}
+
+/// The limit and queue are both full.
+#[derive(Debug, PartialEq)]
+pub struct Saturated {
+    pub running: usize,
+    pub queued: usize,
+}
+
+impl std::fmt::Display for Saturated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Worker is at capacity with {} jobs running and {} queued", self.running, self.queued)
+    }
+}
+
+impl warp::reject::Reject for Saturated {}
+
+/// An admitted job's place, counted until dropped.
+pub struct Ticket {
+    slots: Arc<JobSlots>,
+}
+
+impl JobSlots {
+    pub fn new(capacity: usize, max_queued: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self { slots: Arc::new(Semaphore::new(capacity)), capacity, max_queued, admitted: AtomicUsize::new(0) }
+    }
+
+    /// `MAX_CONCURRENT_JOBS`, the pipelines run at once (default: the
+    /// available CPUs), and `MAX_QUEUED_JOBS`, how many more may wait
+    /// (default 16).
+    pub fn from_env() -> Self {
+        let number = |name: &str| std::env::var(name).ok().and_then(|v| v.parse().ok());
+        let cpus = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4);
+        Self::new(number("MAX_CONCURRENT_JOBS").unwrap_or(cpus), number("MAX_QUEUED_JOBS").unwrap_or(16))
+    }
+
+    /// Admit a job to run now or wait its turn, unless the queue is full.
+    pub fn admit(self: &Arc<Self>) -> Result<Ticket, Saturated> {
+        let limit = self.capacity + self.max_queued;
+        match self.admitted.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |admitted| (admitted < limit).then_some(admitted + 1)) {
+            Ok(_) => Ok(Ticket { slots: self.clone() }),
+            Err(admitted) => {
+                let running = self.running();
+                Err(Saturated { running, queued: admitted.saturating_sub(running) })
+            }
+        }
+    }
+
+    fn running(&self) -> usize {
+        self.capacity - self.slots.available_permits()
+    }
+
+    /// Running and queued jobs against their limits, for `/health`.
+    pub fn occupancy(&self) -> Value {
+        let running = self.running();
+        json!({
+            "running": running,
+            "capacity": self.capacity,
+            "queued": self.admitted.load(Ordering::SeqCst).saturating_sub(running),
+            "maxQueued": self.max_queued
+        })
+    }
+}
+
+impl Ticket {
+    /// Wait for a slot, held until the permit is dropped.
+    pub async fn slot(&self) -> OwnedSemaphorePermit {
+        self.slots.slots.clone().acquire_owned().await.expect("job slots are never closed")
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        self.slots.admitted.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_jobs_wait_for_slots_then_are_refused() {
+        let slots = Arc::new(JobSlots::new(1, 1));
+        let first = slots.admit().unwrap();
+        let running = first.slot().await;
+        let second = slots.admit().unwrap();
+        assert_eq!(slots.occupancy(), json!({ "running": 1, "capacity": 1, "queued": 1, "maxQueued": 1 }));
+        assert_eq!(slots.admit().err(), Some(Saturated { running: 1, queued: 1 }));
+
+        // The queued job runs once the slot frees up
+        let waiting = tokio::spawn(async move {
+            let _slot = second.slot().await;
+        });
+        drop(running);
+        drop(first);
+        waiting.await.unwrap();
+        assert_eq!(slots.occupancy()["running"], 0);
+        assert_eq!(slots.occupancy()["queued"], 0);
+        assert!(slots.admit().is_ok());
+    }
+}
@@ -0,0 +1,137 @@
+use crate::job_queue::{JobQueue, QueuedJob, POLL_TIMEOUT};
+use futures::future::BoxFuture;
+use rdkafka::config::ClientConfig;
+use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use rdkafka::{Message, Offset, TopicPartitionList};
+use serde_json::Value;
+use std::time::Duration;
+
+/// How long producing a result may wait for room in the send queue.
+const SEND_QUEUE_TIMEOUT: Duration = Duration::from_secs(30);
+/// Longest a job may be graded before the worker is taken to have hung and
+/// its partitions are handed to another.
+const DEFAULT_MAX_POLL_INTERVAL_MS: u64 = 3_600_000;
+
+/// Kafka job queue. The workers of a type form one consumer group on
+/// `{KAFKA_TOPIC_PREFIX}{worker_type}`, so partitions are spread over the
+/// fleet and a burst is buffered in the topic until drained. Jobs are graded
+/// one at a time in offset order and a job's offset is committed only once
+/// its result is on the result topic, so a worker that dies leaves its
+/// partitions to resume from the first job without a result. The consumer
+/// isn't polled while a job is graded, so `max.poll.interval.ms` is raised
+/// above the longest job to keep the worker in its group meanwhile.
+pub struct KafkaQueue {
+    consumer: StreamConsumer,
+    producer: FutureProducer,
+    result_topic: String,
+}
+
+impl KafkaQueue {
+    /// `KAFKA_BROKERS` (required), `KAFKA_TOPIC_PREFIX` (default
+    /// `grader-jobs.`), `KAFKA_GROUP_ID` (default `grader-{worker_type}`),
+    /// `KAFKA_RESULT_TOPIC` (default `grader-results`) and
+    /// `KAFKA_MAX_POLL_INTERVAL_MS` (default an hour).
+    pub fn from_env(worker_type: &str) -> Result<Self, String> {
+        let brokers = std::env::var("KAFKA_BROKERS").map_err(|_| "KAFKA_BROKERS is required for the Kafka job queue".to_string())?;
+        let var = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+        let topic = format!("{}{}", var("KAFKA_TOPIC_PREFIX", "grader-jobs."), worker_type);
+        let max_poll_interval = match std::env::var("KAFKA_MAX_POLL_INTERVAL_MS") {
+            Ok(v) => v.parse::<u64>().map_err(|_| format!("Invalid KAFKA_MAX_POLL_INTERVAL_MS: {}", v))?,
+            Err(_) => DEFAULT_MAX_POLL_INTERVAL_MS,
+        };
+
+        let consumer: StreamConsumer = ClientConfig::new()
+            .set("bootstrap.servers", &brokers)
+            .set("group.id", var("KAFKA_GROUP_ID", &format!("grader-{}", worker_type)))
+            .set("enable.auto.commit", "false")
+            .set("auto.offset.reset", "earliest")
+            .set("max.poll.interval.ms", max_poll_interval.to_string())
+            .create()
+            .map_err(|e| format!("Failed to create Kafka consumer: {}", e))?;
+        consumer.subscribe(&[&topic]).map_err(|e| format!("Failed to subscribe to {}: {}", topic, e))?;
+
+        let producer: FutureProducer = ClientConfig::new()
+            .set("bootstrap.servers", &brokers)
+            .set("enable.idempotence", "true")
+            .create()
+            .map_err(|e| format!("Failed to create Kafka producer: {}", e))?;
+
+        Ok(Self { consumer, producer, result_topic: var("KAFKA_RESULT_TOPIC", "grader-results") })
+    }
+
+    /// Commit everything up to and including the message at `offset`, waiting
+    /// for the broker to confirm it.
+    fn commit(&self, topic: &str, partition: i32, offset: i64) -> Result<(), String> {
+        let mut offsets = TopicPartitionList::new();
+        offsets
+            .add_partition_offset(topic, partition, Offset::Offset(offset + 1))
+            .map_err(|e| format!("Invalid offset {} of {}/{}: {}", offset, topic, partition, e))?;
+        tokio::task::block_in_place(|| self.consumer.commit(&offsets, CommitMode::Sync))
+            .map_err(|e| format!("Failed to commit offset {} of {}/{}: {}", offset, topic, partition, e))
+    }
+}
+
+/// `topic/partition/offset`, as kept in a job's receipt.
+fn parse_receipt(receipt: &str) -> Option<(&str, i32, i64)> {
+    let mut parts = receipt.rsplitn(3, '/');
+    let offset = parts.next()?.parse().ok()?;
+    let partition = parts.next()?.parse().ok()?;
+    Some((parts.next()?, partition, offset))
+}
+
+impl JobQueue for KafkaQueue {
+    fn next(&self) -> BoxFuture<'_, Result<Option<QueuedJob>, String>> {
+        Box::pin(async move {
+            let message = match tokio::time::timeout(POLL_TIMEOUT, self.consumer.recv()).await {
+                Err(_) => return Ok(None),
+                Ok(Err(e)) => return Err(format!("Failed to receive job: {}", e)),
+                Ok(Ok(message)) => message,
+            };
+            let payload = message.payload().and_then(|bytes| serde_json::from_slice::<Value>(bytes).ok());
+            match payload {
+                Some(payload) if payload.is_object() => Ok(Some(QueuedJob {
+                    payload,
+                    receipt: format!("{}/{}/{}", message.topic(), message.partition(), message.offset()),
+                })),
+                _ => {
+                    // Nothing to grade or to report a result under, so skip it
+                    eprintln!("Warning: Skipping malformed job at {}/{}/{}", message.topic(), message.partition(), message.offset());
+                    self.commit(message.topic(), message.partition(), message.offset())?;
+                    Ok(None)
+                }
+            }
+        })
+    }
+
+    fn complete<'a>(&'a self, job: &'a QueuedJob, result: &'a Value) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let job_id = job.payload.get("jobId").and_then(|v| v.as_str()).unwrap_or_default();
+            let (topic, partition, offset) =
+                parse_receipt(&job.receipt).ok_or_else(|| format!("Invalid Kafka receipt: {}", job.receipt))?;
+            let body = result.to_string();
+            let record = FutureRecord::to(&self.result_topic).key(job_id).payload(&body);
+            if let Err((e, _)) = self.producer.send(record, SEND_QUEUE_TIMEOUT).await {
+                // Rewind so the job is graded again rather than committed
+                // past with the next one
+                if let Err(seek_error) = self.consumer.seek(topic, partition, Offset::Offset(offset), SEND_QUEUE_TIMEOUT) {
+                    eprintln!("Warning: Failed to rewind {}/{} to job {}: {}", topic, partition, job_id, seek_error);
+                }
+                return Err(format!("Failed to publish result of job {}: {}", job_id, e));
+            }
+            self.commit(topic, partition, offset)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_receipt() {
+        assert_eq!(parse_receipt("grader-jobs.grader_rust/3/1042"), Some(("grader-jobs.grader_rust", 3, 1042)));
+        assert_eq!(parse_receipt("grader-jobs/x/1"), None);
+        assert_eq!(parse_receipt("1042"), None);
+    }
+}
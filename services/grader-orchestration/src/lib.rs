@@ -4,6 +4,81 @@ pub mod fuzzer;
 pub mod grader;
 pub mod compiler;
 pub mod anti_cheat;
+pub mod forge;
+pub mod quota;
+pub mod aptos;
+pub mod integrity;
+pub mod rust_policy;
+pub mod scoring;
+pub mod history;
+pub mod quick_eval;
+pub mod vyper;
+pub mod cargo_test;
+pub mod ink;
+pub mod harness;
+pub mod typescript;
+pub mod backfill;
+pub mod replay;
+pub mod cosmwasm;
+pub mod evm;
+pub mod artifacts;
+pub mod git_source;
+pub mod stubs;
+pub mod escalation;
+pub mod diff;
+pub mod memory_budget;
+pub mod reports;
+pub mod impact;
+pub mod result_protocol;
+pub mod workspace;
+pub mod validation;
+pub mod hooks;
+pub mod diagnostics;
+pub mod compile_cache;
+pub mod cargo_cache;
+pub mod toolchain;
+pub mod lint;
+pub mod security;
+pub mod templates;
+pub mod fixture_source;
+pub mod fixture_schema;
+pub mod fixture_signing;
+pub mod backoff;
+pub mod fixture_files;
+pub mod fixture_cache;
+pub mod suites;
+pub mod sealing;
+pub mod crash_signature;
+pub mod fuzz_config;
+pub mod invariants;
+pub mod sol_fuzz;
+pub mod cargo_fuzz;
+pub mod repro;
+pub mod edge_cases;
+pub mod fuzz_checkpoint;
+pub mod crash_severity;
+pub mod fingerprint_store;
+pub mod winnowing;
+pub mod token_fingerprint;
+pub mod minhash;
+pub mod collusion;
+pub mod plagiarism_batch;
+pub mod tree_edit;
+pub mod anti_cheat_config;
+pub mod ai_heuristics;
+pub mod retention;
+pub mod evasion;
+pub mod similarity_report;
+pub mod job_queue;
+pub mod nats_queue;
+pub mod kafka_queue;
+pub mod sqs_queue;
+pub mod aws_sigv4;
+pub mod job_registry;
+pub mod grpc;
+pub mod auth;
+pub mod job_slots;
+pub mod challenge_config;
 
 #[cfg(test)]
 mod tests {
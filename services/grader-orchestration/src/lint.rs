@@ -0,0 +1,136 @@
+use crate::cargo_cache::CargoCache;
+use crate::compiler::Diagnostic;
+use crate::diagnostics;
+use crate::sandbox::{execute_in_sandbox_with_env, SandboxConfig};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Per-challenge clippy configuration, the `lint` table of the scoring rubric.
+/// Findings of `deny` lints cost `penalty` points each (at most `maxPenalty`),
+/// and with `failOnDeny` any of them fails the submission.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct LintRules {
+    /// Clippy lint names, with or without the `clippy::` prefix
+    pub deny: Vec<String>,
+    pub penalty: usize,
+    pub max_penalty: Option<usize>,
+    pub fail_on_deny: bool,
+}
+
+impl Default for LintRules {
+    fn default() -> Self {
+        Self {
+            deny: vec![],
+            penalty: 2,
+            max_penalty: None,
+            fail_on_deny: false,
+        }
+    }
+}
+
+impl LintRules {
+    fn denies(&self, lint: &str) -> bool {
+        self.deny.iter().any(|rule| rule.trim_start_matches("clippy::") == lint)
+    }
+
+    /// Points deducted for `denied` findings of deny lints.
+    pub fn penalty_for(&self, denied: usize) -> usize {
+        let penalty = denied * self.penalty;
+        self.max_penalty.map(|max| penalty.min(max)).unwrap_or(penalty)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintFinding {
+    pub lint: String,
+    pub denied: bool,
+    #[serde(flatten)]
+    pub diagnostic: Diagnostic,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LintReport {
+    pub findings: Vec<LintFinding>,
+    /// Findings of the challenge's deny lints
+    pub denied: usize,
+    /// Why clippy couldn't check the submission; its lints are then unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl LintReport {
+    pub fn from_clippy_output(stdout: &str, rules: &LintRules) -> Self {
+        let findings: Vec<LintFinding> = diagnostics::parse_clippy_lints(stdout)
+            .into_iter()
+            .map(|m| LintFinding { denied: rules.denies(&m.lint), lint: m.lint, diagnostic: m.diagnostic })
+            .collect();
+        Self { denied: findings.iter().filter(|f| f.denied).count(), findings, error: None }
+    }
+
+    /// Report of a lint stage that didn't complete.
+    pub fn failed(error: String) -> Self {
+        Self { error: Some(error), ..Self::default() }
+    }
+}
+
+/// Run clippy over the compiled Rust project in `workspace`. Deny lints are
+/// force-enabled as warnings, so allow-by-default lints such as `unwrap_used`
+/// are reported without failing the build and a submission's own
+/// `#![allow(...)]` can't silence them.
+pub async fn run_clippy(workspace: &Path, rules: &LintRules) -> Result<LintReport, String> {
+    let sandbox_config = SandboxConfig {
+        time_limit: Duration::from_secs(60), // Same budget as compilation
+        memory_limit: 1024 * 1024 * 1024, // 1GB
+        cpu_limit: 50,
+        network_disabled: true,
+        max_file_size: 100 * 1024 * 1024, // 100MB
+        max_processes: 10,
+        disk_quota: 500 * 1024 * 1024, // 500MB
+    };
+
+    let mut args = vec!["clippy".to_string(), "--release".to_string(), "--message-format=json".to_string(), "--".to_string()];
+    for rule in &rules.deny {
+        args.push("--force-warn".to_string());
+        args.push(format!("clippy::{}", rule.trim_start_matches("clippy::")));
+    }
+    let args_refs: Vec<&str> = args.iter().map(|s| s.as_str()).collect();
+
+    let env = CargoCache::global().prepare(workspace).await?;
+    let result = execute_in_sandbox_with_env("cargo", &args_refs, &env, &sandbox_config, workspace).await?;
+    if !result.success {
+        return Err(format!("cargo clippy failed: {}", result.stderr.trim()));
+    }
+
+    Ok(LintReport::from_clippy_output(&result.stdout, rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_marks_denied_lints() {
+        let stdout = r#"{"reason":"compiler-message","message":{"level":"warning","message":"used `unwrap()` on an `Option` value","code":{"code":"clippy::unwrap_used"},"spans":[{"file_name":"main.rs","line_start":3,"column_start":13,"is_primary":true}]}}
+{"reason":"compiler-message","message":{"level":"warning","message":"redundant clone","code":{"code":"clippy::redundant_clone"},"spans":[{"file_name":"main.rs","line_start":5,"column_start":9,"is_primary":true}]}}
+{"reason":"compiler-message","message":{"level":"warning","message":"unused variable: `x`","code":{"code":"unused_variables"},"spans":[{"file_name":"main.rs","line_start":2,"column_start":9,"is_primary":true}]}}"#;
+
+        let rules = LintRules { deny: vec!["clippy::unwrap_used".to_string()], max_penalty: Some(3), ..LintRules::default() };
+        let report = LintReport::from_clippy_output(stdout, &rules);
+
+        assert_eq!(report.findings.len(), 2);
+        assert_eq!(report.denied, 1);
+        assert!(report.findings[0].denied);
+        assert_eq!(report.findings[0].diagnostic.line, Some(3));
+        assert!(!report.findings[1].denied);
+        assert_eq!(rules.penalty_for(1), 2);
+        assert_eq!(rules.penalty_for(4), 3);
+        assert!(report.error.is_none());
+
+        let failed = serde_json::to_value(LintReport::failed("cargo clippy failed".to_string())).unwrap();
+        assert_eq!(failed["error"], "cargo clippy failed");
+    }
+}
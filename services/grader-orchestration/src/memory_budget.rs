@@ -0,0 +1,114 @@
+use std::sync::{Arc, OnceLock};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+const MB: u64 = 1024 * 1024;
+
+/// Share of the host's available memory handed to sandboxes when no explicit
+/// budget is configured; the rest is headroom for the worker and toolchains.
+const DEFAULT_BUDGET_SHARE: f64 = 0.8;
+
+static GLOBAL_BUDGET: OnceLock<MemoryBudget> = OnceLock::new();
+
+/// Host-wide memory budget shared by all sandboxes of the worker. Launches
+/// reserve their memory limit up front and wait while the budget is exhausted,
+/// so concurrent jobs cannot oversubscribe the host.
+pub struct MemoryBudget {
+    total_mb: u32,
+    semaphore: Arc<Semaphore>,
+}
+
+/// Memory reserved for one sandbox, returned to the budget when dropped.
+pub struct MemoryReservation {
+    _permit: OwnedSemaphorePermit,
+    pub reserved_bytes: u64,
+}
+
+impl MemoryBudget {
+    pub fn new(total_bytes: u64) -> Self {
+        let total_mb = (total_bytes / MB).clamp(1, u32::MAX as u64) as u32;
+        Self {
+            total_mb,
+            semaphore: Arc::new(Semaphore::new(total_mb as usize)),
+        }
+    }
+
+    /// The budget used by `execute_in_sandbox`: `SANDBOX_MEMORY_BUDGET_MB` if
+    /// set, otherwise a share of the host's currently available memory.
+    pub fn global() -> &'static MemoryBudget {
+        GLOBAL_BUDGET.get_or_init(|| {
+            let configured = std::env::var("SANDBOX_MEMORY_BUDGET_MB")
+                .ok()
+                .and_then(|v| v.parse::<u64>().ok())
+                .map(|mb| mb * MB);
+            let total = configured.unwrap_or_else(|| {
+                let available = host_available_memory().unwrap_or(4096 * MB);
+                (available as f64 * DEFAULT_BUDGET_SHARE) as u64
+            });
+            println!("Sandbox memory budget: {}MB", total / MB);
+            MemoryBudget::new(total)
+        })
+    }
+
+    /// Reserve `bytes`, waiting until enough of the budget is free. Requests
+    /// larger than the whole budget are clamped so they run alone rather than
+    /// wait forever.
+    pub async fn reserve(&self, bytes: u64) -> MemoryReservation {
+        let mb = self.request_mb(bytes);
+        let permit = self
+            .semaphore
+            .clone()
+            .acquire_many_owned(mb)
+            .await
+            .expect("memory budget semaphore is never closed");
+        MemoryReservation { _permit: permit, reserved_bytes: mb as u64 * MB }
+    }
+
+    /// Reserve `bytes` only if that much of the budget is free right now.
+    pub fn try_reserve(&self, bytes: u64) -> Option<MemoryReservation> {
+        let mb = self.request_mb(bytes);
+        let permit = self.semaphore.clone().try_acquire_many_owned(mb).ok()?;
+        Some(MemoryReservation { _permit: permit, reserved_bytes: mb as u64 * MB })
+    }
+
+    pub fn available_bytes(&self) -> u64 {
+        self.semaphore.available_permits() as u64 * MB
+    }
+
+    fn request_mb(&self, bytes: u64) -> u32 {
+        (bytes.div_ceil(MB).clamp(1, self.total_mb as u64)) as u32
+    }
+}
+
+/// `MemAvailable` from /proc/meminfo, in bytes.
+fn host_available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    meminfo
+        .lines()
+        .find(|line| line.starts_with("MemAvailable:"))?
+        .split_whitespace()
+        .nth(1)?
+        .parse::<u64>()
+        .ok()
+        .map(|kb| kb * 1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reservations_are_admission_controlled() {
+        let budget = MemoryBudget::new(1024 * MB);
+
+        let first = budget.try_reserve(768 * MB).unwrap();
+        assert!(budget.try_reserve(512 * MB).is_none());
+        assert_eq!(budget.available_bytes(), 256 * MB);
+
+        drop(first);
+        assert!(budget.try_reserve(512 * MB).is_some());
+
+        // Oversized requests take the whole budget instead of never fitting
+        let oversized = budget.try_reserve(4096 * MB).unwrap();
+        assert_eq!(oversized.reserved_bytes, 1024 * MB);
+    }
+}
@@ -0,0 +1,65 @@
+/// Hash functions per signature.
+pub const NUM_HASHES: usize = BANDS * ROWS;
+/// LSH bands, and signature rows per band. Two submissions become candidates
+/// when all rows of any band agree, which for winnowed fingerprints with
+/// Jaccard similarity s happens with probability 1 - (1 - s^ROWS)^BANDS:
+/// about 95% at s = 0.3 and 27% at s = 0.1.
+const BANDS: usize = 32;
+const ROWS: usize = 2;
+
+/// MinHash signature of a set of winnowed hashes: the minimum of each of
+/// `NUM_HASHES` independent hash functions over the set. The share of rows
+/// two signatures agree on estimates the Jaccard similarity of the sets.
+pub fn signature(hashes: &[u64]) -> Vec<u64> {
+    if hashes.is_empty() {
+        return Vec::new();
+    }
+    (0..NUM_HASHES as u64)
+        .map(|i| hashes.iter().map(|h| mix(h ^ mix(i))).min().unwrap())
+        .collect()
+}
+
+/// Locality-sensitive index keys of a signature, one per band. Similar
+/// submissions share at least one key with high probability, so candidates
+/// are found by key lookup instead of comparing against every submission.
+pub fn band_keys(signature: &[u64]) -> Vec<u64> {
+    if signature.len() != NUM_HASHES {
+        return Vec::new();
+    }
+    signature
+        .chunks(ROWS)
+        .enumerate()
+        .map(|(band, rows)| rows.iter().fold(mix(band as u64), |key, row| mix(key ^ row)))
+        .collect()
+}
+
+/// splitmix64 finalizer; stable across builds since keys are stored.
+fn mix(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_similar_sets_share_band_keys() {
+        let original: Vec<u64> = (0..100).collect();
+        let edited: Vec<u64> = (10..110).collect();
+        let unrelated: Vec<u64> = (1000..1100).collect();
+
+        let sig = signature(&original);
+        assert_eq!(sig.len(), NUM_HASHES);
+        let agreement = sig.iter().zip(signature(&edited)).filter(|(a, b)| **a == *b).count();
+        assert!(agreement > NUM_HASHES / 2);
+
+        let keys = band_keys(&sig);
+        assert_eq!(keys.len(), BANDS);
+        assert!(band_keys(&signature(&edited)).iter().any(|k| keys.contains(k)));
+        assert!(!band_keys(&signature(&unrelated)).iter().any(|k| keys.contains(k)));
+        assert!(band_keys(&signature(&[])).is_empty());
+    }
+}
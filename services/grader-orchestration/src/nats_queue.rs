@@ -0,0 +1,188 @@
+use crate::job_queue::{self, JobQueue, QueuedJob, POLL_TIMEOUT};
+use async_nats::header::{HeaderMap, NATS_MESSAGE_ID};
+use async_nats::jetstream::consumer::{pull, AckPolicy};
+use async_nats::jetstream::stream::{self, LastRawMessageErrorKind};
+use futures::future::BoxFuture;
+use futures::StreamExt;
+use serde_json::Value;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+/// NATS JetStream job queue. Jobs are published to
+/// `{NATS_SUBJECT_PREFIX}{worker_type}` and pulled through a durable
+/// consumer with explicit acks, so a job is redelivered until a worker
+/// acknowledges it. Jobs being graded are kept from redelivery with progress
+/// acks. A job is only acknowledged once its result is stored in the result
+/// stream, under `{NATS_RESULT_SUBJECT_PREFIX}{jobId}` with the job id as
+/// message id, so a redelivered job's result is published once. Results are
+/// kept, and deduplicated, for `NATS_RESULT_MAX_AGE_SECS`.
+pub struct NatsQueue {
+    client: async_nats::Client,
+    jetstream: async_nats::jetstream::Context,
+    messages: Mutex<pull::Stream>,
+    results: stream::Stream,
+    result_prefix: String,
+    max_in_flight: usize,
+    ack_wait: Duration,
+}
+
+impl NatsQueue {
+    /// `NATS_URL` (required), `NATS_STREAM` (default `GRADER_JOBS`),
+    /// `NATS_SUBJECT_PREFIX` (default `grader.jobs.`), `NATS_DURABLE`
+    /// (default `grader-{worker_type}`), `NATS_RESULT_STREAM` (default
+    /// `GRADER_RESULTS`), `NATS_RESULT_SUBJECT_PREFIX` (default
+    /// `grader.results.`), `NATS_RESULT_MAX_AGE_SECS` (default 86400),
+    /// `NATS_MAX_IN_FLIGHT` (default 4), `NATS_ACK_WAIT_SECS` (default 120)
+    /// and `NATS_MAX_DELIVER` (default 5). Streams that don't exist yet are
+    /// created, and the result stream's limits are applied to an existing one.
+    pub async fn connect(worker_type: &str) -> Result<Self, String> {
+        let url = std::env::var("NATS_URL").map_err(|_| "NATS_URL is required for the NATS job queue".to_string())?;
+        let var = |name: &str, default: &str| std::env::var(name).unwrap_or_else(|_| default.to_string());
+        let number = |name: &str, default: u64| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        let subject_prefix = var("NATS_SUBJECT_PREFIX", "grader.jobs.");
+        let result_prefix = var("NATS_RESULT_SUBJECT_PREFIX", "grader.results.");
+        let durable = var("NATS_DURABLE", &format!("grader-{}", worker_type));
+        let max_in_flight = number("NATS_MAX_IN_FLIGHT", 4).max(1) as usize;
+        let ack_wait = Duration::from_secs(number("NATS_ACK_WAIT_SECS", 120).max(2));
+        let result_max_age = Duration::from_secs(number("NATS_RESULT_MAX_AGE_SECS", 86400).max(1));
+
+        let client = async_nats::connect(url.as_str()).await.map_err(|e| format!("Failed to connect to NATS: {}", e))?;
+        let jetstream = async_nats::jetstream::new(client.clone());
+        let jobs = jetstream
+            .get_or_create_stream(stream::Config {
+                name: var("NATS_STREAM", "GRADER_JOBS"),
+                subjects: vec![format!("{}>", subject_prefix)],
+                ..Default::default()
+            })
+            .await
+            .map_err(|e| format!("Failed to open the job stream: {}", e))?;
+        // Deduplicating over the whole retention period keeps a result from
+        // being published twice however late its job is redelivered
+        let result_config = stream::Config {
+            name: var("NATS_RESULT_STREAM", "GRADER_RESULTS"),
+            subjects: vec![format!("{}>", result_prefix)],
+            max_age: result_max_age,
+            duplicate_window: result_max_age,
+            ..Default::default()
+        };
+        let results = jetstream
+            .get_or_create_stream(&result_config)
+            .await
+            .map_err(|e| format!("Failed to open the result stream: {}", e))?;
+        let limits = &results.cached_info().config;
+        if limits.max_age != result_max_age || limits.duplicate_window != result_max_age {
+            jetstream
+                .update_stream(&result_config)
+                .await
+                .map_err(|e| format!("Failed to apply the result stream's limits: {}", e))?;
+        }
+
+        let consumer = jobs
+            .get_or_create_consumer(
+                &durable,
+                pull::Config {
+                    durable_name: Some(durable.clone()),
+                    filter_subject: format!("{}{}", subject_prefix, worker_type),
+                    ack_policy: AckPolicy::Explicit,
+                    ack_wait,
+                    max_deliver: number("NATS_MAX_DELIVER", 5) as i64,
+                    max_ack_pending: max_in_flight as i64,
+                    ..Default::default()
+                },
+            )
+            .await
+            .map_err(|e| format!("Failed to create consumer {}: {}", durable, e))?;
+        let messages = consumer.messages().await.map_err(|e| format!("Failed to pull from consumer {}: {}", durable, e))?;
+
+        Ok(Self { client, jetstream, messages: Mutex::new(messages), results, result_prefix, max_in_flight, ack_wait })
+    }
+
+    async fn acknowledge(&self, receipt: &str, kind: &'static str) -> Result<(), String> {
+        self.client
+            .publish(receipt.to_string(), kind.into())
+            .await
+            .map_err(|e| format!("Failed to acknowledge job: {}", e))
+    }
+
+    /// Whether the job's result is already in the result stream, as when a
+    /// worker published it but died before acknowledging the job.
+    async fn already_graded(&self, job_id: &str) -> bool {
+        match self.results.get_last_raw_message_by_subject(&format!("{}{}", self.result_prefix, job_id)).await {
+            Ok(_) => true,
+            Err(e) if e.kind() == LastRawMessageErrorKind::NoMessageFound => false,
+            Err(e) => {
+                eprintln!("Warning: Failed to look up the result of redelivered job {}: {}", job_id, e);
+                false
+            }
+        }
+    }
+}
+
+impl JobQueue for NatsQueue {
+    fn next(&self) -> BoxFuture<'_, Result<Option<QueuedJob>, String>> {
+        Box::pin(async move {
+            let message = {
+                let mut messages = self.messages.lock().await;
+                match tokio::time::timeout(POLL_TIMEOUT, messages.next()).await {
+                    Err(_) => return Ok(None),
+                    Ok(None) => return Err("JetStream consumer closed".to_string()),
+                    Ok(Some(Err(e))) => return Err(format!("Failed to pull job: {}", e)),
+                    Ok(Some(Ok(message))) => message,
+                }
+            };
+            let receipt = match &message.reply {
+                Some(reply) => reply.to_string(),
+                None => return Err("JetStream message without a reply subject".to_string()),
+            };
+
+            let payload = match serde_json::from_slice::<Value>(&message.payload) {
+                Ok(payload) if payload.is_object() => payload,
+                _ => {
+                    // Redelivering it wouldn't help
+                    eprintln!("Warning: Dropping malformed job from {}", message.subject);
+                    self.acknowledge(&receipt, "+TERM").await?;
+                    return Ok(None);
+                }
+            };
+
+            let delivered = message.info().map(|info| info.delivered).unwrap_or(1);
+            if delivered > 1 {
+                let job_id = job_queue::job_id(&payload);
+                println!("Job {} redelivered (delivery {})", job_id, delivered);
+                if self.already_graded(&job_id).await {
+                    self.acknowledge(&receipt, "+ACK").await?;
+                    return Ok(None);
+                }
+            }
+            Ok(Some(QueuedJob { payload, receipt }))
+        })
+    }
+
+    fn complete<'a>(&'a self, job: &'a QueuedJob, result: &'a Value) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let job_id = job.payload.get("jobId").and_then(|v| v.as_str()).unwrap_or_default();
+            let mut headers = HeaderMap::new();
+            headers.insert(NATS_MESSAGE_ID, job_id);
+            self.jetstream
+                .publish_with_headers(format!("{}{}", self.result_prefix, job_id), headers, result.to_string().into())
+                .await
+                .map_err(|e| format!("Failed to publish result of job {}: {}", job_id, e))?
+                .await
+                .map_err(|e| format!("Result of job {} wasn't stored: {}", job_id, e))?;
+            self.acknowledge(&job.receipt, "+ACK").await
+        })
+    }
+
+    fn max_in_flight(&self) -> usize {
+        self.max_in_flight
+    }
+
+    fn keep_alive_interval(&self) -> Option<Duration> {
+        Some(self.ack_wait / 2)
+    }
+
+    /// Reset the job's ack wait, so it isn't redelivered while still graded.
+    fn keep_alive<'a>(&'a self, job: &'a QueuedJob) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(self.acknowledge(&job.receipt, "+WPI"))
+    }
+}
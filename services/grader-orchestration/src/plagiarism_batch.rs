@@ -0,0 +1,211 @@
+use crate::anti_cheat::{AntiCheatEngine, MatchedSubmission, RiskLevel};
+use crate::anti_cheat_config::AntiCheatConfig;
+use crate::collusion::CollusionCluster;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Default similarity at which two submissions are linked into a cluster.
+const DEFAULT_CLUSTER_SIMILARITY: f64 = 0.7;
+const DEFAULT_RETENTION: Duration = Duration::from_secs(3600);
+const DEFAULT_MAX_RUNNING: usize = 2;
+
+/// What a batch analysis covers, from the request body.
+#[derive(Clone, Debug)]
+pub struct BatchAnalysisRequest {
+    pub challenge_id: String,
+    pub language: String,
+    pub cluster_similarity: f64,
+    pub config: AntiCheatConfig,
+}
+
+impl BatchAnalysisRequest {
+    /// `deployment` is the config the payload's `antiCheat` overrides.
+    pub fn from_payload(challenge_id: &str, payload: &Value, deployment: &AntiCheatConfig) -> Result<Self, String> {
+        let language = payload
+            .get("language")
+            .and_then(|v| v.as_str())
+            .ok_or("Missing language")?
+            .to_lowercase();
+        let cluster_similarity = match payload.get("clusterSimilarity") {
+            None => DEFAULT_CLUSTER_SIMILARITY,
+            Some(value) => value
+                .as_f64()
+                .filter(|s| (0.0..=1.0).contains(s))
+                .ok_or("clusterSimilarity must be a number between 0 and 1")?,
+        };
+        let config = AntiCheatConfig::from_payload(payload, deployment)?;
+        Ok(Self { challenge_id: challenge_id.to_string(), language, cluster_similarity, config })
+    }
+}
+
+/// One submission's standing in a challenge-wide analysis: its closest
+/// matches among other users' submissions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RankedSubmission {
+    pub submission_id: String,
+    pub user_id: String,
+    pub similarity_score: f64,
+    pub risk_level: RiskLevel,
+    pub matched_submissions: Vec<MatchedSubmission>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChallengeReport {
+    pub challenge_id: String,
+    pub language: String,
+    /// Most similar first
+    pub ranked: Vec<RankedSubmission>,
+    pub clusters: Vec<CollusionCluster>,
+    pub analysis_time_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum AnalysisStatus {
+    Running { processed: usize, total: usize },
+    Completed { report: ChallengeReport },
+    Failed { error: String },
+}
+
+/// Challenge-wide analyses run in the background, by analysis id. Finished
+/// analyses are kept for the retention period, in memory, so they are lost
+/// when the worker restarts. Each scores every submission to a challenge, so
+/// only a few run at once.
+pub struct AnalysisJobs {
+    jobs: Mutex<HashMap<String, Entry>>,
+    retention: Duration,
+    max_running: usize,
+}
+
+struct Entry {
+    status: AnalysisStatus,
+    finished_at: Option<DateTime<Utc>>,
+}
+
+impl AnalysisJobs {
+    pub fn new(retention: Duration, max_running: usize) -> Self {
+        Self { jobs: Mutex::new(HashMap::new()), retention, max_running: max_running.max(1) }
+    }
+
+    /// `PLAGIARISM_ANALYSIS_RETENTION_SECS`, how long finished analyses are
+    /// kept (default 3600), and `MAX_CONCURRENT_ANALYSES`, how many run at
+    /// once (default 2).
+    pub fn from_env() -> Self {
+        let number = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+        let retention = number("PLAGIARISM_ANALYSIS_RETENTION_SECS").map(Duration::from_secs).unwrap_or(DEFAULT_RETENTION);
+        Self::new(retention, number("MAX_CONCURRENT_ANALYSES").map_or(DEFAULT_MAX_RUNNING, |n| n as usize))
+    }
+
+    /// Start analysing the request's challenge and return the analysis id,
+    /// unless the maximum number of analyses are running already.
+    pub fn start(self: &Arc<Self>, engine: Arc<AntiCheatEngine>, request: BatchAnalysisRequest) -> Result<String, String> {
+        let analysis_id = uuid::Uuid::new_v4().to_string();
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            self.prune(&mut jobs, Utc::now());
+            let running = jobs.values().filter(|entry| entry.finished_at.is_none()).count();
+            if running >= self.max_running {
+                return Err(format!("{} plagiarism analyses are running already", running));
+            }
+            let status = AnalysisStatus::Running { processed: 0, total: 0 };
+            jobs.insert(analysis_id.clone(), Entry { status, finished_at: None });
+        }
+
+        let jobs = self.clone();
+        let id = analysis_id.clone();
+        tokio::spawn(async move {
+            let progress = |processed, total| jobs.set(&id, AnalysisStatus::Running { processed, total });
+            let status = match engine
+                .analyze_challenge(&request.challenge_id, &request.language, request.cluster_similarity, &request.config, progress)
+                .await
+            {
+                Ok(report) => AnalysisStatus::Completed { report },
+                Err(error) => {
+                    eprintln!("Warning: Plagiarism analysis of {} failed: {}", request.challenge_id, error);
+                    AnalysisStatus::Failed { error }
+                }
+            };
+            jobs.set(&id, status);
+        });
+        Ok(analysis_id)
+    }
+
+    pub fn status(&self, analysis_id: &str) -> Option<AnalysisStatus> {
+        let mut jobs = self.jobs.lock().unwrap();
+        self.prune(&mut jobs, Utc::now());
+        jobs.get(analysis_id).map(|entry| entry.status.clone())
+    }
+
+    fn set(&self, analysis_id: &str, status: AnalysisStatus) {
+        if let Some(entry) = self.jobs.lock().unwrap().get_mut(analysis_id) {
+            if !matches!(status, AnalysisStatus::Running { .. }) {
+                entry.finished_at = Some(Utc::now());
+            }
+            entry.status = status;
+        }
+    }
+
+    /// Forget analyses that finished longer than the retention period ago.
+    fn prune(&self, jobs: &mut HashMap<String, Entry>, now: DateTime<Utc>) {
+        let retention = chrono::Duration::from_std(self.retention).unwrap_or(chrono::Duration::MAX);
+        jobs.retain(|_, entry| match entry.finished_at {
+            Some(finished) => now - finished < retention,
+            None => true,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_analysis_ranks_copied_submissions_first() {
+        let engine = Arc::new(AntiCheatEngine::new());
+        let solution = "fn solve(v: Vec<i32>) -> i32 { let mut total = 0; for x in v { total = total + x; } if total > 10 { total = 10; } total }";
+        let copy = solution.replace("total", "sum").replace("solve", "answer");
+        let other = "struct S; impl S { fn new() -> S { S } } enum E { A } fn main() { let s = S::new(); s.run(); }";
+        for (id, user, code) in [("c:rust:alice:1", "alice", solution), ("c:rust:bob:2", "bob", copy.as_str()), ("c:rust:carol:3", "carol", other)] {
+            engine.store_submission(id, code, "rust", user, "c", true).await.unwrap();
+        }
+
+        let request = BatchAnalysisRequest::from_payload("c", &json!({ "language": "Rust" }), engine.config()).unwrap();
+        let jobs = Arc::new(AnalysisJobs::new(Duration::from_secs(3600), 1));
+        let analysis_id = jobs.start(engine.clone(), request.clone()).unwrap();
+        // Only one runs at once
+        assert!(jobs.start(engine.clone(), request.clone()).is_err());
+        let report = loop {
+            match jobs.status(&analysis_id).unwrap() {
+                AnalysisStatus::Completed { report } => break report,
+                AnalysisStatus::Failed { error } => panic!("{}", error),
+                AnalysisStatus::Running { .. } => tokio::task::yield_now().await,
+            }
+        };
+
+        assert_eq!(report.ranked.len(), 3);
+        assert_eq!(report.ranked[0].risk_level, RiskLevel::Critical);
+        assert_eq!(report.ranked[2].submission_id, "c:rust:carol:3");
+        assert_eq!(report.clusters.len(), 1);
+        assert_eq!(report.clusters[0].user_ids, vec!["alice", "bob"]);
+
+        let deployment = AntiCheatConfig::default();
+        assert!(BatchAnalysisRequest::from_payload("c", &json!({ "language": "rust", "clusterSimilarity": 2 }), &deployment).is_err());
+        assert!(BatchAnalysisRequest::from_payload("c", &json!({ "language": "rust", "antiCheat": { "reportThreshold": -1 } }), &deployment).is_err());
+        assert!(jobs.status("unknown").is_none());
+
+        // Finished analyses are forgotten after the retention period
+        let jobs = Arc::new(AnalysisJobs::new(Duration::ZERO, 1));
+        let analysis_id = jobs.start(engine, request).unwrap();
+        while let Some(AnalysisStatus::Running { .. }) = jobs.status(&analysis_id) {
+            tokio::task::yield_now().await;
+        }
+        assert!(jobs.status(&analysis_id).is_none());
+    }
+}
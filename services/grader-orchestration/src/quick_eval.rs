@@ -0,0 +1,161 @@
+use crate::sandbox::{execute_in_sandbox, ExecutionResult, SandboxConfig};
+use serde_json::{json, Value};
+use std::time::Duration;
+
+/// Largest snippet accepted by the quick-eval endpoint
+pub const MAX_SNIPPET_SIZE: usize = 16 * 1024;
+/// Output beyond this many bytes is truncated
+const MAX_OUTPUT_SIZE: usize = 64 * 1024;
+
+fn eval_sandbox_config(time_limit: Duration) -> SandboxConfig {
+    SandboxConfig {
+        time_limit,
+        memory_limit: 128 * 1024 * 1024, // 128MB
+        cpu_limit: 25,
+        network_disabled: true,
+        max_file_size: 1024 * 1024, // 1MB
+        max_processes: 4,
+        disk_quota: 10 * 1024 * 1024, // 10MB
+    }
+}
+
+/// Run a small snippet with tight limits and no fixtures or fuzzing, for the
+/// editor's "Run" button. The result has the same fields whichever `stage`,
+/// compilation or execution, it ended in.
+pub async fn quick_eval(code: &str, language: &str) -> Result<Value, String> {
+    if code.len() > MAX_SNIPPET_SIZE {
+        return Err(format!("Snippet exceeds {} bytes", MAX_SNIPPET_SIZE));
+    }
+
+    let temp_dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
+    let workspace = temp_dir.path();
+    let run_config = eval_sandbox_config(Duration::from_secs(1));
+
+    let result = match language {
+        "javascript" => {
+            std::fs::write(workspace.join("snippet.js"), code).map_err(|e| e.to_string())?;
+            execute_in_sandbox("node", &["snippet.js"], &run_config, workspace).await
+        },
+        "python" => {
+            std::fs::write(workspace.join("snippet.py"), code).map_err(|e| e.to_string())?;
+            execute_in_sandbox("python3", &["snippet.py"], &run_config, workspace).await
+        },
+        "rust" => {
+            std::fs::write(workspace.join("snippet.rs"), code).map_err(|e| e.to_string())?;
+
+            // rustc directly, skipping cargo's dependency resolution
+            let compile_config = eval_sandbox_config(Duration::from_secs(10));
+            let compiled = execute_in_sandbox(
+                "rustc",
+                &["--edition", "2021", "-o", "snippet", "snippet.rs"],
+                &compile_config,
+                workspace,
+            ).await?;
+
+            if !compiled.success {
+                return Ok(eval_report("compilation", Ok(compiled)));
+            }
+
+            execute_in_sandbox("./snippet", &[], &run_config, workspace).await
+        },
+        _ => return Err(format!("Quick eval is not supported for language: {}", language)),
+    };
+
+    Ok(eval_report("execution", result))
+}
+
+/// The response to a snippet that ended in `stage`; a sandbox failure is
+/// reported as the stage's diagnostics.
+fn eval_report(stage: &str, result: Result<ExecutionResult, String>) -> Value {
+    match result {
+        Ok(exec_result) => json!({
+            "success": exec_result.success,
+            "stage": stage,
+            "exitCode": exec_result.exit_code,
+            "stdout": truncate_output(&exec_result.stdout),
+            "diagnostics": truncate_output(&exec_result.stderr),
+            "timeUsed": exec_result.execution_time.as_millis() as u64
+        }),
+        Err(e) => json!({
+            "success": false,
+            "stage": stage,
+            "exitCode": null,
+            "stdout": "",
+            "diagnostics": e,
+            "timeUsed": 0
+        }),
+    }
+}
+
+fn truncate_output(output: &str) -> String {
+    if output.len() <= MAX_OUTPUT_SIZE {
+        return output.to_string();
+    }
+
+    let mut end = MAX_OUTPUT_SIZE;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}\n... output truncated", &output[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha2::{Digest, Sha256};
+
+    fn execution(success: bool, stdout: &str, stderr: &str) -> ExecutionResult {
+        ExecutionResult {
+            success,
+            exit_code: Some(if success { 0 } else { 1 }),
+            stdout: stdout.to_string(),
+            stdout_sha256: hex::encode(Sha256::digest(stdout.as_bytes())),
+            stderr: stderr.to_string(),
+            execution_time: Duration::from_millis(12),
+            cpu_time: Duration::from_millis(10),
+            memory_used: 0,
+            gas_used: 0,
+            trace_events: vec![],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_snippet_limits() {
+        let oversized = "x".repeat(MAX_SNIPPET_SIZE + 1);
+        let err = quick_eval(&oversized, "python").await.unwrap_err();
+        assert!(err.contains("exceeds"));
+
+        let err = quick_eval("print(1)", "cobol").await.unwrap_err();
+        assert!(err.contains("not supported"));
+
+        // Output is cut at the limit without splitting a character
+        let long_output = "é".repeat(MAX_OUTPUT_SIZE);
+        let truncated = truncate_output(&long_output);
+        assert!(truncated.ends_with("... output truncated"));
+        assert!(truncated.len() <= MAX_OUTPUT_SIZE + "\n... output truncated".len());
+        assert_eq!(truncate_output("hello"), "hello");
+    }
+
+    #[test]
+    fn test_result_shape() {
+        let ran = eval_report("execution", Ok(execution(true, "42\n", "")));
+        assert_eq!(ran["success"], true);
+        assert_eq!(ran["stage"], "execution");
+        assert_eq!(ran["exitCode"], 0);
+        assert_eq!(ran["stdout"], "42\n");
+        assert_eq!(ran["timeUsed"], 12);
+
+        let failed = eval_report("compilation", Ok(execution(false, "", "error[E0425]: cannot find value")));
+        assert_eq!(failed["success"], false);
+        assert_eq!(failed["stage"], "compilation");
+        assert_eq!(failed["diagnostics"], "error[E0425]: cannot find value");
+
+        let crashed = eval_report("execution", Err("Sandbox unavailable".to_string()));
+        assert_eq!(crashed["diagnostics"], "Sandbox unavailable");
+
+        // Every outcome carries the same fields
+        let fields = |report: &Value| report.as_object().unwrap().keys().cloned().collect::<Vec<_>>();
+        assert_eq!(fields(&ran), fields(&failed));
+        assert_eq!(fields(&ran), fields(&crashed));
+    }
+}
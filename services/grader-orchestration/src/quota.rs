@@ -0,0 +1,361 @@
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const HISTORY_DAYS: usize = 30;
+
+/// Clients tracked at once; the longest idle one is forgotten to make room.
+const MAX_TRACKED_KEYS: usize = 10_000;
+
+pub const ANONYMOUS_KEY: &str = "anonymous";
+
+#[derive(Clone, Debug)]
+pub struct QuotaLimits {
+    /// Steady rate at which a client's bucket refills; 0 disables rate limiting
+    pub requests_per_minute: u32,
+    /// Bucket size, how many requests may come at once after a quiet spell
+    pub burst: u32,
+    pub max_concurrent_jobs: u32,
+}
+
+impl QuotaLimits {
+    pub fn from_env() -> Self {
+        Self {
+            requests_per_minute: env::var("RATE_LIMIT_PER_MINUTE")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60),
+            burst: env::var("RATE_LIMIT_BURST")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(10),
+            max_concurrent_jobs: env::var("MAX_CONCURRENT_JOBS_PER_KEY")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(4),
+        }
+    }
+}
+
+#[derive(Default, Clone, serde::Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DailyUsage {
+    requests: u64,
+    jobs_completed: u64,
+    execution_ms: u64,
+}
+
+/// Why a client's job was turned away.
+#[derive(Debug, PartialEq)]
+pub enum QuotaExceeded {
+    /// The client's bucket is empty; a request is allowed again after `retry_after`
+    RateLimited { retry_after: Duration },
+    /// The client already has `limit` jobs running
+    TooManyJobs { limit: u32 },
+}
+
+impl QuotaExceeded {
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            QuotaExceeded::RateLimited { retry_after } => Some(*retry_after),
+            QuotaExceeded::TooManyJobs { .. } => None,
+        }
+    }
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QuotaExceeded::RateLimited { retry_after } => {
+                write!(f, "Rate limit exceeded, retry in {} ms", retry_after.as_millis())
+            }
+            QuotaExceeded::TooManyJobs { limit } => write!(f, "Too many concurrent jobs, at most {} are allowed", limit),
+        }
+    }
+}
+
+impl warp::reject::Reject for QuotaExceeded {}
+
+struct KeyUsage {
+    /// Requests the client may still make right away
+    tokens: f64,
+    refilled_at: Instant,
+    concurrent_jobs: u32,
+    total_requests: u64,
+    daily: BTreeMap<String, DailyUsage>,
+    last_seen: Instant,
+}
+
+impl KeyUsage {
+    fn new(limits: &QuotaLimits) -> Self {
+        Self {
+            tokens: limits.burst as f64,
+            refilled_at: Instant::now(),
+            concurrent_jobs: 0,
+            total_requests: 0,
+            daily: BTreeMap::new(),
+            last_seen: Instant::now(),
+        }
+    }
+
+    /// Tokens in the bucket at `now`, refilled since it was last used.
+    fn tokens_at(&self, limits: &QuotaLimits, now: Instant) -> f64 {
+        let per_second = limits.requests_per_minute as f64 / 60.0;
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        (self.tokens + elapsed * per_second).min(limits.burst as f64)
+    }
+
+    fn refill(&mut self, limits: &QuotaLimits, now: Instant) {
+        self.tokens = self.tokens_at(limits, now);
+        self.refilled_at = now;
+    }
+
+    /// How long after `now` the bucket holds a whole token again.
+    fn next_token_in(&self, limits: &QuotaLimits, now: Instant) -> Duration {
+        let tokens = self.tokens_at(limits, now);
+        match tokens >= 1.0 || limits.requests_per_minute == 0 {
+            true => Duration::ZERO,
+            false => Duration::from_secs_f64((1.0 - tokens) * 60.0 / limits.requests_per_minute as f64),
+        }
+    }
+
+    fn today(&mut self) -> &mut DailyUsage {
+        let day = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        if !self.daily.contains_key(&day) {
+            // Keep a bounded amount of history per key
+            while self.daily.len() >= HISTORY_DAYS {
+                let oldest = self.daily.keys().next().cloned().unwrap();
+                self.daily.remove(&oldest);
+            }
+        }
+        self.daily.entry(day).or_default()
+    }
+}
+
+/// Per client usage counters: a token bucket limiting the request rate, jobs
+/// in flight and daily consumption history.
+pub struct UsageTracker {
+    limits: QuotaLimits,
+    usage: Mutex<HashMap<String, KeyUsage>>,
+}
+
+impl UsageTracker {
+    pub fn new(limits: QuotaLimits) -> Self {
+        Self {
+            limits,
+            usage: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn limits(&self) -> &QuotaLimits {
+        &self.limits
+    }
+
+    /// `api_key`'s usage, tracked from now on if it wasn't already. At
+    /// `MAX_TRACKED_KEYS`, the client idle the longest without jobs running
+    /// is forgotten first.
+    fn entry<'a>(&self, usage: &'a mut HashMap<String, KeyUsage>, api_key: &str) -> &'a mut KeyUsage {
+        if !usage.contains_key(api_key) && usage.len() >= MAX_TRACKED_KEYS {
+            let idlest = usage
+                .iter()
+                .filter(|(_, entry)| entry.concurrent_jobs == 0)
+                .min_by_key(|(_, entry)| entry.last_seen)
+                .map(|(key, _)| key.clone());
+            if let Some(idlest) = idlest {
+                usage.remove(&idlest);
+            }
+        }
+        let entry = usage.entry(api_key.to_string()).or_insert_with(|| KeyUsage::new(&self.limits));
+        entry.last_seen = Instant::now();
+        entry
+    }
+
+    /// Take a token from `api_key`'s bucket for a new job and reserve one
+    /// of its concurrent jobs for it, unless the bucket is empty or the
+    /// client is at its concurrent job limit. The job counts as running,
+    /// waiting for a worker slot included, until the guard is dropped.
+    pub fn admit(self: &Arc<Self>, api_key: &str) -> Result<ActiveJob, QuotaExceeded> {
+        self.admit_at(api_key, Instant::now())
+    }
+
+    fn admit_at(self: &Arc<Self>, api_key: &str, now: Instant) -> Result<ActiveJob, QuotaExceeded> {
+        let mut usage = self.usage.lock().unwrap();
+        let entry = self.entry(&mut usage, api_key);
+        if entry.concurrent_jobs >= self.limits.max_concurrent_jobs {
+            return Err(QuotaExceeded::TooManyJobs { limit: self.limits.max_concurrent_jobs });
+        }
+        if self.limits.requests_per_minute > 0 {
+            entry.refill(&self.limits, now);
+            if entry.tokens < 1.0 {
+                return Err(QuotaExceeded::RateLimited { retry_after: entry.next_token_in(&self.limits, now) });
+            }
+            entry.tokens -= 1.0;
+        }
+        Ok(self.start_job(entry, api_key))
+    }
+
+    /// Record the start of a job taken from a queue, which isn't admitted
+    /// but counts against its client's concurrent jobs all the same until
+    /// the guard is dropped.
+    pub fn begin_job(self: &Arc<Self>, api_key: &str) -> ActiveJob {
+        let mut usage = self.usage.lock().unwrap();
+        let entry = self.entry(&mut usage, api_key);
+        self.start_job(entry, api_key)
+    }
+
+    fn start_job(self: &Arc<Self>, entry: &mut KeyUsage, api_key: &str) -> ActiveJob {
+        entry.concurrent_jobs += 1;
+        entry.total_requests += 1;
+        entry.today().requests += 1;
+        ActiveJob { tracker: self.clone(), api_key: api_key.to_string(), started: Instant::now() }
+    }
+
+    /// Usage of `api_key` for `/quota`; clients not seen yet have a full
+    /// bucket and no history.
+    pub fn snapshot(&self, api_key: &str) -> Value {
+        let usage = self.usage.lock().unwrap();
+        let fresh = KeyUsage::new(&self.limits);
+        let entry = usage.get(api_key).unwrap_or(&fresh);
+        let now = Instant::now();
+
+        json!({
+            "apiKey": api_key,
+            "rateLimit": {
+                "limit": self.limits.requests_per_minute,
+                "burst": self.limits.burst,
+                "remaining": entry.tokens_at(&self.limits, now).floor() as u32,
+                "nextTokenInMs": entry.next_token_in(&self.limits, now).as_millis() as u64
+            },
+            "concurrency": {
+                "limit": self.limits.max_concurrent_jobs,
+                "active": entry.concurrent_jobs
+            },
+            "totalRequests": entry.total_requests,
+            "history": entry.daily
+        })
+    }
+}
+
+/// A job counted against its client's concurrent jobs until dropped, so jobs
+/// that fail or panic partway still give their slot back.
+pub struct ActiveJob {
+    tracker: Arc<UsageTracker>,
+    api_key: String,
+    started: Instant,
+}
+
+impl ActiveJob {
+    /// Record the job as completed in its client's daily usage.
+    pub fn finish(self) {
+        let mut usage = self.tracker.usage.lock().unwrap();
+        if let Some(entry) = usage.get_mut(&self.api_key) {
+            let today = entry.today();
+            today.jobs_completed += 1;
+            today.execution_ms += self.started.elapsed().as_millis() as u64;
+        }
+        // Released before the guard's drop takes the lock again
+        drop(usage);
+    }
+}
+
+impl std::fmt::Debug for ActiveJob {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ActiveJob").field("api_key", &self.api_key).finish_non_exhaustive()
+    }
+}
+
+impl Drop for ActiveJob {
+    fn drop(&mut self) {
+        let mut usage = self.tracker.usage.lock().unwrap();
+        if let Some(entry) = usage.get_mut(&self.api_key) {
+            entry.concurrent_jobs = entry.concurrent_jobs.saturating_sub(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_usage_snapshot_tracks_jobs() {
+        let tracker = Arc::new(UsageTracker::new(QuotaLimits {
+            requests_per_minute: 10,
+            burst: 10,
+            max_concurrent_jobs: 2,
+        }));
+
+        let mut jobs: Vec<_> = (0..2).map(|_| tracker.admit("key-a").unwrap()).collect();
+        jobs.pop().unwrap().finish();
+
+        let snapshot = tracker.snapshot("key-a");
+        assert_eq!(snapshot["rateLimit"]["remaining"], 8);
+        assert_eq!(snapshot["concurrency"]["active"], 1);
+        assert_eq!(snapshot["totalRequests"], 2);
+        assert_eq!(snapshot["history"].as_object().unwrap().values().next().unwrap()["jobsCompleted"], 1);
+
+        // A job dropped without finishing, say on an early return, still
+        // gives its slot back
+        drop(jobs);
+        assert_eq!(tracker.snapshot("key-a")["concurrency"]["active"], 0);
+
+        let other = tracker.snapshot("key-b");
+        assert_eq!(other["totalRequests"], 0);
+        assert_eq!(other["rateLimit"]["remaining"], 10);
+        assert!(!tracker.usage.lock().unwrap().contains_key("key-b"));
+    }
+
+    #[test]
+    fn test_idle_clients_are_forgotten_at_capacity() {
+        let tracker = Arc::new(UsageTracker::new(QuotaLimits {
+            requests_per_minute: 0,
+            burst: 10,
+            max_concurrent_jobs: 2,
+        }));
+        let _running = tracker.begin_job("busy");
+        for i in 0..MAX_TRACKED_KEYS {
+            tracker.admit(&format!("client-{}", i)).unwrap().finish();
+        }
+        let usage = tracker.usage.lock().unwrap();
+        assert_eq!(usage.len(), MAX_TRACKED_KEYS);
+        assert!(usage.contains_key("busy"));
+        assert!(!usage.contains_key("client-0"));
+    }
+
+    #[test]
+    fn test_token_bucket_and_concurrency_limits() {
+        let tracker = Arc::new(UsageTracker::new(QuotaLimits {
+            requests_per_minute: 60,
+            burst: 2,
+            max_concurrent_jobs: 1,
+        }));
+        let start = Instant::now();
+
+        // The burst is spent at once, then tokens come back at the steady rate
+        tracker.admit_at("key-a", start).unwrap();
+        tracker.admit_at("key-a", start).unwrap();
+        let limited = tracker.admit_at("key-a", start).unwrap_err();
+        assert_eq!(limited.retry_after(), Some(Duration::from_secs(1)));
+        assert!(tracker.admit_at("key-a", start + Duration::from_millis(500)).is_err());
+        tracker.admit_at("key-a", start + Duration::from_secs(1)).unwrap();
+        // Refills stop at the burst size
+        for _ in 0..2 {
+            tracker.admit_at("key-a", start + Duration::from_secs(60)).unwrap();
+        }
+        assert!(tracker.admit_at("key-a", start + Duration::from_secs(60)).is_err());
+
+        // An admitted job holds its client's slot while it waits to run
+        let waiting = tracker.admit_at("key-b", start).unwrap();
+        let busy = tracker.admit_at("key-b", start + Duration::from_secs(60)).unwrap_err();
+        assert_eq!(busy, QuotaExceeded::TooManyJobs { limit: 1 });
+        assert_eq!(busy.retry_after(), None);
+        drop(waiting);
+
+        let job = tracker.begin_job("key-b");
+        assert!(tracker.admit_at("key-b", start + Duration::from_secs(60)).is_err());
+        job.finish();
+        tracker.admit_at("key-b", start + Duration::from_secs(60)).unwrap();
+    }
+}
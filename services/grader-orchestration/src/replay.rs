@@ -0,0 +1,295 @@
+use crate::fixtures::TestFixture;
+use crate::sealing::Sealer;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const DEFAULT_CAPACITY: usize = 50;
+const METADATA_FILE: &str = "replay.json";
+const HIDDEN_FILE: &str = "hidden.sealed";
+
+/// Everything needed to re-run a job's fixtures against its compiled workspace.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplayMetadata {
+    pub language: String,
+    pub fixtures: Vec<TestFixture>,
+    /// Kept out of `replay.json`: sealed next to it when a hidden tests key
+    /// is configured, not preserved at all otherwise
+    #[serde(skip)]
+    pub hidden_fixtures: Vec<TestFixture>,
+    pub gas_limit: u64,
+    pub time_limit: u64,
+    pub compare_output: bool,
+    /// The challenge's Foundry test files, whose tests grade fixtures
+    #[serde(default)]
+    pub test_files: Vec<String>,
+    /// Who submitted the job, so their snapshots can be deleted
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<String>,
+}
+
+/// Preserved workspaces of recent jobs, so individual fixtures can be re-run
+/// later to investigate flaky verdicts. Oldest snapshots are deleted first.
+pub struct SnapshotStore {
+    root: PathBuf,
+    capacity: usize,
+    order: Mutex<VecDeque<String>>,
+    sealer: Option<Sealer>,
+}
+
+impl SnapshotStore {
+    pub fn from_env() -> Result<Self, String> {
+        let root = std::env::var("REPLAY_SNAPSHOT_DIR").unwrap_or_else(|_| "/tmp/fathuss_snapshots".to_string());
+        let capacity = std::env::var("REPLAY_SNAPSHOT_CAPACITY")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_CAPACITY);
+        Ok(Self::new(PathBuf::from(root), capacity).with_sealer(Sealer::from_env()?))
+    }
+
+    pub fn new(root: PathBuf, capacity: usize) -> Self {
+        Self {
+            root,
+            capacity,
+            order: Mutex::new(VecDeque::new()),
+            sealer: None,
+        }
+    }
+
+    /// Encrypt snapshotted hidden tests, so they can be replayed too.
+    pub fn with_sealer(mut self, sealer: Option<Sealer>) -> Self {
+        self.sealer = sealer;
+        self
+    }
+
+    fn job_dir(&self, job_id: &str) -> Result<PathBuf, String> {
+        // Job ids come from requests; keep them from escaping the snapshot root
+        if job_id.is_empty() || !job_id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+            return Err(format!("Invalid job id for snapshot: {}", job_id));
+        }
+        Ok(self.root.join(job_id))
+    }
+
+    /// Copy the job's workspace and replay metadata into the store.
+    pub fn snapshot(&self, job_id: &str, workspace: &Path, metadata: &ReplayMetadata) -> Result<(), String> {
+        let job_dir = self.job_dir(job_id)?;
+        if job_dir.exists() {
+            std::fs::remove_dir_all(&job_dir).map_err(|e| format!("Failed to replace snapshot: {}", e))?;
+        }
+
+        copy_dir(workspace, &job_dir.join("workspace"))?;
+        let metadata_json = serde_json::to_string(metadata).map_err(|e| e.to_string())?;
+        std::fs::write(job_dir.join(METADATA_FILE), metadata_json)
+            .map_err(|e| format!("Failed to write replay metadata: {}", e))?;
+        if let (Some(sealer), false) = (&self.sealer, metadata.hidden_fixtures.is_empty()) {
+            let hidden_json = serde_json::to_vec(&metadata.hidden_fixtures).map_err(|e| e.to_string())?;
+            std::fs::write(job_dir.join(HIDDEN_FILE), sealer.seal(&hidden_json)?)
+                .map_err(|e| format!("Failed to write sealed hidden tests: {}", e))?;
+        }
+
+        let mut order = self.order.lock().unwrap();
+        order.retain(|id| id != job_id);
+        order.push_back(job_id.to_string());
+        while order.len() > self.capacity {
+            if let Some(oldest) = order.pop_front() {
+                let _ = std::fs::remove_dir_all(self.root.join(oldest));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Restore a job's snapshot into `target`, returning its replay metadata.
+    /// Replays run on the copy so the snapshot stays pristine across runs.
+    pub fn restore(&self, job_id: &str, target: &Path) -> Result<ReplayMetadata, String> {
+        let job_dir = self.job_dir(job_id)?;
+        let metadata_json = std::fs::read_to_string(job_dir.join(METADATA_FILE))
+            .map_err(|_| format!("No workspace snapshot for job {}", job_id))?;
+        let mut metadata: ReplayMetadata = serde_json::from_str(&metadata_json).map_err(|e| format!("Corrupt replay metadata: {}", e))?;
+        if let (Some(sealer), Ok(sealed)) = (&self.sealer, std::fs::read(job_dir.join(HIDDEN_FILE))) {
+            metadata.hidden_fixtures = serde_json::from_slice(&sealer.open(&sealed)?)
+                .map_err(|e| format!("Corrupt sealed hidden tests: {}", e))?;
+        }
+
+        copy_dir(&job_dir.join("workspace"), target)?;
+        Ok(metadata)
+    }
+
+    /// Delete every snapshot of the user's jobs, returning how many there were.
+    pub fn delete_user(&self, user_id: &str) -> Result<usize, String> {
+        let entries = match std::fs::read_dir(&self.root) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(format!("Failed to read {}: {}", self.root.display(), e)),
+        };
+        let mut deleted = Vec::new();
+        for job_dir in entries.flatten().map(|entry| entry.path()) {
+            let metadata = std::fs::read_to_string(job_dir.join(METADATA_FILE))
+                .ok()
+                .and_then(|json| serde_json::from_str::<ReplayMetadata>(&json).ok());
+            if metadata.and_then(|m| m.user_id).as_deref() == Some(user_id) {
+                std::fs::remove_dir_all(&job_dir).map_err(|e| format!("Failed to delete {}: {}", job_dir.display(), e))?;
+                deleted.push(job_dir.file_name().unwrap_or_default().to_string_lossy().into_owned());
+            }
+        }
+        self.order.lock().unwrap().retain(|id| !deleted.contains(id));
+        Ok(deleted.len())
+    }
+}
+
+pub(crate) fn copy_dir(from: &Path, to: &Path) -> Result<(), String> {
+    std::fs::create_dir_all(to).map_err(|e| format!("Failed to create {}: {}", to.display(), e))?;
+
+    for entry in std::fs::read_dir(from).map_err(|e| format!("Failed to read {}: {}", from.display(), e))? {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+        let dest = to.join(entry.file_name());
+
+        if file_type.is_dir() {
+            copy_dir(&entry.path(), &dest)?;
+        } else if file_type.is_file() {
+            std::fs::copy(entry.path(), &dest)
+                .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+        }
+        // Symlinks are skipped so a snapshot never points outside the workspace
+    }
+
+    Ok(())
+}
+
+/// Outcome of a single replay run.
+#[derive(Clone, Debug)]
+pub struct ReplayRun {
+    pub passed: bool,
+    pub wall_time_ms: u64,
+    pub error: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    StablePass,
+    StableFail,
+    Flaky,
+}
+
+/// Verdict stability over repeated runs of one fixture.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StabilityReport {
+    pub fixture_id: String,
+    pub runs: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub pass_rate: f64,
+    pub verdict: Verdict,
+    pub min_wall_time_ms: u64,
+    pub max_wall_time_ms: u64,
+    pub mean_wall_time_ms: f64,
+    /// Distinct failure messages seen across runs
+    pub errors: Vec<String>,
+}
+
+impl StabilityReport {
+    pub fn from_runs(fixture_id: &str, runs: &[ReplayRun]) -> Self {
+        let passed = runs.iter().filter(|r| r.passed).count();
+        let failed = runs.len() - passed;
+        let times: Vec<u64> = runs.iter().map(|r| r.wall_time_ms).collect();
+
+        let verdict = if failed == 0 {
+            Verdict::StablePass
+        } else if passed == 0 {
+            Verdict::StableFail
+        } else {
+            Verdict::Flaky
+        };
+
+        let errors: BTreeSet<String> = runs.iter().filter_map(|r| r.error.clone()).collect();
+
+        Self {
+            fixture_id: fixture_id.to_string(),
+            runs: runs.len(),
+            passed,
+            failed,
+            pass_rate: if runs.is_empty() { 0.0 } else { passed as f64 / runs.len() as f64 },
+            verdict,
+            min_wall_time_ms: times.iter().copied().min().unwrap_or(0),
+            max_wall_time_ms: times.iter().copied().max().unwrap_or(0),
+            mean_wall_time_ms: if times.is_empty() { 0.0 } else { times.iter().sum::<u64>() as f64 / times.len() as f64 },
+            errors: errors.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(passed: bool, wall_time_ms: u64, error: Option<&str>) -> ReplayRun {
+        ReplayRun { passed, wall_time_ms, error: error.map(|e| e.to_string()) }
+    }
+
+    #[test]
+    fn test_stability_report_detects_flaky() {
+        let runs = vec![
+            run(true, 10, None),
+            run(false, 30, Some("timeout")),
+            run(true, 20, None),
+            run(false, 40, Some("timeout")),
+        ];
+
+        let report = StabilityReport::from_runs("fixture-1", &runs);
+        assert_eq!(report.verdict, Verdict::Flaky);
+        assert_eq!(report.pass_rate, 0.5);
+        assert_eq!((report.min_wall_time_ms, report.max_wall_time_ms), (10, 40));
+        assert_eq!(report.errors, vec!["timeout".to_string()]);
+
+        let stable = StabilityReport::from_runs("fixture-1", &[run(true, 5, None), run(true, 7, None)]);
+        assert_eq!(stable.verdict, Verdict::StablePass);
+    }
+
+    #[test]
+    fn test_snapshot_rejects_path_job_ids() {
+        let store = SnapshotStore::new(std::env::temp_dir(), 1);
+        assert!(store.job_dir("../etc").is_err());
+        assert!(store.job_dir("3f2c-job_1").is_ok());
+    }
+
+    #[test]
+    fn test_hidden_fixtures_are_sealed_or_dropped() {
+        let fixture = |id: &str, answer: &str| -> TestFixture {
+            serde_json::from_value(serde_json::json!({ "id": id, "expected_output": answer })).unwrap()
+        };
+        let metadata = ReplayMetadata {
+            language: "python".to_string(),
+            fixtures: vec![fixture("public", "public-answer")],
+            hidden_fixtures: vec![fixture("secret", "secret-answer")],
+            gas_limit: 1000,
+            time_limit: 10,
+            compare_output: true,
+            test_files: vec![],
+            user_id: Some("alice".to_string()),
+        };
+        let root = tempfile::tempdir().unwrap();
+        let workspace = tempfile::tempdir().unwrap();
+
+        let plain = SnapshotStore::new(root.path().join("plain"), 1);
+        plain.snapshot("job", workspace.path(), &metadata).unwrap();
+        let on_disk = std::fs::read_to_string(root.path().join("plain/job").join(METADATA_FILE)).unwrap();
+        assert!(on_disk.contains("public-answer") && !on_disk.contains("secret"));
+        assert!(plain.restore("job", &root.path().join("out1")).unwrap().hidden_fixtures.is_empty());
+
+        let sealed = SnapshotStore::new(root.path().join("sealed"), 1).with_sealer(Some(Sealer::new(&[1u8; 32]).unwrap()));
+        sealed.snapshot("job", workspace.path(), &metadata).unwrap();
+        let on_disk = std::fs::read(root.path().join("sealed/job").join(HIDDEN_FILE)).unwrap();
+        assert!(!String::from_utf8_lossy(&on_disk).contains("secret-answer"));
+        let restored = sealed.restore("job", &root.path().join("out2")).unwrap();
+        assert_eq!(restored.hidden_fixtures[0].id, "secret");
+
+        assert_eq!(sealed.delete_user("bob").unwrap(), 0);
+        assert_eq!(sealed.delete_user("alice").unwrap(), 1);
+        assert!(sealed.restore("job", &root.path().join("out3")).is_err());
+    }
+}
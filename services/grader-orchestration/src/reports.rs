@@ -0,0 +1,164 @@
+use crate::fuzzer::{CrashSeverity, FuzzCrash};
+use serde_json::{json, Value};
+
+/// One test outcome as it appears in a JUnit report.
+pub struct ReportCase {
+    pub name: String,
+    pub passed: bool,
+    pub time_ms: u64,
+    pub failure: Option<String>,
+}
+
+/// A named group of test cases (`public`, `hidden`).
+pub struct ReportSuite {
+    pub name: String,
+    pub cases: Vec<ReportCase>,
+}
+
+/// Render test results as JUnit XML for LMS and CI ingestion.
+pub fn junit_xml(job_id: &str, suites: &[ReportSuite]) -> String {
+    let total: usize = suites.iter().map(|s| s.cases.len()).sum();
+    let failures: usize = suites.iter().map(|s| s.cases.iter().filter(|c| !c.passed).count()).sum();
+    let time_ms: u64 = suites.iter().flat_map(|s| s.cases.iter()).map(|c| c.time_ms).sum();
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{}\">\n",
+        escape_xml(job_id), total, failures, seconds(time_ms)
+    ));
+
+    for suite in suites {
+        let suite_failures = suite.cases.iter().filter(|c| !c.passed).count();
+        let suite_time: u64 = suite.cases.iter().map(|c| c.time_ms).sum();
+        xml.push_str(&format!(
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{}\">\n",
+            escape_xml(&suite.name), suite.cases.len(), suite_failures, seconds(suite_time)
+        ));
+
+        for case in &suite.cases {
+            let open = format!(
+                "    <testcase name=\"{}\" classname=\"{}\" time=\"{}\"",
+                escape_xml(&case.name), escape_xml(&suite.name), seconds(case.time_ms)
+            );
+            if case.passed {
+                xml.push_str(&open);
+                xml.push_str("/>\n");
+            } else {
+                let message = case.failure.as_deref().unwrap_or("Test failed");
+                let summary = message.lines().next().unwrap_or("");
+                xml.push_str(&open);
+                xml.push_str(">\n");
+                xml.push_str(&format!(
+                    "      <failure message=\"{}\">{}</failure>\n",
+                    escape_xml(summary), escape_xml(message)
+                ));
+                xml.push_str("    </testcase>\n");
+            }
+        }
+
+        xml.push_str("  </testsuite>\n");
+    }
+
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+/// Render fuzzing crashes as a SARIF 2.1.0 log for code-scanning tools.
+pub fn fuzz_sarif(source_file: &str, crashes: &[FuzzCrash]) -> Value {
+    let results: Vec<Value> = crashes
+        .iter()
+        .map(|crash| {
+            json!({
+                "ruleId": "fuzz-crash",
+                "level": sarif_level(&crash.severity),
+                "message": {
+                    "text": format!("{} (input: {})", crash.error_message, crash.input)
+                },
+                "locations": [{
+                    "physicalLocation": { "artifactLocation": { "uri": source_file } }
+                }],
+                "partialFingerprints": { "crashSignature/v1": crash.signature },
+                "properties": {
+                    "input": crash.input,
+                    "stackTrace": crash.stack_trace,
+                    "gasUsed": crash.gas_used,
+                    "occurrences": crash.occurrences
+                }
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "fathuss-fuzzer",
+                    "informationUri": "https://github.com/Podamekalajagadeesh/Fathuss",
+                    "rules": [{
+                        "id": "fuzz-crash",
+                        "shortDescription": { "text": "Submission crashed on a fuzzed input" }
+                    }]
+                }
+            },
+            "results": results
+        }]
+    })
+}
+
+fn sarif_level(severity: &CrashSeverity) -> &'static str {
+    match severity {
+        CrashSeverity::Critical | CrashSeverity::High => "error",
+        CrashSeverity::Medium => "warning",
+        CrashSeverity::Low => "note",
+    }
+}
+
+fn seconds(ms: u64) -> String {
+    format!("{:.3}", ms as f64 / 1000.0)
+}
+
+fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than tab and newlines are not valid XML
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_junit_xml_escapes_and_counts() {
+        let xml = junit_xml("job-1", &[ReportSuite {
+            name: "public".to_string(),
+            cases: vec![
+                ReportCase { name: "adds".to_string(), passed: true, time_ms: 1500, failure: None },
+                ReportCase {
+                    name: "a<b".to_string(),
+                    passed: false,
+                    time_ms: 20,
+                    failure: Some("expected \"1\" & got 2\nsecond line\u{1b}".to_string()),
+                },
+            ],
+        }]);
+
+        assert!(xml.contains("<testsuites name=\"job-1\" tests=\"2\" failures=\"1\" time=\"1.520\">"));
+        assert!(xml.contains("<testcase name=\"adds\" classname=\"public\" time=\"1.500\"/>"));
+        assert!(xml.contains("<testcase name=\"a&lt;b\""));
+        assert!(xml.contains("<failure message=\"expected &quot;1&quot; &amp; got 2\">"));
+        assert!(!xml.contains('\u{1b}'));
+    }
+}
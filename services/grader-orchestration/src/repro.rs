@@ -0,0 +1,163 @@
+use crate::artifacts::{self, ArtifactHandle, ArtifactStore};
+use crate::cargo_fuzz;
+use crate::fuzz_config::{FuzzConfig, InputDelivery};
+use crate::fuzzer::FuzzCrash;
+use crate::sol_fuzz::SolidityFuzzEngine;
+use crate::toolchain::ToolchainVersions;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+
+/// Directory of reproduction bundles, inside the workspace artifact directory.
+const REPRO_DIR: &str = "repro";
+
+/// Everything needed to replay one fuzz crash locally: the crashing input,
+/// the commands that hit it from the project root, and the toolchain and
+/// seed the campaign ran with.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reproduction {
+    pub signature: String,
+    /// File name the commands expect the input under, in the project root
+    pub input_name: String,
+    pub input_file: ArtifactHandle,
+    /// Build first, then the command that crashes
+    pub commands: Vec<String>,
+    pub toolchain: ToolchainVersions,
+    pub seed: Option<u64>,
+    /// `repro.json`, this recipe persisted next to the input
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bundle: Option<ArtifactHandle>,
+}
+
+impl Reproduction {
+    /// Copy the input and recipe into the artifact store, so the bundle can
+    /// still be downloaded once the workspace is gone.
+    pub fn persist(mut self, store: &ArtifactStore, job_id: &str, workspace: &Path) -> Result<Self, String> {
+        let handles = std::iter::once(self.input_file.clone()).chain(self.bundle.take()).collect();
+        let mut handles = store.persist(job_id, workspace, handles)?.into_iter();
+        self.input_file = handles.next().ok_or("Reproduction input was not stored")?;
+        self.bundle = handles.next();
+        Ok(self)
+    }
+}
+
+/// How the crashes of one campaign are replayed.
+pub struct Recipe<'a> {
+    pub language: &'a str,
+    pub config: &'a FuzzConfig,
+    pub build_command: String,
+    pub run_command: &'a str,
+    pub toolchain: &'a ToolchainVersions,
+    pub seed: Option<u64>,
+}
+
+impl Recipe<'_> {
+    /// Write the crash's input and recipe to `.fathuss/repro/<signature>/`.
+    pub fn write_bundle(&self, workspace: &Path, crash: &FuzzCrash) -> Result<Reproduction, String> {
+        let (input_name, input) = self.input(crash)?;
+        let dir = format!("{}/{}/{}", artifacts::ARTIFACT_DIR, REPRO_DIR, crash.signature);
+        std::fs::create_dir_all(workspace.join(&dir)).map_err(|e| format!("Failed to create {}: {}", dir, e))?;
+        let input_path = format!("{}/{}", dir, input_name);
+        std::fs::write(workspace.join(&input_path), input)
+            .map_err(|e| format!("Failed to write reproduction input: {}", e))?;
+
+        let mut reproduction = Reproduction {
+            signature: crash.signature.clone(),
+            input_file: ArtifactHandle::for_file(workspace, &input_path)?,
+            commands: vec![self.build_command.clone(), self.crash_command(crash, &input_name)],
+            input_name,
+            toolchain: self.toolchain.clone(),
+            seed: self.seed,
+            bundle: None,
+        };
+        let bundle = artifacts::write_json_artifact(workspace, &format!("{}/{}/repro.json", REPRO_DIR, crash.signature), &reproduction)?;
+        reproduction.bundle = Some(bundle);
+        Ok(reproduction)
+    }
+
+    /// The input as the crashing run read it: JSON for input files, raw
+    /// bytes for libFuzzer.
+    fn input(&self, crash: &FuzzCrash) -> Result<(String, Vec<u8>), String> {
+        if self.config.rust_entry_point.is_some() {
+            let bytes = match &crash.input {
+                Value::String(text) => text.as_bytes().to_vec(),
+                input => {
+                    let hex = input.get("hex").and_then(|v| v.as_str()).unwrap_or_default();
+                    hex::decode(hex).map_err(|e| format!("Invalid crash input: {}", e))?
+                },
+            };
+            return Ok((format!("crash-{}", crash.signature), bytes));
+        }
+        let json = serde_json::to_string_pretty(&crash.input).map_err(|e| format!("Failed to serialize crash input: {}", e))?;
+        Ok((format!("crash-{}.json", crash.signature), json.into_bytes()))
+    }
+
+    fn crash_command(&self, crash: &FuzzCrash, input_name: &str) -> String {
+        let seed = self.seed.unwrap_or_default();
+        match self.language {
+            // Solidity findings come from the engine's own campaign, so the
+            // seed replays them; the input is the counterexample for reference
+            "solidity" => match self.config.solidity_engine {
+                SolidityFuzzEngine::Forge => format!("forge test --match-test '^{}$' --fuzz-seed {}", crash.stack_trace, seed),
+                SolidityFuzzEngine::Echidna => self
+                    .config
+                    .property_contracts
+                    .iter()
+                    .map(|c| format!("echidna . --contract {} --seed {}", c.contract, seed))
+                    .collect::<Vec<_>>()
+                    .join(" && "),
+            },
+            "rust" if self.config.rust_entry_point.is_some() => format!("cargo +{} fuzz run entry {}", cargo_fuzz::toolchain(), input_name),
+            _ if self.config.input_delivery == InputDelivery::Stdin => format!("{} < {}", self.run_command, input_name),
+            _ => format!("{} {}", self.run_command, input_name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzer::CrashSeverity;
+    use serde_json::json;
+
+    #[test]
+    fn test_bundle_holds_input_and_commands() {
+        let workspace = tempfile::tempdir().unwrap();
+        let crash = FuzzCrash {
+            input: json!({ "amount": -1 }),
+            error_message: "thread 'main' panicked at src/main.rs:4:5:".to_string(),
+            stack_trace: String::new(),
+            gas_used: 0,
+            severity: CrashSeverity::High,
+            signature: "0123456789abcdef".to_string(),
+            occurrences: 3,
+        };
+        let config = FuzzConfig::default();
+        let toolchain = ToolchainVersions { rustc: Some("1.75.0".to_string()), ..Default::default() };
+        let recipe = Recipe {
+            language: "rust",
+            config: &config,
+            build_command: "cargo build --release".to_string(),
+            run_command: "./target/release/grader-code",
+            toolchain: &toolchain,
+            seed: Some(42),
+        };
+
+        let reproduction = recipe.write_bundle(workspace.path(), &crash).unwrap();
+        assert_eq!(reproduction.commands[1], "./target/release/grader-code crash-0123456789abcdef.json");
+        let input = std::fs::read_to_string(workspace.path().join(&reproduction.input_file.path)).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&input).unwrap(), crash.input);
+        let bundle: Value = serde_json::from_reader(reproduction.bundle.unwrap().open(workspace.path()).unwrap()).unwrap();
+        assert_eq!((bundle["seed"].clone(), bundle["toolchain"]["rustc"].clone()), (json!(42), json!("1.75.0")));
+
+        // The stored bundle outlives the workspace
+        let store_root = tempfile::tempdir().unwrap();
+        let store = ArtifactStore::new(store_root.path().to_path_buf(), 10);
+        let reproduction = recipe.write_bundle(workspace.path(), &crash).unwrap().persist(&store, "job-1", workspace.path()).unwrap();
+        drop(workspace);
+        let bundle: Value = serde_json::from_reader(store.open("job-1", &reproduction.bundle.unwrap().path).unwrap()).unwrap();
+        assert_eq!(bundle["signature"], "0123456789abcdef");
+        assert!(store.open("job-1", &reproduction.input_file.path).is_ok());
+    }
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+
+/// File, relative to the program's working directory, a graded program may
+/// write its structured result to. Programs can rewrite it as they go to
+/// report progress; the last complete write wins.
+pub const RESULT_FILE: &str = "fathuss_result.json";
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Verdict {
+    Pass,
+    Fail,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Progress {
+    pub completed: u64,
+    pub total: u64,
+}
+
+/// Structured result reported by a graded program:
+///
+/// ```json
+/// { "verdict": "pass", "result": 42, "message": "...", "progress": { "completed": 3, "total": 5 } }
+/// ```
+///
+/// Every field is optional. `result` is compared with the fixture's expected
+/// output in place of the last stdout line; `verdict` lets programs that check
+/// themselves decide the outcome.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ProgramResult {
+    #[serde(default)]
+    pub verdict: Option<Verdict>,
+    #[serde(default)]
+    pub result: Option<Value>,
+    #[serde(default)]
+    pub message: Option<String>,
+    #[serde(default)]
+    pub progress: Option<Progress>,
+}
+
+/// Remove a result left by a previous run so it cannot be attributed to the next one.
+pub fn clear_result(workspace: &Path) {
+    let _ = std::fs::remove_file(workspace.join(RESULT_FILE));
+}
+
+/// Read the result the program wrote, if any. Malformed results are an error
+/// rather than silently falling back to stdout, so authors notice them.
+pub fn read_result(workspace: &Path) -> Option<Result<ProgramResult, String>> {
+    let raw = std::fs::read_to_string(workspace.join(RESULT_FILE)).ok()?;
+    Some(serde_json::from_str(&raw).map_err(|e| format!("Invalid {}: {}", RESULT_FILE, e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_read_result() {
+        let workspace = tempfile::tempdir().unwrap();
+        let dir = workspace.path();
+        assert!(read_result(dir).is_none());
+
+        std::fs::write(dir.join(RESULT_FILE), r#"{"verdict": "fail", "result": [1, 2], "progress": {"completed": 2, "total": 4}}"#).unwrap();
+        let result = read_result(dir).unwrap().unwrap();
+        assert_eq!(result.verdict, Some(Verdict::Fail));
+        assert_eq!(result.result, Some(json!([1, 2])));
+        assert_eq!(result.progress, Some(Progress { completed: 2, total: 4 }));
+
+        std::fs::write(dir.join(RESULT_FILE), r#"{"verdict": "maybe"}"#).unwrap();
+        assert!(read_result(dir).unwrap().is_err());
+
+        clear_result(dir);
+        assert!(read_result(dir).is_none());
+    }
+}
@@ -0,0 +1,100 @@
+use crate::anti_cheat::AntiCheatEngine;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long submissions are kept for plagiarism comparisons.
+#[derive(Clone, Debug)]
+pub struct RetentionPolicy {
+    /// Submissions older than this are deleted; kept forever when unset
+    pub max_age: Option<chrono::Duration>,
+    /// Time between prunes
+    pub interval: Duration,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        Self {
+            max_age: None,
+            interval: Duration::from_secs(3600),
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// `ANTI_CHEAT_RETENTION_DAYS` and `ANTI_CHEAT_RETENTION_INTERVAL_SECS`.
+    /// A value that doesn't parse is an error rather than keeping submissions
+    /// forever unnoticed.
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse(
+            std::env::var("ANTI_CHEAT_RETENTION_DAYS").ok().as_deref(),
+            std::env::var("ANTI_CHEAT_RETENTION_INTERVAL_SECS").ok().as_deref(),
+        )
+    }
+
+    fn parse(days: Option<&str>, interval_secs: Option<&str>) -> Result<Self, String> {
+        let default = Self::default();
+        let max_age = match days.map(str::trim).filter(|days| !days.is_empty()) {
+            Some(days) => match days.parse::<i64>() {
+                Ok(days) if days > 0 => Some(chrono::Duration::days(days)),
+                _ => return Err(format!("Invalid ANTI_CHEAT_RETENTION_DAYS: {}", days)),
+            },
+            None => default.max_age,
+        };
+        let interval = match interval_secs.map(str::trim).filter(|secs| !secs.is_empty()) {
+            Some(secs) => match secs.parse::<u64>() {
+                Ok(secs) if secs > 0 => Duration::from_secs(secs),
+                _ => return Err(format!("Invalid ANTI_CHEAT_RETENTION_INTERVAL_SECS: {}", secs)),
+            },
+            None => default.interval,
+        };
+        Ok(Self { max_age, interval })
+    }
+}
+
+/// Delete the submissions older than `max_age`, returning how many.
+pub async fn prune_expired(engine: &AntiCheatEngine, max_age: chrono::Duration) -> Result<usize, String> {
+    engine.prune_submissions(chrono::Utc::now() - max_age).await
+}
+
+/// Prune expired submissions every `policy.interval`, for as long as the
+/// worker runs. Returns at once if the policy keeps submissions forever.
+pub async fn run_retention(engine: Arc<AntiCheatEngine>, policy: RetentionPolicy) {
+    let max_age = match policy.max_age {
+        Some(max_age) => max_age,
+        None => return,
+    };
+    loop {
+        match prune_expired(&engine, max_age).await {
+            Ok(0) => {}
+            Ok(deleted) => println!("Pruned {} submissions past the retention period", deleted),
+            Err(e) => eprintln!("Warning: Failed to prune expired submissions: {}", e),
+        }
+        tokio::time::sleep(policy.interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_prunes_only_expired_submissions() {
+        let engine = AntiCheatEngine::new();
+        let code = "fn main() { let x = 1; }";
+        engine.store_submission("c:rust:alice:1", code, "rust", "alice", "c", true).await.unwrap();
+
+        assert_eq!(prune_expired(&engine, chrono::Duration::days(30)).await.unwrap(), 0);
+        assert_eq!(prune_expired(&engine, chrono::Duration::zero()).await.unwrap(), 1);
+        assert_eq!(engine.find_collusion("c", "rust", 0.7).await.unwrap().submissions, 0);
+    }
+
+    #[test]
+    fn test_invalid_retention_is_an_error() {
+        assert_eq!(RetentionPolicy::parse(None, None).unwrap().max_age, None);
+        assert_eq!(RetentionPolicy::parse(Some("30"), None).unwrap().max_age, Some(chrono::Duration::days(30)));
+        for days in ["30d", "-1", "0"] {
+            assert!(RetentionPolicy::parse(Some(days), None).is_err());
+        }
+        assert!(RetentionPolicy::parse(Some("30"), Some("hourly")).is_err());
+    }
+}
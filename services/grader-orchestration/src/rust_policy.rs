@@ -0,0 +1,406 @@
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Dependency tables of a Cargo manifest that submissions may use.
+const DEPENDENCY_SECTIONS: &[&str] = &["dependencies", "dev-dependencies"];
+
+/// Manifest sections that would change where or how crates are built.
+const FORBIDDEN_SECTIONS: &[&str] = &["build-dependencies", "patch", "replace", "workspace", "target"];
+
+/// `source` of crates.io packages in `cargo metadata`, over the git and the
+/// sparse index protocol.
+const CRATES_IO_SOURCES: &[&str] = &[
+    "registry+https://github.com/rust-lang/crates.io-index",
+    "sparse+https://index.crates.io/",
+];
+
+/// How build-time code execution (build scripts and procedural macros) in
+/// Rust submissions is handled.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BuildPolicyMode {
+    /// Reject submissions with non-allowlisted build scripts or proc-macros
+    Forbid,
+    /// Allow them, but compile under the same confinement as test execution
+    Confine,
+    /// No restrictions beyond the regular compile sandbox
+    Allow,
+}
+
+/// Set per deployment with `RUST_BUILD_POLICY`, and per challenge with a
+/// `buildPolicy` object in the challenge config (or an admin's payload),
+/// whose fields replace the deployment's.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RustBuildPolicy {
+    pub mode: BuildPolicyMode,
+    pub allowed_proc_macros: Vec<String>,
+    /// Dependencies whose build scripts are vetted to run in `forbid` mode
+    pub allowed_build_scripts: Vec<String>,
+    /// Crates a submitted Cargo.toml may depend on, by name, with the version
+    /// range each requirement must fall in (e.g. `"serde": "^1.0.100"`)
+    pub allowed_dependencies: HashMap<String, String>,
+}
+
+impl Default for RustBuildPolicy {
+    fn default() -> Self {
+        Self {
+            mode: BuildPolicyMode::Forbid,
+            // Required by the serde dependency in the generated Cargo.toml
+            // and the vendored crates
+            allowed_proc_macros: vec!["serde_derive".to_string(), "thiserror-impl".to_string()],
+            allowed_build_scripts: ["serde", "serde_json", "proc-macro2", "quote", "libc", "thiserror"]
+                .iter()
+                .map(|name| name.to_string())
+                .collect(),
+            allowed_dependencies: HashMap::from([
+                ("serde".to_string(), "1".to_string()),
+                ("serde_json".to_string(), "1".to_string()),
+            ]),
+        }
+    }
+}
+
+impl RustBuildPolicy {
+    /// `RUST_BUILD_POLICY`, a JSON document, or the defaults.
+    pub fn from_env() -> Result<Self, String> {
+        match std::env::var("RUST_BUILD_POLICY") {
+            Ok(doc) if !doc.trim().is_empty() => {
+                serde_json::from_str(&doc).map_err(|e| format!("Invalid RUST_BUILD_POLICY: {}", e))
+            }
+            _ => Ok(Self::default()),
+        }
+    }
+
+    /// The payload's `buildPolicy` fields applied to `deployment`. Only the
+    /// challenge config and admins may set them, see `auth::authorize_job`.
+    pub fn from_payload(payload: &Value, deployment: &Self) -> Result<Self, String> {
+        let overrides = match payload.get("buildPolicy") {
+            None | Some(Value::Null) => return Ok(deployment.clone()),
+            Some(Value::Object(overrides)) => overrides,
+            Some(_) => return Err("buildPolicy must be an object".to_string()),
+        };
+        let mut merged = serde_json::to_value(deployment).map_err(|e| e.to_string())?;
+        if let Some(merged) = merged.as_object_mut() {
+            merged.extend(overrides.iter().map(|(key, value)| (key.clone(), value.clone())));
+        }
+        serde_json::from_value(merged).map_err(|e| format!("Invalid buildPolicy: {}", e))
+    }
+
+    /// Validate a Cargo.toml supplied with the submission and return it with
+    /// its package renamed to `package_name`, the name the build expects.
+    /// Every dependency must be an allowlisted crates.io crate whose version
+    /// requirement falls in the allowed range; build scripts, git and path
+    /// dependencies, alternative registries and patches are rejected whatever
+    /// the build policy mode.
+    pub fn check_manifest(&self, manifest: &str, package_name: &str) -> Result<String, String> {
+        let mut manifest: toml::Table = manifest
+            .parse()
+            .map_err(|e| format!("Failed to parse Cargo.toml: {}", e))?;
+
+        for section in FORBIDDEN_SECTIONS {
+            if manifest.contains_key(*section) {
+                return Err(format!("Cargo.toml may not contain a [{}] section", section));
+            }
+        }
+
+        let package = manifest
+            .get_mut("package")
+            .and_then(|p| p.as_table_mut())
+            .ok_or("Cargo.toml has no [package] section")?;
+        if package.get("build").map(|b| b.as_bool() != Some(false)).unwrap_or(false) {
+            return Err("Build scripts are not allowed in a submitted Cargo.toml".to_string());
+        }
+        package.insert("name".to_string(), toml::Value::String(package_name.to_string()));
+
+        let mut violations = Vec::new();
+        for section in DEPENDENCY_SECTIONS {
+            let deps = match manifest.get(*section).and_then(|d| d.as_table()) {
+                Some(deps) => deps,
+                None => continue,
+            };
+            for (name, spec) in deps {
+                if let Err(e) = self.check_dependency(name, spec) {
+                    violations.push(e);
+                }
+            }
+        }
+
+        if !violations.is_empty() {
+            return Err(format!("Cargo.toml dependencies not allowed: {}", violations.join(", ")));
+        }
+        toml::to_string(&manifest).map_err(|e| format!("Failed to write Cargo.toml: {}", e))
+    }
+
+    fn check_dependency(&self, name: &str, spec: &toml::Value) -> Result<(), String> {
+        let (krate, requirement) = match spec {
+            toml::Value::String(requirement) => (name, requirement.as_str()),
+            toml::Value::Table(table) => {
+                for source in ["git", "path", "registry", "registry-index"] {
+                    if table.contains_key(source) {
+                        return Err(format!("{} ({} dependency)", name, source));
+                    }
+                }
+                let krate = table.get("package").and_then(|p| p.as_str()).unwrap_or(name);
+                let requirement = table
+                    .get("version")
+                    .and_then(|v| v.as_str())
+                    .ok_or_else(|| format!("{} (no version requirement)", name))?;
+                (krate, requirement)
+            },
+            _ => return Err(format!("{} (invalid dependency)", name)),
+        };
+
+        let allowed = self
+            .allowed_dependencies
+            .get(krate)
+            .ok_or_else(|| format!("{} (not allowlisted)", krate))?;
+        let allowed = VersionReq::parse(allowed).map_err(|e| format!("{} (invalid allowed range: {})", krate, e))?;
+        let requested = VersionReq::parse(requirement)
+            .ok()
+            .and_then(|requirement| version_range(&requirement))
+            .ok_or_else(|| format!("{} (invalid version {})", krate, requirement))?;
+        match version_range(&allowed) {
+            Some(allowed) if allowed.contains(&requested) => Ok(()),
+            _ => Err(format!("{} {} (allowed: {})", krate, requirement, allowed)),
+        }
+    }
+
+    /// Scan the submission's own sources for build scripts and proc-macro crates.
+    pub fn check_workspace(&self, workspace: &Path) -> Result<(), String> {
+        if self.mode != BuildPolicyMode::Forbid {
+            return Ok(());
+        }
+
+        if workspace.join("build.rs").exists() {
+            return Err("Build scripts (build.rs) are not allowed for this challenge".to_string());
+        }
+
+        let manifest = std::fs::read_to_string(workspace.join("Cargo.toml"))
+            .map_err(|e| format!("Failed to read Cargo.toml: {}", e))?;
+        let manifest: toml::Value = manifest
+            .parse()
+            .map_err(|e| format!("Failed to parse Cargo.toml: {}", e))?;
+
+        if manifest.get("package").and_then(|p| p.get("build")).is_some() {
+            return Err("Build scripts are not allowed for this challenge".to_string());
+        }
+
+        let is_proc_macro = manifest
+            .get("lib")
+            .and_then(|l| l.get("proc-macro"))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        if is_proc_macro {
+            return Err("Procedural macro crates are not allowed for this challenge".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Check the resolved dependency graph (`cargo metadata --format-version 1`)
+    /// for dependencies that run code at build time. Allowlisted names only
+    /// count for packages from crates.io, so a path or git package can't take
+    /// one over.
+    pub fn check_dependencies(&self, metadata_json: &str) -> Result<(), String> {
+        if self.mode != BuildPolicyMode::Forbid {
+            return Ok(());
+        }
+
+        let metadata: Value = serde_json::from_str(metadata_json)
+            .map_err(|e| format!("Failed to parse cargo metadata: {}", e))?;
+        let packages = metadata
+            .get("packages")
+            .and_then(|p| p.as_array())
+            .ok_or("cargo metadata has no packages")?;
+
+        let mut violations = Vec::new();
+
+        for package in packages {
+            let name = package.get("name").and_then(|n| n.as_str()).unwrap_or("");
+            let from_crates_io = package
+                .get("source")
+                .and_then(|s| s.as_str())
+                .is_some_and(|source| CRATES_IO_SOURCES.contains(&source));
+            let allowed = |list: &[String]| from_crates_io && list.iter().any(|allowed| allowed == name);
+
+            let targets = package.get("targets").and_then(|t| t.as_array());
+            for target in targets.into_iter().flatten() {
+                let kinds = target.get("kind").and_then(|k| k.as_array());
+                for kind in kinds.into_iter().flatten().filter_map(|k| k.as_str()) {
+                    match kind {
+                        "proc-macro" if !allowed(&self.allowed_proc_macros) => violations.push(format!("{} (proc-macro)", name)),
+                        "custom-build" if !allowed(&self.allowed_build_scripts) => violations.push(format!("{} (build script)", name)),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(format!(
+                "Dependencies executing code at build time are not allowed: {}",
+                violations.join(", ")
+            ))
+        }
+    }
+}
+
+/// The versions a Cargo version requirement accepts, from `lower` up to but
+/// excluding `upper`, if any. Pre-releases are not told apart.
+#[derive(Debug, PartialEq)]
+struct VersionRange {
+    lower: Version,
+    upper: Option<Version>,
+}
+
+impl VersionRange {
+    fn contains(&self, other: &VersionRange) -> bool {
+        other.lower >= self.lower
+            && match (&self.upper, &other.upper) {
+                (None, _) => true,
+                (Some(upper), Some(other_upper)) => other_upper <= upper,
+                (Some(_), None) => false,
+            }
+    }
+}
+
+/// The range a requirement's comparators all accept, e.g. `>=1.2.0, <2.0.0`
+/// for `"1.2"` or `"^1.2"`; `None` if they accept nothing.
+fn version_range(requirement: &VersionReq) -> Option<VersionRange> {
+    let mut range = VersionRange { lower: Version::new(0, 0, 0), upper: None };
+    for comparator in &requirement.comparators {
+        let (lower, upper) = comparator_range(comparator);
+        range.lower = range.lower.max(lower);
+        range.upper = match (range.upper, upper) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+    }
+    match &range.upper {
+        Some(upper) if *upper <= range.lower => None,
+        _ => Some(range),
+    }
+}
+
+/// Bounds of a single comparator, as in Cargo: `1.2` and `=1.2` mean
+/// `>=1.2.0, <1.3.0`, `^0.2.3` means `>=0.2.3, <0.3.0`, and so on.
+fn comparator_range(comparator: &semver::Comparator) -> (Version, Option<Version>) {
+    use semver::Op;
+
+    let major = comparator.major;
+    let (minor, patch) = (comparator.minor.unwrap_or(0), comparator.patch.unwrap_or(0));
+    let start = Version::new(major, minor, patch);
+    // The first version past everything the comparator's given parts match
+    let past = match (comparator.minor, comparator.patch) {
+        (Some(minor), Some(patch)) => Version::new(major, minor, patch + 1),
+        (Some(minor), None) => Version::new(major, minor + 1, 0),
+        _ => Version::new(major + 1, 0, 0),
+    };
+    match comparator.op {
+        Op::Exact | Op::Wildcard => (start, Some(past)),
+        Op::Greater => (past, None),
+        Op::GreaterEq => (start, None),
+        Op::Less => (Version::new(0, 0, 0), Some(start)),
+        Op::LessEq => (Version::new(0, 0, 0), Some(past)),
+        Op::Tilde => match comparator.minor {
+            Some(minor) => (start, Some(Version::new(major, minor + 1, 0))),
+            None => (start, Some(past)),
+        },
+        // The leftmost non-zero part given must not change
+        _ => match (major, comparator.minor, comparator.patch) {
+            (0, Some(0), Some(_)) => (start, Some(past)),
+            (0, Some(minor), _) => (start, Some(Version::new(0, minor + 1, 0))),
+            _ => (start, Some(Version::new(major + 1, 0, 0))),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_dependency_policy() {
+        let metadata = json!({
+            "packages": [
+                { "name": "grader-code", "targets": [{ "kind": ["bin"] }] },
+                { "name": "serde_derive", "source": CRATES_IO_SOURCES[0], "targets": [{ "kind": ["proc-macro"] }] },
+                { "name": "serde", "source": CRATES_IO_SOURCES[0], "targets": [{ "kind": ["lib"] }, { "kind": ["custom-build"] }] },
+                { "name": "proc-macro2", "source": CRATES_IO_SOURCES[1], "targets": [{ "kind": ["lib"] }, { "kind": ["custom-build"] }] },
+                { "name": "evil", "source": CRATES_IO_SOURCES[0], "targets": [{ "kind": ["lib"] }, { "kind": ["custom-build"] }] },
+                { "name": "evil_derive", "source": CRATES_IO_SOURCES[0], "targets": [{ "kind": ["proc-macro"] }] }
+            ]
+        })
+        .to_string();
+
+        let policy = RustBuildPolicy::default();
+        let err = policy.check_dependencies(&metadata).unwrap_err();
+        assert!(err.contains("evil (build script)"));
+        assert!(err.contains("evil_derive (proc-macro)"));
+        assert!(!err.contains("serde"));
+        assert!(!err.contains("proc-macro2"));
+
+        let confine = RustBuildPolicy::from_payload(&json!({ "buildPolicy": { "mode": "confine" } }), &policy).unwrap();
+        assert_eq!(confine.mode, BuildPolicyMode::Confine);
+        assert_eq!(confine.allowed_build_scripts, policy.allowed_build_scripts);
+        assert!(confine.check_dependencies(&metadata).is_ok());
+
+        // Allowlists replace the deployment's
+        let vetted = json!({ "buildPolicy": { "allowedBuildScripts": ["evil"], "allowedProcMacros": ["evil_derive"] } });
+        let err = RustBuildPolicy::from_payload(&vetted, &policy).unwrap().check_dependencies(&metadata).unwrap_err();
+        assert!(err.contains("serde (build script)"));
+        assert!(!err.contains("evil"));
+        assert!(RustBuildPolicy::from_payload(&json!({ "buildPolicy": "allow" }), &policy).is_err());
+    }
+
+    #[test]
+    fn test_allowlisted_name_from_path_is_rejected() {
+        // A local crate named like an allowlisted one runs its own build script
+        let metadata = json!({
+            "packages": [
+                { "name": "serde", "source": null, "targets": [{ "kind": ["lib"] }, { "kind": ["custom-build"] }] },
+                { "name": "serde_derive", "source": "git+https://example.com/serde#abc", "targets": [{ "kind": ["proc-macro"] }] }
+            ]
+        })
+        .to_string();
+
+        let err = RustBuildPolicy::default().check_dependencies(&metadata).unwrap_err();
+        assert!(err.contains("serde (build script)"));
+        assert!(err.contains("serde_derive (proc-macro)"));
+    }
+
+    #[test]
+    fn test_submitted_manifest_allowlist() {
+        let policy = RustBuildPolicy::default();
+        let manifest = policy
+            .check_manifest("[package]\nname = \"mine\"\nversion = \"0.1.0\"\n\n[dependencies]\nserde = { version = \"1.0.190\", features = [\"derive\"] }\n", "grader-code")
+            .unwrap();
+        assert!(manifest.contains("name = \"grader-code\""));
+
+        let err = policy
+            .check_manifest("[package]\nname = \"mine\"\n\n[dependencies]\nserde = \"0.9\"\nrand = \"0.8\"\nserde_json = { git = \"https://example.com/serde_json\" }\n", "grader-code")
+            .unwrap_err();
+        assert!(err.contains("serde 0.9 (allowed: ^1)"));
+        assert!(err.contains("rand (not allowlisted)"));
+        assert!(err.contains("serde_json (git dependency)"));
+
+        // The whole requirement must fall in the allowed range, not just its lower bound
+        let dependency = |requirement: &str| format!("[package]\nname = \"mine\"\n\n[dependencies]\nserde = \"{}\"\n", requirement);
+        for requirement in ["~1.0.100", ">=1.0.100, <1.5", "=1.0.190", "1.*"] {
+            assert!(policy.check_manifest(&dependency(requirement), "grader-code").is_ok(), "{}", requirement);
+        }
+        for requirement in [">=1.0", "*", "<1.5", "0", ">1, <1"] {
+            assert!(policy.check_manifest(&dependency(requirement), "grader-code").is_err(), "{}", requirement);
+        }
+
+        assert!(policy.check_manifest("[package]\nname = \"mine\"\nbuild = \"build.rs\"\n", "grader-code").is_err());
+        assert!(policy.check_manifest("[package]\nname = \"mine\"\n\n[build-dependencies]\ncc = \"1\"\n", "grader-code").is_err());
+    }
+}
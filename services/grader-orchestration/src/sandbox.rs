@@ -1,14 +1,19 @@
+use std::ffi::CString;
+use std::os::unix::ffi::OsStringExt;
 use std::os::unix::process::CommandExt;
 use std::process::{Command, Stdio};
 use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
 use tokio::process::Command as TokioCommand;
 use tokio::time::timeout;
 use rlimit::{setrlimit, Resource};
 use nix::unistd::{setuid, setgid, Uid, Gid};
 use nix::sys::resource::{setrlimit as nix_setrlimit, Resource as NixResource};
 use serde_json::{json, Value};
-use cgroups_rs::{cgroup_builder::CgroupBuilder, Cgroup, Subsystem, CgroupPid};
+use sha2::{Digest, Sha256};
+use cgroups_rs::{cgroup_builder::CgroupBuilder, Cgroup, Subsystem};
 use std::fs;
+use crate::memory_budget::{MemoryBudget, MemoryReservation};
 
 pub struct SandboxConfig {
     pub time_limit: Duration,
@@ -20,6 +25,9 @@ pub struct SandboxConfig {
     pub disk_quota: u64,   // in bytes for ephemeral volumes
 }
 
+/// Error returned by `execute_in_sandbox` when the time limit is exceeded.
+pub const TIMEOUT_ERROR: &str = "Execution timed out";
+
 impl Default for SandboxConfig {
     fn default() -> Self {
         Self {
@@ -38,8 +46,14 @@ pub struct ExecutionResult {
     pub success: bool,
     pub exit_code: Option<i32>,
     pub stdout: String,
+    /// Hex SHA-256 of the raw stdout, which `stdout` only holds lossily
+    /// decoded; binary outputs are compared by it
+    pub stdout_sha256: String,
     pub stderr: String,
     pub execution_time: Duration,
+    /// User + system CPU time of the process and its children, as accounted
+    /// by the sandbox cgroup
+    pub cpu_time: Duration,
     pub memory_used: u64,
     pub gas_used: u64,
     pub trace_events: Vec<TraceEvent>,
@@ -60,6 +74,104 @@ pub async fn execute_in_sandbox(
     config: &SandboxConfig,
     working_dir: &std::path::Path,
 ) -> Result<ExecutionResult, String> {
+    execute_in_sandbox_with_env(command, args, &[], config, working_dir).await
+}
+
+/// Like `execute_in_sandbox`, with extra environment variables set for the process.
+pub async fn execute_in_sandbox_with_env(
+    command: &str,
+    args: &[&str],
+    env: &[(String, String)],
+    config: &SandboxConfig,
+    working_dir: &std::path::Path,
+) -> Result<ExecutionResult, String> {
+    execute_sandboxed(command, args, env, None, config, working_dir).await
+}
+
+/// Like `execute_in_sandbox_with_env`, with `stdin` written to the process's standard input.
+pub async fn execute_in_sandbox_with_stdin(
+    command: &str,
+    args: &[&str],
+    env: &[(String, String)],
+    stdin: &[u8],
+    config: &SandboxConfig,
+    working_dir: &std::path::Path,
+) -> Result<ExecutionResult, String> {
+    execute_sandboxed(command, args, env, Some(stdin), config, working_dir).await
+}
+
+/// Like `execute_in_sandbox`, but processes the command leaves running, e.g. a
+/// service a fixture's setup starts in the background, keep running in the
+/// returned group until it is dropped.
+pub async fn start_in_sandbox(
+    command: &str,
+    args: &[&str],
+    config: &SandboxConfig,
+    working_dir: &std::path::Path,
+) -> Result<(ExecutionResult, ServiceGroup), String> {
+    run_sandboxed(command, args, &[], None, config, working_dir).await
+}
+
+/// The cgroup of a sandboxed command, holding its memory reservation. When
+/// dropped, every process still in it is killed, so nothing a sandbox starts
+/// outlives it or escapes the memory budget.
+pub struct ServiceGroup {
+    name: String,
+    cgroup: Cgroup,
+    _memory_reservation: MemoryReservation,
+}
+
+impl Drop for ServiceGroup {
+    fn drop(&mut self) {
+        for _ in 0..KILL_ATTEMPTS {
+            let procs = self.cgroup.procs();
+            if procs.is_empty() {
+                break;
+            }
+            for pid in procs {
+                // SAFETY: sending a signal has no memory effects
+                unsafe {
+                    libc::kill(pid.pid as libc::pid_t, libc::SIGKILL);
+                }
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        if let Err(e) = self.cgroup.delete() {
+            eprintln!("Warning: Failed to delete cgroup {}: {}", self.name, e);
+        }
+    }
+}
+
+/// Rounds of killing a group's processes, for those forked in between.
+const KILL_ATTEMPTS: usize = 10;
+
+async fn execute_sandboxed(
+    command: &str,
+    args: &[&str],
+    env: &[(String, String)],
+    stdin: Option<&[u8]>,
+    config: &SandboxConfig,
+    working_dir: &std::path::Path,
+) -> Result<ExecutionResult, String> {
+    // Anything the command left running is killed with its group
+    run_sandboxed(command, args, env, stdin, config, working_dir).await.map(|(result, _)| result)
+}
+
+async fn run_sandboxed(
+    command: &str,
+    args: &[&str],
+    env: &[(String, String)],
+    stdin: Option<&[u8]>,
+    config: &SandboxConfig,
+    working_dir: &std::path::Path,
+) -> Result<(ExecutionResult, ServiceGroup), String> {
+    // Wait for host memory before launching; the reservation is held until
+    // the sandbox is torn down
+    let queued_at = Instant::now();
+    let memory_reservation = MemoryBudget::global().reserve(config.memory_limit).await;
+    let queued_ms = queued_at.elapsed().as_millis() as u64;
+
     let start_time = Instant::now();
     let mut trace_events = Vec::new();
 
@@ -70,9 +182,10 @@ pub async fn execute_in_sandbox(
         data: json!({
             "command": command,
             "args": args,
-            "working_dir": working_dir.to_string_lossy()
+            "working_dir": working_dir.to_string_lossy(),
+            "queued_ms": queued_ms
         }),
-        gas_used: 100,
+        gas_used: 0,
         memory_used: 0,
     });
 
@@ -81,6 +194,8 @@ pub async fn execute_in_sandbox(
 
     // Create cgroup with limits
     let cgroup = create_cgroup_with_limits(&cgroup_name, config)?;
+    let group = ServiceGroup { name: cgroup_name, cgroup, _memory_reservation: memory_reservation };
+    let procs_files = cgroup_procs_files(&group.cgroup)?;
 
     // Set up ephemeral volume with disk quota
     let temp_mount_point = setup_ephemeral_volume(config.disk_quota)?;
@@ -90,17 +205,38 @@ pub async fn execute_in_sandbox(
 
     // Execute with timeout
     let execution_result = timeout(config.time_limit, async {
-        let mut child = TokioCommand::new(command)
+        let mut command = TokioCommand::new(command);
+        command
             .args(args)
+            .envs(env.iter().map(|(k, v)| (k, v)))
             .current_dir(working_dir)
+            .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::inherit() })
             .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .map_err(|e| format!("Failed to spawn process: {}", e))?;
+            .stderr(Stdio::piped());
+        // The process joins the cgroup before it runs, so nothing it forks
+        // starts outside
+        // SAFETY: `write_proc_file` only makes system calls
+        unsafe {
+            command.pre_exec(move || {
+                for procs_file in &procs_files {
+                    crate::stubs::write_proc_file(procs_file, b"0")?;
+                }
+                Ok(())
+            });
+        }
+        // Lookups resolve against the DNS stub, failing fast, in this process only
+        if let Some(resolver) = crate::stubs::sandbox_resolver() {
+            // SAFETY: `apply` only makes system calls
+            unsafe {
+                command.pre_exec(move || resolver.apply());
+            }
+        }
+        let mut child = command.spawn().map_err(|e| format!("Failed to spawn process: {}", e))?;
 
-        // Move process to cgroup
-        if let Some(pid) = child.id() {
-            add_process_to_cgroup(&cgroup, pid)?;
+        // The pipe is closed after writing so the program sees end of input.
+        // A program that exits without reading it all is not an error.
+        if let (Some(data), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            let _ = pipe.write_all(data).await;
         }
 
         // If network is disabled, we would set up network namespaces here
@@ -113,10 +249,13 @@ pub async fn execute_in_sandbox(
     }).await;
 
     let execution_time = start_time.elapsed();
+    let cpu_time = cgroup_cpu_time(&group.cgroup);
+    let memory_peak = cgroup_memory_peak(&group.cgroup);
 
     let result = match execution_result {
         Ok(Ok(output)) => {
             let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+            let stdout_sha256 = hex::encode(Sha256::digest(&output.stdout));
             let stderr = String::from_utf8_lossy(&output.stderr).to_string();
             let exit_code = output.status.code();
 
@@ -129,18 +268,21 @@ pub async fn execute_in_sandbox(
                     "stdout_length": stdout.len(),
                     "stderr_length": stderr.len()
                 }),
-                gas_used: 200,
-                memory_used: config.memory_limit / 2, // Simplified memory tracking
+                gas_used: 0,
+                memory_used: memory_peak,
             });
 
             Ok(ExecutionResult {
                 success: output.status.success(),
                 exit_code,
                 stdout,
+                stdout_sha256,
                 stderr,
                 execution_time,
-                memory_used: config.memory_limit / 2, // Simplified
-                gas_used: 300, // Simplified gas calculation
+                cpu_time,
+                memory_used: memory_peak,
+                // Native processes have no gas meter; EVM gas comes from forge or revm
+                gas_used: 0,
                 trace_events,
             })
         },
@@ -152,18 +294,13 @@ pub async fn execute_in_sandbox(
                 event_type: "execution_timeout".to_string(),
                 data: json!({"reason": "time_limit_exceeded"}),
                 gas_used: 0,
-                memory_used: 0,
+                memory_used: memory_peak,
             });
 
-            Err("Execution timed out".to_string())
+            Err(TIMEOUT_ERROR.to_string())
         }
     };
 
-    // Clean up cgroup
-    if let Err(e) = cgroup.delete() {
-        eprintln!("Warning: Failed to delete cgroup {}: {}", cgroup_name, e);
-    }
-
     // Clean up temp mount
     if let Err(e) = std::process::Command::new("umount").arg(&temp_mount_point).status() {
         eprintln!("Warning: Failed to unmount {}: {:?}", temp_mount_point.display(), e);
@@ -172,7 +309,60 @@ pub async fn execute_in_sandbox(
         eprintln!("Warning: Failed to remove temp dir {}: {}", temp_mount_point.display(), e);
     }
 
-    result
+    result.map(|result| (result, group))
+}
+
+/// The `cgroup.procs` file of each of the cgroup's hierarchies; writing 0 to
+/// one moves the writing process there.
+fn cgroup_procs_files(cgroup: &Cgroup) -> Result<Vec<CString>, String> {
+    let mut files: Vec<std::path::PathBuf> = cgroup
+        .subsystems()
+        .iter()
+        .map(|s| s.to_controller().path().join("cgroup.procs"))
+        .collect();
+    files.sort();
+    files.dedup();
+    files
+        .into_iter()
+        .map(|file| CString::new(file.into_os_string().into_vec()).map_err(|e| format!("Invalid cgroup path: {}", e)))
+        .collect()
+}
+
+/// Peak memory usage of the sandbox cgroup in bytes, 0 if the memory
+/// controller is unavailable.
+fn cgroup_memory_peak(cgroup: &Cgroup) -> u64 {
+    cgroup
+        .subsystems()
+        .iter()
+        .find_map(|s| match s {
+            Subsystem::Mem(mem_ctrl) => Some(mem_ctrl.memory_stat().max_usage_in_bytes),
+            _ => None,
+        })
+        .unwrap_or(0)
+}
+
+/// CPU time used by the processes of the sandbox cgroup, so concurrent
+/// sandboxes don't count each other's time. Zero if CPU accounting is
+/// unavailable.
+fn cgroup_cpu_time(cgroup: &Cgroup) -> Duration {
+    cgroup
+        .subsystems()
+        .iter()
+        .find_map(|s| match s {
+            // cgroup v1 accounts in nanoseconds
+            Subsystem::CpuAcct(acct_ctrl) => Some(Duration::from_nanos(acct_ctrl.cpuacct().usage)),
+            // cgroup v2 reports `usage_usec` in cpu.stat
+            Subsystem::Cpu(cpu_ctrl) => parse_usage_usec(&cpu_ctrl.cpu().stat),
+            _ => None,
+        })
+        .unwrap_or(Duration::ZERO)
+}
+
+fn parse_usage_usec(stat: &str) -> Option<Duration> {
+    stat.lines()
+        .find_map(|line| line.strip_prefix("usage_usec "))
+        .and_then(|usec| usec.trim().parse().ok())
+        .map(Duration::from_micros)
 }
 
 fn create_cgroup_with_limits(name: &str, config: &SandboxConfig) -> Result<Cgroup, String> {
@@ -212,10 +402,6 @@ fn create_cgroup_with_limits(name: &str, config: &SandboxConfig) -> Result<Cgrou
     Ok(cgroup)
 }
 
-fn add_process_to_cgroup(cgroup: &Cgroup, pid: u32) -> Result<(), String> {
-    cgroup.add_task(CgroupPid::from(pid as u64)).map_err(|e| format!("Failed to add process to cgroup: {}", e))
-}
-
 fn setup_ephemeral_volume(disk_quota: u64) -> Result<std::path::PathBuf, String> {
     // Create a temporary directory for the mount point
     let mount_point = std::env::temp_dir().join(format!("fathuss_temp_{}", uuid::Uuid::new_v4().simple()));
@@ -268,6 +454,53 @@ fn set_resource_limits(config: &SandboxConfig) -> Result<(), String> {
     Ok(())
 }
 
+/// Bind-mount `source` read-only at `target`, creating `target` as an empty
+/// file to mount over. Used to expose cached fixture files to sandboxed
+/// programs without letting them modify the cache.
+pub fn mount_read_only(source: &std::path::Path, target: &std::path::Path) -> Result<(), String> {
+    mount_or_copy(source, target, Uid::effective().is_root())
+}
+
+/// Mounting needs root, which the worker gives up in `drop_privileges`, so
+/// without it (or when the mount is refused) `target` becomes a read-only
+/// copy of `source` instead.
+fn mount_or_copy(source: &std::path::Path, target: &std::path::Path, privileged: bool) -> Result<(), String> {
+    fs::File::create(target).map_err(|e| format!("Failed to create mount point {}: {}", target.display(), e))?;
+    if privileged {
+        let mount = |args: &[&std::ffi::OsStr]| Command::new("mount").args(args).stderr(Stdio::null()).status();
+        let bind = mount(&["--bind".as_ref(), source.as_os_str(), target.as_os_str()]);
+        let read_only = match bind {
+            Ok(status) if status.success() => mount(&["-o".as_ref(), "remount,bind,ro".as_ref(), target.as_os_str()]),
+            other => other,
+        };
+        if matches!(read_only, Ok(status) if status.success()) {
+            return Ok(());
+        }
+        unmount(target);
+    }
+
+    let copied = fs::copy(source, target)
+        .and_then(|_| {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(target, fs::Permissions::from_mode(0o444))
+        });
+    copied.map_err(|e| {
+        let _ = fs::remove_file(target);
+        format!("Failed to copy {} read-only: {}", source.display(), e)
+    })
+}
+
+/// Undo `mount_read_only`, removing the mount point or copy.
+pub fn unmount(target: &std::path::Path) {
+    // A copy has nothing mounted over it, which umount reports harmlessly
+    if let Err(e) = Command::new("umount").arg(target).stderr(Stdio::null()).status() {
+        eprintln!("Warning: Failed to unmount {}: {:?}", target.display(), e);
+    }
+    if let Err(e) = fs::remove_file(target) {
+        eprintln!("Warning: Failed to remove mount point {}: {}", target.display(), e);
+    }
+}
+
 pub fn drop_privileges() -> Result<(), String> {
     // Drop to nobody user if running as root
     if Uid::current().is_root() {
@@ -275,4 +508,28 @@ pub fn drop_privileges() -> Result<(), String> {
         setuid(Uid::from_raw(65534)).map_err(|e| format!("Failed to setuid: {}", e))?;
     }
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unprivileged_read_only_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("cached.dat");
+        let target = dir.path().join("test_input_1.dat");
+        fs::write(&source, b"large input").unwrap();
+
+        mount_or_copy(&source, &target, false).unwrap();
+        assert_eq!(fs::read(&target).unwrap(), b"large input");
+        assert!(fs::metadata(&target).unwrap().permissions().readonly());
+
+        unmount(&target);
+        assert!(!target.exists());
+        assert_eq!(fs::read(&source).unwrap(), b"large input");
+
+        assert!(mount_or_copy(&dir.path().join("missing"), &target, false).is_err());
+        assert!(!target.exists());
+    }
+}
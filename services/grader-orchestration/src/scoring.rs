@@ -0,0 +1,510 @@
+use crate::crash_severity::CrashSeverityRules;
+use crate::fuzzer::CrashSeverity;
+use crate::lint::LintRules;
+use crate::security::{Impact, SecurityRules};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Linear bonus curve: full points at or below `target`, nothing at or above
+/// `limit`, interpolated in between.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BonusCurve {
+    pub max_points: f64,
+    pub target: u64,
+    pub limit: u64,
+}
+
+impl BonusCurve {
+    pub fn points_for(&self, used: u64) -> f64 {
+        if used <= self.target {
+            self.max_points
+        } else if used >= self.limit || self.limit <= self.target {
+            0.0
+        } else {
+            let span = (self.limit - self.target) as f64;
+            self.max_points * (self.limit - used) as f64 / span
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeMetric {
+    Wall,
+    Cpu,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PerformanceTier {
+    pub max_ms: u64,
+    pub points: f64,
+}
+
+/// Tiered bonus for "optimize this function" challenges: the given percentile
+/// of per-fixture runtimes (over passing fixtures) earns the points of the
+/// first tier whose `maxMs` it fits under.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PerformanceTiers {
+    pub percentile: f64,
+    pub metric: TimeMetric,
+    pub tiers: Vec<PerformanceTier>,
+}
+
+impl PerformanceTiers {
+    /// Returns the measured percentile runtime and the points it earns.
+    pub fn evaluate(&self, fixture_times: &[FixtureTiming]) -> (u64, f64) {
+        let mut times: Vec<u64> = fixture_times
+            .iter()
+            .map(|t| if self.metric == TimeMetric::Cpu { t.cpu_ms } else { t.wall_ms })
+            .collect();
+        if times.is_empty() {
+            return (0, 0.0);
+        }
+        times.sort_unstable();
+
+        // Nearest-rank percentile
+        let rank = ((self.percentile.clamp(0.0, 100.0) / 100.0) * times.len() as f64).ceil() as usize;
+        let measured = times[rank.clamp(1, times.len()) - 1];
+
+        let mut tiers = self.tiers.clone();
+        tiers.sort_by_key(|t| t.max_ms);
+        let points = tiers.iter().find(|t| measured <= t.max_ms).map(|t| t.points).unwrap_or(0.0);
+
+        (measured, points)
+    }
+}
+
+/// Measured runtime of a passing fixture.
+#[derive(Clone, Debug)]
+pub struct FixtureTiming {
+    pub wall_ms: u64,
+    pub cpu_ms: u64,
+}
+
+/// Per-warning compiler penalties, the `warnings` table of the scoring rubric.
+/// A warning costs its code's entry in `codes` (rustc lint names such as
+/// `unused_variables`, solc error codes such as `2072`), else `penalty`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct WarningRules {
+    pub penalty: usize,
+    pub codes: HashMap<String, usize>,
+    pub max_penalty: Option<usize>,
+}
+
+impl Default for WarningRules {
+    fn default() -> Self {
+        Self {
+            penalty: 1,
+            codes: HashMap::new(),
+            max_penalty: None,
+        }
+    }
+}
+
+impl WarningRules {
+    pub fn penalty_for(&self, codes: &[Option<String>]) -> usize {
+        let penalty = codes
+            .iter()
+            .map(|code| code.as_ref().and_then(|c| self.codes.get(c)).copied().unwrap_or(self.penalty))
+            .sum();
+        self.max_penalty.map(|max| std::cmp::min(penalty, max)).unwrap_or(penalty)
+    }
+}
+
+/// Per-challenge scoring configuration.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScoringRubric {
+    /// Minimum score (out of 100) for a submission to be considered passing
+    pub pass_threshold: usize,
+    /// Points deducted per fuzzing crash, unless `crashSeverity` prices them
+    pub crash_penalty: usize,
+    /// Severity rules and per-severity penalties for fuzzing crashes
+    pub crash_severity: Option<CrashSeverityRules>,
+    /// Points deducted per fuzz input that hung, up to `maxHangPenalty`
+    pub hang_penalty: usize,
+    pub max_hang_penalty: usize,
+    /// Share of the test score given to public tests. When neither weight is
+    /// set every test counts equally.
+    pub public_weight: Option<f64>,
+    pub hidden_weight: Option<f64>,
+    pub gas_bonus: Option<BonusCurve>,
+    /// Bonus based on total execution time in milliseconds
+    pub time_bonus: Option<BonusCurve>,
+    pub performance: Option<PerformanceTiers>,
+    /// Clippy deny rules; enables the lint stage for Rust submissions
+    pub lint: Option<LintRules>,
+    /// Slither penalties; enables security analysis for Solidity submissions
+    pub security: Option<SecurityRules>,
+    /// Penalties for compiler warnings; warnings are not scored without it
+    pub warnings: Option<WarningRules>,
+}
+
+impl Default for ScoringRubric {
+    fn default() -> Self {
+        Self {
+            pass_threshold: 70,
+            crash_penalty: 5,
+            crash_severity: None,
+            hang_penalty: 2,
+            max_hang_penalty: 10,
+            public_weight: None,
+            hidden_weight: None,
+            gas_bonus: None,
+            time_bonus: None,
+            performance: None,
+            lint: None,
+            security: None,
+            warnings: None,
+        }
+    }
+}
+
+impl ScoringRubric {
+    /// Read the `scoringRubric` field of the job payload, given either as a
+    /// JSON object or as a TOML document string. It comes from the
+    /// challenge's config, as only admins may set it in a job.
+    pub fn from_payload(payload: &Value) -> Result<Self, String> {
+        let rubric: Self = match payload.get("scoringRubric") {
+            None | Some(Value::Null) => return Ok(Self::default()),
+            Some(Value::String(doc)) => Self::from_toml_str(doc)?,
+            Some(rubric) => serde_json::from_value(rubric.clone())
+                .map_err(|e| format!("Invalid scoringRubric: {}", e))?,
+        };
+        if let Some(rules) = &rubric.crash_severity {
+            rules.validate()?;
+        }
+        Ok(rubric)
+    }
+
+    pub fn from_toml_str(doc: &str) -> Result<Self, String> {
+        toml::from_str(doc).map_err(|e| format!("Invalid scoring rubric TOML: {}", e))
+    }
+}
+
+/// One term of the final score, with the raw inputs it was computed from.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreComponent {
+    pub name: String,
+    pub points: f64,
+    pub detail: String,
+}
+
+/// Exactly how a final score was computed, so disputes can be resolved from data.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScoreBreakdown {
+    pub components: Vec<ScoreComponent>,
+    pub rubric: ScoringRubric,
+    pub final_score: usize,
+    pub passed: bool,
+}
+
+pub struct ScoreInputs {
+    pub public_passed: usize,
+    pub public_total: usize,
+    pub hidden_passed: usize,
+    pub hidden_total: usize,
+    /// Severities of the distinct fuzzing crashes
+    pub fuzz_crashes: Vec<CrashSeverity>,
+    pub fuzz_hangs: usize,
+    /// Findings of the rubric's clippy deny lints
+    pub lint_denied: usize,
+    /// Whether clippy failed to check the submission
+    pub lint_errored: bool,
+    /// Impacts of the Slither findings kept by the rubric's security rules
+    pub security_impacts: Vec<Impact>,
+    /// Whether Slither failed to analyze the submission
+    pub security_errored: bool,
+    /// Codes of the compiler warnings, `None` for warnings without one
+    pub warning_codes: Vec<Option<String>>,
+    pub gas_used: u64,
+    pub time_used_ms: u64,
+    pub fixture_timings: Vec<FixtureTiming>,
+}
+
+pub fn compute_score(inputs: &ScoreInputs, rubric: &ScoringRubric) -> ScoreBreakdown {
+    let (public_points, hidden_points) = test_points(inputs, rubric);
+    let fuzz_penalty = match &rubric.crash_severity {
+        Some(rules) => rules.penalty_for(&inputs.fuzz_crashes),
+        None => inputs.fuzz_crashes.len() * rubric.crash_penalty,
+    };
+    let hang_penalty = (inputs.fuzz_hangs * rubric.hang_penalty).min(rubric.max_hang_penalty);
+    let lint_penalty = rubric.lint.as_ref().map(|l| l.penalty_for(inputs.lint_denied)).unwrap_or(0);
+    // A submission clippy couldn't check can't be shown free of deny lints
+    let lint_failed = rubric.lint.as_ref().map(|l| l.fail_on_deny && (inputs.lint_denied > 0 || inputs.lint_errored)).unwrap_or(false);
+    let security_penalty = rubric.security.as_ref().map(|r| r.penalty_for(&inputs.security_impacts)).unwrap_or(0);
+    // Neither can a submission Slither couldn't analyze be shown free of failOn findings
+    let security_failed = rubric
+        .security
+        .as_ref()
+        .map(|r| r.fails(&inputs.security_impacts) || (inputs.security_errored && r.fail_on.is_some()))
+        .unwrap_or(false);
+    let warning_penalty = rubric.warnings.as_ref().map(|w| w.penalty_for(&inputs.warning_codes)).unwrap_or(0);
+
+    let gas_points = rubric.gas_bonus.as_ref().map(|c| c.points_for(inputs.gas_used)).unwrap_or(0.0);
+    let time_points = rubric.time_bonus.as_ref().map(|c| c.points_for(inputs.time_used_ms)).unwrap_or(0.0);
+
+    let (measured_ms, performance_points) = rubric
+        .performance
+        .as_ref()
+        .map(|p| p.evaluate(&inputs.fixture_timings))
+        .unwrap_or((0, 0.0));
+
+    let raw_score = (public_points + hidden_points + gas_points + time_points + performance_points).floor() as usize;
+    // Bonuses make up for penalties but never lift a score past the maximum
+    let final_score = raw_score
+        .saturating_sub(fuzz_penalty + hang_penalty + lint_penalty + security_penalty + warning_penalty)
+        .min(MAX_SCORE);
+
+    let components = vec![
+        ScoreComponent {
+            name: "public".to_string(),
+            points: public_points,
+            detail: format!("{}/{} public tests passed", inputs.public_passed, inputs.public_total),
+        },
+        ScoreComponent {
+            name: "hidden".to_string(),
+            points: hidden_points,
+            detail: format!("{}/{} hidden tests passed", inputs.hidden_passed, inputs.hidden_total),
+        },
+        ScoreComponent {
+            name: "fuzzPenalty".to_string(),
+            points: -(fuzz_penalty as f64),
+            detail: match &rubric.crash_severity {
+                Some(_) => format!("{} distinct crashes, priced by severity", inputs.fuzz_crashes.len()),
+                None => format!("{} distinct crashes x {} points", inputs.fuzz_crashes.len(), rubric.crash_penalty),
+            },
+        },
+        ScoreComponent {
+            name: "hangPenalty".to_string(),
+            points: -(hang_penalty as f64),
+            detail: format!("{} hung inputs x {} points, at most {}", inputs.fuzz_hangs, rubric.hang_penalty, rubric.max_hang_penalty),
+        },
+        ScoreComponent {
+            name: "lint".to_string(),
+            points: -(lint_penalty as f64),
+            detail: match &rubric.lint {
+                Some(_) if inputs.lint_errored && lint_failed => "Lint stage failed, submission fails".to_string(),
+                Some(_) if inputs.lint_errored => "Lint stage failed, not scored".to_string(),
+                Some(_) if lint_failed => format!("{} denied lint findings, submission fails", inputs.lint_denied),
+                Some(lint) => format!("{} denied lint findings x {} points", inputs.lint_denied, lint.penalty),
+                None => "Not scored".to_string(),
+            },
+        },
+        ScoreComponent {
+            name: "securityPenalty".to_string(),
+            points: -(security_penalty as f64),
+            detail: match &rubric.security {
+                Some(_) if inputs.security_errored && security_failed => "Security analysis failed, submission fails".to_string(),
+                Some(_) if inputs.security_errored => "Security analysis failed, not scored".to_string(),
+                Some(_) if security_failed => format!("{} security findings, submission fails", inputs.security_impacts.len()),
+                Some(_) => format!("{} security findings", inputs.security_impacts.len()),
+                None => "Not scored".to_string(),
+            },
+        },
+        ScoreComponent {
+            name: "warningPenalty".to_string(),
+            points: -(warning_penalty as f64),
+            detail: match &rubric.warnings {
+                Some(_) => format!("{} compiler warnings", inputs.warning_codes.len()),
+                None => "Not scored".to_string(),
+            },
+        },
+        ScoreComponent {
+            name: "gasBonus".to_string(),
+            points: gas_points,
+            detail: match &rubric.gas_bonus {
+                Some(curve) => format!("{} gas used (target {}, limit {})", inputs.gas_used, curve.target, curve.limit),
+                None => "Not scored".to_string(),
+            },
+        },
+        ScoreComponent {
+            name: "timeBonus".to_string(),
+            points: time_points,
+            detail: match &rubric.time_bonus {
+                Some(curve) => format!("{}ms used (target {}ms, limit {}ms)", inputs.time_used_ms, curve.target, curve.limit),
+                None => "Not scored".to_string(),
+            },
+        },
+        ScoreComponent {
+            name: "performanceBonus".to_string(),
+            points: performance_points,
+            detail: match &rubric.performance {
+                Some(p) => format!("p{} {:?} time {}ms over {} passing fixtures", p.percentile, p.metric, measured_ms, inputs.fixture_timings.len()),
+                None => "Not scored".to_string(),
+            },
+        },
+    ];
+
+    ScoreBreakdown {
+        components,
+        rubric: rubric.clone(),
+        final_score,
+        passed: final_score >= rubric.pass_threshold && !lint_failed && !security_failed,
+    }
+}
+
+/// Highest score a submission can get, bonuses included.
+pub const MAX_SCORE: usize = 100;
+
+/// Points (out of 100) earned by public and hidden tests.
+fn test_points(inputs: &ScoreInputs, rubric: &ScoringRubric) -> (f64, f64) {
+    if rubric.public_weight.is_none() && rubric.hidden_weight.is_none() {
+        let total_tests = inputs.public_total + inputs.hidden_total;
+        let points_per_test = if total_tests > 0 { 100.0 / total_tests as f64 } else { 0.0 };
+        return (
+            inputs.public_passed as f64 * points_per_test,
+            inputs.hidden_passed as f64 * points_per_test,
+        );
+    }
+
+    // Suites without tests don't take a share of the score
+    let public_weight = if inputs.public_total > 0 { rubric.public_weight.unwrap_or(0.0) } else { 0.0 };
+    let hidden_weight = if inputs.hidden_total > 0 { rubric.hidden_weight.unwrap_or(0.0) } else { 0.0 };
+    let total_weight = public_weight + hidden_weight;
+    if total_weight <= 0.0 {
+        return (0.0, 0.0);
+    }
+
+    let ratio = |passed: usize, total: usize| if total > 0 { passed as f64 / total as f64 } else { 0.0 };
+    (
+        100.0 * public_weight / total_weight * ratio(inputs.public_passed, inputs.public_total),
+        100.0 * hidden_weight / total_weight * ratio(inputs.hidden_passed, inputs.hidden_total),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inputs() -> ScoreInputs {
+        ScoreInputs {
+            public_passed: 2,
+            public_total: 3,
+            hidden_passed: 3,
+            hidden_total: 3,
+            fuzz_crashes: vec![CrashSeverity::Critical],
+            fuzz_hangs: 0,
+            lint_denied: 0,
+            lint_errored: false,
+            security_impacts: vec![],
+            security_errored: false,
+            warning_codes: vec![],
+            gas_used: 50_000,
+            time_used_ms: 1_000,
+            fixture_timings: [120, 80, 95, 300, 110].iter().map(|&ms| FixtureTiming { wall_ms: ms, cpu_ms: ms / 2 }).collect(),
+        }
+    }
+
+    #[test]
+    fn test_compute_score_matches_components() {
+        let breakdown = compute_score(&inputs(), &ScoringRubric::default());
+
+        // 5/6 tests => 83 points, minus one crash
+        assert_eq!(breakdown.final_score, 78);
+        assert!(breakdown.passed);
+        assert_eq!(breakdown.components[2].points, -5.0);
+
+        // Hangs cost less than crashes, and at most maxHangPenalty together
+        assert_eq!(compute_score(&ScoreInputs { fuzz_hangs: 2, ..inputs() }, &ScoringRubric::default()).final_score, 74);
+        let hung = compute_score(&ScoreInputs { fuzz_hangs: 40, ..inputs() }, &ScoringRubric::default());
+        assert_eq!((hung.final_score, hung.components[3].points), (68, -10.0));
+    }
+
+    #[test]
+    fn test_lint_deny_rules_deduct_and_fail() {
+        let rubric = ScoringRubric::from_toml_str(r#"
+[lint]
+deny = ["unwrap_used"]
+penalty = 4
+"#).unwrap();
+        let breakdown = compute_score(&ScoreInputs { lint_denied: 2, ..inputs() }, &rubric);
+        assert_eq!(breakdown.final_score, 70);
+        assert!(breakdown.passed);
+
+        let strict = ScoringRubric::from_toml_str("[lint]\ndeny = [\"unwrap_used\"]\nfailOnDeny = true\n").unwrap();
+        assert!(!compute_score(&ScoreInputs { lint_denied: 1, ..inputs() }, &strict).passed);
+        // A failed lint stage can't pass failOnDeny
+        let breakdown = compute_score(&ScoreInputs { lint_errored: true, ..inputs() }, &strict);
+        assert!(!breakdown.passed);
+        assert_eq!(breakdown.components[4].detail, "Lint stage failed, submission fails");
+        assert!(compute_score(&ScoreInputs { lint_errored: true, ..inputs() }, &rubric).passed);
+    }
+
+    #[test]
+    fn test_failed_security_analysis_fails_fail_on() {
+        let security = ScoringRubric::from_toml_str("[security]\nfailOn = \"high\"\n").unwrap();
+        assert!(!compute_score(&ScoreInputs { security_errored: true, ..inputs() }, &security).passed);
+        assert!(compute_score(&ScoreInputs { security_errored: true, ..inputs() }, &ScoringRubric::default()).passed);
+    }
+
+    #[test]
+    fn test_warning_penalties_by_code() {
+        let rubric = ScoringRubric::from_toml_str(r#"
+[warnings]
+penalty = 1
+maxPenalty = 6
+
+[warnings.codes]
+unused_variables = 3
+"#).unwrap();
+        let warning_codes = vec![Some("unused_variables".to_string()), Some("dead_code".to_string()), None];
+        let breakdown = compute_score(&ScoreInputs { warning_codes, ..inputs() }, &rubric);
+
+        // 3 + 1 + 1 points off the default 78
+        assert_eq!(breakdown.final_score, 73);
+        let unused = vec![Some("unused_variables".to_string()); 3];
+        assert_eq!(rubric.warnings.as_ref().unwrap().penalty_for(&unused), 6);
+        assert_eq!(compute_score(&ScoreInputs { warning_codes: vec![None], ..inputs() }, &ScoringRubric::default()).final_score, 78);
+    }
+
+    #[test]
+    fn test_rubric_weights_and_bonus_from_toml() {
+        let rubric = ScoringRubric::from_toml_str(r#"
+passThreshold = 90
+crashPenalty = 10
+publicWeight = 0.25
+hiddenWeight = 0.75
+
+[gasBonus]
+maxPoints = 10.0
+target = 40000
+limit = 60000
+"#).unwrap();
+
+        let breakdown = compute_score(&inputs(), &rubric);
+
+        // 25 * 2/3 + 75 * 3/3 + 5 gas bonus = 96.67, minus 10 for the crash
+        assert_eq!(breakdown.final_score, 86);
+        assert!(!breakdown.passed);
+
+        // A full score plus a bonus is still 100
+        let perfect = ScoreInputs { public_passed: 3, fuzz_crashes: vec![], ..inputs() };
+        assert_eq!(compute_score(&perfect, &rubric).final_score, MAX_SCORE);
+    }
+
+    #[test]
+    fn test_performance_tiers_use_percentile() {
+        let tiers = PerformanceTiers {
+            percentile: 80.0,
+            metric: TimeMetric::Wall,
+            tiers: vec![
+                PerformanceTier { max_ms: 50, points: 10.0 },
+                PerformanceTier { max_ms: 150, points: 5.0 },
+            ],
+        };
+
+        // p80 of [80, 95, 110, 120, 300] is 120ms => second tier
+        assert_eq!(tiers.evaluate(&inputs().fixture_timings), (120, 5.0));
+
+        let cpu_tiers = PerformanceTiers { metric: TimeMetric::Cpu, ..tiers };
+        assert_eq!(cpu_tiers.evaluate(&inputs().fixture_timings), (60, 5.0));
+    }
+}
@@ -0,0 +1,67 @@
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Nonce};
+
+const NONCE_LEN: usize = 12;
+
+/// AES-256-GCM encryption for hidden test data that has to be written to
+/// disk. Sealed data is a random nonce followed by the ciphertext, so
+/// tampering or a different key fails to open rather than yielding garbage.
+pub struct Sealer {
+    cipher: Aes256Gcm,
+}
+
+impl Sealer {
+    /// `HIDDEN_TESTS_KEY` (hex, 32 bytes). Without it hidden tests are never
+    /// written to disk at all.
+    pub fn from_env() -> Result<Option<Self>, String> {
+        match std::env::var("HIDDEN_TESTS_KEY") {
+            Ok(key) if !key.is_empty() => {
+                let bytes = hex::decode(key.trim()).map_err(|e| format!("Invalid hidden tests key: {}", e))?;
+                Self::new(&bytes).map(Some)
+            },
+            _ => Ok(None),
+        }
+    }
+
+    pub fn new(key: &[u8]) -> Result<Self, String> {
+        let cipher = Aes256Gcm::new_from_slice(key).map_err(|_| "Invalid hidden tests key: expected 32 bytes".to_string())?;
+        Ok(Self { cipher })
+    }
+
+    pub fn seal(&self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = self
+            .cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext)
+            .map_err(|_| "Failed to encrypt hidden tests".to_string())?;
+        Ok([&nonce[..], &ciphertext].concat())
+    }
+
+    pub fn open(&self, sealed: &[u8]) -> Result<Vec<u8>, String> {
+        if sealed.len() < NONCE_LEN {
+            return Err("Sealed hidden tests are truncated".to_string());
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "Failed to decrypt hidden tests: wrong key or tampered data".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_round_trip_and_tampering() {
+        let sealer = Sealer::new(&[7u8; 32]).unwrap();
+        let sealed = sealer.seal(b"{\"expected_output\": 42}").unwrap();
+        assert_eq!(sealer.open(&sealed).unwrap(), b"{\"expected_output\": 42}");
+
+        let mut tampered = sealed.clone();
+        *tampered.last_mut().unwrap() ^= 1;
+        assert!(sealer.open(&tampered).is_err());
+        assert!(Sealer::new(&[8u8; 32]).unwrap().open(&sealed).is_err());
+        assert!(Sealer::new(&[7u8; 16]).is_err());
+    }
+}
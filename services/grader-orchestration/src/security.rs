@@ -0,0 +1,203 @@
+use crate::sandbox::{execute_in_sandbox, SandboxConfig};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+
+/// Slither's impact levels, lowest first. Optimization findings count as
+/// informational.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Impact {
+    Informational,
+    Low,
+    Medium,
+    High,
+}
+
+impl Impact {
+    fn from_slither(impact: &str) -> Self {
+        match impact {
+            "High" => Impact::High,
+            "Medium" => Impact::Medium,
+            "Low" => Impact::Low,
+            _ => Impact::Informational,
+        }
+    }
+}
+
+/// Points deducted per finding of each impact.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImpactPenalties {
+    pub high: usize,
+    pub medium: usize,
+    pub low: usize,
+    pub informational: usize,
+}
+
+impl Default for ImpactPenalties {
+    fn default() -> Self {
+        Self { high: 10, medium: 5, low: 1, informational: 0 }
+    }
+}
+
+/// Per-challenge Slither configuration, the `security` table of the scoring
+/// rubric. Findings below `minSeverity` are dropped; the rest cost their
+/// impact's penalty (at most `maxPenalty` in total), and any finding at or
+/// above `failOn` fails the submission.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SecurityRules {
+    pub min_severity: Impact,
+    pub penalties: ImpactPenalties,
+    pub max_penalty: Option<usize>,
+    pub fail_on: Option<Impact>,
+}
+
+impl Default for SecurityRules {
+    fn default() -> Self {
+        Self {
+            min_severity: Impact::Low,
+            penalties: ImpactPenalties::default(),
+            max_penalty: None,
+            fail_on: None,
+        }
+    }
+}
+
+impl SecurityRules {
+    pub fn penalty_for(&self, impacts: &[Impact]) -> usize {
+        let penalty = impacts
+            .iter()
+            .map(|impact| match impact {
+                Impact::High => self.penalties.high,
+                Impact::Medium => self.penalties.medium,
+                Impact::Low => self.penalties.low,
+                Impact::Informational => self.penalties.informational,
+            })
+            .sum();
+        self.max_penalty.map(|max| std::cmp::min(penalty, max)).unwrap_or(penalty)
+    }
+
+    pub fn fails(&self, impacts: &[Impact]) -> bool {
+        self.fail_on.map(|fail_on| impacts.iter().any(|&impact| impact >= fail_on)).unwrap_or(false)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityFinding {
+    /// Slither detector, e.g. `reentrancy-eth`
+    pub check: String,
+    pub impact: Impact,
+    pub confidence: String,
+    pub description: String,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityReport {
+    pub findings: Vec<SecurityFinding>,
+    /// Why Slither couldn't analyze the submission; its findings are then unknown
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl SecurityReport {
+    pub fn impacts(&self) -> Vec<Impact> {
+        self.findings.iter().map(|f| f.impact).collect()
+    }
+
+    /// Parse `slither --json -` output, keeping findings at or above the
+    /// rules' minimum severity.
+    pub fn from_slither_json(stdout: &str, rules: &SecurityRules) -> Result<Self, String> {
+        let output: Value = serde_json::from_str(stdout).map_err(|e| format!("Failed to parse Slither output: {}", e))?;
+        if output.get("success").and_then(|s| s.as_bool()) != Some(true) {
+            let error = output.get("error").and_then(|e| e.as_str()).unwrap_or("unknown error");
+            return Err(format!("Slither failed: {}", error));
+        }
+
+        let detectors = output.pointer("/results/detectors").and_then(|d| d.as_array()).cloned().unwrap_or_default();
+        let findings = detectors
+            .iter()
+            .map(|detector| {
+                let location = detector.pointer("/elements/0/source_mapping");
+                SecurityFinding {
+                    check: detector.get("check").and_then(|c| c.as_str()).unwrap_or("").to_string(),
+                    impact: Impact::from_slither(detector.get("impact").and_then(|i| i.as_str()).unwrap_or("")),
+                    confidence: detector.get("confidence").and_then(|c| c.as_str()).unwrap_or("").to_string(),
+                    description: detector.get("description").and_then(|d| d.as_str()).unwrap_or("").trim().to_string(),
+                    file: location.and_then(|l| l.get("filename_relative")).and_then(|f| f.as_str()).map(String::from),
+                    line: location.and_then(|l| l.pointer("/lines/0")).and_then(|l| l.as_u64()).map(|l| l as u32),
+                }
+            })
+            .filter(|finding| finding.impact >= rules.min_severity)
+            .collect();
+
+        Ok(Self { findings, error: None })
+    }
+
+    /// Report of an analysis that didn't complete.
+    pub fn failed(error: String) -> Self {
+        Self { error: Some(error), ..Self::default() }
+    }
+}
+
+/// Run Slither over the compiled Solidity project in `workspace`, skipping
+/// findings in dependencies.
+pub async fn run_slither(workspace: &Path, rules: &SecurityRules) -> Result<SecurityReport, String> {
+    let sandbox_config = SandboxConfig {
+        time_limit: Duration::from_secs(120), // 2 minutes, Slither recompiles the project
+        memory_limit: 1024 * 1024 * 1024, // 1GB
+        cpu_limit: 50,
+        network_disabled: true,
+        max_file_size: 100 * 1024 * 1024, // 100MB
+        max_processes: 10,
+        disk_quota: 500 * 1024 * 1024, // 500MB
+    };
+
+    // Slither exits non-zero whenever it reports findings, so success is
+    // read from its JSON output instead
+    let result = execute_in_sandbox(
+        "slither",
+        &[".", "--json", "-", "--exclude-dependencies", "--filter-paths", "lib|test"],
+        &sandbox_config,
+        workspace,
+    ).await?;
+
+    SecurityReport::from_slither_json(&result.stdout, rules)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slither_findings_filtered_and_scored() {
+        let stdout = r#"{"success": true, "error": null, "results": {"detectors": [
+            {"check": "reentrancy-eth", "impact": "High", "confidence": "Medium", "description": "Reentrancy in Vault.withdraw()\n",
+             "elements": [{"source_mapping": {"filename_relative": "src/Contract.sol", "lines": [14, 15]}}]},
+            {"check": "solc-version", "impact": "Informational", "confidence": "High", "description": "Pragma version too recent", "elements": []},
+            {"check": "missing-zero-check", "impact": "Low", "confidence": "Medium", "description": "Missing zero check", "elements": []}
+        ]}}"#;
+
+        let rules = SecurityRules { fail_on: Some(Impact::High), ..SecurityRules::default() };
+        let report = SecurityReport::from_slither_json(stdout, &rules).unwrap();
+
+        assert_eq!(report.findings.len(), 2);
+        assert_eq!(report.findings[0].check, "reentrancy-eth");
+        assert_eq!(report.findings[0].line, Some(14));
+        assert_eq!(rules.penalty_for(&report.impacts()), 11);
+        assert!(rules.fails(&report.impacts()));
+        assert!(!rules.fails(&[Impact::Medium]));
+
+        assert!(SecurityReport::from_slither_json(r#"{"success": false, "error": "compilation failed"}"#, &rules).is_err());
+        assert!(report.error.is_none());
+        let failed = serde_json::to_value(SecurityReport::failed("Slither failed: compilation failed".to_string())).unwrap();
+        assert_eq!(failed["error"], "Slither failed: compilation failed");
+        assert_eq!(failed["findings"], serde_json::json!([]));
+    }
+}
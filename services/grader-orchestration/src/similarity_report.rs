@@ -0,0 +1,269 @@
+use crate::ai_heuristics::KEYWORDS;
+use crate::anti_cheat::{RiskLevel, SimilarityBreakdown};
+use crate::collusion::CollusionCluster;
+use crate::fingerprint_store::{MatchScope, StoredSubmission};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+/// Matching runs shorter than this many non-trivial lines aren't regions.
+const MIN_REGION_LINES: usize = 2;
+/// Sources whose line counts multiply past this aren't aligned, bounding
+/// the quadratic work.
+const MAX_ALIGNED_LINE_PAIRS: usize = 4_000_000;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubmissionMetadata {
+    pub submission_id: String,
+    pub challenge_id: String,
+    pub language: String,
+    pub user_id: String,
+    pub accepted: bool,
+}
+
+impl SubmissionMetadata {
+    pub fn of(submission: &StoredSubmission) -> Self {
+        Self {
+            submission_id: submission.submission_id.clone(),
+            challenge_id: submission.challenge_id.clone(),
+            language: submission.language.clone(),
+            user_id: submission.user_id.clone(),
+            accepted: submission.accepted,
+        }
+    }
+}
+
+/// Lines of the reported submission (`source`) matching lines of the other
+/// one (`target`), 1-based and inclusive.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchedRegion {
+    pub source_start: usize,
+    pub source_end: usize,
+    pub target_start: usize,
+    pub target_end: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchReport {
+    pub submission: SubmissionMetadata,
+    pub code: String,
+    pub scope: MatchScope,
+    pub risk_level: RiskLevel,
+    pub scores: SimilarityBreakdown,
+    pub regions: Vec<MatchedRegion>,
+}
+
+/// Everything a reviewer needs to judge a submission's high-risk matches.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SimilarityReport {
+    pub submission: SubmissionMetadata,
+    pub code: String,
+    /// Most similar first
+    pub matches: Vec<MatchReport>,
+    /// The collusion cluster the submission belongs to, if any
+    pub cluster: Option<CollusionCluster>,
+    pub generated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// Runs of lines that are the same in both sources once identifiers are
+/// renamed and whitespace dropped, longest first and not overlapping.
+pub fn matched_regions(source: &str, target: &str) -> Vec<MatchedRegion> {
+    let (source, target) = (normalize_lines(source), normalize_lines(target));
+    if source.len() * target.len() > MAX_ALIGNED_LINE_PAIRS {
+        return Vec::new();
+    }
+
+    // run[i][j]: matching lines ending at source line i and target line j
+    let mut run = vec![vec![0usize; target.len() + 1]; source.len() + 1];
+    let mut runs = Vec::new();
+    for i in 1..=source.len() {
+        for j in 1..=target.len() {
+            if source[i - 1] == target[j - 1] {
+                run[i][j] = run[i - 1][j - 1] + 1;
+            }
+        }
+    }
+    for i in 1..=source.len() {
+        for j in 1..=target.len() {
+            let maximal = i == source.len() || j == target.len() || run[i + 1][j + 1] == 0;
+            if run[i][j] > 0 && maximal {
+                let substantial = source[i - run[i][j]..i].iter().filter(|l| l.len() > 2).count();
+                if substantial >= MIN_REGION_LINES {
+                    runs.push((run[i][j], i, j));
+                }
+            }
+        }
+    }
+    runs.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+
+    let mut regions: Vec<MatchedRegion> = Vec::new();
+    for (length, i, j) in runs {
+        let region = MatchedRegion { source_start: i - length + 1, source_end: i, target_start: j - length + 1, target_end: j };
+        let overlaps = regions.iter().any(|r| {
+            (region.source_start <= r.source_end && r.source_start <= region.source_end)
+                || (region.target_start <= r.target_end && r.target_start <= region.target_end)
+        });
+        if !overlaps {
+            regions.push(region);
+        }
+    }
+    regions
+}
+
+fn normalize_lines(code: &str) -> Vec<String> {
+    let identifier = Regex::new(r"[A-Za-z_][A-Za-z0-9_]*").unwrap();
+    code.lines()
+        .map(|line| {
+            let renamed = identifier.replace_all(line, |c: &regex::Captures| match KEYWORDS.contains(&&c[0]) {
+                true => c[0].to_string(),
+                false => "v".to_string(),
+            });
+            renamed.chars().filter(|c| !c.is_whitespace()).collect()
+        })
+        .collect()
+}
+
+/// The report as a standalone HTML page, each match's regions highlighted
+/// side by side.
+pub fn render_html(report: &SimilarityReport) -> String {
+    let mut html = String::from(
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>Similarity report</title><style>\n\
+         body{font-family:sans-serif;margin:2em}table{border-collapse:collapse}td,th{border:1px solid #ccc;padding:4px 8px;text-align:left}\n\
+         .sources{display:flex;gap:1em}.sources pre{flex:1;overflow-x:auto;background:#f8f8f8;padding:8px}\n\
+         .r0{background:#ffd6d6}.r1{background:#d6e4ff}.r2{background:#d6ffd9}.r3{background:#fff3c4}.r4{background:#ecd6ff}\n\
+         </style></head><body>\n",
+    );
+    html.push_str(&format!("<h1>Similarity report for {}</h1>\n", escape_html(&report.submission.submission_id)));
+    html.push_str(&format!("<p>Generated {}</p>\n", report.generated_at.to_rfc3339()));
+    html.push_str(&metadata_table(&report.submission));
+
+    if let Some(cluster) = &report.cluster {
+        html.push_str(&format!(
+            "<h2>Collusion cluster</h2>\n<p>{} submissions by {}, mean similarity {:.2} ({:?} risk)</p>\n<ul>\n",
+            cluster.submission_ids.len(),
+            escape_html(&cluster.user_ids.join(", ")),
+            cluster.mean_similarity,
+            cluster.risk_level
+        ));
+        for id in &cluster.submission_ids {
+            html.push_str(&format!("<li>{}</li>\n", escape_html(id)));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    if report.matches.is_empty() {
+        html.push_str("<p>No high-risk matches.</p>\n");
+    }
+    for matched in &report.matches {
+        let scores = &matched.scores;
+        html.push_str(&format!(
+            "<h2>Match with {} ({:?} risk, {:?})</h2>\n",
+            escape_html(&matched.submission.submission_id),
+            matched.risk_level,
+            matched.scope
+        ));
+        html.push_str(&metadata_table(&matched.submission));
+        html.push_str(&format!(
+            "<table><tr><th>Combined</th><th>Winnowing</th><th>Token sequence</th><th>Structure</th><th>Tree edit</th></tr>\n\
+             <tr><td>{:.3}</td><td>{:.3}</td><td>{:.3}</td><td>{:.3}</td><td>{}</td></tr></table>\n",
+            scores.combined,
+            scores.winnowing,
+            scores.token_sequence,
+            scores.structure,
+            scores.tree_edit.map(|s| format!("{:.3}", s)).unwrap_or_else(|| "n/a".to_string())
+        ));
+        let source_regions: Vec<(usize, usize)> = matched.regions.iter().map(|r| (r.source_start, r.source_end)).collect();
+        let target_regions: Vec<(usize, usize)> = matched.regions.iter().map(|r| (r.target_start, r.target_end)).collect();
+        html.push_str("<div class=\"sources\">\n");
+        html.push_str(&highlighted_source(&report.code, &source_regions));
+        html.push_str(&highlighted_source(&matched.code, &target_regions));
+        html.push_str("</div>\n");
+    }
+    html.push_str("</body></html>\n");
+    html
+}
+
+fn metadata_table(submission: &SubmissionMetadata) -> String {
+    format!(
+        "<table><tr><th>Submission</th><th>User</th><th>Challenge</th><th>Language</th><th>Accepted</th></tr>\n\
+         <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr></table>\n",
+        escape_html(&submission.submission_id),
+        escape_html(&submission.user_id),
+        escape_html(&submission.challenge_id),
+        escape_html(&submission.language),
+        submission.accepted
+    )
+}
+
+/// Numbered source lines, those in the `n`th region marked with class `rn`.
+fn highlighted_source(code: &str, regions: &[(usize, usize)]) -> String {
+    let mut html = String::from("<pre>");
+    for (index, line) in code.lines().enumerate() {
+        let number = index + 1;
+        let text = format!("{:>4}  {}", number, escape_html(line));
+        match regions.iter().position(|(start, end)| (*start..=*end).contains(&number)) {
+            Some(region) => html.push_str(&format!("<span class=\"r{}\">{}</span>\n", region % 5, text)),
+            None => html.push_str(&format!("{}\n", text)),
+        }
+    }
+    html.push_str("</pre>\n");
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report_highlights_matched_regions() {
+        let source = "fn main() {\n    let total = 0;\n    for x in items {\n        total += x;\n    }\n    println!(\"{}\", total);\n}\n";
+        let target = "// copied\nfn main() {\n    let sum = 0;\n    for y in items {\n        sum += y;\n    }\n    if sum > 1 { print(sum); }\n}\n";
+        let regions = matched_regions(source, target);
+        assert_eq!(regions[0], MatchedRegion { source_start: 1, source_end: 5, target_start: 2, target_end: 6 });
+
+        let metadata = |id: &str, user: &str| SubmissionMetadata {
+            submission_id: id.to_string(),
+            challenge_id: "c".to_string(),
+            language: "rust".to_string(),
+            user_id: user.to_string(),
+            accepted: true,
+        };
+        let report = SimilarityReport {
+            submission: metadata("c:rust:alice:1", "<alice>"),
+            code: source.to_string(),
+            matches: vec![MatchReport {
+                submission: metadata("c:rust:bob:2", "bob"),
+                code: target.to_string(),
+                scope: MatchScope::OtherUsers,
+                risk_level: RiskLevel::High,
+                scores: SimilarityBreakdown { winnowing: 0.8, token_sequence: 0.9, structure: 0.7, tree_edit: None, combined: 0.82 },
+                regions,
+            }],
+            cluster: None,
+            generated_at: chrono::Utc::now(),
+        };
+        let html = render_html(&report);
+        assert!(html.contains("&lt;alice&gt;"));
+        assert!(html.contains("<span class=\"r0\">   2      let total = 0;</span>"));
+        assert!(html.contains("<td>0.820</td>"));
+        assert!(serde_json::to_value(&report).unwrap()["matches"][0]["regions"][0]["targetStart"] == 2);
+    }
+}
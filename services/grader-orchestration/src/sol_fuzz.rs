@@ -0,0 +1,261 @@
+use crate::crash_signature;
+use crate::forge;
+use crate::fuzz_config::FuzzConfig;
+use crate::fuzzer::{CrashSeverity, FuzzCrash, FuzzResult};
+use crate::invariants::{self, InvariantViolation};
+use crate::sandbox::{execute_in_sandbox_with_env, SandboxConfig, TIMEOUT_ERROR};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Component, Path};
+use std::time::Duration;
+
+/// Fuzzer for Solidity challenges, which take no JSON input files.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SolidityFuzzEngine {
+    /// forge's built-in fuzzer, running `testFuzz_*` and `invariant_*` tests
+    #[default]
+    Forge,
+    /// Echidna, checking the property contracts' `echidna_*` properties and assertions
+    Echidna,
+}
+
+/// A challenge-provided contract of properties or fuzz tests, written into
+/// the workspace before the campaign.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PropertyContract {
+    /// Path in the workspace, e.g. `test/Properties.t.sol`
+    pub path: String,
+    /// Contract name, which Echidna needs to pick its target
+    pub contract: String,
+    pub source: String,
+}
+
+impl PropertyContract {
+    pub fn validate(&self) -> Result<(), String> {
+        let path = Path::new(&self.path);
+        if !self.path.ends_with(".sol") || !path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("Invalid property contract path: {}", self.path));
+        }
+        match !self.contract.is_empty() && self.contract.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+            true => Ok(()),
+            false => Err(format!("Invalid property contract name: {}", self.contract)),
+        }
+    }
+}
+
+/// Fuzz a compiled Foundry workspace with the configured engine, mapping
+/// failed fuzz tests and assertions to crashes and failed invariants and
+/// properties to invariant violations. A run killed at the end of its budget
+/// counts as a hang.
+pub async fn run_campaign(config: &FuzzConfig, working_dir: &Path, runs: usize, seed: u64, budget: Duration) -> Result<FuzzResult, String> {
+    let start_time = std::time::Instant::now();
+    for contract in &config.property_contracts {
+        let path = working_dir.join(&contract.path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create {}: {}", parent.display(), e))?;
+        }
+        std::fs::write(&path, &contract.source).map_err(|e| format!("Failed to write property contract: {}", e))?;
+    }
+
+    let mut crashes_found = Vec::new();
+    let mut invariant_violations = Vec::new();
+    let mut hangs = Vec::new();
+    let mut inputs_tested = 0;
+
+    match config.solidity_engine {
+        SolidityFuzzEngine::Forge => {
+            let (runs, seed) = (runs.to_string(), seed.to_string());
+            let env = [("FOUNDRY_INVARIANT_RUNS".to_string(), runs.clone())];
+            let result = execute_in_sandbox_with_env(
+                "forge",
+                &["test", "--json", "--fuzz-runs", &runs, "--fuzz-seed", &seed],
+                &env,
+                &sandbox_config(budget),
+                working_dir,
+            ).await;
+            let result = match result {
+                Err(e) if e == TIMEOUT_ERROR => {
+                    hangs.push(json!({ "engine": "forge" }));
+                    None
+                },
+                result => Some(result?),
+            };
+            let tests = match &result {
+                Some(result) => forge::parse_forge_fuzz_json(&result.stdout)?,
+                None => vec![],
+            };
+            for test in &tests {
+                inputs_tested += test.runs as usize;
+                if test.passed {
+                    continue;
+                }
+                let reason = test.reason.clone().unwrap_or_else(|| "Fuzz test failed".to_string());
+                match test.invariant {
+                    true => invariants::record_violation(&mut invariant_violations, invariant_name(config, &test.name), test.counterexample.as_ref(), reason),
+                    false => record_finding(&mut crashes_found, &test.name, test.counterexample.clone().unwrap_or(Value::Null), reason),
+                }
+            }
+            // The challenge's own invariants must have run, so one the
+            // submission's tests shadow or drop doesn't pass unchecked
+            for (name, test) in config.invariants.iter().filter_map(|i| i.forge_test.as_deref().map(|test| (&i.name, test))) {
+                if result.is_some() && !tests.iter().any(|t| t.invariant && t.name == test) {
+                    invariants::record_violation(&mut invariant_violations, name, None, format!("Forge invariant test {} did not run", test));
+                }
+            }
+        },
+        SolidityFuzzEngine::Echidna => {
+            // The budget is shared by the contracts, each getting an equal
+            // part of what the ones before it left
+            let contracts = &config.property_contracts;
+            for (index, contract) in contracts.iter().enumerate() {
+                let remaining = budget.saturating_sub(start_time.elapsed());
+                let share = remaining / (contracts.len() - index) as u32;
+                let test_limit = (runs / contracts.len()).max(1);
+                let timeout = share.as_secs().max(1).to_string();
+                let result = execute_in_sandbox_with_env(
+                    "echidna",
+                    &[".", "--contract", &contract.contract, "--format", "json", "--test-limit", &test_limit.to_string(), "--seed", &seed.to_string(), "--timeout", &timeout],
+                    &[],
+                    // Echidna stops itself at its timeout; the grace period
+                    // lets it report what it found
+                    &sandbox_config(share + ECHIDNA_GRACE),
+                    working_dir,
+                ).await;
+                match result {
+                    Err(e) if e == TIMEOUT_ERROR => hangs.push(json!({ "engine": "echidna", "contract": contract.contract })),
+                    result => {
+                        let result = result?;
+                        inputs_tested += test_limit;
+                        merge_echidna_json(&result.stdout, &result.stderr, &mut crashes_found, &mut invariant_violations)?;
+                    },
+                }
+            }
+            for invariant in &config.invariants {
+                if let Some(test) = &invariant.forge_test {
+                    let message = match invariants::run_forge_test(test, working_dir).await {
+                        Ok(message) => message,
+                        Err(e) => Some(format!("Forge invariant test {} failed to run: {}", test, e)),
+                    };
+                    if let Some(message) = message {
+                        invariants::record_violation(&mut invariant_violations, &invariant.name, None, message);
+                    }
+                }
+            }
+        },
+    }
+
+    Ok(FuzzResult {
+        inputs_tested,
+        crashes_found,
+        invariant_violations,
+        hangs,
+        slowest_inputs: vec![],
+        seed: Some(seed),
+        unique_paths: 0,
+        coverage_score: 0.0,
+        execution_time: start_time.elapsed(),
+        error: None,
+    })
+}
+
+/// Time Echidna gets past its own timeout to write its report.
+const ECHIDNA_GRACE: Duration = Duration::from_secs(30);
+
+fn sandbox_config(time_limit: Duration) -> SandboxConfig {
+    SandboxConfig {
+        time_limit,
+        memory_limit: 1024 * 1024 * 1024, // 1GB
+        cpu_limit: 50,
+        network_disabled: true,
+        max_file_size: 100 * 1024 * 1024, // 100MB
+        max_processes: 10,
+        disk_quota: 500 * 1024 * 1024, // 500MB
+    }
+}
+
+/// The configured invariant a forge invariant test checks, else the test itself.
+fn invariant_name<'a>(config: &'a FuzzConfig, test: &'a str) -> &'a str {
+    config
+        .invariants
+        .iter()
+        .find(|i| i.forge_test.as_deref() == Some(test))
+        .map_or(test, |i| i.name.as_str())
+}
+
+/// Merge an `echidna --format json` report: failed `echidna_*` properties are
+/// invariant violations, failed assertions crashes.
+fn merge_echidna_json(stdout: &str, stderr: &str, crashes: &mut Vec<FuzzCrash>, violations: &mut Vec<InvariantViolation>) -> Result<(), String> {
+    // Echidna may log progress before the JSON document
+    let json_start = stdout.find('{').ok_or_else(|| format!("No JSON found in echidna output: {}", stderr.trim()))?;
+    let report: Value = serde_json::from_str(stdout[json_start..].trim())
+        .map_err(|e| format!("Failed to parse echidna JSON: {}", e))?;
+
+    for test in report.get("tests").and_then(|v| v.as_array()).into_iter().flatten() {
+        if test.get("status").and_then(|v| v.as_str()) != Some("solved") {
+            continue;
+        }
+        let name = test.get("name").and_then(|v| v.as_str()).unwrap_or("unknown");
+        let sequence = test.get("transactions").cloned().unwrap_or(Value::Null);
+        let message = test
+            .get("error")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .unwrap_or_else(|| format!("{} falsified", name));
+        match test.get("testType").and_then(|v| v.as_str()) {
+            Some("property") => invariants::record_violation(violations, name, Some(&sequence), message),
+            _ => record_finding(crashes, name, sequence, message),
+        }
+    }
+    Ok(())
+}
+
+fn record_finding(crashes: &mut Vec<FuzzCrash>, test: &str, input: Value, message: String) {
+    let signature = crash_signature::signature(&message, test);
+    match crashes.iter_mut().find(|c| c.signature == signature) {
+        Some(existing) => existing.occurrences += 1,
+        None => crashes.push(FuzzCrash {
+            input,
+            error_message: message,
+            stack_trace: test.to_string(),
+            gas_used: 0,
+            severity: CrashSeverity::High,
+            signature,
+            occurrences: 1,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echidna_report_is_merged() {
+        let stdout = r#"Analyzing contract: TokenProperties
+{"success":true,"error":null,"seed":42,"tests":[
+  {"contract":"TokenProperties","name":"echidna_supply_constant","status":"solved","error":null,"testType":"property",
+   "transactions":[{"contract":"TokenProperties","function":"mint","arguments":["1"],"gas":12500000,"gasprice":"0"}]},
+  {"contract":"TokenProperties","name":"transfer(address,uint256)","status":"solved","error":null,"testType":"assertion","transactions":[]},
+  {"contract":"TokenProperties","name":"echidna_owner_fixed","status":"passed","error":null,"testType":"property","transactions":[]}
+]}"#;
+        let (mut crashes, mut violations) = (Vec::new(), Vec::new());
+        merge_echidna_json(stdout, "", &mut crashes, &mut violations).unwrap();
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].invariant, "echidna_supply_constant");
+        assert_eq!(violations[0].input.as_ref().unwrap()[0]["function"], "mint");
+        assert_eq!(crashes.len(), 1);
+        assert_eq!(crashes[0].error_message, "transfer(address,uint256) falsified");
+
+        let contract = |path: &str| PropertyContract { path: path.to_string(), contract: "P".to_string(), source: String::new() };
+        assert!(contract("test/Properties.t.sol").validate().is_ok());
+        assert!(contract("../Properties.sol").validate().is_err());
+
+        let config = FuzzConfig::from_payload(
+            &serde_json::json!({ "fuzzing": { "invariants": [{ "name": "solvent", "forgeTest": "invariant_solvent" }] } }),
+            &crate::fuzz_config::FuzzLimits::default(),
+        ).unwrap();
+        assert_eq!(invariant_name(&config, "invariant_solvent"), "solvent");
+        assert_eq!(invariant_name(&config, "invariant_other"), "invariant_other");
+    }
+}
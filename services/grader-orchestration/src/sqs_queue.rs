@@ -0,0 +1,260 @@
+use crate::aws_sigv4::{uri_encode, Credentials};
+use crate::job_queue::{self, JobQueue, QueuedJob};
+use futures::future::BoxFuture;
+use reqwest::{Client, Url};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::time::Duration;
+
+/// AWS SQS job queue with results published to an SNS topic, for graders on
+/// spot fleets. Jobs are long-polled one at a time; while a job is graded its
+/// visibility timeout is extended every half timeout, so a long fuzz
+/// campaign keeps it but a reclaimed instance's job reappears soon after.
+/// The job is deleted once its result is published. A job received more
+/// than `max_receives` times, such as one that crashes every worker grading
+/// it, is given a failed result and deleted instead.
+pub struct SqsQueue {
+    client: Client,
+    region: String,
+    credentials: Credentials,
+    sqs_endpoint: Url,
+    sns_endpoint: Url,
+    queue_url: String,
+    topic_arn: String,
+    visibility_timeout: Duration,
+    wait_time: Duration,
+    max_receives: u32,
+}
+
+impl SqsQueue {
+    /// `SQS_QUEUE_URL` and `SNS_TOPIC_ARN` (required),
+    /// `SQS_VISIBILITY_TIMEOUT_SECS` (default 300), `SQS_WAIT_TIME_SECS`
+    /// (default 20, the longest long poll), `SQS_MAX_RECEIVES` (default 5),
+    /// `SQS_ENDPOINT` and
+    /// `SNS_ENDPOINT` (default AWS's in `AWS_REGION`) and the standard
+    /// `AWS_*` credentials, which are required.
+    pub fn from_env() -> Result<Self, String> {
+        let required = |name: &str| std::env::var(name).map_err(|_| format!("{} is required for the SQS job queue", name));
+        let number = |name: &str, default: u64| std::env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        let region = std::env::var("AWS_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+        let endpoint = |name: &str, service: &str| {
+            let url = std::env::var(name).unwrap_or_else(|_| format!("https://{}.{}.amazonaws.com", service, region));
+            Url::parse(&url).map_err(|e| format!("Invalid {} {}: {}", name, url, e))
+        };
+        Ok(Self {
+            client: Client::new(),
+            credentials: Credentials::from_env().ok_or("AWS_ACCESS_KEY_ID and AWS_SECRET_ACCESS_KEY are required for the SQS job queue")?,
+            sqs_endpoint: endpoint("SQS_ENDPOINT", "sqs")?,
+            sns_endpoint: endpoint("SNS_ENDPOINT", "sns")?,
+            queue_url: required("SQS_QUEUE_URL")?,
+            topic_arn: required("SNS_TOPIC_ARN")?,
+            visibility_timeout: Duration::from_secs(number("SQS_VISIBILITY_TIMEOUT_SECS", 300).max(2)),
+            wait_time: Duration::from_secs(number("SQS_WAIT_TIME_SECS", 20).min(20)),
+            max_receives: number("SQS_MAX_RECEIVES", 5).clamp(1, u32::MAX as u64) as u32,
+            region,
+        })
+    }
+
+    /// A SigV4-signed POST of `body` to the service's endpoint.
+    fn signed_post(&self, endpoint: &Url, service: &str, content_type: &str, target: Option<&str>, body: String) -> reqwest::RequestBuilder {
+        let now = chrono::Utc::now();
+        let host = match endpoint.port() {
+            Some(port) => format!("{}:{}", endpoint.host_str().unwrap_or(""), port),
+            None => endpoint.host_str().unwrap_or("").to_string(),
+        };
+        let mut headers = vec![
+            ("content-type".to_string(), content_type.to_string()),
+            ("host".to_string(), host),
+            ("x-amz-date".to_string(), now.format("%Y%m%dT%H%M%SZ").to_string()),
+        ];
+        if let Some(token) = &self.credentials.session_token {
+            headers.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+        if let Some(target) = target {
+            headers.push(("x-amz-target".to_string(), target.to_string()));
+        }
+        let signed_names = headers.iter().map(|(name, _)| name.as_str()).collect::<Vec<_>>().join(";");
+        let canonical_headers: String = headers.iter().map(|(name, value)| format!("{}:{}\n", name, value.trim())).collect();
+        let canonical_request = format!(
+            "POST\n{}\n\n{}\n{}\n{}",
+            endpoint.path(),
+            canonical_headers,
+            signed_names,
+            hex::encode(Sha256::digest(body.as_bytes()))
+        );
+        let authorization = self.credentials.authorization(&self.region, service, now, &canonical_request, &signed_names);
+
+        let mut request = self.client.post(endpoint.clone()).body(body).header("authorization", authorization);
+        for (name, value) in headers.into_iter().filter(|(name, _)| name != "host") {
+            request = request.header(name, value);
+        }
+        request
+    }
+
+    /// Call an SQS action over its JSON protocol.
+    async fn sqs(&self, action: &str, request: Value) -> Result<Value, String> {
+        let target = format!("AmazonSQS.{}", action);
+        let response = self
+            .signed_post(&self.sqs_endpoint, "sqs", "application/x-amz-json-1.0", Some(&target), request.to_string())
+            .send()
+            .await
+            .map_err(|e| format!("SQS {} failed: {}", action, e))?;
+        let status = response.status();
+        let body = response.text().await.map_err(|e| format!("SQS {} failed: {}", action, e))?;
+        if !status.is_success() {
+            return Err(format!("SQS {} failed ({}): {}", action, status, body));
+        }
+        match body.is_empty() {
+            true => Ok(Value::Null),
+            false => serde_json::from_str(&body).map_err(|e| format!("Invalid SQS {} response: {}", action, e)),
+        }
+    }
+
+    async fn delete(&self, receipt: &str) -> Result<(), String> {
+        self.sqs("DeleteMessage", json!({ "QueueUrl": self.queue_url, "ReceiptHandle": receipt })).await.map(|_| ())
+    }
+}
+
+/// The job in a message body, unwrapping the envelope of jobs fanned out
+/// through an SNS subscription without raw message delivery.
+fn job_payload(body: &str) -> Option<Value> {
+    let payload: Value = serde_json::from_str(body).ok()?;
+    let payload = match (payload.get("Type").and_then(|v| v.as_str()), payload.get("Message").and_then(|v| v.as_str())) {
+        (Some("Notification"), Some(message)) => serde_json::from_str(message).ok()?,
+        _ => payload,
+    };
+    payload.is_object().then_some(payload)
+}
+
+/// Form body of an SNS `Publish` of a job's result. FIFO topics get the job
+/// id as deduplication id, so a result published twice is delivered once.
+fn publish_form(topic_arn: &str, job_id: &str, result: &Value) -> String {
+    let status = result.get("status").and_then(|v| v.as_str()).unwrap_or("completed");
+    let mut fields = vec![
+        ("Action", "Publish".to_string()),
+        ("Version", "2010-03-31".to_string()),
+        ("TopicArn", topic_arn.to_string()),
+        ("Message", result.to_string()),
+        ("MessageAttributes.entry.1.Name", "jobId".to_string()),
+        ("MessageAttributes.entry.1.Value.DataType", "String".to_string()),
+        ("MessageAttributes.entry.1.Value.StringValue", job_id.to_string()),
+        ("MessageAttributes.entry.2.Name", "status".to_string()),
+        ("MessageAttributes.entry.2.Value.DataType", "String".to_string()),
+        ("MessageAttributes.entry.2.Value.StringValue", status.to_string()),
+    ];
+    if topic_arn.ends_with(".fifo") {
+        fields.push(("MessageGroupId", job_id.to_string()));
+        fields.push(("MessageDeduplicationId", job_id.to_string()));
+    }
+    fields.iter().map(|(name, value)| format!("{}={}", name, uri_encode(value))).collect::<Vec<_>>().join("&")
+}
+
+impl JobQueue for SqsQueue {
+    fn next(&self) -> BoxFuture<'_, Result<Option<QueuedJob>, String>> {
+        Box::pin(async move {
+            let response = self
+                .sqs(
+                    "ReceiveMessage",
+                    json!({
+                        "QueueUrl": self.queue_url,
+                        "MaxNumberOfMessages": 1,
+                        "WaitTimeSeconds": self.wait_time.as_secs(),
+                        "VisibilityTimeout": self.visibility_timeout.as_secs(),
+                        "MessageSystemAttributeNames": ["ApproximateReceiveCount"],
+                    }),
+                )
+                .await?;
+            let message = match response.get("Messages").and_then(|m| m.get(0)) {
+                Some(message) => message,
+                None => return Ok(None),
+            };
+            let receipt = message.get("ReceiptHandle").and_then(|v| v.as_str()).ok_or("SQS message without a receipt handle")?;
+            let body = message.get("Body").and_then(|v| v.as_str()).unwrap_or_default();
+
+            let mut payload = match job_payload(body) {
+                Some(payload) => payload,
+                None => {
+                    // Nothing to grade or to report a result under, so drop it
+                    eprintln!("Warning: Dropping malformed job from {}", self.queue_url);
+                    self.delete(receipt).await?;
+                    return Ok(None);
+                }
+            };
+            let receives = message["Attributes"]["ApproximateReceiveCount"].as_str().and_then(|v| v.parse::<u32>().ok()).unwrap_or(1);
+            if receives > 1 {
+                let job_id = job_queue::job_id(&payload);
+                println!("Job {} received again (receive {})", job_id, receives);
+                if receives > self.max_receives {
+                    eprintln!("Warning: Giving up on job {} after {} receives", job_id, receives - 1);
+                    let result = json!({
+                        "jobId": job_id,
+                        "status": "failed",
+                        "error": format!("Job was abandoned by its worker {} times", receives - 1)
+                    });
+                    payload["jobId"] = json!(job_id);
+                    self.complete(&QueuedJob { payload, receipt: receipt.to_string() }, &result).await?;
+                    return Ok(None);
+                }
+            }
+            Ok(Some(QueuedJob { payload, receipt: receipt.to_string() }))
+        })
+    }
+
+    fn complete<'a>(&'a self, job: &'a QueuedJob, result: &'a Value) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let job_id = job.payload.get("jobId").and_then(|v| v.as_str()).unwrap_or_default();
+            let response = self
+                .signed_post(&self.sns_endpoint, "sns", "application/x-www-form-urlencoded; charset=utf-8", None, publish_form(&self.topic_arn, job_id, result))
+                .send()
+                .await
+                .map_err(|e| format!("Failed to publish result of job {}: {}", job_id, e))?;
+            if !response.status().is_success() {
+                let status = response.status();
+                let body = response.text().await.unwrap_or_default();
+                return Err(format!("Failed to publish result of job {} ({}): {}", job_id, status, body));
+            }
+            self.delete(&job.receipt).await
+        })
+    }
+
+    fn keep_alive_interval(&self) -> Option<Duration> {
+        Some(self.visibility_timeout / 2)
+    }
+
+    fn keep_alive<'a>(&'a self, job: &'a QueuedJob) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            self.sqs(
+                "ChangeMessageVisibility",
+                json!({
+                    "QueueUrl": self.queue_url,
+                    "ReceiptHandle": job.receipt,
+                    "VisibilityTimeout": self.visibility_timeout.as_secs(),
+                }),
+            )
+            .await
+            .map(|_| ())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_payloads_and_result_publication() {
+        assert_eq!(job_payload(r#"{"jobId": "j1", "code": "x"}"#).unwrap()["jobId"], "j1");
+        let envelope = json!({ "Type": "Notification", "Message": r#"{"jobId": "j2"}"# }).to_string();
+        assert_eq!(job_payload(&envelope).unwrap()["jobId"], "j2");
+        assert!(job_payload("[1, 2]").is_none());
+        assert!(job_payload("not json").is_none());
+
+        let result = json!({ "jobId": "j1", "status": "failed", "error": "a & b" });
+        let form = publish_form("arn:aws:sns:us-east-1:123456789012:grader-results", "j1", &result);
+        assert!(form.starts_with("Action=Publish&Version=2010-03-31&TopicArn=arn%3Aaws%3Asns%3Aus-east-1%3A123456789012%3Agrader-results&Message=%7B"));
+        assert!(form.contains("MessageAttributes.entry.2.Value.StringValue=failed"));
+        assert!(!form.contains("MessageDeduplicationId"));
+        let fifo = publish_form("arn:aws:sns:us-east-1:123456789012:grader-results.fifo", "j1", &result);
+        assert!(fifo.ends_with("&MessageGroupId=j1&MessageDeduplicationId=j1"));
+    }
+}
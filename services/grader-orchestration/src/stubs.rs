@@ -0,0 +1,266 @@
+use std::ffi::CString;
+use std::net::{SocketAddr, UdpSocket as StdUdpSocket};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970).
+const NTP_UNIX_OFFSET: u64 = 2_208_988_800;
+const NTP_PACKET_SIZE: usize = 48;
+
+/// resolv.conf naming the DNS stub, mounted over `/etc/resolv.conf` for
+/// sandboxed processes only. Unset unless the stub is running.
+static SANDBOX_RESOLV_CONF: OnceLock<PathBuf> = OnceLock::new();
+
+/// Addresses of the optional stub services reachable from sandboxed code.
+#[derive(Clone, Debug, Default)]
+pub struct StubConfig {
+    /// Resolver answering every query with NXDOMAIN, e.g. `127.0.0.1:53`
+    pub dns: Option<SocketAddr>,
+    /// NTP server answering with the worker's clock, e.g. `127.0.0.1:123`
+    pub time: Option<SocketAddr>,
+}
+
+impl StubConfig {
+    pub fn from_env() -> Result<Self, String> {
+        let parse = |name: &str| -> Result<Option<SocketAddr>, String> {
+            match std::env::var(name) {
+                Ok(addr) if !addr.is_empty() => addr
+                    .parse()
+                    .map(Some)
+                    .map_err(|e| format!("Invalid {}: {}", name, e)),
+                _ => Ok(None),
+            }
+        };
+
+        let config = Self {
+            dns: parse("SANDBOX_DNS_STUB")?,
+            time: parse("SANDBOX_TIME_STUB")?,
+        };
+        // resolv.conf can't name a port
+        if let Some(dns) = config.dns.filter(|addr| addr.port() != 53) {
+            return Err(format!("Invalid SANDBOX_DNS_STUB: {} must use port 53", dns));
+        }
+        Ok(config)
+    }
+}
+
+/// Bind and start the configured stubs. Sockets are bound synchronously so
+/// this can run before privileges are dropped (ports 53 and 123 are privileged).
+pub fn start_stubs(config: &StubConfig) -> Result<(), String> {
+    if let Some(addr) = config.dns {
+        let socket = bind(addr)?;
+        let resolv_conf = std::env::temp_dir().join("fathuss_resolv.conf");
+        std::fs::write(&resolv_conf, format!("nameserver {}\n", addr.ip()))
+            .map_err(|e| format!("Failed to write {}: {}", resolv_conf.display(), e))?;
+        tokio::spawn(serve(socket, dns_nxdomain_response));
+        let _ = SANDBOX_RESOLV_CONF.set(resolv_conf);
+        println!("DNS stub listening on {}, used by sandboxed processes", addr);
+    }
+
+    if let Some(addr) = config.time {
+        let socket = bind(addr)?;
+        tokio::spawn(serve(socket, |request| ntp_response(request, SystemTime::now())));
+        println!("Time stub listening on {}", addr);
+    }
+
+    Ok(())
+}
+
+/// The resolver isolation for a sandboxed process, if the DNS stub is running.
+pub fn sandbox_resolver() -> Option<ResolverIsolation> {
+    SANDBOX_RESOLV_CONF.get().map(|resolv_conf| ResolverIsolation::new(resolv_conf))
+}
+
+/// Points a sandboxed process, and only it, at the DNS stub: the process gets
+/// its own user and mount namespaces, in which the stub's resolv.conf is
+/// bind-mounted over `/etc/resolv.conf`. The worker's own lookups are
+/// unaffected. Needs unprivileged user namespaces when the worker doesn't run
+/// as root.
+pub struct ResolverIsolation {
+    resolv_conf: CString,
+    uid_map: Vec<u8>,
+    gid_map: Vec<u8>,
+    /// setgroups, uid_map and gid_map of /proc/self, then /etc/resolv.conf
+    paths: [CString; 4],
+}
+
+fn c_path(path: &str) -> CString {
+    CString::new(path).unwrap_or_default()
+}
+
+impl ResolverIsolation {
+    /// Everything `apply` needs is prepared here, before the fork.
+    fn new(resolv_conf: &Path) -> Self {
+        // SAFETY: getuid and getgid can't fail
+        let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+        Self {
+            resolv_conf: CString::new(resolv_conf.as_os_str().as_bytes()).unwrap_or_default(),
+            uid_map: format!("{} {} 1", uid, uid).into_bytes(),
+            gid_map: format!("{} {} 1", gid, gid).into_bytes(),
+            paths: [
+                c_path("/proc/self/setgroups"),
+                c_path("/proc/self/uid_map"),
+                c_path("/proc/self/gid_map"),
+                c_path("/etc/resolv.conf"),
+            ],
+        }
+    }
+
+    /// Run in the forked child before exec, so it only makes system calls.
+    pub fn apply(&self) -> std::io::Result<()> {
+        // SAFETY: plain system calls on paths owned by `self`
+        unsafe {
+            if libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNS) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            write_proc_file(&self.paths[0], b"deny")?;
+            write_proc_file(&self.paths[1], &self.uid_map)?;
+            write_proc_file(&self.paths[2], &self.gid_map)?;
+            if libc::mount(self.resolv_conf.as_ptr(), self.paths[3].as_ptr(), std::ptr::null(), libc::MS_BIND, std::ptr::null()) != 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write `data` to `path` with raw system calls.
+pub(crate) unsafe fn write_proc_file(path: &CString, data: &[u8]) -> std::io::Result<()> {
+    let fd = libc::open(path.as_ptr(), libc::O_WRONLY);
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let written = libc::write(fd, data.as_ptr() as *const libc::c_void, data.len());
+    libc::close(fd);
+    if written != data.len() as isize {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn bind(addr: SocketAddr) -> Result<UdpSocket, String> {
+    let socket = StdUdpSocket::bind(addr).map_err(|e| format!("Failed to bind stub on {}: {}", addr, e))?;
+    socket.set_nonblocking(true).map_err(|e| e.to_string())?;
+    UdpSocket::from_std(socket).map_err(|e| e.to_string())
+}
+
+async fn serve<F>(socket: UdpSocket, respond: F)
+where
+    F: Fn(&[u8]) -> Option<Vec<u8>> + Send + 'static,
+{
+    let mut buf = [0u8; 512];
+    loop {
+        let (len, peer) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                eprintln!("Warning: Stub receive failed: {}", e);
+                continue;
+            }
+        };
+
+        // Malformed requests are dropped, as a real server would
+        if let Some(response) = respond(&buf[..len]) {
+            let _ = socket.send_to(&response, peer).await;
+        }
+    }
+}
+
+/// Answer a DNS query with NXDOMAIN, echoing its id and question.
+pub fn dns_nxdomain_response(query: &[u8]) -> Option<Vec<u8>> {
+    if query.len() < 12 || query[2] & 0x80 != 0 {
+        return None;
+    }
+
+    // Echo only the first question: QNAME labels, then QTYPE and QCLASS
+    let mut end = 12;
+    if u16::from_be_bytes([query[4], query[5]]) > 0 {
+        loop {
+            let label_len = *query.get(end)? as usize;
+            if label_len & 0xc0 != 0 {
+                return None;
+            }
+            end += 1 + label_len;
+            if label_len == 0 {
+                break;
+            }
+        }
+        end += 4;
+        if end > query.len() {
+            return None;
+        }
+    }
+
+    let mut response = Vec::with_capacity(end);
+    response.extend_from_slice(&query[..2]);
+    // QR set, opcode and RD echoed; RA set, RCODE 3 (NXDOMAIN)
+    response.push(0x80 | (query[2] & 0x79));
+    response.push(0x80 | 0x03);
+    let qdcount: u16 = if end > 12 { 1 } else { 0 };
+    response.extend_from_slice(&qdcount.to_be_bytes());
+    response.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    response.extend_from_slice(&query[12..end]);
+    Some(response)
+}
+
+/// Answer an NTP client request with `now` as the receive and transmit time.
+pub fn ntp_response(request: &[u8], now: SystemTime) -> Option<Vec<u8>> {
+    if request.len() < NTP_PACKET_SIZE {
+        return None;
+    }
+
+    let since_unix = now.duration_since(UNIX_EPOCH).ok()?;
+    let seconds = (since_unix.as_secs() + NTP_UNIX_OFFSET) as u32;
+    let fraction = ((since_unix.subsec_nanos() as u64) << 32) / 1_000_000_000;
+    let mut timestamp = [0u8; 8];
+    timestamp[..4].copy_from_slice(&seconds.to_be_bytes());
+    timestamp[4..].copy_from_slice(&(fraction as u32).to_be_bytes());
+
+    let version = (request[0] >> 3) & 0x07;
+    let mut response = vec![0u8; NTP_PACKET_SIZE];
+    response[0] = (version << 3) | 4; // server mode
+    response[1] = 1; // stratum 1
+    response[2] = request[2]; // poll
+    response[3] = 0xec; // precision ~2^-20s
+    response[12..16].copy_from_slice(b"STUB");
+    response[16..24].copy_from_slice(&timestamp); // reference
+    response[24..32].copy_from_slice(&request[40..48]); // originate = client transmit
+    response[32..40].copy_from_slice(&timestamp); // receive
+    response[40..48].copy_from_slice(&timestamp); // transmit
+    Some(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_dns_stub_answers_nxdomain() {
+        // id 0xabcd, RD set, one question: example.com A IN
+        let mut query = vec![0xab, 0xcd, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        query.extend_from_slice(b"\x07example\x03com\x00\x00\x01\x00\x01");
+
+        let response = dns_nxdomain_response(&query).unwrap();
+        assert_eq!(&response[..2], &[0xab, 0xcd]);
+        assert_eq!(response[2], 0x81);
+        assert_eq!(response[3] & 0x0f, 3);
+        assert_eq!(&response[12..], &query[12..]);
+
+        assert!(dns_nxdomain_response(&query[..5]).is_none());
+    }
+
+    #[test]
+    fn test_time_stub_echoes_originate() {
+        let mut request = vec![0u8; NTP_PACKET_SIZE];
+        request[0] = (4 << 3) | 3; // v4 client
+        request[40..48].copy_from_slice(&[1, 2, 3, 4, 5, 6, 7, 8]);
+
+        let response = ntp_response(&request, UNIX_EPOCH + Duration::from_secs(1)).unwrap();
+        assert_eq!(response[0], (4 << 3) | 4);
+        assert_eq!(&response[24..32], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(u32::from_be_bytes(response[40..44].try_into().unwrap()) as u64, NTP_UNIX_OFFSET + 1);
+    }
+}
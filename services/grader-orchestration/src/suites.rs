@@ -0,0 +1,164 @@
+use crate::fixtures::TestFixture;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Pass counts of one suite of fixtures.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SuiteSummary {
+    pub name: String,
+    pub passed: usize,
+    pub total: usize,
+    /// Fixtures not run because a prerequisite suite failed
+    pub skipped: usize,
+}
+
+impl SuiteSummary {
+    pub fn all_passed(&self) -> bool {
+        self.passed == self.total
+    }
+}
+
+/// Order fixtures so every suite runs after the suites it depends on,
+/// keeping document order otherwise. Fails on dependency cycles.
+pub fn order_fixtures(fixtures: &[TestFixture]) -> Result<Vec<TestFixture>, String> {
+    let mut suite_deps: HashMap<&str, Vec<&str>> = HashMap::new();
+    for fixture in fixtures {
+        if let Some(suite) = &fixture.suite {
+            suite_deps.entry(suite.as_str()).or_default().extend(fixture.depends_on.iter().map(|d| d.as_str()));
+        }
+    }
+
+    let mut levels: HashMap<&str, usize> = HashMap::new();
+    for suite in suite_deps.keys() {
+        suite_level(*suite, &suite_deps, &mut levels, &mut Vec::new())?;
+    }
+    let fixture_level = |fixture: &TestFixture| match &fixture.suite {
+        Some(suite) => levels[suite.as_str()],
+        None => fixture.depends_on.iter().map(|d| levels.get(d.as_str()).map_or(0, |l| l + 1)).max().unwrap_or(0),
+    };
+
+    let mut ordered = fixtures.to_vec();
+    ordered.sort_by_key(|f| fixture_level(f));
+    Ok(ordered)
+}
+
+/// Depth of `suite` in the dependency graph: 0 without prerequisites, else one
+/// more than its deepest prerequisite. Suites outside this document count as
+/// already run.
+fn suite_level<'a>(
+    suite: &'a str,
+    deps: &HashMap<&'a str, Vec<&'a str>>,
+    levels: &mut HashMap<&'a str, usize>,
+    visiting: &mut Vec<&'a str>,
+) -> Result<usize, String> {
+    if let Some(level) = levels.get(suite) {
+        return Ok(*level);
+    }
+    if visiting.contains(&suite) {
+        visiting.push(suite);
+        return Err(format!("Fixture suites depend on each other: {}", visiting.join(" -> ")));
+    }
+
+    visiting.push(suite);
+    let mut level = 0;
+    for dep in deps.get(suite).into_iter().flatten() {
+        if deps.contains_key(dep) {
+            level = level.max(suite_level(*dep, deps, levels, visiting)? + 1);
+        }
+    }
+    visiting.pop();
+    levels.insert(suite, level);
+    Ok(level)
+}
+
+/// Suite results as fixtures complete, deciding which fixtures to skip.
+pub struct SuiteTracker {
+    /// Suites of this run, in the order they were first seen
+    summaries: Vec<SuiteSummary>,
+    /// Whether each suite of an earlier run (public fixtures, for hidden
+    /// tests) passed
+    prior: HashMap<String, bool>,
+}
+
+impl SuiteTracker {
+    pub fn new(prior: &[SuiteSummary]) -> Self {
+        Self {
+            summaries: Vec::new(),
+            prior: prior.iter().map(|s| (s.name.clone(), s.all_passed())).collect(),
+        }
+    }
+
+    /// Why `fixture` can't run: a prerequisite suite that failed or doesn't exist.
+    pub fn blocked_by(&self, fixture: &TestFixture) -> Option<String> {
+        fixture.depends_on.iter().find_map(|dep| {
+            let passed = match self.summaries.iter().find(|s| &s.name == dep) {
+                Some(summary) => Some(summary.all_passed()),
+                None => self.prior.get(dep).copied(),
+            };
+            match passed {
+                Some(true) => None,
+                Some(false) => Some(format!("prerequisite suite `{}` failed", dep)),
+                None => Some(format!("prerequisite suite `{}` does not exist", dep)),
+            }
+        })
+    }
+
+    pub fn record(&mut self, fixture: &TestFixture, passed: bool, skipped: bool) {
+        let name = match &fixture.suite {
+            Some(name) => name,
+            None => return,
+        };
+        let index = match self.summaries.iter().position(|s| &s.name == name) {
+            Some(index) => index,
+            None => {
+                self.summaries.push(SuiteSummary { name: name.clone(), passed: 0, total: 0, skipped: 0 });
+                self.summaries.len() - 1
+            },
+        };
+        let summary = &mut self.summaries[index];
+        summary.total += 1;
+        summary.passed += passed as usize;
+        summary.skipped += skipped as usize;
+    }
+
+    pub fn into_summaries(self) -> Vec<SuiteSummary> {
+        self.summaries
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn fixture(id: &str, suite: Option<&str>, depends_on: &[&str]) -> TestFixture {
+        serde_json::from_value(json!({ "id": id, "suite": suite, "depends_on": depends_on })).unwrap()
+    }
+
+    #[test]
+    fn test_suites_run_in_dependency_order_and_skip() {
+        let fixtures = vec![
+            fixture("transfer", Some("interact"), &["deploy"]),
+            fixture("smoke", None, &[]),
+            fixture("deploy-token", Some("deploy"), &[]),
+            fixture("deploy-vault", Some("deploy"), &[]),
+        ];
+        let ordered: Vec<String> = order_fixtures(&fixtures).unwrap().into_iter().map(|f| f.id).collect();
+        assert_eq!(ordered, vec!["smoke", "deploy-token", "deploy-vault", "transfer"]);
+
+        let mut tracker = SuiteTracker::new(&[]);
+        tracker.record(&fixtures[2], true, false);
+        tracker.record(&fixtures[3], false, false);
+        assert_eq!(tracker.blocked_by(&fixtures[0]).unwrap(), "prerequisite suite `deploy` failed");
+        tracker.record(&fixtures[0], false, true);
+        assert_eq!(tracker.into_summaries()[1], SuiteSummary { name: "interact".to_string(), passed: 0, total: 1, skipped: 1 });
+
+        // Hidden tests can depend on public suites
+        let prior = [SuiteSummary { name: "deploy".to_string(), passed: 2, total: 2, skipped: 0 }];
+        assert!(SuiteTracker::new(&prior).blocked_by(&fixtures[0]).is_none());
+
+        let cycle = vec![fixture("a", Some("a"), &["b"]), fixture("b", Some("b"), &["a"])];
+        assert!(order_fixtures(&cycle).unwrap_err().contains("depend on each other"));
+    }
+}
@@ -0,0 +1,164 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command as TokioCommand;
+use tokio::time::timeout;
+
+const UNPACK_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Directories of a project template that are linked rather than copied into
+/// the workspace: large, read-only dependency trees.
+const LINKED_DIRS: &[&str] = &["node_modules", "lib"];
+
+/// The prebuilt Hardhat project: `HARDHAT_TEMPLATE_DIR`, default
+/// /opt/hardhat-template as installed by the worker image.
+pub fn hardhat_template_dir() -> PathBuf {
+    PathBuf::from(std::env::var("HARDHAT_TEMPLATE_DIR").unwrap_or_else(|_| "/opt/hardhat-template".to_string()))
+}
+
+/// The default Foundry project (foundry.toml, remappings and forge-std in
+/// `lib/`) for challenges without their own template: `FOUNDRY_TEMPLATE_DIR`,
+/// default /opt/foundry-template as installed by the worker image.
+pub fn foundry_template_dir() -> PathBuf {
+    PathBuf::from(std::env::var("FOUNDRY_TEMPLATE_DIR").unwrap_or_else(|_| "/opt/foundry-template".to_string()))
+}
+
+/// Unpack a downloaded `.tar.gz` template bundle into `dest`. The bundle is
+/// extracted next to `dest` and renamed into place, so a concurrent job never
+/// sees a partial template.
+pub async fn unpack_template(archive: &[u8], dest: &Path) -> Result<(), String> {
+    let parent = dest.parent().ok_or("Template directory has no parent")?;
+    std::fs::create_dir_all(parent).map_err(|e| format!("Failed to create template cache: {}", e))?;
+    let staging = tempfile::tempdir_in(parent).map_err(|e| e.to_string())?;
+    let archive_path = staging.path().join("template.tar.gz");
+    let unpacked = staging.path().join("template");
+    std::fs::write(&archive_path, archive).map_err(|e| format!("Failed to write template bundle: {}", e))?;
+    std::fs::create_dir_all(&unpacked).map_err(|e| e.to_string())?;
+
+    let output = timeout(
+        UNPACK_TIMEOUT,
+        TokioCommand::new("tar")
+            .args(["-xzf", "template.tar.gz", "--no-same-owner", "--no-same-permissions", "-C", "template"])
+            .current_dir(staging.path())
+            .output(),
+    )
+    .await
+    .map_err(|_| "Unpacking template timed out".to_string())?
+    .map_err(|e| format!("Failed to run tar: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Failed to unpack template: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+
+    // Another job may have cached the same template meanwhile; either copy is fine
+    if let Err(e) = std::fs::rename(&unpacked, dest) {
+        if !dest.exists() {
+            return Err(format!("Failed to cache template: {}", e));
+        }
+    }
+    Ok(())
+}
+
+/// Copy a project template into `workspace`. Dependency directories are
+/// symlinked so jobs share one installed copy without being able to change
+/// the template's sources or config.
+pub fn install_template(template: &Path, workspace: &Path) -> Result<(), String> {
+    let entries = std::fs::read_dir(template)
+        .map_err(|e| format!("Project template not found at {}: {}", template.display(), e))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| e.to_string())?;
+        let name = entry.file_name();
+        let dest = workspace.join(&name);
+        let file_type = entry.file_type().map_err(|e| e.to_string())?;
+
+        if LINKED_DIRS.contains(&name.to_string_lossy().as_ref()) {
+            std::os::unix::fs::symlink(entry.path(), &dest)
+                .map_err(|e| format!("Failed to link {}: {}", entry.path().display(), e))?;
+        } else if file_type.is_dir() {
+            crate::replay::copy_dir(&entry.path(), &dest)?;
+        } else if file_type.is_file() {
+            std::fs::copy(entry.path(), &dest)
+                .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Test files of a Foundry project template, relative to the project root
+/// (e.g. `test/Contract.t.sol`). Only tests from these files grade fixtures;
+/// a submission can't add its own.
+pub fn test_files(template: &Path) -> Vec<String> {
+    fn walk(root: &Path, dir: &Path, files: &mut Vec<String>) {
+        let Ok(entries) = std::fs::read_dir(dir) else { return };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(file_type) if file_type.is_dir() => walk(root, &path, files),
+                Ok(file_type) if file_type.is_file() => {
+                    if let Ok(relative) = path.strip_prefix(root) {
+                        files.push(relative.to_string_lossy().into_owned());
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    let mut files = Vec::new();
+    walk(template, &template.join("test"), &mut files);
+    files.sort();
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_install_template_links_dependencies() {
+        let template = tempfile::tempdir().unwrap();
+        std::fs::write(template.path().join("hardhat.config.js"), "module.exports = {};").unwrap();
+        std::fs::create_dir_all(template.path().join("contracts")).unwrap();
+        std::fs::create_dir_all(template.path().join("node_modules/hardhat")).unwrap();
+
+        let workspace = tempfile::tempdir().unwrap();
+        install_template(template.path(), workspace.path()).unwrap();
+
+        assert!(workspace.path().join("hardhat.config.js").is_file());
+        assert!(workspace.path().join("contracts").is_dir());
+        let node_modules = workspace.path().join("node_modules");
+        assert!(node_modules.symlink_metadata().unwrap().file_type().is_symlink());
+        assert!(node_modules.join("hardhat").is_dir());
+
+        assert!(install_template(Path::new("/nonexistent/template"), workspace.path()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unpack_foundry_template() {
+        let project = tempfile::tempdir().unwrap();
+        std::fs::write(project.path().join("foundry.toml"), "[profile.default]\nsrc = \"src\"\n").unwrap();
+        std::fs::create_dir_all(project.path().join("test")).unwrap();
+        std::fs::write(project.path().join("test/Contract.t.sol"), "contract ContractTest {}").unwrap();
+        std::fs::create_dir_all(project.path().join("lib/forge-std/src")).unwrap();
+        let archive = std::process::Command::new("tar")
+            .args(["-czf", "-", "-C"])
+            .arg(project.path())
+            .arg(".")
+            .output()
+            .unwrap()
+            .stdout;
+
+        let cache = tempfile::tempdir().unwrap();
+        let template = cache.path().join("challenge-1");
+        unpack_template(&archive, &template).await.unwrap();
+        assert!(template.join("test/Contract.t.sol").is_file());
+        assert_eq!(test_files(&template), vec!["test/Contract.t.sol".to_string()]);
+
+        let workspace = tempfile::tempdir().unwrap();
+        install_template(&template, workspace.path()).unwrap();
+        assert!(workspace.path().join("foundry.toml").is_file());
+        assert!(workspace.path().join("lib").symlink_metadata().unwrap().file_type().is_symlink());
+
+        assert!(unpack_template(b"not a tarball", &cache.path().join("broken")).await.is_err());
+    }
+}
@@ -0,0 +1,351 @@
+use crate::tree_edit::TokenTree;
+use std::collections::HashMap;
+
+/// Whether `language` is fingerprinted from its token stream. Move and Vyper
+/// have no parser crate, so their submissions (and Python's, which Vyper's
+/// syntax follows) are lexed instead, with blocks taken from braces or
+/// indentation.
+pub fn supports(language: &str) -> bool {
+    matches!(language, "move" | "vyper" | "python")
+}
+
+/// The shared fingerprint token for a keyword, so the same construct
+/// produces the same token as in the Rust, TypeScript and Solidity
+/// extractors.
+fn keyword_token(language: &str, word: &str) -> Option<&'static str> {
+    let token = match (language, word) {
+        (_, "if") => "if",
+        (_, "for") => "for",
+        (_, "while") => "while",
+        (_, "return") => "return",
+
+        ("move", "fun") => "fn",
+        ("move", "struct") => "struct",
+        ("move", "module") | ("move", "script") => "contract",
+        ("move", "loop") => "while",
+        ("move", "let") => "let",
+        ("move", "abort") => "revert",
+        ("move", "true") | ("move", "false") => "literal",
+
+        (_, "def") | (_, "lambda") => "fn",
+        (_, "class") | ("vyper", "struct") | ("vyper", "interface") => "struct",
+        (_, "elif") => "if",
+        (_, "raise") | (_, "assert") => "revert",
+        ("vyper", "event") => "event",
+        ("vyper", "log") => "emit",
+        (_, "True") | (_, "False") | (_, "None") => "literal",
+        _ => return None,
+    };
+    Some(token)
+}
+
+/// Keywords without a token of their own: modifiers, operators and the like,
+/// which are left out rather than treated as identifiers.
+fn is_keyword(language: &str, word: &str) -> bool {
+    const MOVE: &[&str] = &[
+        "acquires", "as", "break", "const", "continue", "copy", "else", "entry", "friend", "has", "inline", "invariant",
+        "move", "mut", "native", "phantom", "public", "spec", "use",
+    ];
+    const PYTHON: &[&str] = &[
+        "and", "as", "async", "await", "break", "continue", "del", "else", "except", "finally", "from", "global", "import",
+        "in", "is", "nonlocal", "not", "or", "pass", "try", "with", "yield", "implements", "constant", "immutable",
+    ];
+    match language {
+        "move" => MOVE.contains(&word),
+        _ => PYTHON.contains(&word),
+    }
+}
+
+/// Statements that say nothing about the solution.
+fn is_import(language: &str, word: &str) -> bool {
+    match language {
+        "move" => word == "use",
+        _ => word == "import" || word == "from",
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum Lexeme {
+    Word(String),
+    Literal,
+    Punct(String),
+    /// End of a logical line, for indentation-structured languages
+    Newline { indent: usize },
+}
+
+/// Split `code` into words, literals and punctuation, dropping comments.
+/// Python and Vyper lines end at newlines outside brackets.
+fn lex(code: &str, language: &str) -> Result<Vec<Lexeme>, String> {
+    let indented = language != "move";
+    let chars: Vec<char> = code.chars().collect();
+    let mut lexemes = Vec::new();
+    let mut i = 0;
+    let mut brackets = 0usize;
+    let mut line_start = true;
+    while i < chars.len() {
+        if indented && line_start {
+            // Measure the indent of a line that holds code
+            let start = i;
+            while i < chars.len() && (chars[i] == ' ' || chars[i] == '\t') {
+                i += 1;
+            }
+            match chars.get(i) {
+                None | Some('\n') | Some('\r') | Some('#') => {},
+                Some(_) => {
+                    lexemes.push(Lexeme::Newline { indent: i - start });
+                    line_start = false;
+                },
+            }
+            if i >= chars.len() || !matches!(chars[i], '\n' | '\r' | '#') {
+                continue;
+            }
+        }
+        let c = chars[i];
+        match c {
+            '\n' => {
+                if indented && brackets == 0 && lexemes.last() != Some(&Lexeme::Punct("\\".to_string())) {
+                    line_start = true;
+                }
+                i += 1;
+            },
+            _ if c.is_whitespace() => i += 1,
+            '#' if indented => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            },
+            '/' if !indented && chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    i += 1;
+                }
+            },
+            '/' if !indented && chars.get(i + 1) == Some(&'*') => {
+                let end = (i + 2..chars.len().saturating_sub(1)).find(|j| chars[*j] == '*' && chars[j + 1] == '/');
+                i = end.ok_or("Parse error: unterminated comment")? + 2;
+            },
+            '"' | '\'' => {
+                i = string_end(&chars, i, indented)?;
+                lexemes.push(Lexeme::Literal);
+            },
+            _ if c.is_ascii_digit() => {
+                while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_' || chars[i] == '.') {
+                    i += 1;
+                }
+                lexemes.push(Lexeme::Literal);
+            },
+            _ if c.is_alphabetic() || c == '_' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                // String prefixes: r"", b"", f"" and Move's b"" and x""
+                let prefix = word.len() <= 2 && word.chars().all(|c| "rbfuxRBFU".contains(c));
+                match chars.get(i) {
+                    Some('"') | Some('\'') if prefix => {
+                        i = string_end(&chars, i, indented)?;
+                        lexemes.push(Lexeme::Literal);
+                    },
+                    _ => lexemes.push(Lexeme::Word(word)),
+                }
+            },
+            '(' | '[' | '{' | ')' | ']' | '}' | ',' | ';' | '.' | '@' => {
+                match c {
+                    '(' | '[' => brackets += 1,
+                    ')' | ']' => brackets = brackets.saturating_sub(1),
+                    '{' if indented => brackets += 1,
+                    '}' if indented => brackets = brackets.saturating_sub(1),
+                    _ => {},
+                }
+                lexemes.push(Lexeme::Punct(c.to_string()));
+                i += 1;
+            },
+            _ => {
+                let start = i;
+                while i < chars.len() && "=!<>+-*/%&|^~:\\".contains(chars[i]) {
+                    i += 1;
+                }
+                if i == start {
+                    i += 1;
+                }
+                lexemes.push(Lexeme::Punct(chars[start..i].iter().collect()));
+            },
+        }
+    }
+    Ok(lexemes)
+}
+
+/// Index just past the string literal opening at `start`, including Python's
+/// triple-quoted strings.
+fn string_end(chars: &[char], start: usize, indented: bool) -> Result<usize, String> {
+    let quote = chars[start];
+    let triple = indented && chars.get(start + 1) == Some(&quote) && chars.get(start + 2) == Some(&quote);
+    let mut i = start + if triple { 3 } else { 1 };
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 2,
+            c if c == quote && !triple => return Ok(i + 1),
+            c if c == quote && chars.get(i + 1) == Some(&quote) && chars.get(i + 2) == Some(&quote) => return Ok(i + 3),
+            '\n' if !triple => break,
+            _ => i += 1,
+        }
+    }
+    Err("Parse error: unterminated string".to_string())
+}
+
+/// Whether a punctuation lexeme assigns: `=`, or an augmented assignment.
+fn is_assignment(punct: &str) -> bool {
+    punct == "=" || (punct.len() >= 2 && punct.ends_with('=') && !matches!(punct, "==" | "!=" | "<=" | ">=" | "==="))
+}
+
+/// Tokens and structural features of `code`. Each statement's construct
+/// (`fn`, `if`, `assign`, ...) is pushed at its block's depth and the rest
+/// of the statement one level below it, so the statements of a block become
+/// children of the construct that opens it. Identifiers become
+/// `ident_<name>` so they normalize the same way as in the other extractors.
+pub fn extract_tokens(code: &str, language: &str) -> Result<(TokenTree, HashMap<String, u32>), String> {
+    if !supports(language) {
+        return Err(format!("No token fingerprinting for {}", language));
+    }
+    let lexemes = lex(code, language)?;
+
+    let mut tokens = TokenTree::default();
+    let mut features = HashMap::new();
+    let mut depth = 0;
+    // Indents of the enclosing blocks, for indentation-structured languages
+    let mut indents: Vec<usize> = vec![0];
+    let mut statement: Vec<&Lexeme> = Vec::new();
+    for lexeme in &lexemes {
+        match lexeme {
+            Lexeme::Newline { indent } => {
+                emit_statement(language, &statement, depth, &mut tokens, &mut features);
+                statement.clear();
+                if *indent > *indents.last().unwrap_or(&0) {
+                    indents.push(*indent);
+                }
+                while indents.len() > 1 && *indent < *indents.last().unwrap_or(&0) {
+                    indents.pop();
+                }
+                depth = indents.len() - 1;
+            },
+            Lexeme::Punct(p) if language == "move" && matches!(p.as_str(), ";" | "{" | "}") => {
+                emit_statement(language, &statement, depth, &mut tokens, &mut features);
+                statement.clear();
+                match p.as_str() {
+                    "{" => depth += 1,
+                    "}" => depth = depth.saturating_sub(1),
+                    _ => {},
+                }
+            },
+            Lexeme::Punct(p) if p == ";" => {
+                emit_statement(language, &statement, depth, &mut tokens, &mut features);
+                statement.clear();
+            },
+            _ => statement.push(lexeme),
+        }
+    }
+    emit_statement(language, &statement, depth, &mut tokens, &mut features);
+    Ok((tokens, features))
+}
+
+fn emit_statement(language: &str, statement: &[&Lexeme], depth: usize, tokens: &mut TokenTree, features: &mut HashMap<String, u32>) {
+    let mut push = |token: String, depth: usize, tokens: &mut TokenTree| {
+        if !token.starts_with("ident_") {
+            *features.entry(token.clone()).or_insert(0) += 1;
+        }
+        tokens.push_at(token, depth);
+    };
+
+    // The statement's construct: its first keyword with a token, past any
+    // modifiers, or an assignment
+    let words = statement.iter().take_while(|l| matches!(l, Lexeme::Word(w) if is_keyword(language, w) || keyword_token(language, w).is_some()));
+    let mut head = None;
+    for (index, lexeme) in words.enumerate() {
+        if let Lexeme::Word(word) = lexeme {
+            if index == 0 && is_import(language, word) {
+                return;
+            }
+            if let Some(token) = keyword_token(language, word) {
+                head = Some(index);
+                push(token.to_string(), depth, tokens);
+                break;
+            }
+        }
+    }
+    let rest_depth = match head {
+        Some(_) => depth + 1,
+        None if statement.iter().any(|l| matches!(l, Lexeme::Punct(p) if is_assignment(p))) => {
+            push("assign".to_string(), depth, tokens);
+            depth + 1
+        },
+        None => depth,
+    };
+
+    let start = head.map_or(0, |h| h + 1);
+    let mut after_definition = head.is_some_and(|h| matches!(statement[h], Lexeme::Word(w) if matches!(keyword_token(language, w), Some("fn" | "struct" | "contract" | "event"))));
+    for (index, lexeme) in statement.iter().enumerate().skip(start) {
+        match lexeme {
+            Lexeme::Word(word) => {
+                if let Some(token) = keyword_token(language, word) {
+                    push(token.to_string(), rest_depth, tokens);
+                } else if !is_keyword(language, word) {
+                    // A name followed by arguments is called, unless it is the
+                    // name being defined
+                    let called = matches!(statement.get(index + 1), Some(Lexeme::Punct(p)) if p == "(");
+                    if called && !after_definition {
+                        push("call".to_string(), rest_depth, tokens);
+                    }
+                    push(format!("ident_{}", word), rest_depth, tokens);
+                }
+                after_definition = false;
+            },
+            Lexeme::Literal => push("literal".to_string(), rest_depth, tokens),
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_constructs_map_to_shared_tokens() {
+        let (python, _) = extract_tokens("def f(n):\n    total = 0  # running sum\n    for i in n:\n        total += g(i)\n    return total\n", "python").unwrap();
+        assert_eq!(
+            python.sequence(),
+            vec!["fn", "ident_f", "ident_n", "assign", "ident_total", "literal", "for", "ident_i", "ident_n", "assign", "ident_total", "call", "ident_g", "ident_i", "return", "ident_total"]
+        );
+        // The loop and the return after it are both in the function body
+        let function = &python.tree().children[0];
+        assert_eq!(function.children.iter().map(|n| n.label.as_str()).collect::<Vec<_>>(), vec!["ident_f", "ident_n", "assign", "for", "return"]);
+
+        let vyper = "@external\ndef transfer(to: address, amount: uint256) -> bool:\n    assert self.balances[msg.sender] >= amount, \"low\"\n    log Transfer(msg.sender, to, amount)\n    return True\n";
+        let (tokens, features) = extract_tokens(vyper, "vyper").unwrap();
+        assert!(tokens.sequence().starts_with(&["ident_external".to_string(), "fn".to_string(), "ident_transfer".to_string()]));
+        assert_eq!((features.get("revert"), features.get("emit"), features.get("literal")), (Some(&1), Some(&1), Some(&2)));
+
+        let move_code = r#"
+            module 0xcafe::counter {
+                use std::signer;
+                /* state */
+                struct Counter has key { value: u64 }
+                public entry fun bump(account: &signer) acquires Counter {
+                    let counter = borrow_global_mut<Counter>(signer::address_of(account));
+                    if (counter.value > 10) abort 1;
+                    counter.value = counter.value + 1;
+                }
+            }
+        "#;
+        let (tokens, features) = extract_tokens(move_code, "move").unwrap();
+        let sequence = tokens.sequence();
+        assert_eq!(sequence[0], "contract");
+        assert!(!sequence.contains(&"ident_std".to_string()));
+        assert_eq!((features.get("fn"), features.get("let"), features.get("if"), features.get("assign")), (Some(&1), Some(&1), Some(&1), Some(&1)));
+        let module = &tokens.tree().children[0];
+        assert!(module.children.iter().any(|n| n.label == "fn"));
+
+        assert!(extract_tokens("x = 'open\n", "python").is_err());
+        assert!(!supports("go"));
+    }
+}
@@ -0,0 +1,227 @@
+use regex::Regex;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::process::Command as TokioCommand;
+use tokio::time::timeout;
+
+const INSTALL_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// Toolchain versions a challenge pins, from the payload's `toolchain` field
+/// (`{ "solc": "0.8.24", "rust": "1.75.0" }`). Unpinned Solidity follows the
+/// sources' `pragma solidity`; unpinned Rust uses the worker's default
+/// toolchain or the project's own rust-toolchain.toml.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ToolchainPin {
+    pub solc: Option<String>,
+    pub rust: Option<String>,
+}
+
+/// The exact toolchain versions a job was compiled with, reported in the
+/// response so a grade can be reproduced.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolchainVersions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub solc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rustc: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compiler: Option<String>,
+}
+
+impl ToolchainPin {
+    pub fn from_payload(payload: &Value) -> Result<Self, String> {
+        let pin: Self = match payload.get("toolchain") {
+            None | Some(Value::Null) => return Ok(Self::default()),
+            Some(toolchain) => serde_json::from_value(toolchain.clone())
+                .map_err(|e| format!("Invalid toolchain: {}", e))?,
+        };
+        if let Some(solc) = &pin.solc {
+            Version::parse(solc).map_err(|_| format!("Invalid solc version: {}", solc))?;
+        }
+        if let Some(rust) = &pin.rust {
+            // Channel names are written into rust-toolchain.toml
+            if !rust.chars().all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-') {
+                return Err(format!("Invalid rust toolchain: {}", rust));
+            }
+        }
+        Ok(pin)
+    }
+
+    /// Select the toolchain for the prepared `workspace`, installing the
+    /// Solidity compiler if needed, and report the versions in use.
+    pub async fn apply(&self, language: &str, workspace: &Path) -> Result<ToolchainVersions, String> {
+        let mut versions = ToolchainVersions::default();
+
+        match language {
+            "solidity" => {
+                let solc = match &self.solc {
+                    Some(solc) => Version::parse(solc).map_err(|e| e.to_string())?,
+                    None => match pragma_requirement(workspace)? {
+                        Some(requirement) => select_solc(&requirement, &installed_solc_versions())
+                            .ok_or_else(|| format!("No solc version satisfies pragma solidity {}", requirement))?,
+                        // No pragma: leave the choice to forge
+                        None => return Ok(versions),
+                    },
+                };
+                ensure_solc(&solc).await?;
+                pin_foundry_solc(workspace, &solc)?;
+                versions.solc = Some(solc.to_string());
+            },
+            "rust" | "ink" | "cosmwasm" => {
+                if let Some(channel) = &self.rust {
+                    std::fs::write(workspace.join("rust-toolchain.toml"), format!("[toolchain]\nchannel = \"{}\"\n", channel))
+                        .map_err(|e| format!("Failed to write rust-toolchain.toml: {}", e))?;
+                }
+                // rustup resolves the toolchain from the workspace's rust-toolchain.toml
+                versions.rustc = tool_version("rustc", workspace).await;
+            },
+            "vyper" => versions.compiler = tool_version("vyper", workspace).await,
+            "typescript" => versions.compiler = tool_version("tsc", workspace).await,
+            "move" => versions.compiler = tool_version("aptos", workspace).await,
+            _ => {},
+        }
+
+        Ok(versions)
+    }
+}
+
+/// The combined `pragma solidity` requirement of the Solidity sources at the
+/// top of the workspace and in `src/`, as a semver requirement.
+fn pragma_requirement(workspace: &Path) -> Result<Option<VersionReq>, String> {
+    let pragma = Regex::new(r"pragma\s+solidity\s+([^;]+);").unwrap();
+
+    let mut comparators = Vec::new();
+    for dir in [workspace.to_path_buf(), workspace.join("src")] {
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => continue,
+        };
+        for path in entries.flatten().map(|e| e.path()).filter(|p| p.extension().map(|e| e == "sol").unwrap_or(false)) {
+            let source = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            for caps in pragma.captures_iter(&source) {
+                // A bare version is exact in Solidity but caret in semver
+                comparators.extend(caps[1].split_whitespace().map(|c| match c.starts_with(|ch: char| ch.is_ascii_digit()) {
+                    true => format!("={}", c),
+                    false => c.to_string(),
+                }));
+            }
+        }
+    }
+
+    if comparators.is_empty() {
+        return Ok(None);
+    }
+    // Solidity separates the comparators of a range with spaces, semver with commas
+    let requirement = comparators.join(", ");
+    match VersionReq::parse(&requirement) {
+        Ok(requirement) => Ok(Some(requirement)),
+        Err(e) => {
+            // e.g. `||` alternatives; forge's own auto-detection handles these
+            eprintln!("Warning: Unsupported pragma solidity {}: {}", requirement, e);
+            Ok(None)
+        }
+    }
+}
+
+/// The newest installed solc satisfying `requirement`, or else the lowest
+/// version the requirement allows, to be installed.
+fn select_solc(requirement: &VersionReq, installed: &[Version]) -> Option<Version> {
+    if let Some(newest) = installed.iter().filter(|v| requirement.matches(v)).max() {
+        return Some(newest.clone());
+    }
+    requirement
+        .comparators
+        .iter()
+        .filter(|c| !matches!(c.op, semver::Op::Less | semver::Op::LessEq))
+        .map(|c| Version::new(c.major, c.minor.unwrap_or(0), c.patch.unwrap_or(0)))
+        .find(|v| requirement.matches(v))
+}
+
+/// Where svm keeps installed compilers: `$SVM_HOME`, else `~/.svm`.
+fn svm_home() -> PathBuf {
+    match std::env::var("SVM_HOME") {
+        Ok(home) => PathBuf::from(home),
+        Err(_) => PathBuf::from(std::env::var("HOME").unwrap_or_else(|_| "/root".to_string())).join(".svm"),
+    }
+}
+
+fn installed_solc_versions() -> Vec<Version> {
+    std::fs::read_dir(svm_home())
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| Version::parse(&entry.file_name().to_string_lossy()).ok())
+        .collect()
+}
+
+async fn ensure_solc(version: &Version) -> Result<(), String> {
+    let binary = svm_home().join(version.to_string()).join(format!("solc-{}", version));
+    if binary.exists() {
+        return Ok(());
+    }
+
+    println!("Installing solc {}", version);
+    let output = timeout(INSTALL_TIMEOUT, TokioCommand::new("svm").args(["install", &version.to_string()]).output())
+        .await
+        .map_err(|_| format!("Installing solc {} timed out", version))?
+        .map_err(|e| format!("Failed to run svm: {}", e))?;
+    if !output.status.success() {
+        return Err(format!("Failed to install solc {}: {}", version, String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    Ok(())
+}
+
+/// Set `solc_version` in the project's foundry.toml, creating it if needed,
+/// so `forge build` and `forge test` both use the selected compiler.
+fn pin_foundry_solc(workspace: &Path, version: &Version) -> Result<(), String> {
+    let path = workspace.join("foundry.toml");
+    let mut config: toml::Table = match std::fs::read_to_string(&path) {
+        Ok(config) => config.parse().map_err(|e| format!("Failed to parse foundry.toml: {}", e))?,
+        Err(_) => toml::Table::new(),
+    };
+
+    let profile = config
+        .entry("profile")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or("foundry.toml profile is not a table")?
+        .entry("default")
+        .or_insert_with(|| toml::Value::Table(toml::Table::new()))
+        .as_table_mut()
+        .ok_or("foundry.toml profile.default is not a table")?;
+    profile.insert("solc_version".to_string(), toml::Value::String(version.to_string()));
+    profile.remove("auto_detect_solc");
+
+    let config = toml::to_string(&config).map_err(|e| format!("Failed to write foundry.toml: {}", e))?;
+    std::fs::write(&path, config).map_err(|e| format!("Failed to write foundry.toml: {}", e))
+}
+
+async fn tool_version(tool: &str, workspace: &Path) -> Option<String> {
+    let output = TokioCommand::new(tool).arg("--version").current_dir(workspace).output().await.ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pragma_selects_solc() {
+        let workspace = tempfile::tempdir().unwrap();
+        std::fs::write(workspace.path().join("Contract.sol"), "// SPDX\npragma solidity >=0.8.4 <0.9.0;\ncontract C {}\n").unwrap();
+        let requirement = pragma_requirement(workspace.path()).unwrap().unwrap();
+
+        let installed = vec![Version::new(0, 7, 6), Version::new(0, 8, 19), Version::new(0, 8, 24)];
+        assert_eq!(select_solc(&requirement, &installed), Some(Version::new(0, 8, 24)));
+        assert_eq!(select_solc(&requirement, &[]), Some(Version::new(0, 8, 4)));
+
+        pin_foundry_solc(workspace.path(), &Version::new(0, 8, 24)).unwrap();
+        let config = std::fs::read_to_string(workspace.path().join("foundry.toml")).unwrap();
+        assert!(config.contains("solc_version = \"0.8.24\""));
+    }
+}
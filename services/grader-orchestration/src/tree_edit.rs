@@ -0,0 +1,220 @@
+use serde::{Deserialize, Serialize};
+
+/// Trees with more nodes than this are not compared: the distance takes
+/// time and memory quadratic in the tree sizes.
+pub const MAX_TREE_NODES: usize = 1000;
+/// Deeper trees are flattened to this depth: the distance also grows with
+/// the depth of the trees, and walking them recurses once per level.
+pub const MAX_TREE_DEPTH: usize = 32;
+
+/// A node of a submission's normalized syntax tree, labelled with the same
+/// tokens as its token sequence.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TreeNode {
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<TreeNode>,
+}
+
+impl TreeNode {
+    pub fn size(&self) -> usize {
+        1 + self.children.iter().map(TreeNode::size).sum::<usize>()
+    }
+
+    pub fn depth(&self) -> usize {
+        1 + self.children.iter().map(TreeNode::depth).max().unwrap_or(0)
+    }
+}
+
+/// Tokens in the order the extractors visit the syntax tree, each with its
+/// depth, from which the tree is rebuilt. Extractors `enter` before visiting
+/// the children of the token they just pushed and `leave` after.
+#[derive(Debug, Default)]
+pub struct TokenTree {
+    tokens: Vec<(String, usize)>,
+    depth: usize,
+}
+
+impl TokenTree {
+    pub fn push(&mut self, token: String) {
+        self.tokens.push((token, self.depth));
+    }
+
+    pub fn push_at(&mut self, token: String, depth: usize) {
+        self.tokens.push((token, depth));
+    }
+
+    pub fn enter(&mut self) {
+        self.depth += 1;
+    }
+
+    pub fn leave(&mut self) {
+        self.depth -= 1;
+    }
+
+    pub fn map_labels(&mut self, f: impl FnOnce(&[String]) -> Vec<String>) {
+        let labels: Vec<String> = self.tokens.iter().map(|(token, _)| token.clone()).collect();
+        for ((token, _), label) in self.tokens.iter_mut().zip(f(&labels)) {
+            *token = label;
+        }
+    }
+
+    pub fn sequence(&self) -> Vec<String> {
+        self.tokens.iter().map(|(token, _)| token.clone()).collect()
+    }
+
+    /// The tree under a `root` node: each token's parent is the nearest
+    /// earlier token one level up. Tokens below `MAX_TREE_DEPTH` are
+    /// attached at the deepest level instead.
+    pub fn tree(&self) -> TreeNode {
+        fn build(tokens: &[(&String, usize)], index: &mut usize, depth: Option<usize>) -> Vec<TreeNode> {
+            let mut nodes = Vec::new();
+            while let Some((label, token_depth)) = tokens.get(*index) {
+                if depth.is_some_and(|d| *token_depth <= d) {
+                    break;
+                }
+                *index += 1;
+                let children = build(tokens, index, Some(*token_depth));
+                nodes.push(TreeNode { label: label.to_string(), children });
+            }
+            nodes
+        }
+        let tokens: Vec<(&String, usize)> = self.tokens.iter().map(|(token, depth)| (token, (*depth).min(MAX_TREE_DEPTH - 2))).collect();
+        TreeNode { label: "root".to_string(), children: build(&tokens, &mut 0, None) }
+    }
+}
+
+/// Trees in postorder: labels, the leftmost leaf descendant of each node,
+/// and the keyroots (nodes with a left sibling, and the root).
+struct Postorder<'a> {
+    labels: Vec<&'a str>,
+    leftmost: Vec<usize>,
+    keyroots: Vec<usize>,
+}
+
+impl<'a> Postorder<'a> {
+    fn new(tree: &'a TreeNode) -> Self {
+        fn visit<'a>(node: &'a TreeNode, labels: &mut Vec<&'a str>, leftmost: &mut Vec<usize>) -> usize {
+            let mut first_leaf = None;
+            for child in &node.children {
+                let child_leaf = visit(child, labels, leftmost);
+                first_leaf.get_or_insert(child_leaf);
+            }
+            labels.push(&node.label);
+            leftmost.push(first_leaf.unwrap_or(labels.len() - 1));
+            leftmost[labels.len() - 1]
+        }
+        let (mut labels, mut leftmost) = (Vec::new(), Vec::new());
+        visit(tree, &mut labels, &mut leftmost);
+
+        // The highest node with each leftmost leaf
+        let mut keyroots: Vec<usize> = (0..labels.len())
+            .filter(|i| !(*i + 1..labels.len()).any(|j| leftmost[j] == leftmost[*i]))
+            .collect();
+        keyroots.sort_unstable();
+        Self { labels, leftmost, keyroots }
+    }
+}
+
+/// Unit-cost tree edit distance: the fewest node insertions, deletions and
+/// relabellings turning one ordered tree into the other. Computed with
+/// Zhang and Shasha's algorithm, which APTED refines to a better worst case
+/// with the same result.
+pub fn distance(a: &TreeNode, b: &TreeNode) -> usize {
+    let (a, b) = (Postorder::new(a), Postorder::new(b));
+    let mut tree_dist = vec![vec![0u32; b.labels.len()]; a.labels.len()];
+
+    for &i in &a.keyroots {
+        for &j in &b.keyroots {
+            let (li, lj) = (a.leftmost[i], b.leftmost[j]);
+            let (rows, cols) = (i - li + 2, j - lj + 2);
+            let mut forest = vec![vec![0u32; cols]; rows];
+            for di in 1..rows {
+                forest[di][0] = forest[di - 1][0] + 1;
+            }
+            for dj in 1..cols {
+                forest[0][dj] = forest[0][dj - 1] + 1;
+            }
+            for di in 1..rows {
+                for dj in 1..cols {
+                    let (x, y) = (li + di - 1, lj + dj - 1);
+                    let delete = forest[di - 1][dj] + 1;
+                    let insert = forest[di][dj - 1] + 1;
+                    if a.leftmost[x] == li && b.leftmost[y] == lj {
+                        let relabel = forest[di - 1][dj - 1] + (a.labels[x] != b.labels[y]) as u32;
+                        forest[di][dj] = delete.min(insert).min(relabel);
+                        tree_dist[x][y] = forest[di][dj];
+                    } else {
+                        let subtree = forest[a.leftmost[x] - li][b.leftmost[y] - lj] + tree_dist[x][y];
+                        forest[di][dj] = delete.min(insert).min(subtree);
+                    }
+                }
+            }
+        }
+    }
+    tree_dist[a.labels.len() - 1][b.labels.len() - 1] as usize
+}
+
+/// 1 minus the edit distance relative to the larger tree, or `None` when
+/// either tree is too large or too deep to compare. Takes up to about a
+/// second at the limits, so callers run it off the async runtime.
+pub fn similarity(a: &TreeNode, b: &TreeNode) -> Option<f64> {
+    let (size_a, size_b) = (a.size(), b.size());
+    if size_a > MAX_TREE_NODES || size_b > MAX_TREE_NODES || a.depth() > MAX_TREE_DEPTH || b.depth() > MAX_TREE_DEPTH {
+        return None;
+    }
+    // Differently shaped trees can be further apart than the larger one is big
+    Some((1.0 - distance(a, b) as f64 / size_a.max(size_b) as f64).max(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(label: &str, children: Vec<TreeNode>) -> TreeNode {
+        TreeNode { label: label.to_string(), children }
+    }
+
+    #[test]
+    fn test_tree_edit_distance() {
+        let mut tokens = TokenTree::default();
+        tokens.push("fn".to_string());
+        tokens.enter();
+        tokens.push("if".to_string());
+        tokens.enter();
+        tokens.push("call".to_string());
+        tokens.leave();
+        tokens.push("return".to_string());
+        tokens.leave();
+        tokens.push_at("struct".to_string(), 0);
+        let tree = tokens.tree();
+        assert_eq!(
+            tree,
+            node("root", vec![node("fn", vec![node("if", vec![node("call", vec![])]), node("return", vec![])]), node("struct", vec![])])
+        );
+
+        assert_eq!(distance(&tree, &tree), 0);
+        // Moving `return` into the `if` deletes and reinserts one node
+        let moved = node("root", vec![node("fn", vec![node("if", vec![node("call", vec![]), node("return", vec![])])]), node("struct", vec![])]);
+        assert_eq!(distance(&tree, &moved), 2);
+        // The classic Zhang-Shasha example
+        let f = node("f", vec![node("d", vec![node("a", vec![]), node("c", vec![node("b", vec![])])]), node("e", vec![])]);
+        let g = node("f", vec![node("c", vec![node("d", vec![node("a", vec![]), node("b", vec![])])]), node("e", vec![])]);
+        assert_eq!(distance(&f, &g), 2);
+        assert_eq!(similarity(&f, &g), Some(1.0 - 2.0 / 6.0));
+
+        // Deep nesting is flattened at the depth limit
+        let mut nested = TokenTree::default();
+        for depth in 0..100 {
+            nested.push_at("if".to_string(), depth);
+        }
+        let tree = nested.tree();
+        assert_eq!((tree.depth(), tree.size()), (MAX_TREE_DEPTH, 101));
+        assert!(similarity(&tree, &tree).is_some());
+        let mut deep = node("leaf", vec![]);
+        for _ in 0..MAX_TREE_DEPTH {
+            deep = node("if", vec![deep]);
+        }
+        assert_eq!(similarity(&deep, &deep), None);
+    }
+}
@@ -0,0 +1,72 @@
+use regex::Regex;
+use std::path::Path;
+
+/// A type error reported by `tsc`.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct TscDiagnostic {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub code: String,
+    pub message: String,
+}
+
+/// Ambient declarations so `tsc` accepts code written against the Deno runtime.
+const DENO_SHIM_FILE: &str = "fathuss-deno.d.ts";
+const DENO_SHIM: &str = "declare const Deno: any;\n";
+
+pub fn write_deno_shim(workspace: &Path) -> Result<(), String> {
+    std::fs::write(workspace.join(DENO_SHIM_FILE), DENO_SHIM)
+        .map_err(|e| format!("Failed to write {}: {}", DENO_SHIM_FILE, e))
+}
+
+/// Arguments for type-checking `files` without emitting JavaScript.
+pub fn tsc_check_args(files: &[&str]) -> Vec<String> {
+    let mut args: Vec<String> = ["--noEmit", "--strict", "--target", "es2022", "--module", "es2022", "--allowImportingTsExtensions"]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    args.push(DENO_SHIM_FILE.to_string());
+    args.extend(files.iter().map(|f| f.to_string()));
+    args
+}
+
+/// Parse `tsc` output lines such as
+/// `code.ts(3,7): error TS2322: Type 'string' is not assignable to type 'number'.`
+pub fn parse_tsc_diagnostics(output: &str) -> Vec<TscDiagnostic> {
+    let pattern = Regex::new(r"^(.+?)\((\d+),(\d+)\): error (TS\d+): (.*)$").unwrap();
+
+    output
+        .lines()
+        .filter_map(|line| pattern.captures(line.trim()))
+        .map(|caps| TscDiagnostic {
+            file: caps[1].to_string(),
+            line: caps[2].parse().unwrap_or(0),
+            column: caps[3].parse().unwrap_or(0),
+            code: caps[4].to_string(),
+            message: caps[5].to_string(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tsc_diagnostics() {
+        let output = "\
+code.ts(3,7): error TS2322: Type 'string' is not assignable to type 'number'.
+code.ts(10,1): error TS2304: Cannot find name 'foo'.
+
+Found 2 errors in the same file, starting at: code.ts:3
+";
+
+        let diagnostics = parse_tsc_diagnostics(output);
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0].line, 3);
+        assert_eq!(diagnostics[0].column, 7);
+        assert_eq!(diagnostics[0].code, "TS2322");
+        assert_eq!(diagnostics[1].message, "Cannot find name 'foo'.");
+    }
+}
@@ -0,0 +1,134 @@
+use crate::fuzzer::FuzzCrash;
+use serde::Serialize;
+use serde_json::Value;
+
+/// Outcome of one fixture when grading a reference solution.
+pub struct FixtureOutcome<'a> {
+    pub fixture_id: &'a str,
+    pub name: &'a str,
+    pub hidden: bool,
+    pub passed: bool,
+    pub timed_out: bool,
+    pub error: Option<&'a str>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FailedFixture {
+    pub fixture_id: String,
+    pub name: String,
+    pub hidden: bool,
+    pub timed_out: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationCrash {
+    pub input: Value,
+    pub error: String,
+    pub occurrences: usize,
+}
+
+/// Result of grading a challenge author's reference solution. A challenge is
+/// only valid for publishing if the reference passes every fixture and the
+/// fuzzer finds no crashes; anything else points at a broken fixture.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub passed_fixtures: usize,
+    pub total_fixtures: usize,
+    pub failed_fixtures: Vec<FailedFixture>,
+    pub fuzz_crashes: Vec<ValidationCrash>,
+    pub issues: Vec<String>,
+}
+
+impl ValidationReport {
+    pub fn from_run(outcomes: &[FixtureOutcome], crashes: &[FuzzCrash]) -> Self {
+        let failed_fixtures: Vec<FailedFixture> = outcomes
+            .iter()
+            .filter(|o| !o.passed)
+            .map(|o| FailedFixture {
+                fixture_id: o.fixture_id.to_string(),
+                name: o.name.to_string(),
+                hidden: o.hidden,
+                timed_out: o.timed_out,
+                error: o.error.map(|e| e.to_string()),
+            })
+            .collect();
+
+        let mut issues = Vec::new();
+        if outcomes.is_empty() {
+            issues.push("Challenge has no fixtures".to_string());
+        }
+        if !failed_fixtures.is_empty() {
+            issues.push(format!(
+                "Reference solution failed {} of {} fixtures",
+                failed_fixtures.len(),
+                outcomes.len()
+            ));
+        }
+        if !crashes.is_empty() {
+            issues.push(format!("Fuzzing found {} distinct crashes in the reference solution", crashes.len()));
+        }
+
+        Self {
+            valid: issues.is_empty(),
+            passed_fixtures: outcomes.len() - failed_fixtures.len(),
+            total_fixtures: outcomes.len(),
+            failed_fixtures,
+            fuzz_crashes: crashes
+                .iter()
+                .map(|c| ValidationCrash { input: c.input.clone(), error: c.error_message.clone(), occurrences: c.occurrences })
+                .collect(),
+            issues,
+        }
+    }
+
+    pub fn compile_failed() -> Self {
+        Self {
+            valid: false,
+            passed_fixtures: 0,
+            total_fixtures: 0,
+            failed_fixtures: vec![],
+            fuzz_crashes: vec![],
+            issues: vec!["Reference solution failed to compile".to_string()],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fuzzer::CrashSeverity;
+    use serde_json::json;
+
+    fn outcome(fixture_id: &str, hidden: bool, passed: bool) -> FixtureOutcome<'_> {
+        FixtureOutcome { fixture_id, name: fixture_id, hidden, passed, timed_out: false, error: None }
+    }
+
+    #[test]
+    fn test_report_flags_failures_and_crashes() {
+        let report = ValidationReport::from_run(&[outcome("a", false, true), outcome("b", true, true)], &[]);
+        assert!(report.valid);
+        assert_eq!(report.passed_fixtures, 2);
+
+        let crash = FuzzCrash {
+            input: json!({ "n": -1 }),
+            error_message: "overflow".to_string(),
+            stack_trace: String::new(),
+            gas_used: 0,
+            severity: CrashSeverity::High,
+            signature: "0f3a9c1d2b4e5f60".to_string(),
+            occurrences: 1,
+        };
+        let report = ValidationReport::from_run(&[outcome("a", false, true), outcome("b", true, false)], &[crash]);
+        assert!(!report.valid);
+        assert_eq!(report.failed_fixtures.len(), 1);
+        assert!(report.failed_fixtures[0].hidden);
+        assert_eq!(report.issues.len(), 2);
+
+        assert!(!ValidationReport::from_run(&[], &[]).valid);
+    }
+}
@@ -0,0 +1,66 @@
+use regex::Regex;
+
+/// A compiler error reported by `vyper`.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct VyperDiagnostic {
+    pub kind: String,
+    pub message: String,
+    pub line: Option<u32>,
+    pub column: Option<u32>,
+}
+
+/// Parse vyper's stderr into diagnostics.
+///
+/// Errors look like `vyper.exceptions.SyntaxException: invalid syntax` followed
+/// by a source excerpt containing `line 3:4`.
+pub fn parse_vyper_diagnostics(stderr: &str) -> Vec<VyperDiagnostic> {
+    let header = Regex::new(r"(?:vyper\.exceptions\.(\w+)|^(\w+(?:Exception|Error))): (.*)").unwrap();
+    let location = Regex::new(r"line (\d+):(\d+)").unwrap();
+
+    let mut diagnostics: Vec<VyperDiagnostic> = Vec::new();
+
+    for line in stderr.lines() {
+        if let Some(caps) = header.captures(line) {
+            let kind = caps.get(1).or_else(|| caps.get(2)).map(|m| m.as_str()).unwrap_or("");
+            diagnostics.push(VyperDiagnostic {
+                kind: kind.to_string(),
+                message: caps[3].trim().to_string(),
+                line: None,
+                column: None,
+            });
+        }
+
+        // The location may appear on the header line or in the excerpt below it
+        if let (Some(last), Some(caps)) = (diagnostics.last_mut(), location.captures(line)) {
+            if last.line.is_none() {
+                last.line = caps[1].parse().ok();
+                last.column = caps[2].parse().ok();
+            }
+        }
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_vyper_diagnostics() {
+        let stderr = r#"Error compiling: Contract.vy
+vyper.exceptions.UndeclaredDefinition: 'balnce' has not been declared.
+
+  contract "Contract.vy:7", function "withdraw", line 7:11
+       6     def withdraw(amount: uint256):
+  ---> 7     assert self.balnce >= amount
+  ------------------^
+"#;
+
+        let diagnostics = parse_vyper_diagnostics(stderr);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].kind, "UndeclaredDefinition");
+        assert_eq!(diagnostics[0].line, Some(7));
+        assert_eq!(diagnostics[0].column, Some(11));
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::BTreeSet;
+
+/// Tokens per k-gram. Matches shorter than this are never detected.
+pub const K: usize = 5;
+/// k-grams per window. Any match at least `K + W - 1` tokens long is
+/// guaranteed to share a selected hash.
+pub const W: usize = 4;
+
+/// Winnowing (Schleimer, Wilkerson and Aiken, the algorithm behind MOSS):
+/// hash every k-gram of the token stream, then keep the minimum hash of each
+/// window of `W` consecutive k-grams. Two submissions sharing a long enough
+/// run of tokens share a selected hash wherever that run appears in either,
+/// so reordering code, or inserting junk between copied parts, only loses
+/// the k-grams that straddle the edits.
+pub fn fingerprints(tokens: &[String]) -> Vec<u64> {
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+    if tokens.len() < K {
+        return vec![hash(tokens)];
+    }
+
+    let hashes: Vec<u64> = tokens.windows(K).map(hash).collect();
+    let mut selected = BTreeSet::new();
+    for window in hashes.windows(W.min(hashes.len())) {
+        selected.insert(*window.iter().min().unwrap());
+    }
+    selected.into_iter().collect()
+}
+
+/// Jaccard similarity of two fingerprint sets, which `fingerprints` returns sorted.
+pub fn similarity(a: &[u64], b: &[u64]) -> f64 {
+    if a.is_empty() && b.is_empty() {
+        return 0.0;
+    }
+    let (a, b): (BTreeSet<_>, BTreeSet<_>) = (a.iter().collect(), b.iter().collect());
+    a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+}
+
+/// Stable across builds, unlike `DefaultHasher`, since fingerprints are stored.
+fn hash(kgram: &[String]) -> u64 {
+    let digest = md5::compute(kgram.join("\u{0}"));
+    u64::from_le_bytes(digest.0[..8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tokens(s: &str) -> Vec<String> {
+        s.split_whitespace().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_fingerprints_survive_reordering_and_junk() {
+        let first = "fn let call if assign for literal call block expr_stmt ident assign return literal call path if";
+        let second = "fn let path while call path literal assign method_call for block if assign literal return call";
+        let original = tokens(&format!("{} {}", first, second));
+        let reordered = tokens(&format!("{} {}", second, first));
+        let padded = tokens(&format!("{} other_stmt other_stmt other_stmt {}", first, second));
+        let unrelated = tokens("struct impl fn method_call method_call enum struct impl fn literal");
+
+        let fp = fingerprints(&original);
+        assert_eq!(fp, fingerprints(&original));
+        assert!(similarity(&fp, &fingerprints(&reordered)) >= 0.5);
+        assert!(similarity(&fp, &fingerprints(&padded)) >= 0.5);
+        assert_eq!(similarity(&fp, &fingerprints(&unrelated)), 0.0);
+        assert_eq!(fingerprints(&tokens("fn main")).len(), 1);
+    }
+}
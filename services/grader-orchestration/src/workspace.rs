@@ -0,0 +1,134 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+use tempfile::TempDir;
+
+/// Prefix of challenge ids that name a local challenge directory.
+pub const LOCAL_PREFIX: &str = "local:";
+
+/// Directory local challenge workspaces live under, from
+/// `LOCAL_WORKSPACE_ROOT`; local workspaces are disabled without it.
+fn local_root() -> Option<&'static Path> {
+    static ROOT: OnceLock<Option<PathBuf>> = OnceLock::new();
+    ROOT.get_or_init(|| std::env::var("LOCAL_WORKSPACE_ROOT").ok().filter(|root| !root.is_empty()).map(PathBuf::from))
+        .as_deref()
+}
+
+/// The directory a `local:` challenge id names under `LOCAL_WORKSPACE_ROOT`,
+/// `None` for other ids.
+pub fn local_challenge_dir(challenge_id: &str) -> Option<Result<PathBuf, String>> {
+    let name = challenge_id.strip_prefix(LOCAL_PREFIX)?;
+    Some(match local_root() {
+        Some(root) => resolve_local(root, name),
+        None => Err("Local challenges are disabled; set LOCAL_WORKSPACE_ROOT".to_string()),
+    })
+}
+
+/// Resolve a relative path under `root` that can't leave it: absolute paths,
+/// `..` and symlinks out of `root` are refused.
+fn resolve_local(root: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(name);
+    if name.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_) | Component::CurDir)) {
+        return Err(format!("Local workspace must be a relative path without '..': {}", name));
+    }
+    let root = root
+        .canonicalize()
+        .map_err(|e| format!("Invalid local workspace root {}: {}", root.display(), e))?;
+    let path = root
+        .join(relative)
+        .canonicalize()
+        .map_err(|e| format!("Invalid local workspace {}: {}", name, e))?;
+    if !path.starts_with(&root) {
+        return Err(format!("Local workspace is outside {}: {}", root.display(), name));
+    }
+    if !path.is_dir() {
+        return Err(format!("Local workspace is not a directory: {}", path.display()));
+    }
+    Ok(path)
+}
+
+/// Directory a grading job runs in. A temporary workspace owns its directory
+/// and removes it when the job drops the workspace, on success and error paths
+/// alike; a local workspace points at an existing directory that is left in place.
+pub struct Workspace {
+    path: PathBuf,
+    temp_dir: Option<TempDir>,
+}
+
+impl Workspace {
+    pub fn temporary() -> Result<Self, String> {
+        let temp_dir = tempfile::tempdir().map_err(|e| format!("Failed to create temp dir: {}", e))?;
+        Ok(Self {
+            path: temp_dir.path().to_path_buf(),
+            temp_dir: Some(temp_dir),
+        })
+    }
+
+    /// Use an existing directory under `root`, named by a relative path that
+    /// can't leave it.
+    pub fn local(root: &Path, name: &str) -> Result<Self, String> {
+        Ok(Self {
+            path: resolve_local(root, name)?,
+            temp_dir: None,
+        })
+    }
+
+    /// Workspace for a challenge: challenge ids such as `local:my-challenge`
+    /// refer to a directory under `LOCAL_WORKSPACE_ROOT`, anything else gets
+    /// a fresh temporary one.
+    pub fn for_challenge(challenge_id: &str) -> Result<Self, String> {
+        match local_challenge_dir(challenge_id) {
+            Some(path) => Ok(Self { path: path?, temp_dir: None }),
+            None => Self::temporary(),
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn is_temporary(&self) -> bool {
+        self.temp_dir.is_some()
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if let Some(temp_dir) = self.temp_dir.take() {
+            if let Err(e) = temp_dir.close() {
+                eprintln!("Warning: Failed to remove workspace {}: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_workspace_lifetime() {
+        let workspace = Workspace::temporary().unwrap();
+        let path = workspace.path().to_path_buf();
+        std::fs::write(path.join("code.py"), "print(1)").unwrap();
+        assert!(path.join("code.py").exists());
+        drop(workspace);
+        assert!(!path.exists());
+
+        // Local workspaces are validated and never deleted
+        let root = tempfile::tempdir().unwrap();
+        std::fs::create_dir(root.path().join("challenge")).unwrap();
+        let local = Workspace::local(root.path(), "challenge").unwrap();
+        assert!(!local.is_temporary());
+        drop(local);
+        assert!(root.path().join("challenge").exists());
+
+        assert!(Workspace::local(root.path(), "missing").is_err());
+        assert!(Workspace::local(root.path(), "/etc").is_err());
+        assert!(Workspace::local(root.path(), "challenge/../..").is_err());
+        std::os::unix::fs::symlink("/", root.path().join("escape")).unwrap();
+        assert!(Workspace::local(root.path(), "escape").is_err());
+
+        // Absolute challenge ids are ordinary ids now, graded in a temporary workspace
+        assert!(Workspace::for_challenge("/etc").unwrap().is_temporary());
+    }
+}
@@ -0,0 +1,5 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+// Compiled once when the worker image is built so the default solc is cached
+contract Warmup {}
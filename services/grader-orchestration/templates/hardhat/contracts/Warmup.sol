@@ -0,0 +1,6 @@
+// SPDX-License-Identifier: MIT
+pragma solidity 0.8.24;
+
+// Compiled once while building the worker image to cache the solc download;
+// removed from the template afterwards.
+contract Warmup {}
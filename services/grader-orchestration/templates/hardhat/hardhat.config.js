@@ -0,0 +1,11 @@
+// Project used by the compiler_hardhat worker. The worker image installs
+// node_modules and downloads this compiler version once, so compiles never
+// reach the npm registry or the solc mirror.
+module.exports = {
+  solidity: "0.8.24",
+  paths: {
+    sources: "./contracts",
+    artifacts: "./artifacts",
+    cache: "./cache",
+  },
+};